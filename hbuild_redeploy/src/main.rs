@@ -1,31 +1,184 @@
 use anyhow::Result;
 use aws_config::BehaviorVersion;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::Client;
+use sha2::{Digest, Sha256};
 use sonic_rs::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{Read, Write};
-use std::{net::SocketAddr, process::Command};
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpListener;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{error, field, info, info_span, warn, Instrument};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+#[cfg(test)]
+mod main_tests;
 
 // Constants for timeouts
 //const SOCKET_TIMEOUT: Duration = Duration::from_secs(30);
 
 // make sure build is run in sudo mode
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Largest deploy request read before giving up on the client
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// How long a client has to send its whole deploy request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The build deployed when a request doesn't name a version
+const LATEST_VERSION: &str = "latest";
+
+/// How many hex digits of its SHA-256 a build of `latest` is kept under, see [`Releases::name`]
+const RELEASE_HASH_LEN: usize = 12;
+
+/// What a deploy request asks for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeployAction {
+    /// Downloads `version` from S3 and installs it
+    #[default]
+    Deploy,
+    /// Installs the release named `version` kept from an earlier deploy, without downloading it
+    Rollback,
+}
+
+/// Sent by the client as JSON, e.g.
+/// `{"token": "...", "user_id": "...", "instance_id": "...", "version": "1.0.113"}`,
+/// with `"action": "rollback"` to roll back to the kept release `version` names instead
+#[derive(Deserialize, Serialize)]
 pub struct HBuildDeployRequest {
+    /// The shared secret the service was started with as `DEPLOY_TOKEN`
+    #[serde(default)]
+    token: Option<String>,
     user_id: String,
     instance_id: String,
+    #[serde(default)]
+    action: DeployAction,
+    /// The build to deploy from `<user_id>/<cluster_id>/helix/<version>`, `latest` if left out,
+    /// or the release to roll back to
+    #[serde(default = "latest_version")]
     version: String,
 }
 
+fn latest_version() -> String {
+    LATEST_VERSION.to_string()
+}
+
+impl std::fmt::Debug for HBuildDeployRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HBuildDeployRequest")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("user_id", &self.user_id)
+            .field("instance_id", &self.instance_id)
+            .field("action", &self.action)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl HBuildDeployRequest {
+    /// Checks the request carries `deploy_token`, comparing them in constant time
+    fn authenticate(&self, deploy_token: &str) -> Result<(), AdminError> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| AdminError::Unauthorized("no token".to_string()))?;
+        // comparing digests takes the same time whatever the token's length or contents
+        let (expected, actual) = (Sha256::digest(deploy_token), Sha256::digest(token));
+        let difference = expected
+            .iter()
+            .zip(actual.iter())
+            .fold(0u8, |difference, (a, b)| difference | (a ^ b));
+        match difference {
+            0 => Ok(()),
+            _ => Err(AdminError::Unauthorized("invalid token".to_string())),
+        }
+    }
+
+    /// Checks every field is set, the request is for this instance, and the version is
+    /// `latest` or `[v]MAJOR.MINOR.PATCH[-PRERELEASE]`, so it's safe to put in the build's S3 key,
+    /// or the name of a release for a rollback
+    fn validate(&self, instance_id: &str) -> Result<(), AdminError> {
+        for (name, value) in [
+            ("user_id", &self.user_id),
+            ("instance_id", &self.instance_id),
+            ("version", &self.version),
+        ] {
+            if value.trim().is_empty() {
+                return Err(AdminError::InvalidParameter(format!("{} is empty", name)));
+            }
+        }
+        if self.instance_id != instance_id {
+            return Err(AdminError::InvalidParameter(format!(
+                "instance_id {} is not this instance",
+                self.instance_id
+            )));
+        }
+        match self.action {
+            DeployAction::Deploy if !is_valid_version(&self.version) => {
+                Err(AdminError::InvalidParameter(format!(
+                    "version must be latest or MAJOR.MINOR.PATCH: {}",
+                    self.version
+                )))
+            }
+            DeployAction::Rollback if !is_valid_release(&self.version) => {
+                Err(AdminError::InvalidParameter(format!(
+                    "version must be a release name to roll back to: {}",
+                    self.version
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `version` is `latest` or a semver version, with an optional leading `v` and
+/// a prerelease of ASCII letters, digits, `.` and `-`
+fn is_valid_version(version: &str) -> bool {
+    if version == LATEST_VERSION {
+        return true;
+    }
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let (release, prerelease) = match version.split_once('-') {
+        Some((release, prerelease)) => (release, Some(prerelease)),
+        None => (version, None),
+    };
+    let numbers: Vec<&str> = release.split('.').collect();
+    numbers.len() == 3
+        && numbers
+            .iter()
+            .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        && prerelease.is_none_or(|prerelease| {
+            !prerelease.is_empty()
+                && prerelease
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+        })
+}
+
 #[derive(Debug, Serialize)]
 pub struct DeployResponse {
     success: bool,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Correlates the response with the deploy's log lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deploy_id: Option<String>,
+    /// The version the request asked for, once it has been read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    /// The release the installed binary is kept as, to roll back to later
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release: Option<String>,
 }
 
 impl DeployResponse {
@@ -34,6 +187,9 @@ impl DeployResponse {
             success: true,
             message,
             error: None,
+            deploy_id: None,
+            version: None,
+            release: None,
         }
     }
 
@@ -42,16 +198,50 @@ impl DeployResponse {
             success: false,
             message,
             error: Some(error),
+            deploy_id: None,
+            version: None,
+            release: None,
+        }
+    }
+}
+
+/// Whether `name` is a name [`Releases::name`] gives a build: a version other than `latest`,
+/// or `latest-` followed by the start of a SHA-256, so it's safe to put in a path
+fn is_valid_release(name: &str) -> bool {
+    match name.strip_prefix("latest-") {
+        Some(hash) => {
+            hash.len() == RELEASE_HASH_LEN
+                && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
         }
+        None => name != LATEST_VERSION && is_valid_version(name),
     }
 }
 
+/// Logs to stdout as one JSON object per line, at the levels `RUST_LOG` sets, e.g.
+/// `RUST_LOG=debug` or `RUST_LOG=hbuild_redploy=debug,aws_config=warn`, defaulting to `info`.
+///
+/// Each line of a deploy has the `deploy_id`, `user_id`, `cluster_id` and `version` of its
+/// deploy in its `span`, and the `stage` it was logged in among its fields.
+fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(false)
+        .init();
+}
+
 #[tokio::main]
 async fn main() -> Result<(), AdminError> {
-    println!("Starting helix build service");
+    init_logging();
+    info!(
+        service_version = env!("CARGO_PKG_VERSION"),
+        "Starting helix build service"
+    );
     // Initialize AWS SDK with explicit region configuration
     let bucket_region = std::env::var("S3_BUCKET_REGION").unwrap_or("us-west-1".to_string());
-    println!("Using S3 bucket region: {}", bucket_region);
+    info!(%bucket_region, "Using S3 bucket region");
 
     let config = aws_config::load_defaults(BehaviorVersion::latest())
         .await
@@ -60,121 +250,757 @@ async fn main() -> Result<(), AdminError> {
         .build();
     let s3_client = Client::new(&config);
 
-    println!("AWS region configured: {:?}", config.region());
+    info!(region = ?config.region(), "AWS region configured");
+
+    let health_check = HealthCheck::from_env()?;
+    let releases = Releases::from_env()?;
+    info!(dir = %releases.dir.display(), keep = releases.keep, "Keeping releases");
+    let download_backoff =
+        Backoff::from_env("S3_DOWNLOAD_ATTEMPTS", 3, "S3_RETRY_INTERVAL_MS", 500)?;
+    info!(
+        url = %format!("http://{}{}", health_check.host, health_check.path),
+        "Checking health"
+    );
 
-    let user_id = std::env::var("USER_ID").expect("USER_ID is not set");
-    let cluster_id = std::env::var("CLUSTER_ID").expect("CLUSTER_ID is not set");
+    let user_id = required_env("USER_ID")?;
+    let cluster_id = required_env("CLUSTER_ID")?;
+    let instance_id = required_env("INSTANCE_ID")?;
+    // every request has to carry it, so the service won't run commands for just anyone
+    let deploy_token = required_env("DEPLOY_TOKEN")?;
     // run server on specified port
-    let port = std::env::var("PORT").unwrap_or("6900".to_string());
-    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
+    let port = env_number("PORT", 6900)?;
+    let port = u16::try_from(port)
+        .map_err(|_| AdminError::InvalidParameter(format!("PORT must be a port: {}", port)))?;
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     let listener = TcpListener::bind(&addr).await.map_err(|e| {
-        eprintln!("Failed to bind to address {}: {}", addr, e);
+        error!(%addr, error = %e, "Failed to bind to address");
         AdminError::AdminConnectionError("Failed to bind to address".to_string(), e)
     })?;
 
-    println!("Server listening on {}", addr);
+    info!(%addr, "Server listening");
+
+    // only one deploy may move binaries around at a time
+    let deploy_lock = Arc::new(Mutex::new(()));
 
     loop {
         match listener.accept().await {
             Ok((mut conn, addr)) => {
-                println!("New connection from {}", addr);
+                let deploy_id = Uuid::new_v4().to_string();
+                let span = info_span!(
+                    "deploy",
+                    deploy_id = %deploy_id,
+                    user_id = %user_id,
+                    cluster_id = %cluster_id,
+                    action = field::Empty,
+                    version = field::Empty,
+                    %addr,
+                );
+                span.in_scope(|| info!("New connection"));
                 let s3_client_clone = s3_client.clone();
                 let user_id_clone = user_id.clone();
                 let cluster_id_clone = cluster_id.clone();
-                tokio::spawn(async move {
-                    // rename old binary
-                    Command::new("mv")
-                        .arg("helix")
-                        .arg("helix_old")
-                        .spawn()
-                        .unwrap();
-
-                    // pull binary from s3
-                    let response = s3_client_clone
-                        .get_object()
-                        .bucket("helix-build")
-                        .key(format!(
-                            "{}/{}/helix/latest",
-                            user_id_clone, cluster_id_clone
-                        ))
-                        .send()
-                        .await
-                        .unwrap();
-
-                    // create binary file or overwrite if it exists
-                    let mut file = File::create("helix").unwrap();
-                    let body = match response.body.collect().await {
-                        Ok(body) => body.to_vec(),
+                let instance_id = instance_id.clone();
+                let deploy_token = deploy_token.clone();
+                let deploy_lock = Arc::clone(&deploy_lock);
+                let service = Systemd {
+                    binary: Binary::in_dir("."),
+                    health_check: health_check.clone(),
+                };
+                let releases = releases.clone();
+                let handle = async move {
+                    // nothing is downloaded or moved for a request that isn't authorized and valid
+                    let request = match read_request(&mut conn).await.and_then(|request| {
+                        request.authenticate(&deploy_token)?;
+                        request.validate(&instance_id)?;
+                        Ok(request)
+                    }) {
+                        Ok(request) => request,
                         Err(e) => {
-                            eprintln!("Error collecting body: {:?}", e);
+                            let message = match e {
+                                AdminError::Unauthorized(_) => "Unauthorized deploy request",
+                                _ => "Invalid deploy request",
+                            };
+                            warn!(error = %e, "{}", message);
+                            let response = DeployResponse {
+                                deploy_id: Some(deploy_id),
+                                ..DeployResponse::error(message.to_string(), e.to_string())
+                            };
+                            if let Err(e) = send_response(&mut conn, &response).await {
+                                error!(error = %e, "Error sending deploy response");
+                            }
                             return;
                         }
                     };
-                    file.write_all(&body).unwrap();
-
-                    // set permissions
-                    Command::new("sudo")
-                        .arg("chmod")
-                        .arg("+x")
-                        .arg("helix")
-                        .spawn()
-                        .unwrap();
-
-                    // restart systemd service
-                    Command::new("sudo")
-                        .arg("systemctl")
-                        .arg("restart")
-                        .arg("helix")
-                        .spawn()
-                        .unwrap();
-
-                    // check if service is running
-                    let output = Command::new("sudo")
-                        .arg("systemctl")
-                        .arg("status")
-                        .arg("helix")
-                        .output()
-                        .unwrap();
-
-                    // if not revert
-                    if !output.status.success() {
-                        Command::new("mv")
-                            .arg("helix_old")
-                            .arg("helix")
-                            .spawn()
-                            .unwrap();
-
-                        Command::new("sudo")
-                            .arg("systemctl")
-                            .arg("restart")
-                            .arg("helix")
-                            .spawn()
-                            .unwrap();
-
-                        return;
-                    } else {
-                        // delete old binary
-                        Command::new("rm").arg("helix_old").spawn().unwrap();
+                    let span = tracing::Span::current();
+                    span.record("action", field::debug(request.action));
+                    span.record("version", request.version.as_str());
+
+                    // the guard is held until the deploy finishes or fails and released on drop
+                    let response = match deploy_lock.try_lock() {
+                        Ok(_guard) => {
+                            let result = match request.action {
+                                DeployAction::Deploy => {
+                                    deploy(
+                                        &s3_client_clone,
+                                        &user_id_clone,
+                                        &cluster_id_clone,
+                                        &request.version,
+                                        &releases,
+                                        &service,
+                                        &download_backoff,
+                                    )
+                                    .await
+                                }
+                                DeployAction::Rollback => {
+                                    rollback(&releases, &request.version, &service).await
+                                }
+                            };
+                            match result {
+                                Ok(response) => response,
+                                Err(e) => {
+                                    error!(error = %e, "Deploy failed");
+                                    DeployResponse::error(
+                                        "Deploy failed".to_string(),
+                                        e.to_string(),
+                                    )
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            warn!("Deploy rejected, another deploy is in progress");
+                            DeployResponse::error(
+                                "Deploy rejected".to_string(),
+                                "deploy already in progress".to_string(),
+                            )
+                        }
+                    };
+                    let response = DeployResponse {
+                        deploy_id: Some(deploy_id),
+                        version: Some(request.version),
+                        ..response
+                    };
+
+                    if let Err(e) = send_response(&mut conn, &response).await {
+                        error!(error = %e, "Error sending deploy response");
                     }
-                });
+                };
+                tokio::spawn(handle.instrument(span));
+            }
+            Err(e) => {
+                error!(error = ?e, "Error accepting connection");
             }
+        }
+    }
+}
+
+/// Reads the deploy request the client sends as JSON, giving up if it's malformed, larger than
+/// [`MAX_REQUEST_SIZE`] or not sent within [`REQUEST_TIMEOUT`].
+///
+/// The client may keep its side open for the response, so reading stops as soon as the bytes
+/// read so far hold the whole request.
+async fn read_request(conn: &mut TcpStream) -> Result<HBuildDeployRequest, AdminError> {
+    let read = async {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = conn.read(&mut chunk).await.map_err(|e| {
+                AdminError::AdminConnectionError("Failed to read request".to_string(), e)
+            })?;
+            buf.extend_from_slice(&chunk[..n]);
+            match sonic_rs::from_slice::<HBuildDeployRequest>(&buf) {
+                Ok(request) => return Ok(request),
+                Err(e) if e.is_eof() && n > 0 && buf.len() <= MAX_REQUEST_SIZE => continue,
+                Err(_) if buf.len() > MAX_REQUEST_SIZE => {
+                    return Err(AdminError::InvalidParameter(format!(
+                        "request is larger than {} bytes",
+                        MAX_REQUEST_SIZE
+                    )))
+                }
+                Err(e) => {
+                    return Err(AdminError::InvalidParameter(format!(
+                        "malformed request: {}",
+                        e
+                    )))
+                }
+            }
+        }
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, read)
+        .await
+        .unwrap_or_else(|_| {
+            Err(AdminError::InvalidParameter(
+                "request not sent in time".to_string(),
+            ))
+        })
+}
+
+/// Replaces the helix binary with `version`'s build from S3 and restarts the service,
+/// reverting to the previous binary if the service fails to come back up.
+///
+/// The build is only installed if its SHA-256 matches the `<version>.sha256` object next to it,
+/// which holds the hex digest optionally followed by the file name as written by `sha256sum`.
+/// Once the service is healthy on it, it's kept in `releases` to roll back to.
+///
+/// Logs each stage of the deploy in the current span, so its lines carry the deploy's id.
+///
+/// A failed install that was reverted is reported as an error response rather than an `AdminError`,
+/// which is only returned for steps that could not be carried out or reverted at all.
+async fn deploy(
+    s3_client: &Client,
+    user_id: &str,
+    cluster_id: &str,
+    version: &str,
+    releases: &Releases,
+    service: &impl Service,
+    download_backoff: &Backoff,
+) -> Result<DeployResponse, AdminError> {
+    // pull binary and its checksum from s3
+    let key = format!("{}/{}/helix/{}", user_id, cluster_id, version);
+    info!(stage = "download", %key, "Downloading build");
+    // a version that isn't in the bucket, e.g. a typo, leaves the current binary running
+    let Some(body) = download(s3_client, &key, download_backoff).await? else {
+        warn!(stage = "download", %key, "Build not found");
+        return Ok(DeployResponse::error(
+            format!("version {} not found in bucket", version),
+            format!("no object at {}", key),
+        ));
+    };
+    let checksum_key = format!("{}.sha256", key);
+    let Some(checksum) = download(s3_client, &checksum_key, download_backoff).await? else {
+        warn!(stage = "download", key = %checksum_key, "Checksum not found");
+        return Ok(DeployResponse::error(
+            format!("checksum for version {} not found in bucket", version),
+            format!("no object at {}", checksum_key),
+        ));
+    };
+
+    // refuse to install a binary that doesn't match its checksum, leaving the current one in place
+    let expected = String::from_utf8_lossy(&checksum)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let actual = format!("{:x}", Sha256::digest(&body));
+    if expected != actual {
+        error!(
+            stage = "download",
+            %expected,
+            %actual,
+            "Checksum mismatch, keeping previous binary"
+        );
+        return Ok(DeployResponse::error(
+            "Checksum mismatch, kept previous binary".to_string(),
+            format!("expected sha256 {}, got {}", expected, actual),
+        ));
+    }
+
+    info!(stage = "download", bytes = body.len(), sha256 = %actual, "Downloaded build");
+
+    if let Some(reverted) = activate(&body, service).await? {
+        return Ok(reverted);
+    }
+
+    let release = Releases::name(version, &actual);
+    releases.record(&release, &body);
+    info!(%release, "Deployed binary");
+    Ok(DeployResponse {
+        release: Some(release),
+        ..DeployResponse::success(format!("Deployed {} binary", version))
+    })
+}
+
+/// Replaces the helix binary with the kept release `name` and restarts the service, reverting
+/// to the previous binary like [`deploy`] if the service fails to come back up
+async fn rollback(
+    releases: &Releases,
+    name: &str,
+    service: &impl Service,
+) -> Result<DeployResponse, AdminError> {
+    info!(stage = "rollback", release = %name, "Rolling back to release");
+    let body = releases.read(name)?;
+
+    if let Some(reverted) = activate(&body, service).await? {
+        return Ok(reverted);
+    }
+
+    releases.record(name, &body);
+    info!(release = %name, "Rolled back");
+    Ok(DeployResponse {
+        release: Some(name.to_string()),
+        ..DeployResponse::success(format!("Rolled back to release {}", name))
+    })
+}
+
+/// Swaps `body` in for the binary `service` runs, keeping the old one aside until the service
+/// is healthy on the new one.
+///
+/// Returns the error response to send if the install failed and was reverted.
+async fn activate(
+    body: &[u8],
+    service: &impl Service,
+) -> Result<Option<DeployResponse>, AdminError> {
+    let binary = service.binary();
+    binary.set_aside()?;
+
+    // if the new binary can't be installed or the service doesn't come back up, revert
+    if let Err(e) = install(binary, body, service).await {
+        error!(stage = "revert", error = %e, "Install failed, reverting");
+        binary.restore()?;
+        service.restart().await?;
+        info!(stage = "revert", "Reverted to previous binary");
+        return Ok(Some(DeployResponse::error(
+            "Deploy failed, reverted to previous binary".to_string(),
+            e.to_string(),
+        )));
+    }
+
+    // the service is healthy on the new binary by now, so an old one left behind is only logged
+    if let Err(e) = binary.discard_old() {
+        warn!(stage = "install", error = %e, "Failed to remove old binary");
+    }
+    Ok(None)
+}
+
+/// Where the running binary is installed, and where the one it replaces is kept aside until
+/// the service is healthy on the new one
+#[derive(Clone, Debug)]
+struct Binary {
+    path: PathBuf,
+    old: PathBuf,
+}
+
+impl Binary {
+    /// `helix` in `dir`, with the old binary kept aside as `helix_old` next to it
+    fn in_dir(dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: dir.as_ref().join("helix"),
+            old: dir.as_ref().join("helix_old"),
+        }
+    }
+
+    fn set_aside(&self) -> Result<(), AdminError> {
+        std::fs::rename(&self.path, &self.old)
+            .map_err(|e| AdminError::FileError("Failed to move old binary".to_string(), e))
+    }
+
+    /// Writes `body` in place of the binary set aside and makes it executable
+    fn write(&self, body: &[u8]) -> Result<(), AdminError> {
+        // create binary file or overwrite if it exists
+        let mut file = File::create(&self.path)
+            .map_err(|e| AdminError::FileError("Failed to create binary".to_string(), e))?;
+        file.write_all(body)
+            .map_err(|e| AdminError::FileError("Failed to write binary".to_string(), e))?;
+        // close the file so the service can execute it
+        drop(file);
+        std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| AdminError::FileError("Failed to set permissions".to_string(), e))
+    }
+
+    /// Puts the binary set aside back
+    fn restore(&self) -> Result<(), AdminError> {
+        std::fs::rename(&self.old, &self.path)
+            .map_err(|e| AdminError::FileError("Failed to restore old binary".to_string(), e))
+    }
+
+    /// Removes the binary set aside, if there still is one
+    fn discard_old(&self) -> Result<(), AdminError> {
+        match std::fs::remove_file(&self.old) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(AdminError::FileError(
+                "Failed to remove old binary".to_string(),
+                e,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Binaries kept from successful deploys, each at `<dir>/<name>/helix`, so any of the last
+/// `keep` can be rolled back to
+#[derive(Clone, Debug)]
+struct Releases {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl Releases {
+    /// Reads `RELEASES_DIR` and `KEEP_RELEASES`, defaulting to keeping the last 3 in `releases`,
+    /// and always keeping at least the one running
+    fn from_env() -> Result<Self, AdminError> {
+        Ok(Self {
+            dir: PathBuf::from(std::env::var("RELEASES_DIR").unwrap_or("releases".to_string())),
+            keep: env_number("KEEP_RELEASES", 3)?.clamp(1, usize::MAX as u64) as usize,
+        })
+    }
+
+    /// The name a build of `version` is kept under, which is the version itself, except for
+    /// `latest`, which changes from one deploy to the next, so each of its builds is named by
+    /// the start of its SHA-256, e.g. `latest-3f2a9c0d41b7`
+    fn name(version: &str, sha256: &str) -> String {
+        match version {
+            LATEST_VERSION => format!("latest-{}", &sha256[..RELEASE_HASH_LEN]),
+            version => version.to_string(),
+        }
+    }
+
+    fn binary(&self, name: &str) -> PathBuf {
+        self.dir.join(name).join("helix")
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, AdminError> {
+        std::fs::read(self.binary(name))
+            .map_err(|e| AdminError::FileError(format!("Failed to read release {}", name), e))
+    }
+
+    /// Keeps `body` as the release `name`, marking it the most recently deployed, and removes
+    /// the least recently deployed releases past the last `keep`.
+    ///
+    /// The binary is already running by now, so a release that can't be kept is only logged.
+    fn record(&self, name: &str, body: &[u8]) {
+        let binary = self.binary(name);
+        // writing the binary, even unchanged for a rollback, dates it to this deploy
+        let written = std::fs::create_dir_all(self.dir.join(name))
+            .and_then(|()| std::fs::write(&binary, body));
+        if let Err(e) = written {
+            warn!(stage = "retain", release = %name, error = %e, "Failed to keep release");
+            return;
+        }
+
+        let mut kept = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let deployed = entry
+                        .path()
+                        .join("helix")
+                        .metadata()
+                        .ok()?
+                        .modified()
+                        .ok()?;
+                    Some((deployed, entry.path()))
+                })
+                .collect::<Vec<_>>(),
             Err(e) => {
-                eprintln!("Error accepting connection: {:?}", e);
+                warn!(stage = "retain", error = %e, "Failed to list releases");
+                return;
+            }
+        };
+        kept.sort_by_key(|(deployed, _)| std::cmp::Reverse(*deployed));
+        for (_, path) in kept.into_iter().skip(self.keep) {
+            let release = path.display();
+            match std::fs::remove_dir_all(&path) {
+                Ok(()) => info!(stage = "retain", %release, "Removed old release"),
+                Err(e) => {
+                    warn!(stage = "retain", %release, error = %e, "Failed to remove old release");
+                }
             }
         }
     }
 }
 
+/// Writes the new binary in place of the one set aside and restarts the service,
+/// waiting for each step so the health check sees the restarted service
+async fn install(binary: &Binary, body: &[u8], service: &impl Service) -> Result<(), AdminError> {
+    info!(stage = "install", "Installing build");
+    binary.write(body)?;
+
+    info!(stage = "restart", "Restarting service");
+    service.restart().await?;
+
+    // a running service may still crash before serving, so wait until it answers
+    info!(stage = "health", "Waiting for service to become healthy");
+    service.wait_until_healthy().await
+}
+
+/// The service running the helix binary, restarted onto each binary swapped in
+trait Service {
+    /// Where the binary the service runs is installed
+    fn binary(&self) -> &Binary;
+
+    /// Restarts the service, failing if it isn't running afterwards
+    async fn restart(&self) -> Result<(), AdminError>;
+
+    /// Waits until the restarted service is serving requests
+    async fn wait_until_healthy(&self) -> Result<(), AdminError>;
+}
+
+/// The `helix` systemd unit running `binary`, checked with `health_check` once it has restarted
+#[derive(Clone, Debug)]
+struct Systemd {
+    binary: Binary,
+    health_check: HealthCheck,
+}
+
+impl Service for Systemd {
+    fn binary(&self) -> &Binary {
+        &self.binary
+    }
+
+    async fn restart(&self) -> Result<(), AdminError> {
+        run(
+            Command::new("sudo")
+                .arg("systemctl")
+                .arg("restart")
+                .arg("helix"),
+            "Failed to restart service",
+        )
+        .await?;
+
+        // check if service is running
+        run(
+            Command::new("sudo")
+                .arg("systemctl")
+                .arg("status")
+                .arg("helix"),
+            "Service is not running after restart",
+        )
+        .await
+    }
+
+    async fn wait_until_healthy(&self) -> Result<(), AdminError> {
+        self.health_check.wait_until_healthy().await
+    }
+}
+
+/// Runs a command to completion, failing if it can't be started or exits unsuccessfully
+async fn run(command: &mut Command, message: &str) -> Result<(), AdminError> {
+    let status = command
+        .status()
+        .await
+        .map_err(|e| AdminError::CommandError(message.to_string(), e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(AdminError::CommandError(
+            message.to_string(),
+            std::io::Error::other(format!("exited with {}", status)),
+        )),
+    }
+}
+
+/// Where and how often to check that the restarted service is serving requests
+#[derive(Clone, Debug)]
+struct HealthCheck {
+    /// `host:port` to connect to
+    host: String,
+    path: String,
+    backoff: Backoff,
+}
+
+impl HealthCheck {
+    /// Reads `HEALTH_URL`, `HEALTH_CHECK_ATTEMPTS` and `HEALTH_CHECK_INTERVAL_MS`,
+    /// defaulting to 5 attempts of `http://127.0.0.1:$HELIX_PORT/health` starting 1s apart
+    fn from_env() -> Result<Self, AdminError> {
+        let url = std::env::var("HEALTH_URL").unwrap_or_else(|_| {
+            let port = std::env::var("HELIX_PORT").unwrap_or("6969".to_string());
+            format!("http://127.0.0.1:{}/health", port)
+        });
+        let backoff =
+            Backoff::from_env("HEALTH_CHECK_ATTEMPTS", 5, "HEALTH_CHECK_INTERVAL_MS", 1000)?;
+
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            AdminError::InvalidParameter(format!("HEALTH_URL must be an http:// url: {}", url))
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let host = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{}:80", host),
+        };
+
+        Ok(Self {
+            host,
+            path: path.to_string(),
+            backoff,
+        })
+    }
+
+    /// Polls the health url until it answers with a 2xx status, backing off between attempts
+    async fn wait_until_healthy(&self) -> Result<(), AdminError> {
+        let mut interval = self.backoff.interval;
+        let mut last_error = String::from("no attempts made");
+        for attempt in 1..=self.backoff.attempts {
+            tokio::time::sleep(interval).await;
+            match tokio::time::timeout(Duration::from_secs(5), self.check()).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => last_error = e.to_string(),
+                Err(_) => last_error = "timed out".to_string(),
+            }
+            warn!(
+                stage = "health",
+                attempt,
+                attempts = self.backoff.attempts,
+                error = %last_error,
+                "Health check failed"
+            );
+            interval *= 2;
+        }
+        Err(AdminError::HealthCheckError(format!(
+            "http://{}{} not healthy after {} attempts: {}",
+            self.host, self.path, self.backoff.attempts, last_error
+        )))
+    }
+
+    async fn check(&self) -> std::io::Result<()> {
+        let mut stream = TcpStream::connect(&self.host).await?;
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.path, self.host
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line).await?;
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(std::io::Error::other(format!(
+                "unhealthy response: {}",
+                status_line.trim()
+            ))),
+        }
+    }
+}
+
+/// How many times to try something and how long to wait between tries
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    attempts: u32,
+    /// Wait after the first failed attempt, doubled after each one after it
+    interval: Duration,
+}
+
+impl Backoff {
+    /// Reads the attempts and interval in milliseconds from env vars, making at least one attempt
+    fn from_env(
+        attempts_var: &str,
+        default_attempts: u64,
+        interval_var: &str,
+        default_interval_ms: u64,
+    ) -> Result<Self, AdminError> {
+        Ok(Self {
+            attempts: env_number(attempts_var, default_attempts)?.clamp(1, u32::MAX as u64) as u32,
+            interval: Duration::from_millis(env_number(interval_var, default_interval_ms)?),
+        })
+    }
+}
+
+/// Reads an env var the service can't run without, failing if it isn't set or is empty
+fn required_env(name: &str) -> Result<String, AdminError> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| AdminError::InvalidParameter(format!("{} is not set", name)))
+}
+
+/// Reads a number from an env var, using `default` if it isn't set
+fn env_number(name: &str, default: u64) -> Result<u64, AdminError> {
+    match std::env::var(name) {
+        Ok(value) => value.parse().map_err(|_| {
+            AdminError::InvalidParameter(format!("{} must be a number: {}", name, value))
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Why an object couldn't be downloaded from the build bucket
+#[derive(Debug)]
+enum DownloadError {
+    /// There's no object at the key, which retrying won't change
+    NotFound,
+    Failed(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Downloads an object from the build bucket, retrying failed attempts with backoff, or `None`
+/// if the bucket has no object at `key`.
+///
+/// Each attempt collects the body from scratch, so a retry never appends to a partial download.
+async fn download(
+    s3_client: &Client,
+    key: &str,
+    backoff: &Backoff,
+) -> Result<Option<Vec<u8>>, AdminError> {
+    let mut interval = backoff.interval;
+    let mut attempt = 1;
+    loop {
+        match download_once(s3_client, key).await {
+            Ok(body) => return Ok(Some(body)),
+            Err(DownloadError::NotFound) => return Ok(None),
+            Err(DownloadError::Failed(e)) if attempt < backoff.attempts => {
+                warn!(
+                    stage = "download",
+                    %key,
+                    attempt,
+                    attempts = backoff.attempts,
+                    retry_in = ?interval,
+                    error = %e,
+                    "Download failed, retrying"
+                );
+                tokio::time::sleep(interval).await;
+                interval *= 2;
+                attempt += 1;
+            }
+            Err(DownloadError::Failed(e)) => {
+                return Err(AdminError::S3DownloadError(
+                    format!("Failed to download {} after {} attempts", key, attempt),
+                    e,
+                ));
+            }
+        }
+    }
+}
+
+async fn download_once(s3_client: &Client, key: &str) -> Result<Vec<u8>, DownloadError> {
+    let response = match s3_client
+        .get_object()
+        .bucket("helix-build")
+        .key(key)
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) if is_not_found(&e) => return Err(DownloadError::NotFound),
+        Err(e) => return Err(DownloadError::Failed(e.into())),
+    };
+    let body = response
+        .body
+        .collect()
+        .await
+        .map_err(|e| DownloadError::Failed(e.into()))?;
+    Ok(body.to_vec())
+}
+
+/// Whether S3 answered that there's no such object, either as a `NoSuchKey` error or a bare 404
+fn is_not_found(error: &SdkError<GetObjectError, HttpResponse>) -> bool {
+    error
+        .as_service_error()
+        .is_some_and(GetObjectError::is_no_such_key)
+        || error
+            .raw_response()
+            .is_some_and(|response| response.status().as_u16() == 404)
+}
+
+/// Writes the outcome of a deploy back to the client that requested it as JSON
+async fn send_response(conn: &mut TcpStream, response: &DeployResponse) -> Result<(), AdminError> {
+    let body = sonic_rs::to_vec(response).map_err(|e| {
+        AdminError::AdminConnectionError("Failed to serialize response".to_string(), e.into())
+    })?;
+    conn.write_all(&body)
+        .await
+        .map_err(|e| AdminError::AdminConnectionError("Failed to write response".to_string(), e))?;
+    conn.flush()
+        .await
+        .map_err(|e| AdminError::AdminConnectionError("Failed to write response".to_string(), e))
+}
+
 #[derive(Debug)]
 pub enum AdminError {
     AdminConnectionError(String, std::io::Error),
-    S3DownloadError(
-        String,
-        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
-    ),
+    S3DownloadError(String, Box<dyn std::error::Error + Send + Sync>),
     CommandError(String, std::io::Error),
     FileError(String, std::io::Error),
     InvalidParameter(String),
+    HealthCheckError(String),
+    Unauthorized(String),
 }
 
 impl std::fmt::Display for AdminError {
@@ -187,6 +1013,8 @@ impl std::fmt::Display for AdminError {
             AdminError::CommandError(msg, err) => write!(f, "Command error: {}: {}", msg, err),
             AdminError::FileError(msg, err) => write!(f, "File error: {}: {}", msg, err),
             AdminError::InvalidParameter(msg) => write!(f, "Invalid parameter: {}", msg),
+            AdminError::HealthCheckError(msg) => write!(f, "Health check failed: {}", msg),
+            AdminError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
         }
     }
 }
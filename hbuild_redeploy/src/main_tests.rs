@@ -0,0 +1,243 @@
+use super::{
+    activate, rollback, AdminError, Binary, DeployAction, HBuildDeployRequest, Releases, Service,
+};
+use std::{cell::RefCell, os::unix::fs::PermissionsExt, path::Path};
+use tempfile::TempDir;
+
+/// A service that records the binary it was restarted on each time, and is healthy on
+/// any binary but `unhealthy`
+struct FakeService<'a> {
+    binary: &'a Binary,
+    unhealthy: &'a [u8],
+    restarted_on: RefCell<Vec<Vec<u8>>>,
+}
+
+impl<'a> FakeService<'a> {
+    fn new(binary: &'a Binary, unhealthy: &'a [u8]) -> Self {
+        Self {
+            binary,
+            unhealthy,
+            restarted_on: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn restarted_on(&self) -> Vec<Vec<u8>> {
+        self.restarted_on.borrow().clone()
+    }
+}
+
+impl Service for FakeService<'_> {
+    fn binary(&self) -> &Binary {
+        self.binary
+    }
+
+    async fn restart(&self) -> Result<(), AdminError> {
+        let running = std::fs::read(&self.binary.path)
+            .map_err(|e| AdminError::FileError("no binary to restart on".to_string(), e))?;
+        self.restarted_on.borrow_mut().push(running);
+        Ok(())
+    }
+
+    async fn wait_until_healthy(&self) -> Result<(), AdminError> {
+        match self.restarted_on.borrow().last() {
+            Some(running) if running != self.unhealthy => Ok(()),
+            _ => Err(AdminError::HealthCheckError("not healthy".to_string())),
+        }
+    }
+}
+
+/// A directory holding a running binary with `body`
+fn setup_binary(body: &[u8]) -> (Binary, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let binary = Binary::in_dir(temp_dir.path());
+    std::fs::write(&binary.path, body).unwrap();
+    (binary, temp_dir)
+}
+
+fn releases(dir: &Path, keep: usize) -> Releases {
+    Releases {
+        dir: dir.join("releases"),
+        keep,
+    }
+}
+
+#[tokio::test]
+async fn test_activate_swaps_in_new_binary() {
+    let (binary, _temp_dir) = setup_binary(b"old");
+    let service = FakeService::new(&binary, b"");
+
+    assert!(activate(b"new", &service).await.unwrap().is_none());
+    assert_eq!(std::fs::read(&binary.path).unwrap(), b"new");
+    let mode = std::fs::metadata(&binary.path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o111, 0o111);
+    assert!(!binary.old.exists());
+    assert_eq!(service.restarted_on(), vec![b"new".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_activate_reverts_when_unhealthy() {
+    let (binary, _temp_dir) = setup_binary(b"old");
+    let service = FakeService::new(&binary, b"broken");
+
+    let reverted = activate(b"broken", &service).await.unwrap().unwrap();
+    assert!(!reverted.success);
+    assert_eq!(std::fs::read(&binary.path).unwrap(), b"old");
+    assert!(!binary.old.exists());
+    assert_eq!(
+        service.restarted_on(),
+        vec![b"broken".to_vec(), b"old".to_vec()]
+    );
+}
+
+#[tokio::test]
+async fn test_activate_without_running_binary_changes_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let binary = Binary::in_dir(temp_dir.path());
+    let service = FakeService::new(&binary, b"");
+
+    assert!(matches!(
+        activate(b"new", &service).await,
+        Err(AdminError::FileError(_, _))
+    ));
+    assert!(!binary.path.exists());
+    assert!(service.restarted_on().is_empty());
+}
+
+#[test]
+fn test_discard_old_ignores_missing_binary() {
+    let (binary, _temp_dir) = setup_binary(b"old");
+    binary.set_aside().unwrap();
+    binary.discard_old().unwrap();
+    assert!(!binary.old.exists());
+    binary.discard_old().unwrap();
+}
+
+#[tokio::test]
+async fn test_rollback_installs_kept_release() {
+    let (binary, temp_dir) = setup_binary(b"2.0.0");
+    let releases = releases(temp_dir.path(), 3);
+    releases.record("1.0.0", b"1.0.0");
+    releases.record("2.0.0", b"2.0.0");
+    let service = FakeService::new(&binary, b"");
+
+    let response = rollback(&releases, "1.0.0", &service).await.unwrap();
+    assert!(response.success);
+    assert_eq!(response.release.as_deref(), Some("1.0.0"));
+    assert_eq!(std::fs::read(&binary.path).unwrap(), b"1.0.0");
+    assert_eq!(service.restarted_on(), vec![b"1.0.0".to_vec()]);
+
+    // a release that was never kept is refused before the running binary is touched
+    assert!(matches!(
+        rollback(&releases, "0.9.0", &service).await,
+        Err(AdminError::FileError(_, _))
+    ));
+    assert_eq!(std::fs::read(&binary.path).unwrap(), b"1.0.0");
+    assert_eq!(service.restarted_on().len(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_rollback_keeps_running_binary() {
+    let (binary, temp_dir) = setup_binary(b"2.0.0");
+    let releases = releases(temp_dir.path(), 3);
+    releases.record("1.0.0", b"1.0.0");
+    let service = FakeService::new(&binary, b"1.0.0");
+
+    let reverted = rollback(&releases, "1.0.0", &service).await.unwrap();
+    assert!(!reverted.success);
+    assert_eq!(std::fs::read(&binary.path).unwrap(), b"2.0.0");
+    assert_eq!(
+        service.restarted_on(),
+        vec![b"1.0.0".to_vec(), b"2.0.0".to_vec()]
+    );
+}
+
+/// A request from `user` to `instance-1` carrying the token `secret`
+fn deploy_request(action: DeployAction, version: &str) -> HBuildDeployRequest {
+    HBuildDeployRequest {
+        token: Some("secret".to_string()),
+        user_id: "user".to_string(),
+        instance_id: "instance-1".to_string(),
+        action,
+        version: version.to_string(),
+    }
+}
+
+fn is_invalid(result: Result<(), AdminError>) -> bool {
+    matches!(result, Err(AdminError::InvalidParameter(_)))
+}
+
+#[test]
+fn test_validate_accepts_versions_and_releases() {
+    for version in ["latest", "1.0.113", "v2.10.0", "1.0.0-rc.1"] {
+        assert!(
+            deploy_request(DeployAction::Deploy, version).validate("instance-1").is_ok(),
+            "{}",
+            version
+        );
+    }
+    for release in ["1.0.113", "latest-3f2a9c0d41b7"] {
+        assert!(
+            deploy_request(DeployAction::Rollback, release).validate("instance-1").is_ok(),
+            "{}",
+            release
+        );
+    }
+}
+
+#[test]
+fn test_validate_rejects_unsafe_versions() {
+    for version in ["", " ", "../latest", "1.0", "1.0.0/x", "1.a.0", "1.0.0-", "1.0.0-rc 1"] {
+        assert!(
+            is_invalid(deploy_request(DeployAction::Deploy, version).validate("instance-1")),
+            "{:?}",
+            version
+        );
+    }
+    // `latest` changes from one deploy to the next, so only a named build of it is kept
+    for release in ["latest", "latest-3F2A9C0D41B7", "latest-3f2a", "latest-../../etc"] {
+        assert!(
+            is_invalid(deploy_request(DeployAction::Rollback, release).validate("instance-1")),
+            "{:?}",
+            release
+        );
+    }
+}
+
+#[test]
+fn test_validate_rejects_other_instances_and_empty_fields() {
+    let request = deploy_request(DeployAction::Deploy, "latest");
+    assert!(is_invalid(request.validate("instance-2")));
+
+    let mut request = deploy_request(DeployAction::Deploy, "latest");
+    request.user_id = String::new();
+    assert!(is_invalid(request.validate("instance-1")));
+
+    let request: HBuildDeployRequest =
+        sonic_rs::from_str(r#"{"user_id":"user","instance_id":""}"#).unwrap();
+    assert_eq!((request.action, request.version.as_str()), (DeployAction::Deploy, "latest"));
+    assert!(is_invalid(request.validate("")));
+}
+
+#[test]
+fn test_authenticate_requires_matching_token() {
+    let is_unauthorized = |result: Result<(), AdminError>| {
+        matches!(result, Err(AdminError::Unauthorized(_)))
+    };
+    let mut request = deploy_request(DeployAction::Deploy, "latest");
+    assert!(request.authenticate("secret").is_ok());
+    assert!(is_unauthorized(request.authenticate("secret2")));
+    assert!(is_unauthorized(request.authenticate("")));
+
+    request.token = Some(String::new());
+    assert!(is_unauthorized(request.authenticate("secret")));
+    request.token = None;
+    assert!(is_unauthorized(request.authenticate("secret")));
+}
+
+#[test]
+fn test_token_left_out_of_debug_output() {
+    let request = deploy_request(DeployAction::Deploy, "latest");
+    let debug = format!("{:?}", request);
+    assert!(!debug.contains("secret"));
+    assert!(debug.contains("<redacted>"));
+}
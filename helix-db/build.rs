@@ -0,0 +1,49 @@
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Runs git in the crate's directory, or `None` if it isn't installed or this isn't a checkout
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !stdout.trim().is_empty()).then(|| stdout.trim().to_string())
+}
+
+fn main() {
+    // the build timestamp is kept fresh by rerunning on any change to the crate,
+    // and the commit by rerunning when the checked out commit changes
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=HELIX_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if let Some(head) = git(&["rev-parse", "--git-path", "HEAD"]) {
+        println!("cargo:rerun-if-changed={}", head);
+    }
+    if let Some(branch) = git(&["symbolic-ref", "-q", "HEAD"])
+        && let Some(branch) = git(&["rev-parse", "--git-path", &branch])
+    {
+        println!("cargo:rerun-if-changed={}", branch);
+    }
+
+    // set when building outside a checkout, e.g. from a source archive in a container
+    let commit = env::var("HELIX_GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=HELIX_GIT_COMMIT={}", commit);
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let built_at = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+    println!("cargo:rustc-env=HELIX_BUILD_TIMESTAMP={}", built_at);
+}
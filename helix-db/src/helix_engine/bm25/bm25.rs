@@ -4,7 +4,11 @@ use std::{borrow::Cow, collections::HashMap};
 
 use crate::{
     helix_engine::{
-        storage_core::storage_core::HelixGraphStorage,
+        storage_core::{
+            setup::{OpenOrCreate, SetupTxn},
+            storage_core::HelixGraphStorage,
+            timestamps::RESERVED_PROPERTIES,
+        },
         types::GraphError,
         vector_core::{hnsw::HNSW, vector::HVector},
     },
@@ -62,32 +66,32 @@ pub struct HBM25Config {
 }
 
 impl HBM25Config {
-    pub fn new(graph_env: &Env, wtxn: &mut RwTxn) -> Result<HBM25Config, GraphError> {
+    pub fn new(graph_env: &Env, txn: &mut SetupTxn) -> Result<HBM25Config, GraphError> {
         let inverted_index_db: Database<Bytes, Bytes> = graph_env
             .database_options()
             .types::<Bytes, Bytes>()
             .flags(heed3::DatabaseFlags::DUP_SORT)
             .name(DB_BM25_INVERTED_INDEX)
-            .create(wtxn)?;
+            .open_or_create(txn)?;
 
         let doc_lengths_db: Database<U128<heed3::byteorder::BE>, U32<heed3::byteorder::BE>> =
             graph_env
                 .database_options()
                 .types::<U128<heed3::byteorder::BE>, U32<heed3::byteorder::BE>>()
                 .name(DB_BM25_DOC_LENGTHS)
-                .create(wtxn)?;
+                .open_or_create(txn)?;
 
         let term_frequencies_db: Database<Bytes, U32<heed3::byteorder::BE>> = graph_env
             .database_options()
             .types::<Bytes, U32<heed3::byteorder::BE>>()
             .name(DB_BM25_TERM_FREQUENCIES)
-            .create(wtxn)?;
+            .open_or_create(txn)?;
 
         let metadata_db: Database<Bytes, Bytes> = graph_env
             .database_options()
             .types::<Bytes, Bytes>()
             .name(DB_BM25_METADATA)
-            .create(wtxn)?;
+            .open_or_create(txn)?;
 
         Ok(HBM25Config {
             graph_env: graph_env.clone(),
@@ -411,9 +415,13 @@ pub trait BM25Flatten {
 }
 
 impl BM25Flatten for HashMap<String, Value> {
+    /// Timestamps set by the engine aren't searchable text, so they're left out
     fn flatten_bm25(&self) -> String {
         let mut s = String::with_capacity(self.len() * 2);
         for (k, v) in self.iter() {
+            if RESERVED_PROPERTIES.contains(&k.as_str()) {
+                continue;
+            }
             s.push_str(&k);
             s.push_str(&v.to_string());
         }
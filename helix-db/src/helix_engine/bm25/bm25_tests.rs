@@ -2,7 +2,8 @@
 mod tests {
     use crate::helix_engine::bm25::bm25::{BM25Metadata, HBM25Config, HybridSearch, BM25};
     use crate::helix_engine::{
-        graph_core::config::Config, storage_core::storage_core::HelixGraphStorage,
+        graph_core::config::Config,
+        storage_core::{setup::SetupTxn, storage_core::HelixGraphStorage},
     };
     use heed3::{Env, EnvOpenOptions};
     use tempfile::tempdir;
@@ -24,9 +25,9 @@ mod tests {
 
     fn setup_bm25_config() -> (HBM25Config, tempfile::TempDir) {
         let (env, temp_dir) = setup_test_env();
-        let mut wtxn = env.write_txn().unwrap();
-        let config = HBM25Config::new(&env, &mut wtxn).unwrap();
-        wtxn.commit().unwrap();
+        let mut txn = SetupTxn::Write(env.write_txn().unwrap());
+        let config = HBM25Config::new(&env, &mut txn).unwrap();
+        txn.commit().unwrap();
         (config, temp_dir)
     }
 
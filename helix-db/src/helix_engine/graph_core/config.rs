@@ -1,4 +1,4 @@
-use crate::helix_engine::types::GraphError;
+use crate::helix_engine::{storage_core::compression::Compression, types::GraphError};
 use std::{
     path::PathBuf,
     fmt,
@@ -15,6 +15,53 @@ pub struct VectorConfig {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GraphConfig {
     pub secondary_indices: Option<Vec<String>>,
+    /// Most hops any traversal may take, whatever depth the caller asks for. Unlimited if unset
+    #[serde(default)]
+    pub max_traversal_depth: Option<usize>,
+    /// Clamp caller depths over `max_traversal_depth` to it rather than rejecting them
+    #[serde(default)]
+    pub clamp_traversal_depth: bool,
+    /// Most nodes the graph may hold. Unlimited if unset
+    #[serde(default)]
+    pub max_nodes: Option<u64>,
+    /// Most edges the graph may hold. Unlimited if unset
+    #[serde(default)]
+    pub max_edges: Option<u64>,
+}
+
+/// Tuning for the LMDB environment the graph is stored in, which keeps its defaults if unset
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Most read txns that can be open at once
+    pub max_readers: u32,
+    /// Skip flushing to disk when a txn commits, for write-heavy loads.
+    /// A system crash can then lose the most recent commits
+    pub no_sync: bool,
+    /// Turn off the OS reading ahead of what is asked for, which keeps memory for the pages
+    /// actually used when the database is much larger than RAM
+    pub no_read_ahead: bool,
+    /// How node and edge values are compressed, which is off by default
+    pub compression: Compression,
+    /// Open an existing database without write access, e.g. on a replica.
+    /// Every write then fails with `GraphError::ReadOnly`
+    pub read_only: bool,
+}
+
+impl StorageConfig {
+    pub const DEFAULT_MAX_READERS: u32 = 200;
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            max_readers: Self::DEFAULT_MAX_READERS,
+            no_sync: false,
+            no_read_ahead: false,
+            compression: Compression::None,
+            read_only: false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,6 +73,11 @@ pub struct Config {
     pub schema: Option<String>,
     pub embedding_model: Option<String>,
     pub graphvis_node_label: Option<String>,
+    /// Path of the append-only operation log, which is disabled if unset
+    #[serde(default)]
+    pub oplog_path: Option<String>,
+    #[serde(default)]
+    pub storage_config: StorageConfig,
 }
 
 impl Config {
@@ -46,12 +98,18 @@ impl Config {
             },
             graph_config: GraphConfig {
                 secondary_indices: None,
+                max_traversal_depth: None,
+                clamp_traversal_depth: false,
+                max_nodes: None,
+                max_edges: None,
             },
             db_max_size_gb: Some(db_max_size_gb),
             mcp: true,
             schema,
             embedding_model,
             graphvis_node_label,
+            oplog_path: None,
+            storage_config: StorageConfig::default(),
         }
     }
 
@@ -109,12 +167,18 @@ impl Default for Config {
             },
             graph_config: GraphConfig {
                 secondary_indices: None,
+                max_traversal_depth: None,
+                clamp_traversal_depth: false,
+                max_nodes: None,
+                max_edges: None,
             },
             db_max_size_gb: Some(10),
             mcp: true,
             schema: None,
             embedding_model: Some("text-embedding-ada-002".to_string()),
             graphvis_node_label: None,
+            oplog_path: None,
+            storage_config: StorageConfig::default(),
         }
     }
 }
@@ -129,7 +193,9 @@ impl fmt::Display for Config {
             mcp: {:?}\n
             schema: {:?}\n
             embedding_model: {:?}\n
-            graphvis_node_label: {:?}",
+            graphvis_node_label: {:?}\n
+            oplog_path: {:?}\n
+            storage_config: {:?}",
             self.vector_config.m,
             self.vector_config.ef_construction,
             self.vector_config.ef_search,
@@ -139,6 +205,8 @@ impl fmt::Display for Config {
             self.schema,
             self.embedding_model,
             self.graphvis_node_label,
+            self.oplog_path,
+            self.storage_config,
         )
     }
 }
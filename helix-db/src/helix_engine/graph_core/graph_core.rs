@@ -1,8 +1,48 @@
+use crate::helix_engine::query::ast::{Query, QueryResult};
+use crate::helix_engine::storage_core::aggregate::{AggregateOp, AggregateResult};
+use crate::helix_engine::storage_core::changes::{ChangeEvent, ChangeFilter};
+use crate::helix_engine::storage_core::csv::{CsvImportSummary, CsvMethods};
+use crate::helix_engine::storage_core::direction::EdgeDirection;
+use crate::helix_engine::storage_core::jsonl::JsonlMethods;
+use crate::helix_engine::storage_core::oplog::OpLog;
+use crate::helix_engine::storage_core::schema::FieldSchema;
 use crate::helix_engine::storage_core::storage_core::HelixGraphStorage;
+use crate::helix_engine::storage_core::subgraph::Subgraph;
+use crate::helix_engine::storage_core::storage_methods::{AliasMethods, CountMethods, Direction, StorageMethods};
+use crate::helix_engine::storage_core::timestamps::{
+    now_millis, stamp_created, stamp_updated, UPDATED_AT,
+};
 use crate::helix_engine::types::GraphError;
+use crate::helix_engine::vector_core::{
+    hnsw::HNSW,
+    vector::HVector,
+    vector_core::HNSWConfig,
+};
 use crate::helix_gateway::mcp::mcp::{McpBackend, McpConnections};
+use crate::protocol::value::Value;
+use crate::utils::id::v6_uuid;
+use crate::utils::items::{Edge, Node};
+use heed3::{RoTxn, RwTxn};
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use std::ops::Bound;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
 use crate::helix_engine::graph_core::config::Config;
+use crate::helix_engine::graph_core::ops::{
+    g::G,
+    source::{
+        add_n::AddNAdapter,
+        e_from_type::EFromTypeAdapter,
+        n_from_type::NFromTypeAdapter,
+    },
+    tr_val::TraversalVal,
+    util::{
+        paginate::{Page, PaginateAdapter, Paged},
+        predicate::Predicate,
+    },
+};
 
 #[derive(Debug)]
 pub enum QueryInput {
@@ -12,6 +52,29 @@ pub enum QueryInput {
     BooleanValue { value: bool },
 }
 
+/// The label and properties of a node to add in a batch
+pub type NewNode<'a> = (&'a str, Option<Vec<(String, Value)>>);
+
+/// An edge to insert in a batch with [`HelixGraphEngine::insert_edges`]
+#[derive(Debug, Clone)]
+pub struct EdgeInput<'a> {
+    pub label: &'a str,
+    pub properties: Option<Vec<(String, Value)>>,
+    pub from: u128,
+    pub to: u128,
+    pub direction: EdgeDirection,
+}
+
+/// Whether [`HelixGraphEngine::upsert_node`] inserted a node or updated one that existed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    Created,
+    Updated,
+}
+
+/// Nodes [`HelixGraphEngine::delete_nodes_where`] drops in each write txn
+pub const DELETE_BATCH_SIZE: usize = 1000;
+
 pub struct HelixGraphEngine {
     pub storage: Arc<HelixGraphStorage>,
     pub mcp_backend: Option<Arc<McpBackend>>,
@@ -55,6 +118,945 @@ impl HelixGraphEngine {
         })
     }
 
+    /// Opens the existing database at `path` without write access, e.g. on a replica serving reads.
+    ///
+    /// Every write fails with `GraphError::ReadOnly`. LMDB readers see the latest commit
+    /// each time a read txn starts, so an engine opened read-only on the same data file a
+    /// primary writes to keeps up with it without any catching up. Set
+    /// [`StorageConfig::read_only`](crate::helix_engine::graph_core::config::StorageConfig::read_only)
+    /// to open read-only with a config of its own.
+    pub fn open_read_only(path: &str) -> Result<HelixGraphEngine, GraphError> {
+        let mut config = Config::default();
+        config.storage_config.read_only = true;
+        Self::new(HelixGraphEngineOpts {
+            path: path.to_string(),
+            config,
+        })
+    }
+
+    /// Writes a consistent point-in-time backup of the graph to the directory at `path`
+    /// while writes continue. See [`HelixGraphStorage::snapshot`] for the on-disk format.
+    pub fn snapshot(&self, path: &str) -> Result<(), GraphError> {
+        self.storage.snapshot(path)
+    }
+
+    /// Rebuilds a data directory at `target_path` from a backup written by [`HelixGraphEngine::snapshot`].
+    ///
+    /// Open an engine on `target_path` afterwards to use the restored graph.
+    pub fn restore(backup_path: &str, target_path: &str) -> Result<(), GraphError> {
+        HelixGraphStorage::restore(backup_path, target_path)
+    }
+
+    /// Streams every node and then every edge to `writer` as JSON lines from a single read txn.
+    ///
+    /// Returns the number of lines written.
+    pub fn export_jsonl(&self, writer: impl Write) -> Result<usize, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.export_jsonl(&txn, writer)
+    }
+
+    /// Imports the nodes and edges of a JSON lines export, keeping their ids.
+    ///
+    /// Everything is written in one txn, so if any line fails nothing is imported.
+    /// Returns the number of nodes and edges imported.
+    pub fn import_jsonl(&self, reader: impl Read) -> Result<(usize, usize), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let imported = self.storage.import_jsonl(&mut txn, BufReader::new(reader))?;
+        self.storage.commit(txn)?;
+        Ok(imported)
+    }
+
+    /// Imports nodes and edges from a JSON array of export records, keeping their ids.
+    ///
+    /// Elements are parsed and inserted one at a time in a single txn, so memory use stays flat
+    /// however large the array is. Fails without importing anything if more than `max_size` bytes are read.
+    /// Returns the number of nodes and edges imported.
+    pub fn import_json_array(
+        &self,
+        reader: impl Read,
+        max_size: Option<usize>,
+    ) -> Result<(usize, usize), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let imported = self
+            .storage
+            .import_json_array(&mut txn, BufReader::new(reader), max_size)?;
+        self.storage.commit(txn)?;
+        Ok(imported)
+    }
+
+    /// Imports a node with `label` for each row of a CSV file with a header row,
+    /// with a property for each column and its id from the `id_column`.
+    ///
+    /// Rows that are malformed or can't be inserted are skipped and listed in the summary
+    /// by line, and the rest are imported in a single txn.
+    /// See [`CsvMethods::import_csv_nodes`] for how ids and fields are read.
+    pub fn import_csv_nodes(
+        &self,
+        reader: impl Read,
+        label: &str,
+        id_column: &str,
+    ) -> Result<CsvImportSummary, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let summary =
+            self.storage
+                .import_csv_nodes(&mut txn, BufReader::new(reader), label, id_column)?;
+        self.storage.commit(txn)?;
+        Ok(summary)
+    }
+
+    /// Imports a directed edge for each row of a CSV file with a header row, labelled by its
+    /// `type_column` and between the nodes its `from_column` and `to_column` name by the ids
+    /// they were imported with by [`HelixGraphEngine::import_csv_nodes`].
+    ///
+    /// Rows are skipped and reported the same way, including rows whose nodes don't exist.
+    pub fn import_csv_edges(
+        &self,
+        reader: impl Read,
+        type_column: &str,
+        from_column: &str,
+        to_column: &str,
+    ) -> Result<CsvImportSummary, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let summary = self.storage.import_csv_edges(
+            &mut txn,
+            BufReader::new(reader),
+            type_column,
+            from_column,
+            to_column,
+        )?;
+        self.storage.commit(txn)?;
+        Ok(summary)
+    }
+
+    /// Replays the operation log at `path` into this engine, normally a fresh one, in a single write txn.
+    ///
+    /// Replay is idempotent so a log can be replayed over a graph it was already applied to.
+    /// The engine's own log, if it has one, can't be the log being replayed.
+    /// Returns the number of operations applied.
+    pub fn rebuild_from_log(&self, path: &str) -> Result<usize, GraphError> {
+        if let Some(oplog) = &self.storage.oplog
+            && std::fs::canonicalize(&oplog.path)? == std::fs::canonicalize(path)?
+        {
+            return Err(GraphError::New(
+                "Cannot replay an engine's own operation log into it".to_string(),
+            ));
+        }
+
+        let mut txn = self.storage.write_txn()?;
+        let mut applied = 0;
+        for operation in OpLog::read(path)? {
+            self.storage.apply_operation(&mut txn, operation?)?;
+            applied += 1;
+        }
+        self.storage.commit(txn)?;
+        Ok(applied)
+    }
+
+    /// Merges `patch` into a node's properties in a single read-modify-write txn.
+    ///
+    /// Keys set to `Value::Empty` are removed, and `updated_at` is set to the current unix millis.
+    /// Returns `GraphError::NodeNotFound` rather than creating the node if it doesn't exist.
+    pub fn update_node_properties(
+        &self,
+        id: u128,
+        mut patch: HashMap<String, Value>,
+    ) -> Result<Node, GraphError> {
+        stamp_updated(&mut patch, now_millis());
+        let mut txn = self.storage.write_txn()?;
+        let label = self.storage.get_node(&txn, &id)?.label;
+        self.storage.create_timestamp_index(&mut txn, &label)?;
+        let node = self.storage.update_node_properties(&mut txn, &id, patch)?;
+        self.storage.commit(txn)?;
+        Ok(node)
+    }
+
+    /// Inserts a node with `id` if there isn't one, or merges `properties` into the one there is,
+    /// in a single write txn, so concurrent upserts of the same id never both insert it.
+    ///
+    /// An inserted node gets `label` and both timestamps, and an updated node keeps its label and
+    /// `created_at`, with the merge working like [`HelixGraphEngine::update_node_properties`].
+    /// Either way indices and constraints are kept in sync, and if one fails nothing is written.
+    pub fn upsert_node(
+        &self,
+        id: u128,
+        label: &str,
+        mut properties: HashMap<String, Value>,
+    ) -> Result<Upserted, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let upserted = match self.storage.get_node(&txn, &id) {
+            Ok(node) => {
+                stamp_updated(&mut properties, now_millis());
+                self.storage.create_timestamp_index(&mut txn, &node.label)?;
+                self.storage
+                    .update_node_properties(&mut txn, &id, properties)?;
+                Upserted::Updated
+            }
+            Err(GraphError::NodeNotFound) => {
+                // removing a key from a node that doesn't exist yet leaves it out
+                properties.retain(|_, value| !matches!(value, Value::Empty));
+                stamp_created(&mut properties, now_millis());
+                self.storage.create_timestamp_index(&mut txn, label)?;
+                let node = Node {
+                    id,
+                    label: label.to_string(),
+                    properties: Some(properties),
+                };
+                self.storage.insert_node_with_id(&mut txn, &node)?;
+                Upserted::Created
+            }
+            Err(e) => return Err(e),
+        };
+        self.storage.commit(txn)?;
+        Ok(upserted)
+    }
+
+    /// Gets the distinct neighbors of a node with their properties in a single read txn.
+    ///
+    /// An empty `labels` slice follows edges of every label.
+    pub fn neighbors_with_props(
+        &self,
+        node_id: u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage
+            .neighbors_with_props(&txn, &node_id, direction, labels, limit)
+    }
+
+    /// Gets the distinct neighbors of a node for which `predicate` holds, like
+    /// [`HelixGraphEngine::neighbors_with_props`] but filtered in the engine, with `limit`
+    /// counting only the neighbors that match.
+    ///
+    /// Fails with `GraphError::TraversalError` if the predicate compares a neighbor's property
+    /// with a value of another type.
+    pub fn neighbors_where(
+        &self,
+        node_id: u128,
+        direction: Direction,
+        labels: &[&str],
+        predicate: &Predicate,
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage
+            .neighbors_where(&txn, &node_id, direction, labels, predicate, limit)
+    }
+
+    /// Gets the nodes with `label` for which `predicate` holds, in id order, in a single read
+    /// txn that stops once `limit` have matched.
+    ///
+    /// An `Eq`, `Gt` or `Lt` with a number only reads the nodes a range index on the property
+    /// finds, if it has one, which leaves out nodes whose value for it isn't a number. Otherwise
+    /// the nodes with the label are scanned.
+    ///
+    /// Fails with `GraphError::TraversalError` if the predicate compares a node's property
+    /// with a value of another type.
+    pub fn nodes_where(
+        &self,
+        label: &str,
+        predicate: &Predicate,
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut matched = Vec::new();
+        if let Some(ids) = self.range_candidates(&txn, label, predicate)? {
+            for id in ids {
+                if matched.len() >= limit {
+                    break;
+                }
+                let node = match self.storage.get_node(&txn, &id) {
+                    Ok(node) => node,
+                    Err(GraphError::NodeNotFound) => continue,
+                    Err(e) => return Err(e),
+                };
+                if predicate.matches(&node)? {
+                    matched.push(node);
+                }
+            }
+            return Ok(matched);
+        }
+        for node in G::new(Arc::clone(&self.storage), &txn).n_from_type(label) {
+            if matched.len() >= limit {
+                break;
+            }
+            if let TraversalVal::Node(node) = node?
+                && predicate.matches(&node)?
+            {
+                matched.push(node);
+            }
+        }
+        Ok(matched)
+    }
+
+    /// The ids of the nodes with `label` a range index finds for `predicate`, in id order,
+    /// or `None` if there's no index to narrow them down with
+    fn range_candidates(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        predicate: &Predicate,
+    ) -> Result<Option<Vec<u128>>, GraphError> {
+        let (property, literal) = match predicate {
+            Predicate::Eq(property, literal)
+            | Predicate::Gt(property, literal)
+            | Predicate::Lt(property, literal) => (property, literal),
+            _ => return Ok(None),
+        };
+        let Some(bound) = literal.as_f64().filter(|bound| !bound.is_nan()) else {
+            return Ok(None);
+        };
+        if !self.storage.range_indexed_properties(txn, label)?.contains(property) {
+            return Ok(None);
+        }
+        // the bounds are inclusive, so `Gt` and `Lt` are left to the predicate at the bound
+        let (min, max) = match predicate {
+            Predicate::Gt(..) => (Some(bound), None),
+            Predicate::Lt(..) => (None, Some(bound)),
+            _ => (Some(bound), Some(bound)),
+        };
+        let mut ids = self.storage.node_ids_in_range(txn, label, property, min, max)?;
+        ids.sort_unstable();
+        Ok(Some(ids))
+    }
+
+    /// Gets the neighbors of a node in a single read txn, each along with the edge connecting it,
+    /// e.g. to read the `weight` of each edge.
+    ///
+    /// An empty `labels` slice follows edges of every label.
+    pub fn neighbors_with_edges(
+        &self,
+        node_id: u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<(Edge, Node)>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage
+            .neighbors_with_edges(&txn, &node_id, direction, labels, limit)
+    }
+
+    /// Counts the edges of a node going `direction` in a single read txn, without reading them.
+    ///
+    /// See [`StorageMethods::degree`] for how edges are counted.
+    pub fn degree(&self, node_id: u128, direction: Direction) -> Result<u64, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.degree(&txn, &node_id, direction, &[])
+    }
+
+    /// Counts the edges of a node with `label` going `direction`, e.g. its outgoing `FOLLOWS` edges.
+    pub fn degree_by_type(
+        &self,
+        node_id: u128,
+        direction: Direction,
+        label: &str,
+    ) -> Result<u64, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.degree(&txn, &node_id, direction, &[label])
+    }
+
+    /// Extracts the nodes within `radius` hops of any of `seeds` and the edges between them from
+    /// a single read txn, e.g. to draw the neighborhood of a few nodes.
+    ///
+    /// `edge_filter` is the labels of the edges to follow, all of them if it's empty, and
+    /// `max_nodes` caps the size of the subgraph. See [`HelixGraphStorage::extract_subgraph`].
+    pub fn extract_subgraph(
+        &self,
+        seeds: &[u128],
+        radius: usize,
+        edge_filter: &[&str],
+        max_nodes: Option<usize>,
+    ) -> Result<Subgraph, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage
+            .extract_subgraph(&txn, seeds, radius, edge_filter, max_nodes)
+    }
+
+    /// Runs a query such as `MATCH (a)-[FOLLOWS]->(b) WHERE a.name = "x" RETURN b` in a single
+    /// read txn, returning a row for every way its pattern matches.
+    ///
+    /// See [`Query::parse`] for the syntax. Fails with `GraphError::InvalidQuery` if it doesn't parse.
+    pub fn query(&self, query: &str) -> Result<QueryResult, GraphError> {
+        let query = Query::parse(query)?;
+        let txn = self.storage.graph_env.read_txn()?;
+        query.execute(&self.storage, &txn)
+    }
+
+    /// Runs a query like [`HelixGraphEngine::query`], returning one page of its rows.
+    ///
+    /// Pass the returned `next_cursor` back in the next `Page` to continue from where this one stopped.
+    pub fn query_page(&self, query: &str, page: &Page) -> Result<Paged<QueryResult>, GraphError> {
+        let query = Query::parse(query)?;
+        let txn = self.storage.graph_env.read_txn()?;
+        query.execute_page(&self.storage, &txn, page)
+    }
+
+    /// Gets an edge with its properties.
+    ///
+    /// Returns `GraphError::EdgeNotFound` if it doesn't exist or a node at either end has expired.
+    pub fn get_edge(&self, id: u128) -> Result<Edge, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.get_edge(&txn, &id)
+    }
+
+    /// Gets a page of the nodes with the given label, in id order.
+    ///
+    /// Pass the returned `next_cursor` back in the next `Page` to continue from where this one stopped.
+    pub fn nodes_page(&self, label: &str, page: &Page) -> Result<Paged<Vec<Node>>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        let paged = G::new(Arc::clone(&self.storage), &txn)
+            .n_from_type_after(label, page.after()?)
+            .paginate(page)?;
+        Ok(Paged {
+            items: paged
+                .items
+                .into_iter()
+                .filter_map(|item| match item {
+                    TraversalVal::Node(node) => Some(node),
+                    _ => None,
+                })
+                .collect(),
+            next_cursor: paged.next_cursor,
+        })
+    }
+
+    /// Gets a page of the edges with the given label, in id order.
+    pub fn edges_page(&self, label: &str, page: &Page) -> Result<Paged<Vec<Edge>>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        let paged = G::new(Arc::clone(&self.storage), &txn)
+            .e_from_type_after(label, page.after()?)
+            .paginate(page)?;
+        Ok(Paged {
+            items: paged
+                .items
+                .into_iter()
+                .filter_map(|item| match item {
+                    TraversalVal::Edge(edge) => Some(edge),
+                    _ => None,
+                })
+                .collect(),
+            next_cursor: paged.next_cursor,
+        })
+    }
+
+    /// Points an alias at a node, replacing its previous target in a single write txn.
+    ///
+    /// Returns the node id the alias pointed at before, if any.
+    pub fn set_alias(&self, name: &str, node_id: u128) -> Result<Option<u128>, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let previous = self.storage.set_alias(&mut txn, name, &node_id)?;
+        self.storage.commit(txn)?;
+        Ok(previous)
+    }
+
+    /// Gets the node id an alias currently points at
+    pub fn resolve_alias(&self, name: &str) -> Result<u128, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.resolve_alias(&txn, name)
+    }
+
+    /// Exchanges the targets of two aliases atomically
+    pub fn swap_aliases(&self, first: &str, second: &str) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.swap_aliases(&mut txn, first, second)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Removes an alias without touching the node it pointed at
+    pub fn drop_alias(&self, name: &str) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.drop_alias(&mut txn, name)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Makes `property` unique across the nodes with `label`.
+    ///
+    /// Once created, adding or updating a node so it shares the value another node already holds
+    /// fails with `GraphError::UniqueViolation`.
+    /// Fails the same way, without creating the constraint, if existing nodes already share a value.
+    pub fn create_unique_constraint(&self, label: &str, property: &str) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.create_unique_constraint(&mut txn, label, property)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Registers the properties allowed on the nodes and edges with `label`.
+    ///
+    /// Once registered, adding or updating an item with the label fails with
+    /// `GraphError::SchemaViolation` if a required property is missing, a property has the wrong
+    /// type, or a property isn't in the schema. Labels without a schema accept any properties.
+    /// Registering again replaces the schema, and fails if existing items don't match the new one.
+    pub fn register_schema(&self, label: &str, fields: &[FieldSchema]) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.register_schema(&mut txn, label, fields)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Creates an ordered index over the numeric values of `property` on the nodes with `label`
+    /// so [`HelixGraphEngine::find_nodes_in_range`] doesn't have to scan them.
+    pub fn create_range_index(&self, label: &str, property: &str) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.create_range_index(&mut txn, label, property)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Gets the nodes with `label` whose `property` is between `min` and `max`,
+    /// in ascending order of the value, with a single bounded scan of its range index.
+    ///
+    /// Both bounds are inclusive and either can be left open.
+    /// Returns `GraphError::New` if [`HelixGraphEngine::create_range_index`] wasn't called
+    /// for the property.
+    pub fn find_nodes_in_range(
+        &self,
+        label: &str,
+        property: &str,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<Vec<Node>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        let ids = self
+            .storage
+            .node_ids_in_range(&txn, label, property, min, max)?;
+        self.storage.get_nodes(&txn, &ids)
+    }
+
+    /// Creates an inverted index over the lowercased whitespace separated tokens of the string
+    /// `property` on the nodes with `label`, for [`HelixGraphEngine::search_text`].
+    pub fn create_text_index(&self, label: &str, property: &str) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.create_text_index(&mut txn, label, property)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Gets the ids of the nodes with `label` whose `property` shares a token with `query`,
+    /// ranked by how many of the query's tokens they contain.
+    ///
+    /// Returns `GraphError::New` if [`HelixGraphEngine::create_text_index`] wasn't called
+    /// for the property.
+    pub fn search_text(
+        &self,
+        label: &str,
+        property: &str,
+        query: &str,
+    ) -> Result<Vec<u128>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.search_text(&txn, label, property, query)
+    }
+
+    /// Combines `property` across the nodes with `label` without returning the nodes.
+    ///
+    /// Nodes without the property are skipped, and aren't counted towards the average.
+    pub fn aggregate(
+        &self,
+        label: &str,
+        property: &str,
+        op: AggregateOp,
+    ) -> Result<AggregateResult, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.aggregate(&txn, label, property, op)
+    }
+
+    /// Inserts a node and returns its id.
+    ///
+    /// Without an `id` a new one is generated and checked against the existing nodes inside the
+    /// write txn, so it can never collide. Generated ids are time-ordered v6 UUIDs rather than
+    /// random v4 ones, like every id the engine generates, as nodes are appended to the nodes
+    /// table in id order. An `id` that already belongs to a node fails with
+    /// `GraphError::New` rather than overwriting it.
+    ///
+    /// The node's `created_at` and `updated_at` are set to the current unix millis.
+    pub fn insert_node(
+        &self,
+        id: Option<u128>,
+        label: &str,
+        properties: Option<HashMap<String, Value>>,
+    ) -> Result<u128, GraphError> {
+        self.insert_node_expiring(id, label, properties, None)
+    }
+
+    /// Inserts a node like [`HelixGraphEngine::insert_node`] that expires once `ttl` has passed.
+    ///
+    /// From then on the node is absent from reads, traversals and counts, as are its edges.
+    /// They are dropped by the next write txn, or by [`HelixGraphEngine::purge_expired`].
+    /// The expiry is kept apart from the node's properties, see
+    /// [`HelixGraphStorage::expires_at`].
+    pub fn insert_node_with_ttl(
+        &self,
+        id: Option<u128>,
+        label: &str,
+        properties: Option<HashMap<String, Value>>,
+        ttl: Duration,
+    ) -> Result<u128, GraphError> {
+        let expires_at = now_millis().saturating_add(ttl.as_millis() as u64);
+        self.insert_node_expiring(id, label, properties, Some(expires_at))
+    }
+
+    /// Drops every node that has expired, along with its edges, returning how many were dropped.
+    ///
+    /// Every write txn does this first, so this only needs calling to reclaim space from
+    /// a graph that isn't being written to.
+    pub fn purge_expired(&self) -> Result<usize, GraphError> {
+        let (txn, purged) = self.storage.open_write_txn()?;
+        self.storage.commit(txn)?;
+        Ok(purged)
+    }
+
+    fn insert_node_expiring(
+        &self,
+        id: Option<u128>,
+        label: &str,
+        properties: Option<HashMap<String, Value>>,
+        expires_at: Option<u64>,
+    ) -> Result<u128, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let exists = |txn: &RwTxn, id: u128| -> Result<bool, GraphError> {
+            Ok(self
+                .storage
+                .nodes_db
+                .get(txn, HelixGraphStorage::node_key(&id))?
+                .is_some())
+        };
+        let id = match id {
+            Some(id) if exists(&txn, id)? => {
+                return Err(GraphError::New(format!(
+                    "Node {} already exists",
+                    Uuid::from_u128(id)
+                )));
+            }
+            Some(id) => id,
+            None => loop {
+                let id = v6_uuid();
+                if !exists(&txn, id)? {
+                    break id;
+                }
+            },
+        };
+
+        let mut properties = properties.unwrap_or_default();
+        stamp_created(&mut properties, now_millis());
+        self.storage.create_timestamp_index(&mut txn, label)?;
+
+        let node = Node {
+            id,
+            label: label.to_string(),
+            properties: Some(properties),
+        };
+        self.storage.insert_node_with_id(&mut txn, &node)?;
+        if let Some(expires_at) = expires_at {
+            self.storage.set_expiry(&mut txn, id, label, expires_at)?;
+        }
+        self.storage.commit(txn)?;
+        Ok(id)
+    }
+
+    /// Gets the nodes with `label` inserted or updated after the unix millis `since`,
+    /// oldest change first, for syncing changes incrementally.
+    ///
+    /// Only nodes written through [`HelixGraphEngine::insert_node`],
+    /// [`HelixGraphEngine::update_node_properties`] or [`HelixGraphEngine::upsert_node`]
+    /// carry the `updated_at` this reads.
+    pub fn nodes_modified_after(&self, label: &str, since: u64) -> Result<Vec<Node>, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        // the index is created by the first timestamped write to the label
+        if !self
+            .storage
+            .range_indexed_properties(&txn, label)?
+            .iter()
+            .any(|property| property == UPDATED_AT)
+        {
+            return Ok(Vec::new());
+        }
+        let ids = self.storage.node_ids_in_range(
+            &txn,
+            label,
+            UPDATED_AT,
+            Some(since.saturating_add(1) as f64),
+            None,
+        )?;
+        self.storage.get_nodes(&txn, &ids)
+    }
+
+    /// Subscribes to the changes matching `filter`, each sent once the txn making it commits.
+    ///
+    /// Only txns committed through [`HelixGraphStorage::commit`] are published, which the
+    /// engine's own writes and generated queries are. Writers never wait on a subscriber:
+    /// one that falls [`SUBSCRIPTION_CAPACITY`](crate::helix_engine::storage_core::changes::SUBSCRIPTION_CAPACITY)
+    /// events behind misses newer ones until it catches up. Dropping a node publishes a delete
+    /// for each edge dropped along with it, before the node's own.
+    pub fn subscribe(&self, filter: ChangeFilter) -> flume::Receiver<ChangeEvent> {
+        self.storage.changes.subscribe(filter)
+    }
+
+    /// Adds a batch of nodes in a single write txn, each given as its label and properties.
+    ///
+    /// The whole batch is checked against the graph's `max_nodes` before anything is written,
+    /// so a batch that would exceed it returns `GraphError::QuotaExceeded` and adds nothing.
+    pub fn add_nodes(
+        &self,
+        nodes: Vec<NewNode<'_>>,
+    ) -> Result<Vec<Node>, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.check_quota(&txn, nodes.len() as u64, 0)?;
+
+        let mut added = Vec::with_capacity(nodes.len());
+        for (label, properties) in nodes {
+            match G::new_mut(Arc::clone(&self.storage), &mut txn)
+                .add_n(label, properties, None)
+                .next()
+            {
+                Some(Ok(TraversalVal::Node(node))) => added.push(node),
+                Some(Err(e)) => return Err(e),
+                _ => return Err(GraphError::New("Failed to add node".to_string())),
+            }
+        }
+        self.storage.commit(txn)?;
+        Ok(added)
+    }
+
+    /// Inserts an edge from `from` to `to`, or joining them both ways if it is undirected,
+    /// checking that both nodes exist.
+    ///
+    /// Fails with `GraphError::DanglingEdge` if either node is missing or has expired.
+    /// See [`EdgeDirection`] for how undirected edges are stored and traversed.
+    pub fn insert_edge(
+        &self,
+        label: &str,
+        properties: Option<HashMap<String, Value>>,
+        from: u128,
+        to: u128,
+        direction: EdgeDirection,
+    ) -> Result<Edge, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let edge = self
+            .insert_checked_edge(&mut txn, label, properties, from, to, direction)
+            .map_err(|e| match e {
+                GraphError::DanglingEdge(missing) => {
+                    GraphError::DanglingEdge(format!("{} edge has no {}", label, missing))
+                }
+                e => e,
+            })?;
+        self.storage.commit(txn)?;
+        Ok(edge)
+    }
+
+    /// Inserts a batch of edges in a single write txn, checking in it that the nodes at both
+    /// ends of each one exist.
+    ///
+    /// If any endpoint is missing or has expired the whole batch fails with
+    /// `GraphError::DanglingEdge`, naming the edge by its index in `edges`, and nothing is written.
+    /// Like [`HelixGraphEngine::add_nodes`] the batch is checked against `max_edges` up front.
+    pub fn insert_edges(&self, edges: &[EdgeInput<'_>]) -> Result<Vec<Edge>, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        self.storage.check_quota(&txn, 0, edges.len() as u64)?;
+
+        let mut inserted = Vec::with_capacity(edges.len());
+        for (index, input) in edges.iter().enumerate() {
+            let properties = input
+                .properties
+                .as_ref()
+                .map(|props| props.iter().cloned().collect());
+            let edge = self
+                .insert_checked_edge(
+                    &mut txn,
+                    input.label,
+                    properties,
+                    input.from,
+                    input.to,
+                    input.direction,
+                )
+                .map_err(|e| match e {
+                    GraphError::DanglingEdge(missing) => GraphError::DanglingEdge(format!(
+                        "edge {} ({}) has no {}",
+                        index, input.label, missing
+                    )),
+                    e => e,
+                })?;
+            inserted.push(edge);
+        }
+        self.storage.commit(txn)?;
+        Ok(inserted)
+    }
+
+    /// Inserts an edge with a new id once both of its nodes are found, failing with
+    /// `GraphError::DanglingEdge` naming the end that isn't
+    fn insert_checked_edge(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        mut properties: Option<HashMap<String, Value>>,
+        from: u128,
+        to: u128,
+        direction: EdgeDirection,
+    ) -> Result<Edge, GraphError> {
+        for (end, id) in [("from", from), ("to", to)] {
+            match self.storage.get_node(txn, &id) {
+                Ok(_) => {}
+                Err(GraphError::NodeNotFound) => {
+                    return Err(GraphError::DanglingEdge(format!(
+                        "{} node {}",
+                        end,
+                        Uuid::from_u128(id)
+                    )));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        direction.mark(&mut properties);
+        let edge = Edge {
+            id: v6_uuid(),
+            label: label.to_string(),
+            from_node: from,
+            to_node: to,
+            properties,
+        };
+        self.storage.insert_edge_with_id(txn, &edge)?;
+        Ok(edge)
+    }
+
+    /// Drops a node along with all of its edges.
+    ///
+    /// Returns `GraphError::NodeNotFound` if the node doesn't exist.
+    pub fn drop_node(&self, id: u128) -> Result<(), GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        if self.storage.nodes_db.get(&txn, HelixGraphStorage::node_key(&id))?.is_none() {
+            return Err(GraphError::NodeNotFound);
+        }
+        self.storage.drop_node(&mut txn, &id)?;
+        self.storage.commit(txn)?;
+        Ok(())
+    }
+
+    /// Drops every node with `label` for which all of `predicates` hold, along with their edges,
+    /// returning how many were dropped.
+    ///
+    /// The nodes are found and dropped [`DELETE_BATCH_SIZE`] at a time, each batch in its own
+    /// write txn, so a large delete doesn't block other writers for its whole length. If a batch
+    /// fails, the ones before it stay dropped.
+    ///
+    /// Fails with `GraphError::New` if `predicates` is empty, rather than dropping every node
+    /// with the label, and with `GraphError::TraversalError` if one compares a node's property
+    /// with a value of another type, see [`Predicate`].
+    pub fn delete_nodes_where(
+        &self,
+        label: &str,
+        predicates: &[Predicate],
+    ) -> Result<usize, GraphError> {
+        if predicates.is_empty() {
+            return Err(GraphError::New(
+                "delete_nodes_where needs at least one predicate".to_string(),
+            ));
+        }
+        let mut deleted = 0;
+        let mut after = Bound::Unbounded;
+        loop {
+            let mut txn = self.storage.write_txn()?;
+            let mut ids = Vec::with_capacity(DELETE_BATCH_SIZE);
+            for node in self.storage.scan_nodes(&txn, (after, Bound::Unbounded))? {
+                let node = node?;
+                if node.label != label {
+                    continue;
+                }
+                if Predicate::all_match(predicates, &node)? {
+                    ids.push(node.id);
+                    if ids.len() == DELETE_BATCH_SIZE {
+                        break;
+                    }
+                }
+            }
+            for id in &ids {
+                self.storage.drop_node(&mut txn, id)?;
+            }
+            self.storage.commit(txn)?;
+            deleted += ids.len();
+            // the scan stopped short of the end only if the batch filled up
+            match ids.last() {
+                Some(last) if ids.len() == DELETE_BATCH_SIZE => after = Bound::Excluded(*last),
+                _ => return Ok(deleted),
+            }
+        }
+    }
+
+    /// Gets the number of nodes in the graph from the counts kept of them, scanning only the
+    /// nodes that have expired without being purged yet, which aren't counted
+    pub fn node_count(&self) -> Result<u64, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.node_count(&txn)
+    }
+
+    /// Gets the number of nodes with the given label from the counts kept of them, scanning only
+    /// the nodes that have expired without being purged yet, which aren't counted
+    pub fn node_count_by_label(&self, label: &str) -> Result<u64, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.node_count_by_label(&txn, label)
+    }
+
+    /// Gets the number of edges in the graph from the count kept of them, scanning only the edges
+    /// of nodes that have expired without being purged yet, which aren't counted
+    pub fn edge_count(&self) -> Result<u64, GraphError> {
+        let txn = self.storage.graph_env.read_txn()?;
+        self.storage.edge_count(&txn)
+    }
+
+    /// Builds the hnsw index from scratch with the given settings and persists them alongside the graph.
+    ///
+    /// Every stored vector must have `dim` dimensions, and later inserts and searches are held to it.
+    /// Returns the number of vectors indexed.
+    pub fn build_vector_index(
+        &self,
+        dim: usize,
+        m: usize,
+        ef_construction: usize,
+    ) -> Result<usize, GraphError> {
+        let current = self.storage.vectors.config();
+        let mut config = HNSWConfig::new(Some(m), Some(ef_construction), Some(current.ef));
+        config.dimension = Some(dim);
+        self.reindex_vectors(config)
+    }
+
+    /// Rebuilds the hnsw index with its current settings, e.g. after a bulk load
+    pub fn rebuild_vector_index(&self) -> Result<usize, GraphError> {
+        self.reindex_vectors(self.storage.vectors.config())
+    }
+
+    fn reindex_vectors(&self, config: HNSWConfig) -> Result<usize, GraphError> {
+        let mut txn = self.storage.write_txn()?;
+        let count = self.storage.vectors.rebuild_index(&mut txn, config)?;
+        self.storage.commit(txn)?;
+        // searches read the settings committed along with the index, the in memory copy is
+        // only for inserts before the next rebuild
+        self.storage.vectors.set_config(config);
+        Ok(count)
+    }
+
+    /// Gets the k nearest vectors to `query`.
+    ///
+    /// Uses the hnsw index when one exists, falling back to an exact scan otherwise.
+    /// `ef_search` overrides the configured candidate list size for this query.
+    pub fn knn(
+        &self,
+        query: &[f64],
+        k: usize,
+        ef_search: Option<usize>,
+    ) -> Result<Vec<HVector>, GraphError> {
+        type F = fn(&HVector, &heed3::RoTxn) -> bool;
+        let txn = self.storage.graph_env.read_txn()?;
+        let vectors = &self.storage.vectors;
+        let results = match (vectors.has_index(&txn)?, ef_search) {
+            (true, Some(ef)) => vectors.search_ef::<F>(&txn, query, k, ef, None, false)?,
+            (true, None) => vectors.search::<F>(&txn, query, k, None, false)?,
+            (false, _) => vectors.brute_force_search(&txn, query, k)?,
+        };
+        Ok(results)
+    }
+
     // @xav, delete this?
 
     //     let ast: Source = match HelixParser::parse_source(query.as_str()) {
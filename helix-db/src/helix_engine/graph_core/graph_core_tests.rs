@@ -0,0 +1,2631 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    io::{BufReader, Read},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use heed3::{RoTxn, RwTxn};
+use sonic_rs::JsonValueTrait;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tempfile::TempDir;
+
+use super::{
+    config::{Config, StorageConfig},
+    graph_core::{DELETE_BATCH_SIZE, EdgeInput, HelixGraphEngine, HelixGraphEngineOpts, Upserted},
+    ops::{
+        g::G,
+        source::{
+            add_e::{AddEAdapter, EdgeType},
+            add_n::AddNAdapter,
+            e_from_type::EFromTypeAdapter,
+            n_from_alias::NFromAliasAdapter,
+            n_from_id::NFromIdAdapter,
+            n_from_index::NFromIndexAdapter,
+            n_from_type::NFromTypeAdapter,
+        },
+        tr_val::{Traversable, TraversalVal},
+        util::{paginate::Page, predicate::Predicate, update::UpdateAdapter},
+    },
+};
+use crate::{
+    helix_engine::{
+        storage_core::{
+            aggregate::{AggregateOp, AggregateResult},
+            changes::{ChangeFilter, ChangeKind, ItemKind, SUBSCRIPTION_CAPACITY},
+            compression::Compression,
+            csv::csv_node_id,
+            oplog::{OpLog, Operation},
+            direction::{EdgeDirection, UNDIRECTED},
+            schema::{FieldSchema, FieldType},
+            timestamps::{now_millis, CREATED_AT, UPDATED_AT},
+            storage_methods::{CountMethods, Direction, StorageMethods},
+            subgraph::Subgraph,
+        },
+        types::GraphError,
+        vector_core::{hnsw::HNSW, vector::HVector},
+    },
+    props,
+    protocol::value::Value,
+    utils::{
+        filterable::Filterable,
+        items::{Edge, Node},
+        json_stream::for_each_array_element,
+    },
+};
+
+fn setup_test_engine() -> (HelixGraphEngine, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    (HelixGraphEngine::new(opts).unwrap(), temp_dir)
+}
+
+fn open_engine(path: &str) -> HelixGraphEngine {
+    let opts = HelixGraphEngineOpts {
+        path: path.to_string(),
+        config: Config::default(),
+    };
+    HelixGraphEngine::new(opts).unwrap()
+}
+
+fn random_vector(rng: &mut StdRng, dim: usize) -> Vec<f64> {
+    (0..dim).map(|_| rng.random_range(-1.0..1.0)).collect()
+}
+
+fn insert_vectors(engine: &HelixGraphEngine, rng: &mut StdRng, dim: usize, n: usize) {
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    for _ in 0..n {
+        engine
+            .storage
+            .vectors
+            .insert::<fn(&HVector, &RoTxn) -> bool>(&mut txn, &random_vector(rng, dim), None)
+            .unwrap();
+    }
+    txn.commit().unwrap();
+}
+
+fn add_person(engine: &HelixGraphEngine, name: &str) -> u128 {
+    let mut txn = engine.storage.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n("person", Some(props! { "name" => name }), None)
+        .collect_to_val();
+    engine.storage.commit(txn).unwrap();
+    node.id()
+}
+
+#[test]
+fn test_resolve_alias() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let blue = add_person(&engine, "blue");
+
+    assert_eq!(engine.set_alias("current", blue).unwrap(), None);
+    assert_eq!(engine.resolve_alias("current").unwrap(), blue);
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let nodes = G::new(Arc::clone(&engine.storage), &txn)
+        .n_from_alias("current")
+        .collect_to::<Vec<_>>();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].id(), blue);
+}
+
+#[test]
+fn test_alias_errors() {
+    let (engine, _temp_dir) = setup_test_engine();
+
+    assert!(matches!(
+        engine.resolve_alias("missing"),
+        Err(GraphError::AliasNotFound)
+    ));
+    assert!(matches!(
+        engine.set_alias("dangling", 42),
+        Err(GraphError::NodeNotFound)
+    ));
+    assert!(matches!(
+        engine.drop_alias("missing"),
+        Err(GraphError::AliasNotFound)
+    ));
+}
+
+#[test]
+fn test_swap_aliases() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let blue = add_person(&engine, "blue");
+    let green = add_person(&engine, "green");
+
+    engine.set_alias("live", blue).unwrap();
+    engine.set_alias("staging", green).unwrap();
+    engine.swap_aliases("live", "staging").unwrap();
+
+    assert_eq!(engine.resolve_alias("live").unwrap(), green);
+    assert_eq!(engine.resolve_alias("staging").unwrap(), blue);
+
+    engine.drop_alias("staging").unwrap();
+    assert!(engine.resolve_alias("staging").is_err());
+}
+
+#[test]
+fn test_repoint_alias_no_missed_reads() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let engine = Arc::new(engine);
+    let blue = add_person(&engine, "blue");
+    let green = add_person(&engine, "green");
+    engine.set_alias("live", blue).unwrap();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let engine = Arc::clone(&engine);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            let mut reads = 0;
+            while !done.load(Ordering::Relaxed) {
+                let txn = engine.storage.graph_env.read_txn().unwrap();
+                let node = G::new(Arc::clone(&engine.storage), &txn)
+                    .n_from_alias("live")
+                    .collect_to_obj();
+                match node {
+                    TraversalVal::Node(node) => assert!(node.id == blue || node.id == green),
+                    other => panic!("alias did not resolve to a node: {:?}", other),
+                }
+                reads += 1;
+            }
+            reads
+        })
+    };
+
+    for i in 0..200 {
+        let target = if i % 2 == 0 { green } else { blue };
+        engine.set_alias("live", target).unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+
+    assert!(reader.join().unwrap() > 0);
+    assert_eq!(engine.resolve_alias("live").unwrap(), blue);
+}
+
+#[test]
+fn test_knn_recall_against_exact_scan() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut rng = StdRng::seed_from_u64(7);
+    let (dim, k, n, queries) = (16, 10, 500, 10);
+    insert_vectors(&engine, &mut rng, dim, n);
+    assert_eq!(engine.build_vector_index(dim, 16, 128).unwrap(), n);
+
+    let mut recalls = Vec::new();
+    for ef in [k, 32, 128] {
+        let mut hits = 0;
+        for _ in 0..queries {
+            let query = random_vector(&mut rng, dim);
+            let txn = engine.storage.graph_env.read_txn().unwrap();
+            let exact = engine
+                .storage
+                .vectors
+                .brute_force_search(&txn, &query, k)
+                .unwrap();
+            drop(txn);
+            let approx = engine.knn(&query, k, Some(ef)).unwrap();
+
+            let exact_ids = exact.iter().map(|v| v.id).collect::<HashSet<_>>();
+            hits += approx.iter().filter(|v| exact_ids.contains(&v.id)).count();
+        }
+        recalls.push(hits as f64 / (queries * k) as f64);
+    }
+
+    // a wider candidate list finds more of the exact neighbours
+    assert!(recalls[0] >= 0.75, "recall@{} too low: {:?}", k, recalls);
+    assert!(recalls[1] >= 0.9, "recall@{} too low: {:?}", k, recalls);
+    assert!(recalls[2] >= 0.98, "recall@{} too low: {:?}", k, recalls);
+    assert!(recalls[2] >= recalls[0]);
+}
+
+#[test]
+fn test_vector_index_enforces_dimension() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut rng = StdRng::seed_from_u64(7);
+    insert_vectors(&engine, &mut rng, 4, 10);
+
+    assert!(matches!(
+        engine.build_vector_index(8, 16, 128),
+        Err(GraphError::VectorError(_))
+    ));
+    engine.build_vector_index(4, 8, 64).unwrap();
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let result = engine
+        .storage
+        .vectors
+        .insert::<fn(&HVector, &RoTxn) -> bool>(&mut txn, &random_vector(&mut rng, 3), None);
+    assert!(result.is_err());
+    txn.commit().unwrap();
+
+    assert!(engine.knn(&random_vector(&mut rng, 3), 1, None).is_err());
+    assert_eq!(engine.knn(&random_vector(&mut rng, 4), 3, None).unwrap().len(), 3);
+}
+
+#[test]
+fn test_vector_index_persists_and_updates_incrementally() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    {
+        let engine = open_engine(path);
+        insert_vectors(&engine, &mut StdRng::seed_from_u64(7), 8, 50);
+        engine.build_vector_index(8, 8, 64).unwrap();
+    }
+
+    let engine = open_engine(path);
+    let config = engine.storage.vectors.config();
+    assert_eq!((config.m, config.ef_construct, config.dimension), (8, 64, Some(8)));
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let committed = engine.storage.vectors.config_in(&txn).unwrap();
+    assert_eq!((committed.m, committed.ef_construct), (8, 64));
+    drop(txn);
+
+    // inserted after the build, so only reachable if the index was updated incrementally
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let target = vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    let inserted = engine
+        .storage
+        .vectors
+        .insert::<fn(&HVector, &RoTxn) -> bool>(&mut txn, &target, None)
+        .unwrap();
+    txn.commit().unwrap();
+
+    let results = engine.knn(&target, 1, Some(64)).unwrap();
+    assert_eq!(results[0].id, inserted.id);
+
+    assert_eq!(engine.rebuild_vector_index().unwrap(), 51);
+    let results = engine.knn(&target, 1, None).unwrap();
+    assert_eq!(results[0].id, inserted.id);
+}
+
+#[test]
+fn test_update_node_properties_merges_patch() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n(
+            "person",
+            Some(props! { "name" => "John", "age" => 30, "city" => "London" }),
+            None,
+        )
+        .collect_to_val();
+    txn.commit().unwrap();
+
+    let patch = HashMap::from([
+        ("age".to_string(), Value::from(31)),
+        ("city".to_string(), Value::Empty),
+        ("email".to_string(), Value::from("john@helix.db")),
+    ]);
+    let updated = engine.update_node_properties(node.id(), patch).unwrap();
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let stored = engine.storage.get_node(&txn, &node.id()).unwrap();
+    assert_eq!(stored, updated);
+    let properties = stored.properties.unwrap();
+    // the patched properties plus updated_at
+    assert_eq!(properties.len(), 4);
+    assert_eq!(properties["name"], Value::from("John"));
+    assert_eq!(properties["age"], Value::from(31));
+    assert_eq!(properties["email"], Value::from("john@helix.db"));
+    assert!(!properties.contains_key("city"));
+}
+
+#[test]
+fn test_update_node_properties_missing_node() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let patch = HashMap::from([("name".to_string(), Value::from("ghost"))]);
+
+    assert!(matches!(
+        engine.update_node_properties(42, patch),
+        Err(GraphError::NodeNotFound)
+    ));
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    assert!(engine.storage.get_node(&txn, &42).is_err());
+}
+
+#[test]
+fn test_update_node_properties_moves_secondary_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.graph_config.secondary_indices = Some(vec!["name".to_string()]);
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config,
+    })
+    .unwrap();
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n("person", Some(props! { "name" => "John" }), Some(&["name"]))
+        .collect_to_val();
+    txn.commit().unwrap();
+
+    let patch = HashMap::from([("name".to_string(), Value::from("Jane"))]);
+    engine.update_node_properties(node.id(), patch).unwrap();
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let john = G::new(Arc::clone(&engine.storage), &txn)
+        .n_from_index("name", &"John")
+        .collect_to::<Vec<_>>();
+    let jane = G::new(Arc::clone(&engine.storage), &txn)
+        .n_from_index("name", &"Jane")
+        .collect_to::<Vec<_>>();
+    assert!(john.is_empty());
+    assert_eq!(jane.len(), 1);
+    assert_eq!(jane[0].id(), node.id());
+}
+
+#[test]
+fn test_upsert_node_inserts_then_merges() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.graph_config.secondary_indices = Some(vec!["name".to_string()]);
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config,
+    })
+    .unwrap();
+    let by_name = |name: &str| {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        G::new(Arc::clone(&engine.storage), &txn)
+            .n_from_index("name", &name)
+            .collect_to::<Vec<_>>()
+            .iter()
+            .map(|node| node.id())
+            .collect::<Vec<_>>()
+    };
+    let stored = |id| {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        engine.storage.get_node(&txn, &id).unwrap().properties.unwrap()
+    };
+
+    let properties = HashMap::from([
+        ("name".to_string(), Value::from("John")),
+        ("city".to_string(), Value::Empty),
+    ]);
+    assert_eq!(engine.upsert_node(42, "person", properties).unwrap(), Upserted::Created);
+    let created = stored(42);
+    assert!(!created.contains_key("city"));
+    assert_eq!(created[CREATED_AT], created[UPDATED_AT]);
+    assert_eq!(by_name("John"), [42]);
+
+    std::thread::sleep(Duration::from_millis(2));
+    let patch = HashMap::from([
+        ("name".to_string(), Value::from("Jane")),
+        ("age".to_string(), Value::from(31)),
+    ]);
+    // the label is only used when inserting
+    assert_eq!(engine.upsert_node(42, "company", patch).unwrap(), Upserted::Updated);
+    let updated = stored(42);
+    assert_eq!(updated["age"], Value::from(31));
+    assert_eq!(updated[CREATED_AT], created[CREATED_AT]);
+    assert!(updated[UPDATED_AT] > created[UPDATED_AT]);
+    assert!(by_name("John").is_empty());
+    assert_eq!(by_name("Jane"), [42]);
+    assert_eq!(engine.node_count_by_label("person").unwrap(), 1);
+    assert_eq!(engine.node_count_by_label("company").unwrap(), 0);
+    assert_eq!(
+        engine.nodes_modified_after("person", 0).unwrap()[0].id,
+        42
+    );
+
+    // a failed upsert writes nothing
+    engine.create_unique_constraint("person", "email").unwrap();
+    let email = |email: &str| HashMap::from([("email".to_string(), Value::from(email))]);
+    engine.upsert_node(42, "person", email("jane@helix.db")).unwrap();
+    assert!(is_unique_violation(engine.upsert_node(
+        7,
+        "person",
+        email("jane@helix.db")
+    )));
+    assert_eq!(engine.node_count().unwrap(), 1);
+}
+
+fn add_edge(engine: &HelixGraphEngine, from: u128, to: u128) {
+    let mut txn = engine.storage.write_txn().unwrap();
+    G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_e("knows", None, from, to, false, EdgeType::Node)
+        .collect_to::<Vec<_>>();
+    engine.storage.commit(txn).unwrap();
+}
+
+#[test]
+fn test_counts_track_inserts_and_cascade_deletes() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n("company", None, None)
+        .collect_to::<Vec<_>>();
+    txn.commit().unwrap();
+
+    add_edge(&engine, alice, bob);
+    add_edge(&engine, bob, alice);
+    add_edge(&engine, alice, alice);
+    add_edge(&engine, bob, carol);
+
+    assert_eq!(engine.node_count().unwrap(), 4);
+    assert_eq!(engine.node_count_by_label("person").unwrap(), 3);
+    assert_eq!(engine.node_count_by_label("company").unwrap(), 1);
+    assert_eq!(engine.node_count_by_label("missing").unwrap(), 0);
+    assert_eq!(engine.edge_count().unwrap(), 4);
+
+    // the self loop is both an out and an in edge of alice but must only be counted once
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    engine.storage.drop_node(&mut txn, &alice).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(engine.node_count().unwrap(), 3);
+    assert_eq!(engine.node_count_by_label("person").unwrap(), 2);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    assert_eq!(
+        engine.storage.edge_count(&txn).unwrap(),
+        engine.storage.edges_db.len(&txn).unwrap()
+    );
+}
+
+fn add_unnamed(engine: &HelixGraphEngine, label: &str) -> u128 {
+    let mut txn = engine.storage.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n(label, None, None)
+        .collect_to_val();
+    engine.storage.commit(txn).unwrap();
+    node.id()
+}
+
+#[test]
+fn test_counts_exact_under_concurrent_writes() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let engine = Arc::new(engine);
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let engine = Arc::clone(&engine);
+            thread::spawn(move || {
+                let mut kept = 0;
+                let mut previous = add_unnamed(&engine, "person");
+                for i in 0..30 {
+                    let node = add_unnamed(&engine, if i % 2 == 0 { "person" } else { "company" });
+                    add_edge(&engine, previous, node);
+                    add_edge(&engine, node, node);
+                    // every third node is dropped again, taking its edges with it
+                    if i % 3 == 0 {
+                        engine.drop_node(node).unwrap();
+                    } else {
+                        kept += 1;
+                        previous = node;
+                    }
+                }
+                kept
+            })
+        })
+        .collect();
+    let kept: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+    let (nodes, edges) = {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let nodes = engine.storage.nodes_db.len(&txn).unwrap();
+        (nodes, engine.storage.edges_db.len(&txn).unwrap())
+    };
+    assert_eq!(nodes, kept + 8);
+    assert_eq!(engine.node_count().unwrap(), nodes);
+    assert_eq!(
+        engine.node_count_by_label("person").unwrap()
+            + engine.node_count_by_label("company").unwrap(),
+        nodes
+    );
+    // each kept node has its self loop and the edge from the node kept before it
+    assert_eq!(edges, kept * 2);
+    assert_eq!(engine.edge_count().unwrap(), edges);
+}
+
+#[test]
+fn test_counts_backfilled_for_existing_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    {
+        let engine = open_engine(path);
+        let alice = add_person(&engine, "alice");
+        let bob = add_person(&engine, "bob");
+        add_edge(&engine, alice, bob);
+
+        // simulates a database written before counters were kept
+        let mut txn = engine.storage.graph_env.write_txn().unwrap();
+        engine.storage.counts_db.clear(&mut txn).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(engine.node_count().unwrap(), 0);
+    }
+
+    let engine = open_engine(path);
+    assert_eq!(engine.node_count().unwrap(), 2);
+    assert_eq!(engine.node_count_by_label("person").unwrap(), 2);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+}
+
+#[test]
+fn test_nodes_page_walks_every_node_once() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut expected = Vec::new();
+    for i in 0..25 {
+        expected.push(add_person(&engine, &format!("person {}", i)));
+        if i % 5 == 0 {
+            let mut txn = engine.storage.graph_env.write_txn().unwrap();
+            G::new_mut(Arc::clone(&engine.storage), &mut txn)
+                .add_n("company", None, None)
+                .collect_to::<Vec<_>>();
+            txn.commit().unwrap();
+        }
+    }
+    expected.sort();
+
+    let mut seen = Vec::new();
+    let mut page_sizes = Vec::new();
+    let mut page = Page::first(10);
+    loop {
+        let paged = engine.nodes_page("person", &page).unwrap();
+        page_sizes.push(paged.items.len());
+        seen.extend(paged.items.iter().map(|node| node.id));
+        if paged.next_cursor.is_none() {
+            break;
+        }
+        page = Page::next(10, &paged);
+    }
+
+    assert_eq!(page_sizes, vec![10, 10, 5]);
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn test_page_cursor_stable_under_inserts() {
+    let (engine, _temp_dir) = setup_test_engine();
+    for i in 0..5 {
+        add_person(&engine, &format!("person {}", i));
+    }
+
+    let first = engine.nodes_page("person", &Page::first(3)).unwrap();
+    assert_eq!(first.items.len(), 3);
+
+    // inserted after the first page was read so they sort after its cursor
+    let late = [add_person(&engine, "late 1"), add_person(&engine, "late 2")];
+
+    let second = engine.nodes_page("person", &Page::next(3, &first)).unwrap();
+    let third = engine.nodes_page("person", &Page::next(3, &second)).unwrap();
+    assert_eq!(second.items.len(), 3);
+    assert_eq!(third.items.len(), 1);
+    assert!(third.next_cursor.is_none());
+
+    let ids: Vec<u128> = first
+        .items
+        .iter()
+        .chain(second.items.iter())
+        .chain(third.items.iter())
+        .map(|node| node.id)
+        .collect();
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 7);
+    assert!(late.iter().all(|id| ids.contains(id)));
+}
+
+#[test]
+fn test_edges_page_and_invalid_cursor() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    for _ in 0..3 {
+        add_edge(&engine, alice, bob);
+    }
+
+    let first = engine.edges_page("knows", &Page::first(2)).unwrap();
+    assert_eq!(first.items.len(), 2);
+    let second = engine.edges_page("knows", &Page::next(2, &first)).unwrap();
+    assert_eq!(second.items.len(), 1);
+    assert!(second.next_cursor.is_none());
+
+    let page = Page {
+        limit: 2,
+        cursor: Some("not a cursor".to_string()),
+    };
+    assert!(matches!(
+        engine.nodes_page("person", &page),
+        Err(GraphError::ConversionError(_))
+    ));
+}
+
+fn add_labeled_edge(engine: &HelixGraphEngine, label: &str, from: u128, to: u128) {
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_e(label, None, from, to, false, EdgeType::Node)
+        .collect_to::<Vec<_>>();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_neighbors_with_props() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+    let dave = add_person(&engine, "dave");
+    add_labeled_edge(&engine, "knows", alice, bob);
+    add_labeled_edge(&engine, "knows", alice, carol);
+    add_labeled_edge(&engine, "likes", alice, bob);
+    add_labeled_edge(&engine, "follows", alice, dave);
+    add_labeled_edge(&engine, "knows", dave, alice);
+
+    let names = |nodes: Vec<Node>| -> HashSet<String> {
+        nodes
+            .into_iter()
+            .map(|node| match node.check_property("name") {
+                Ok(Value::String(name)) => name.clone(),
+                _ => panic!("neighbor {} missing its properties", node.id),
+            })
+            .collect()
+    };
+
+    let knows = engine
+        .neighbors_with_props(alice, Direction::Out, &["knows"], None)
+        .unwrap();
+    assert_eq!(names(knows), HashSet::from(["bob".to_string(), "carol".to_string()]));
+
+    // bob is reachable along two labels but only returned once
+    let all_out = engine
+        .neighbors_with_props(alice, Direction::Out, &[], None)
+        .unwrap();
+    assert_eq!(all_out.len(), 3);
+
+    let both = engine
+        .neighbors_with_props(alice, Direction::Both, &["knows"], None)
+        .unwrap();
+    assert_eq!(
+        names(both),
+        HashSet::from(["bob".to_string(), "carol".to_string(), "dave".to_string()])
+    );
+
+    let limited = engine
+        .neighbors_with_props(alice, Direction::Out, &[], Some(2))
+        .unwrap();
+    assert_eq!(limited.len(), 2);
+
+    assert!(matches!(
+        engine.neighbors_with_props(u128::MAX, Direction::Out, &[], None),
+        Err(GraphError::NodeNotFound)
+    ));
+}
+
+#[test]
+fn test_get_nodes_batch_preserves_order() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let nodes = engine
+        .storage
+        .get_nodes(&txn, &[bob, u128::MAX, alice, bob])
+        .unwrap();
+    let ids: Vec<u128> = nodes.iter().map(|node| node.id).collect();
+    assert_eq!(ids, vec![bob, alice, bob]);
+    assert!(nodes.iter().all(|node| node.properties.is_some()));
+}
+
+fn dump_graph(engine: &HelixGraphEngine) -> Vec<(u128, Vec<u8>)> {
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let nodes = engine.storage.nodes_db.iter(&txn).unwrap();
+    let edges = engine.storage.edges_db.iter(&txn).unwrap();
+    nodes
+        .chain(edges)
+        .map(|result| {
+            let (id, bytes) = result.unwrap();
+            (id, bytes.to_vec())
+        })
+        .collect()
+}
+
+#[test]
+fn test_snapshot_restore_round_trip() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    add_edge(&engine, alice, bob);
+    engine.set_alias("current", alice).unwrap();
+
+    // a write txn left open across the snapshot must not show up in it
+    let storage = Arc::clone(&engine.storage);
+    let (opened_tx, opened_rx) = std::sync::mpsc::channel();
+    let (snapshotted_tx, snapshotted_rx) = std::sync::mpsc::channel::<()>();
+    let writer = thread::spawn(move || {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        G::new_mut(Arc::clone(&storage), &mut txn)
+            .add_n("person", Some(props! { "name" => "late" }), None)
+            .collect_to::<Vec<_>>();
+        opened_tx.send(()).unwrap();
+        snapshotted_rx.recv().unwrap();
+        txn.commit().unwrap();
+    });
+    opened_rx.recv().unwrap();
+    let expected = dump_graph(&engine);
+
+    let backup_dir = TempDir::new().unwrap();
+    let backup_path = backup_dir.path().join("backup");
+    let backup_path = backup_path.to_str().unwrap();
+    engine.snapshot(backup_path).unwrap();
+    snapshotted_tx.send(()).unwrap();
+    writer.join().unwrap();
+    assert_eq!(engine.node_count().unwrap(), 3);
+
+    assert!(matches!(engine.snapshot(backup_path), Err(GraphError::New(_))));
+
+    let restored_dir = TempDir::new().unwrap();
+    let restored_path = restored_dir.path().to_str().unwrap();
+    HelixGraphEngine::restore(backup_path, restored_path).unwrap();
+    assert!(matches!(
+        HelixGraphEngine::restore(backup_path, restored_path),
+        Err(GraphError::New(_))
+    ));
+
+    let restored = open_engine(restored_path);
+    assert_eq!(dump_graph(&restored), expected);
+    assert_eq!(restored.node_count().unwrap(), 2);
+    assert_eq!(restored.edge_count().unwrap(), 1);
+    assert_eq!(restored.resolve_alias("current").unwrap(), alice);
+}
+
+/// JSON does not carry numeric widths, so numbers only need to hold the same value
+fn assert_same_nodes(left: &HelixGraphEngine, right: &HelixGraphEngine) {
+    let nodes = |engine: &HelixGraphEngine| {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        engine
+            .storage
+            .nodes_db
+            .iter(&txn)
+            .unwrap()
+            .map(|result| {
+                let (id, bytes) = result.unwrap();
+                Node::decode_node(bytes, id).unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+    let (left, right) = (nodes(left), nodes(right));
+    assert_eq!(left.len(), right.len());
+    for (left, right) in left.iter().zip(right.iter()) {
+        assert_eq!(left.id, right.id);
+        assert_eq!(left.label, right.label);
+        let (left, right) = (
+            left.properties.clone().unwrap_or_default(),
+            right.properties.clone().unwrap_or_default(),
+        );
+        assert_eq!(left.len(), right.len());
+        for (key, value) in left.iter() {
+            let other = &right[key];
+            assert!(value == other || (value.as_f64().is_some() && value.as_f64() == other.as_f64()));
+        }
+    }
+}
+
+#[test]
+fn test_jsonl_export_import_round_trip() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n(
+            "company",
+            Some(props! { "name" => "helix", "size" => 12, "public" => false }),
+            None,
+        )
+        .collect_to::<Vec<_>>();
+    txn.commit().unwrap();
+    add_edge(&engine, alice, bob);
+    add_edge(&engine, bob, alice);
+
+    let mut exported = Vec::new();
+    assert_eq!(engine.export_jsonl(&mut exported).unwrap(), 5);
+    let text = String::from_utf8(exported.clone()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[..3].iter().all(|line| line.contains("\"type\":\"node\"")));
+    assert!(lines[3..].iter().all(|line| line.contains("\"type\":\"edge\"")));
+
+    let (restored, _restored_dir) = setup_test_engine();
+    assert_eq!(restored.import_jsonl(exported.as_slice()).unwrap(), (3, 2));
+    assert_same_nodes(&restored, &engine);
+    let edges = |engine: &HelixGraphEngine| {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        engine
+            .storage
+            .edges_db
+            .iter(&txn)
+            .unwrap()
+            .map(|result| {
+                let (id, bytes) = result.unwrap();
+                (id, bytes.to_vec())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(edges(&restored), edges(&engine));
+    assert_eq!(restored.node_count_by_label("person").unwrap(), 2);
+    assert_eq!(restored.edge_count().unwrap(), 2);
+    let knows = restored
+        .neighbors_with_props(alice, Direction::Out, &["knows"], None)
+        .unwrap();
+    assert_eq!(knows.len(), 1);
+    assert_eq!(knows[0].id, bob);
+}
+
+#[test]
+fn test_jsonl_import_holds_back_edges_until_nodes_exist() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    add_edge(&engine, alice, bob);
+    let mut exported = Vec::new();
+    engine.export_jsonl(&mut exported).unwrap();
+
+    // put the edge ahead of the nodes it points at
+    let text = String::from_utf8(exported).unwrap();
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.rotate_right(1);
+    let reordered = lines.join("\n");
+
+    let (restored, _restored_dir) = setup_test_engine();
+    assert_eq!(restored.import_jsonl(reordered.as_bytes()).unwrap(), (2, 1));
+    assert_same_nodes(&restored, &engine);
+    assert_eq!(restored.edge_count().unwrap(), 1);
+
+    // an edge to a node that never shows up fails the whole import
+    let (dangling, _dangling_dir) = setup_test_engine();
+    assert!(matches!(
+        dangling.import_jsonl(lines[0].as_bytes()),
+        Err(GraphError::NodeNotFound)
+    ));
+    assert_eq!(dangling.edge_count().unwrap(), 0);
+
+    assert!(matches!(
+        dangling.import_jsonl("not json".as_bytes()),
+        Err(GraphError::ConversionError(_))
+    ));
+}
+
+#[test]
+fn test_csv_import_reports_malformed_rows() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let nodes = "id,name,age,score,active,zip\r\n\
+        1,alice,30,1.5,true,007\r\n\
+        2,\"Smith, Bob\",25,,false,\r\n\
+        3,\"two\nlines \"\"quoted\"\"\",41,2,true,1\n\
+        \n\
+        4,dave\n\
+        1,again,1,1,true,1\n\
+        5,\"bad\"x,1,1,true,1\n";
+    let summary = engine
+        .import_csv_nodes(nodes.as_bytes(), "person", "id")
+        .unwrap();
+    assert_eq!(summary.imported, 3);
+    let failed: Vec<usize> = summary.failed.iter().map(|row| row.line).collect();
+    assert_eq!(failed, [7, 8, 9]);
+    assert!(matches!(
+        summary.failed[1].error,
+        GraphError::MultipleNodesWithSameId
+    ));
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let alice = engine.storage.get_node(&txn, &csv_node_id("1")).unwrap();
+    let props = alice.properties.as_ref().unwrap();
+    assert_eq!(props["id"], Value::I64(1));
+    assert_eq!(props["name"], Value::String("alice".to_string()));
+    assert_eq!(props["age"], Value::I64(30));
+    assert_eq!(props["score"], Value::F64(1.5));
+    assert_eq!(props["active"], Value::Boolean(true));
+    assert_eq!(props["zip"], Value::String("007".to_string()));
+    assert!(props.contains_key(CREATED_AT));
+    let bob = engine.storage.get_node(&txn, &csv_node_id("2")).unwrap();
+    let props = bob.properties.as_ref().unwrap();
+    assert_eq!(props["name"], Value::String("Smith, Bob".to_string()));
+    assert!(!props.contains_key("score") && !props.contains_key("zip"));
+    let third = engine.storage.get_node(&txn, &csv_node_id("3")).unwrap();
+    assert_eq!(
+        third.properties.as_ref().unwrap()["name"],
+        Value::String("two\nlines \"quoted\"".to_string())
+    );
+    drop(txn);
+
+    let edges = "type,src,dst,weight\n\
+        KNOWS,1,2,0.5\n\
+        KNOWS,2,3,\n\
+        KNOWS,1,9,1\n\
+        ,1,2,1\n";
+    let summary = engine
+        .import_csv_edges(edges.as_bytes(), "type", "src", "dst")
+        .unwrap();
+    assert_eq!(summary.imported, 2);
+    let failed: Vec<usize> = summary.failed.iter().map(|row| row.line).collect();
+    assert_eq!(failed, [4, 5]);
+    assert!(matches!(summary.failed[0].error, GraphError::DanglingEdge(_)));
+    assert_eq!(engine.edge_count().unwrap(), 2);
+    let knows = engine
+        .neighbors_with_props(csv_node_id("1"), Direction::Out, &["KNOWS"], None)
+        .unwrap();
+    assert_eq!(knows.len(), 1);
+    assert_eq!(knows[0].id, csv_node_id("2"));
+    let edge = &dump_edges(&engine)
+        .into_iter()
+        .find(|edge| edge.from_node == csv_node_id("1"))
+        .unwrap();
+    assert_eq!(
+        edge.properties.as_ref().unwrap()["weight"],
+        Value::F64(0.5)
+    );
+
+    // problems with the header fail the whole import
+    assert!(matches!(
+        engine.import_csv_nodes("name\nalice\n".as_bytes(), "person", "id"),
+        Err(GraphError::ConversionError(_))
+    ));
+    assert!(matches!(
+        engine.import_csv_edges("".as_bytes(), "type", "src", "dst"),
+        Err(GraphError::ConversionError(_))
+    ));
+
+    let uuid = uuid::Uuid::from_u128(csv_node_id("1"));
+    assert_eq!(csv_node_id(&uuid.to_string()), uuid.as_u128());
+}
+
+fn dump_edges(engine: &HelixGraphEngine) -> Vec<Edge> {
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    engine
+        .storage
+        .edges_db
+        .iter(&txn)
+        .unwrap()
+        .map(|result| {
+            let (id, bytes) = result.unwrap();
+            Edge::decode_edge(bytes, id).unwrap()
+        })
+        .collect()
+}
+
+fn dump_aliases(engine: &HelixGraphEngine) -> Vec<(String, u128)> {
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    engine
+        .storage
+        .aliases_db
+        .iter(&txn)
+        .unwrap()
+        .map(|result| {
+            let (name, id) = result.unwrap();
+            (name.to_string(), id)
+        })
+        .collect()
+}
+
+#[test]
+fn test_rebuild_from_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_path = temp_dir.path().join("ops.log");
+    let log_path = log_path.to_str().unwrap();
+    let config = Config {
+        oplog_path: Some(log_path.to_string()),
+        ..Config::default()
+    };
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().join("data").to_str().unwrap().to_string(),
+        config,
+    })
+    .unwrap();
+
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+    add_edge(&engine, alice, bob);
+    add_edge(&engine, bob, carol);
+    add_edge(&engine, carol, alice);
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    let bob_node = G::new(Arc::clone(&engine.storage), &txn)
+        .n_from_id(&bob)
+        .collect_to::<Vec<_>>();
+    G::new_mut_from(Arc::clone(&engine.storage), &mut txn, bob_node)
+        .update(Some(props! { "age" => "thirty" }))
+        .collect_to::<Vec<_>>();
+    let edge = G::new(Arc::clone(&engine.storage), &txn)
+        .e_from_type("knows")
+        .collect_to::<Vec<_>>();
+    G::new_mut_from(Arc::clone(&engine.storage), &mut txn, vec![edge[0].clone()])
+        .update(Some(props! { "since" => "2020" }))
+        .collect_to::<Vec<_>>();
+    engine.storage.commit(txn).unwrap();
+
+    let mut patch = HashMap::new();
+    patch.insert("name".to_string(), Value::Empty);
+    patch.insert("city".to_string(), Value::from("paris"));
+    engine.update_node_properties(alice, patch).unwrap();
+
+    engine.set_alias("current", alice).unwrap();
+    engine.set_alias("next", bob).unwrap();
+    engine.swap_aliases("current", "next").unwrap();
+    engine.set_alias("old", carol).unwrap();
+    engine.drop_alias("old").unwrap();
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    engine.storage.drop_node(&mut txn, &carol).unwrap();
+    engine.storage.commit(txn).unwrap();
+
+    let (rebuilt, _rebuilt_dir) = setup_test_engine();
+    let applied = rebuilt.rebuild_from_log(log_path).unwrap();
+    assert!(applied > 0);
+
+    let check = |rebuilt: &HelixGraphEngine| {
+        assert_same_nodes(rebuilt, &engine);
+        assert_eq!(dump_edges(rebuilt), dump_edges(&engine));
+        assert_eq!(dump_aliases(rebuilt), dump_aliases(&engine));
+        assert_eq!(rebuilt.node_count().unwrap(), 2);
+        assert_eq!(rebuilt.edge_count().unwrap(), 1);
+    };
+    check(&rebuilt);
+
+    // replaying again over the rebuilt graph changes nothing
+    assert_eq!(rebuilt.rebuild_from_log(log_path).unwrap(), applied);
+    check(&rebuilt);
+
+    assert!(matches!(
+        engine.rebuild_from_log(log_path),
+        Err(GraphError::New(_))
+    ));
+}
+
+#[test]
+fn test_rebuild_from_log_leaves_out_aborted_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_path = temp_dir.path().join("ops.log");
+    let log_path = log_path.to_str().unwrap();
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().join("data").to_str().unwrap().to_string(),
+        config: Config {
+            oplog_path: Some(log_path.to_string()),
+            ..Config::default()
+        },
+    })
+    .unwrap();
+    let add = |txn: &mut RwTxn, name: &str| {
+        G::new_mut(Arc::clone(&engine.storage), txn)
+            .add_n("person", Some(props! { "name" => name }), None)
+            .collect_to_val()
+            .id()
+    };
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    add(&mut txn, "aborted");
+    engine.storage.abort(txn);
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    add(&mut txn, "dropped");
+    drop(txn);
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    let alice = add(&mut txn, "alice");
+    let nested = engine.storage.nested_write(&mut txn, |txn| {
+        add(txn, "failed row");
+        Err::<(), _>(GraphError::New("row failed".to_string()))
+    });
+    assert!(nested.is_err());
+    let bob = engine
+        .storage
+        .nested_write(&mut txn, |txn| Ok(add(txn, "bob")))
+        .unwrap();
+    engine.storage.commit(txn).unwrap();
+
+    let (rebuilt, _rebuilt_dir) = setup_test_engine();
+    assert_eq!(rebuilt.rebuild_from_log(log_path).unwrap(), 2);
+    assert_same_nodes(&rebuilt, &engine);
+    let txn = rebuilt.storage.graph_env.read_txn().unwrap();
+    assert!(rebuilt.storage.get_node(&txn, &alice).is_ok());
+    assert!(rebuilt.storage.get_node(&txn, &bob).is_ok());
+    drop(txn);
+    assert_eq!(rebuilt.node_count().unwrap(), 2);
+}
+
+#[test]
+fn test_oplog_keeps_only_whole_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_path = temp_dir.path().join("ops.log");
+    let log_path = log_path.to_str().unwrap();
+    let alias = |name: &str| Operation::DropAlias {
+        name: name.to_string(),
+    };
+    let read = || {
+        OpLog::read(log_path)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+
+    let oplog = OpLog::open(log_path).unwrap();
+    assert_eq!(oplog.append(&[alias("a"), alias("b")]).unwrap(), 0);
+    // a txn that failed to commit is taken back out
+    let length = oplog.append(&[alias("c")]).unwrap();
+    oplog.truncate(length).unwrap();
+    assert_eq!(read(), vec![alias("a"), alias("b")]);
+    drop(oplog);
+
+    // a crash part way through a line leaves it torn, which is cut off on reopening
+    let mut file = std::fs::OpenOptions::new().append(true).open(log_path).unwrap();
+    std::io::Write::write_all(&mut file, b"{\"op\":\"drop_al").unwrap();
+    drop(file);
+    let oplog = OpLog::open(log_path).unwrap();
+    assert_eq!(oplog.append(&[alias("d")]).unwrap(), length);
+    assert_eq!(read(), vec![alias("a"), alias("b"), alias("d")]);
+}
+
+fn setup_quota_engine(max_nodes: u64, max_edges: u64) -> (HelixGraphEngine, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.graph_config.max_nodes = Some(max_nodes);
+    config.graph_config.max_edges = Some(max_edges);
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config,
+    })
+    .unwrap();
+    (engine, temp_dir)
+}
+
+#[test]
+fn test_node_quota_on_batch_insert() {
+    let (engine, _temp_dir) = setup_quota_engine(3, 0);
+
+    let added = engine
+        .add_nodes(vec![
+            ("person", Some(props! { "name" => "alice" })),
+            ("person", Some(props! { "name" => "bob" })),
+        ])
+        .unwrap();
+    assert_eq!(added.len(), 2);
+
+    // the batch would take the graph to 4 nodes so none of it is written
+    let err = engine
+        .add_nodes(vec![
+            ("person", Some(props! { "name" => "carol" })),
+            ("person", Some(props! { "name" => "dave" })),
+        ])
+        .unwrap_err();
+    assert!(matches!(err, GraphError::QuotaExceeded(_)));
+    assert_eq!(err.status_code(), 507);
+    assert_eq!(engine.node_count().unwrap(), 2);
+
+    assert_eq!(
+        engine
+            .add_nodes(vec![("person", Some(props! { "name" => "carol" }))])
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(engine.node_count().unwrap(), 3);
+}
+
+#[test]
+fn test_quota_on_single_inserts() {
+    let (engine, _temp_dir) = setup_quota_engine(2, 1);
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let result = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_n("person", Some(props! { "name" => "carol" }), None)
+        .next()
+        .unwrap();
+    assert!(matches!(result, Err(GraphError::QuotaExceeded(_))));
+
+    let first = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_e("knows", None, alice, bob, false, EdgeType::Node)
+        .next()
+        .unwrap();
+    assert!(first.is_ok());
+    let second = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+        .add_e("knows", None, bob, alice, false, EdgeType::Node)
+        .next()
+        .unwrap();
+    assert!(matches!(second, Err(GraphError::QuotaExceeded(_))));
+    txn.commit().unwrap();
+
+    assert_eq!(engine.node_count().unwrap(), 2);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+}
+
+/// Produces a JSON array of `count` node records on demand, counting the bytes handed out
+struct GeneratedNodes {
+    count: usize,
+    next: usize,
+    pending: Vec<u8>,
+    produced: Rc<Cell<usize>>,
+}
+
+impl GeneratedNodes {
+    fn element(i: usize) -> String {
+        format!(
+            r#"{{"type":"node","id":"{}","label":"person","properties":{{"n":{}}}}}"#,
+            uuid::Uuid::from_u128(i as u128 + 1),
+            i
+        )
+    }
+}
+
+impl Read for GeneratedNodes {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() && self.next <= self.count {
+            self.pending = match self.next {
+                i if i == self.count => b"]".to_vec(),
+                0 => format!("[{}", Self::element(0)).into_bytes(),
+                i => format!(",{}", Self::element(i)).into_bytes(),
+            };
+            self.next += 1;
+        }
+        let n = out.len().min(self.pending.len());
+        out[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        self.produced.set(self.produced.get() + n);
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_json_array_stream_reads_one_element_ahead() {
+    let produced = Rc::new(Cell::new(0));
+    let reader = GeneratedNodes {
+        count: 10_000,
+        next: 0,
+        pending: Vec::new(),
+        produced: Rc::clone(&produced),
+    };
+    let element_len = GeneratedNodes::element(9_999).len() + 1;
+    let lookahead = 8 * 1024 + element_len;
+
+    let mut seen = 0;
+    let elements = for_each_array_element(BufReader::new(reader), None, |_: serde_json::Value| {
+        seen += 1;
+        // only the elements parsed so far plus one buffer's worth have been read
+        assert!(produced.get() <= seen * element_len + lookahead);
+        Ok(())
+    });
+    assert_eq!(elements.unwrap(), 10_000);
+    assert_eq!(seen, 10_000);
+}
+
+#[test]
+fn test_import_large_json_array() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let reader = GeneratedNodes {
+        count: 10_000,
+        next: 0,
+        pending: Vec::new(),
+        produced: Rc::new(Cell::new(0)),
+    };
+
+    assert_eq!(engine.import_json_array(reader, None).unwrap(), (10_000, 0));
+    assert_eq!(engine.node_count().unwrap(), 10_000);
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let node = engine.storage.get_node(&txn, &42).unwrap();
+    assert_eq!(node.check_property("n").unwrap().as_f64(), Some(41.0));
+    drop(txn);
+
+    let (engine, _temp_dir) = setup_test_engine();
+    let reader = GeneratedNodes {
+        count: 10_000,
+        next: 0,
+        pending: Vec::new(),
+        produced: Rc::new(Cell::new(0)),
+    };
+    assert!(matches!(
+        engine.import_json_array(reader, Some(64 * 1024)),
+        Err(GraphError::ConversionError(_))
+    ));
+    assert_eq!(engine.node_count().unwrap(), 0);
+}
+
+fn is_unique_violation(result: Result<impl std::fmt::Debug, GraphError>) -> bool {
+    matches!(result, Err(GraphError::UniqueViolation { property, .. }) if property == "email")
+}
+
+#[test]
+fn test_unique_constraint_concurrent_inserts() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let engine = Arc::new(engine);
+    engine.create_unique_constraint("User", "email").unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|t| {
+            let engine = Arc::clone(&engine);
+            thread::spawn(move || {
+                let mut added = 0;
+                for i in 0..25 {
+                    // every thread races for the shared email and a few of its own
+                    let email = match i % 5 {
+                        0 => format!("thread{}-{}@example.com", t, i),
+                        _ => "shared@example.com".to_string(),
+                    };
+                    let result = engine.add_nodes(vec![("User", Some(props! { "email" => email }))]);
+                    match result {
+                        Ok(_) => added += 1,
+                        Err(e) => assert!(is_unique_violation(Err::<(), _>(e))),
+                    }
+                }
+                added
+            })
+        })
+        .collect();
+    let added: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+    assert_eq!(added, 8 * 5 + 1);
+    assert_eq!(engine.node_count_by_label("User").unwrap(), 8 * 5 + 1);
+}
+
+#[test]
+fn test_unique_constraint_on_update_and_drop() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let add = |label: &str, email: &str| {
+        let mut txn = engine.storage.graph_env.write_txn().unwrap();
+        let result = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+            .add_n(label, Some(props! { "email" => email }), None)
+            .next()
+            .unwrap();
+        txn.commit().unwrap();
+        result.map(|node| node.id())
+    };
+    let alice = add("User", "alice@example.com").unwrap();
+    let bob = add("User", "bob@example.com").unwrap();
+    let other = add("User", "alice@example.com").unwrap();
+
+    // existing duplicates keep the constraint from being created
+    assert!(is_unique_violation(engine.create_unique_constraint("User", "email")));
+    engine.drop_node(other).unwrap();
+    engine.create_unique_constraint("User", "email").unwrap();
+    engine.create_unique_constraint("User", "email").unwrap();
+
+    assert!(is_unique_violation(add("User", "alice@example.com")));
+    // the constraint only covers its own label
+    add("Admin", "alice@example.com").unwrap();
+
+    let patch = |email: &str| HashMap::from([("email".to_string(), Value::from(email))]);
+    assert!(is_unique_violation(engine.update_node_properties(bob, patch("alice@example.com"))));
+    engine.update_node_properties(bob, patch("bob@example.com")).unwrap();
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let node = engine.storage.get_node(&txn, &bob).unwrap();
+    let updated = G::new_mut_from(Arc::clone(&engine.storage), &mut txn, vec![TraversalVal::Node(node)])
+        .update(Some(props! { "email" => "alice@example.com" }))
+        .next()
+        .unwrap();
+    assert!(is_unique_violation(updated));
+    drop(txn);
+
+    // updating or dropping the holder frees its value
+    engine.update_node_properties(alice, patch("carol@example.com")).unwrap();
+    engine.update_node_properties(bob, patch("alice@example.com")).unwrap();
+    engine.drop_node(bob).unwrap();
+    add("User", "alice@example.com").unwrap();
+    assert_eq!(engine.node_count_by_label("User").unwrap(), 2);
+}
+
+fn is_schema_violation<T>(result: Result<T, GraphError>, expected: &str) -> bool {
+    matches!(result, Err(GraphError::SchemaViolation(msg)) if msg == expected)
+}
+
+#[test]
+fn test_schema_validates_nodes() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let engine = open_engine(path);
+    engine
+        .register_schema(
+            "User",
+            &[
+                FieldSchema::required("email", FieldType::String),
+                FieldSchema::optional("age", FieldType::Integer),
+                FieldSchema::optional("score", FieldType::Float),
+            ],
+        )
+        .unwrap();
+    let add = |label: &str, properties: Vec<(String, Value)>| {
+        engine.add_nodes(vec![(label, Some(properties))])
+    };
+
+    assert!(is_schema_violation(
+        add("User", props! { "age" => 30 }),
+        "User.email is required"
+    ));
+    assert!(is_schema_violation(
+        add("User", props! { "email" => "a@example.com", "age" => "thirty" }),
+        "User.age must be Integer, found String(\"thirty\")"
+    ));
+    assert!(is_schema_violation(
+        add("User", props! { "email" => "a@example.com", "emial" => "b@example.com" }),
+        "User.emial is not in the schema"
+    ));
+    let user = add("User", props! { "email" => "a@example.com", "age" => 30, "score" => 7 })
+        .unwrap()
+        .remove(0);
+    // labels without a schema stay free-form
+    add("Post", props! { "anything" => true }).unwrap();
+
+    let patch = |key: &str, value: Value| HashMap::from([(key.to_string(), value)]);
+    assert!(is_schema_violation(
+        engine.update_node_properties(user.id, patch("email", Value::Empty)),
+        "User.email is required"
+    ));
+    engine
+        .update_node_properties(user.id, patch("score", Value::F64(1.5)))
+        .unwrap();
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    let node = engine.storage.get_node(&txn, &user.id).unwrap();
+    let updated = G::new_mut_from(Arc::clone(&engine.storage), &mut txn, vec![TraversalVal::Node(node)])
+        .update(Some(props! { "age" => 1.5 }))
+        .next()
+        .unwrap();
+    assert!(is_schema_violation(updated, "User.age must be Integer, found F64(1.5)"));
+    drop(txn);
+
+    // schemas are stored with the graph
+    drop(engine);
+    let engine = open_engine(path);
+    assert!(is_schema_violation(
+        engine.add_nodes(vec![("User", None)]),
+        "User.email is required"
+    ));
+}
+
+#[test]
+fn test_schema_validates_edges_and_existing_items() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+
+    // existing items have to match before a schema is registered
+    assert!(is_schema_violation(
+        engine.register_schema("person", &[FieldSchema::required("age", FieldType::Integer)]),
+        "person.age is required"
+    ));
+    engine
+        .register_schema("person", &[FieldSchema::required("name", FieldType::String)])
+        .unwrap();
+    engine
+        .register_schema("knows", &[FieldSchema::required("since", FieldType::Integer)])
+        .unwrap();
+
+    let add_edge = |properties: Option<Vec<(String, Value)>>| {
+        let mut txn = engine.storage.graph_env.write_txn().unwrap();
+        let result = G::new_mut(Arc::clone(&engine.storage), &mut txn)
+            .add_e("knows", properties, alice, bob, false, EdgeType::Node)
+            .next()
+            .unwrap();
+        txn.commit().unwrap();
+        result
+    };
+    assert!(is_schema_violation(add_edge(None), "knows.since is required"));
+    add_edge(Some(props! { "since" => 2020 })).unwrap();
+    assert_eq!(engine.edge_count().unwrap(), 1);
+}
+
+fn scores(nodes: &[Node]) -> Vec<f64> {
+    nodes
+        .iter()
+        .map(|node| node.check_property("score").unwrap().as_f64().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_range_index_finds_nodes_in_range() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let add = |score: Value| {
+        engine
+            .add_nodes(vec![("player", Some(vec![("score".to_string(), score)]))])
+            .unwrap()
+            .remove(0)
+    };
+    // nodes added before the index is created are indexed too
+    add(Value::I32(-5));
+    let twenty = add(Value::F64(20.5));
+    engine.create_range_index("player", "score").unwrap();
+    for score in [10, 50, 51, 0] {
+        add(Value::I64(score));
+    }
+    add(Value::F64(-0.0));
+    add(Value::String("high".to_string()));
+    engine
+        .add_nodes(vec![("coach", Some(props! { "score" => 30 }))])
+        .unwrap();
+
+    let find = |min, max| scores(&engine.find_nodes_in_range("player", "score", min, max).unwrap());
+    assert_eq!(find(Some(10.0), Some(50.0)), vec![10.0, 20.5, 50.0]);
+    assert_eq!(find(None, Some(0.0)), vec![-5.0, 0.0, -0.0]);
+    assert_eq!(find(Some(50.5), None), vec![51.0]);
+    assert_eq!(find(None, None).len(), 7);
+    assert!(find(Some(50.0), Some(10.0)).is_empty());
+
+    // updates and drops move the index entries
+    let patch = HashMap::from([("score".to_string(), Value::I32(60))]);
+    engine.update_node_properties(twenty.id, patch).unwrap();
+    assert_eq!(find(Some(10.0), Some(50.0)), vec![10.0, 50.0]);
+    engine.drop_node(twenty.id).unwrap();
+    assert_eq!(find(Some(52.0), None), Vec::<f64>::new());
+
+    assert!(matches!(
+        engine.find_nodes_in_range("player", "level", None, None),
+        Err(GraphError::New(_))
+    ));
+}
+
+#[test]
+fn test_text_index_ranks_by_token_overlap() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let add = |title: &str| {
+        engine
+            .add_nodes(vec![("doc", Some(props! { "title" => title }))])
+            .unwrap()
+            .remove(0)
+            .id
+    };
+    let graph = add("Graph databases in Rust");
+    engine.create_text_index("doc", "title").unwrap();
+    let rust = add("rust  for beginners");
+    let both = add("A RUST graph engine, written in Rust");
+    let unrelated = add("cooking with garlic");
+    engine
+        .add_nodes(vec![("note", Some(props! { "title" => "rust graph" }))])
+        .unwrap();
+
+    assert_eq!(engine.search_text("doc", "title", "Rust graph").unwrap(), vec![graph, both, rust]);
+    assert_eq!(engine.search_text("doc", "title", "garlic").unwrap(), vec![unrelated]);
+    assert!(engine.search_text("doc", "title", "  ").unwrap().is_empty());
+
+    // updates and drops remove the stale postings
+    let patch = HashMap::from([("title".to_string(), Value::from("garlic bread"))]);
+    engine.update_node_properties(rust, patch).unwrap();
+    assert_eq!(engine.search_text("doc", "title", "rust").unwrap(), vec![graph, both]);
+    assert_eq!(engine.search_text("doc", "title", "garlic").unwrap(), vec![rust, unrelated]);
+    engine.drop_node(graph).unwrap();
+    assert_eq!(engine.search_text("doc", "title", "graph").unwrap(), vec![both]);
+
+    assert!(matches!(
+        engine.search_text("doc", "body", "rust"),
+        Err(GraphError::New(_))
+    ));
+}
+
+#[test]
+fn test_aggregate_skips_missing_properties() {
+    let (engine, _temp_dir) = setup_test_engine();
+    engine
+        .add_nodes(vec![
+            ("order", Some(props! { "total" => 10 })),
+            ("order", Some(props! { "total" => 2.5 })),
+            ("order", Some(props! { "total" => -4 })),
+            ("order", Some(props! { "note" => "no total" })),
+            ("order", None),
+            ("refund", Some(props! { "total" => 1000 })),
+        ])
+        .unwrap();
+
+    let aggregate = |op| engine.aggregate("order", "total", op).unwrap();
+    assert_eq!(aggregate(AggregateOp::Sum), AggregateResult::Sum(8.5));
+    assert_eq!(aggregate(AggregateOp::Avg), AggregateResult::Avg(Some(8.5 / 3.0)));
+    assert_eq!(aggregate(AggregateOp::Min), AggregateResult::Min(Some(-4.0)));
+    assert_eq!(aggregate(AggregateOp::Max), AggregateResult::Max(Some(10.0)));
+    assert_eq!(aggregate(AggregateOp::Count), AggregateResult::Count(3));
+
+    let empty = |op| engine.aggregate("order", "discount", op).unwrap();
+    assert_eq!(empty(AggregateOp::Sum), AggregateResult::Sum(0.0));
+    assert_eq!(empty(AggregateOp::Avg).as_f64(), None);
+    assert_eq!(empty(AggregateOp::Count).as_f64(), Some(0.0));
+
+    assert_eq!(
+        engine.aggregate("order", "note", AggregateOp::Count).unwrap(),
+        AggregateResult::Count(1)
+    );
+    assert!(matches!(
+        engine.aggregate("order", "note", AggregateOp::Sum),
+        Err(GraphError::ConversionError(_))
+    ));
+}
+
+#[test]
+fn test_insert_node_generates_unique_ids() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let engine = Arc::new(engine);
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let engine = Arc::clone(&engine);
+            thread::spawn(move || {
+                (0..250)
+                    .map(|_| engine.insert_node(None, "item", None).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let ids: HashSet<u128> = handles
+        .into_iter()
+        .flat_map(|h| h.join().unwrap())
+        .collect();
+
+    assert_eq!(ids.len(), 8 * 250);
+    assert!(ids.iter().all(|id| uuid::Uuid::from_u128(*id).get_version_num() == 6));
+    assert_eq!(engine.node_count().unwrap(), 8 * 250);
+}
+
+#[test]
+fn test_insert_node_with_existing_id_fails() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let properties = HashMap::from([("name".to_string(), Value::from("first"))]);
+    let id = engine.insert_node(Some(42), "item", Some(properties)).unwrap();
+    assert_eq!(id, 42);
+
+    assert!(matches!(
+        engine.insert_node(Some(42), "item", None),
+        Err(GraphError::New(_))
+    ));
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let node = engine.storage.get_node(&txn, &42).unwrap();
+    assert_eq!(node.check_property("name").unwrap(), &Value::from("first"));
+}
+
+#[test]
+fn test_insert_and_update_stamp_timestamps() {
+    let (engine, _temp_dir) = setup_test_engine();
+    engine
+        .register_schema("task", &[FieldSchema::required("title", FieldType::String)])
+        .unwrap();
+    let timestamp = |id: u128, property: &str| {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let node = engine.storage.get_node(&txn, &id).unwrap();
+        node.check_property(property).unwrap().as_f64().unwrap() as u64
+    };
+    let title = |title: &str| HashMap::from([("title".to_string(), Value::from(title))]);
+
+    let before = now_millis();
+    let first = engine.insert_node(None, "task", Some(title("first"))).unwrap();
+    let second = engine.insert_node(None, "task", Some(title("second"))).unwrap();
+    let created = timestamp(first, CREATED_AT);
+    assert!(created >= before);
+    assert_eq!(timestamp(first, UPDATED_AT), created);
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let checkpoint = now_millis();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    assert!(engine.nodes_modified_after("task", checkpoint).unwrap().is_empty());
+
+    engine.update_node_properties(first, title("renamed")).unwrap();
+    assert_eq!(timestamp(first, CREATED_AT), created);
+    assert!(timestamp(first, UPDATED_AT) > checkpoint);
+
+    let modified = engine.nodes_modified_after("task", checkpoint).unwrap();
+    assert_eq!(modified.iter().map(|node| node.id).collect::<Vec<_>>(), vec![first]);
+    let all = engine.nodes_modified_after("task", 0).unwrap();
+    assert_eq!(all.iter().map(|node| node.id).collect::<Vec<_>>(), vec![second, first]);
+    assert!(engine.nodes_modified_after("unknown", 0).unwrap().is_empty());
+}
+
+#[test]
+fn test_timestamp_index_remembered_once_committed() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let remembered = |label: &str| {
+        let remembered = engine.storage.timestamp_indexed.read().unwrap();
+        remembered.contains(label)
+    };
+
+    // created by the first insert, which could still have been aborted
+    engine.insert_node(None, "task", None).unwrap();
+    assert!(!remembered("task"));
+    engine.insert_node(None, "task", None).unwrap();
+    assert!(remembered("task"));
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    engine.storage.create_timestamp_index(&mut txn, "note").unwrap();
+    engine.storage.abort(txn);
+    assert!(!remembered("note"));
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    assert!(engine.storage.range_indexed_properties(&txn, "note").unwrap().is_empty());
+    drop(txn);
+
+    let note = engine.insert_node(None, "note", None).unwrap();
+    assert_eq!(engine.nodes_modified_after("note", 0).unwrap()[0].id, note);
+}
+
+#[test]
+fn test_subscribe_receives_committed_changes() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let all = engine.subscribe(ChangeFilter::default());
+    let users = engine.subscribe(ChangeFilter::default().label("user"));
+    let renames = engine.subscribe(ChangeFilter::default().label("user").property("name"));
+    let props = |key: &str, value: &str| HashMap::from([(key.to_string(), Value::from(value))]);
+
+    let user = engine.insert_node(None, "user", Some(props("name", "alice"))).unwrap();
+    let post = engine.insert_node(None, "post", None).unwrap();
+    let wrote = engine
+        .insert_edge("wrote", None, user, post, EdgeDirection::Directed)
+        .unwrap();
+    engine.update_node_properties(user, props("email", "a@example.com")).unwrap();
+    engine.update_node_properties(user, props("name", "alicia")).unwrap();
+    let edges = engine.subscribe(ChangeFilter::default().label("wrote"));
+    engine.drop_node(user).unwrap();
+
+    // the user's edges are dropped along with it
+    let dropped: Vec<_> = edges.try_iter().map(|event| (event.kind, event.item, event.id)).collect();
+    assert_eq!(dropped, vec![(ChangeKind::Delete, ItemKind::Edge, wrote.id)]);
+
+    let kinds: Vec<_> = users.try_iter().map(|event| (event.kind, event.item, event.id)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (ChangeKind::Insert, ItemKind::Node, user),
+            (ChangeKind::Update, ItemKind::Node, user),
+            (ChangeKind::Update, ItemKind::Node, user),
+            (ChangeKind::Delete, ItemKind::Node, user),
+        ]
+    );
+    assert_eq!(all.try_iter().count(), 7);
+
+    // the email update doesn't touch the name, so it is filtered out
+    let renamed: Vec<_> = renames.try_iter().map(|event| event.kind).collect();
+    assert_eq!(
+        renamed,
+        vec![ChangeKind::Insert, ChangeKind::Update, ChangeKind::Delete]
+    );
+}
+
+#[test]
+fn test_subscribe_skips_aborted_txns() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let changes = engine.subscribe(ChangeFilter::default());
+    let node = |label: &str| Node {
+        id: uuid::Uuid::new_v4().as_u128(),
+        label: label.to_string(),
+        properties: None,
+    };
+
+    let mut txn = engine.storage.write_txn().unwrap();
+    engine.storage.insert_node_with_id(&mut txn, &node("aborted")).unwrap();
+    drop(txn);
+    assert!(changes.try_recv().is_err());
+
+    let committed = node("committed");
+    let mut txn = engine.storage.write_txn().unwrap();
+    engine.storage.insert_node_with_id(&mut txn, &committed).unwrap();
+    // nothing is sent until the txn commits
+    assert!(changes.try_recv().is_err());
+    engine.storage.commit(txn).unwrap();
+
+    let events: Vec<_> = changes.try_iter().collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].id, committed.id);
+    assert_eq!(events[0].label, "committed");
+}
+
+#[test]
+fn test_slow_subscriber_does_not_block_writers() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let slow = engine.subscribe(ChangeFilter::default());
+    let dropped = engine.subscribe(ChangeFilter::default());
+    drop(dropped);
+
+    let count = SUBSCRIPTION_CAPACITY + 10;
+    engine
+        .add_nodes((0..count).map(|_| ("item", None)).collect())
+        .unwrap();
+
+    // the newest events are dropped once the subscriber is full
+    assert_eq!(slow.len(), SUBSCRIPTION_CAPACITY);
+    assert_eq!(engine.storage.changes.subscriber_count(), 1);
+}
+
+#[test]
+fn test_storage_config_tunes_environment() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config {
+        storage_config: StorageConfig {
+            max_readers: 64,
+            no_sync: true,
+            no_read_ahead: true,
+            ..StorageConfig::default()
+        },
+        ..Config::default()
+    };
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config,
+    })
+    .unwrap();
+
+    let env = &engine.storage.graph_env;
+    assert_eq!(env.info().maximum_number_of_readers, 64);
+    let flags = env.get_flags().unwrap();
+    assert_ne!(flags & heed3::EnvFlags::NO_SYNC.bits(), 0);
+    assert_ne!(flags & heed3::EnvFlags::NO_READ_AHEAD.bits(), 0);
+    engine.insert_node(None, "item", None).unwrap();
+    assert_eq!(engine.node_count().unwrap(), 1);
+
+    // configs written before the storage options existed keep the defaults
+    let json = r#"{
+        "vector_config": {"m": 16, "ef_construction": 128, "ef_search": 768},
+        "graph_config": {"secondary_indices": []},
+        "db_max_size_gb": 10,
+        "mcp": true
+    }"#;
+    let config: Config = sonic_rs::from_str(json).unwrap();
+    assert_eq!(config.storage_config, StorageConfig::default());
+    assert_eq!(config.storage_config.max_readers, StorageConfig::DEFAULT_MAX_READERS);
+}
+
+#[test]
+fn test_compression_shrinks_values_and_can_be_toggled() {
+    let open = |path: &str, compression: Compression| {
+        let config = Config {
+            storage_config: StorageConfig {
+                compression,
+                ..StorageConfig::default()
+            },
+            ..Config::default()
+        };
+        HelixGraphEngine::new(HelixGraphEngineOpts {
+            path: path.to_string(),
+            config,
+        })
+        .unwrap()
+    };
+    let stored_len = |engine: &HelixGraphEngine, txn: &RoTxn, id: u128| {
+        engine
+            .storage
+            .nodes_db
+            .get(txn, &id)
+            .unwrap()
+            .unwrap()
+            .len()
+    };
+    let bio = "graph databases store relationships directly. ".repeat(40);
+    let bio_props = HashMap::from([("bio".to_string(), Value::from(bio.clone()))]);
+
+    for compression in [Compression::Lz4, Compression::Zstd] {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+
+        let engine = open(&path, compression);
+        let verbose = engine
+            .insert_node(None, "person", Some(bio_props.clone()))
+            .unwrap();
+        // too small to shrink, so it is stored as it is
+        let small = engine.add_nodes(vec![("person", None)]).unwrap()[0].id;
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let node = engine.storage.get_node(&txn, &verbose).unwrap();
+        assert_eq!(node.check_property("bio").unwrap(), &Value::from(bio.clone()));
+        assert!(stored_len(&engine, &txn, verbose) < node.encode_node().unwrap().len() / 4);
+        let node = engine.storage.get_node(&txn, &small).unwrap();
+        assert_eq!(stored_len(&engine, &txn, small), node.encode_node().unwrap().len());
+        drop(txn);
+        drop(engine);
+
+        // turning compression off leaves the compressed values readable
+        let engine = open(&path, Compression::None);
+        let plain = engine
+            .insert_node(None, "person", Some(bio_props.clone()))
+            .unwrap();
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let node = engine.storage.get_node(&txn, &verbose).unwrap();
+        assert_eq!(node.check_property("bio").unwrap(), &Value::from(bio.clone()));
+        let node = engine.storage.get_node(&txn, &plain).unwrap();
+        assert_eq!(stored_len(&engine, &txn, plain), node.encode_node().unwrap().len());
+    }
+}
+
+#[test]
+fn test_expired_nodes_are_absent_and_purged() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let user = engine.insert_node(None, "user", None).unwrap();
+    let session = engine
+        .insert_node_with_ttl(None, "session", None, Duration::from_millis(50))
+        .unwrap();
+    let lasting = engine
+        .insert_node_with_ttl(None, "session", None, Duration::from_secs(3600))
+        .unwrap();
+    add_edge(&engine, user, session);
+    add_edge(&engine, user, lasting);
+    // an edge with the expiring node at both ends is only hidden once
+    add_edge(&engine, session, session);
+
+    let sessions = || {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let mut ids = G::new(Arc::clone(&engine.storage), &txn)
+            .n_from_type("session")
+            .collect_to::<Vec<_>>()
+            .iter()
+            .map(|node| node.id())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    };
+    let edges = || {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        G::new(Arc::clone(&engine.storage), &txn)
+            .e_from_type("knows")
+            .collect_to::<Vec<_>>()
+            .len()
+    };
+    let mut both = vec![session, lasting];
+    both.sort_unstable();
+    assert_eq!(sessions(), both);
+    assert_eq!(engine.node_count_by_label("session").unwrap(), 2);
+    assert_eq!(engine.edge_count().unwrap(), 3);
+
+    std::thread::sleep(Duration::from_millis(100));
+
+    // expired but not yet purged, so only hidden
+    {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        assert!(engine.storage.nodes_db.get(&txn, &session).unwrap().is_some());
+        assert!(matches!(
+            engine.storage.get_node(&txn, &session),
+            Err(GraphError::NodeNotFound)
+        ));
+    }
+    assert_eq!(sessions(), vec![lasting]);
+    assert_eq!(edges(), 1);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+    assert_eq!(engine.node_count().unwrap(), 2);
+    assert_eq!(engine.node_count_by_label("session").unwrap(), 1);
+    let neighbors = engine
+        .neighbors_with_props(user, Direction::Out, &["knows"], None)
+        .unwrap();
+    assert_eq!(neighbors.iter().map(|node| node.id).collect::<Vec<_>>(), vec![lasting]);
+
+    // the next write drops it along with its edges
+    engine.insert_node(None, "user", None).unwrap();
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    assert!(engine.storage.nodes_db.get(&txn, &session).unwrap().is_none());
+    assert_eq!(engine.storage.expiries_db.len(&txn).unwrap(), 1);
+    drop(txn);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+    assert_eq!(engine.node_count().unwrap(), 3);
+    assert_eq!(engine.purge_expired().unwrap(), 0);
+}
+
+#[test]
+fn test_expiry_is_kept_apart_from_properties() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_path = temp_dir.path().join("ops.log");
+    let log_path = log_path.to_str().unwrap();
+    let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+        path: temp_dir.path().join("data").to_str().unwrap().to_string(),
+        config: Config {
+            oplog_path: Some(log_path.to_string()),
+            ..Config::default()
+        },
+    })
+    .unwrap();
+
+    // a property of the same name neither sets nor moves the expiry
+    let session = engine
+        .insert_node_with_ttl(None, "session", None, Duration::from_secs(3600))
+        .unwrap();
+    let user = engine
+        .insert_node(None, "user", Some(props! { "expires_at" => 0u64 }.into_iter().collect()))
+        .unwrap();
+    {
+        let txn = engine.storage.graph_env.read_txn().unwrap();
+        let node = engine.storage.get_node(&txn, &session).unwrap();
+        assert!(node.check_property("expires_at").is_err());
+        let node = engine.storage.get_node(&txn, &user).unwrap();
+        assert_eq!(node.check_property("expires_at").unwrap(), &Value::U64(0));
+        assert_eq!(engine.storage.expires_at(&txn, user).unwrap(), None);
+    }
+    let mut patch = HashMap::new();
+    patch.insert("expires_at".to_string(), Value::Empty);
+    engine.update_node_properties(session, patch).unwrap();
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let expires_at = engine.storage.expires_at(&txn, session).unwrap();
+    assert!(expires_at.is_some());
+    drop(txn);
+    assert_eq!(engine.node_count().unwrap(), 2);
+
+    // the expiry is carried through the log and through JSON lines
+    let (rebuilt, _rebuilt_dir) = setup_test_engine();
+    rebuilt.rebuild_from_log(log_path).unwrap();
+    let txn = rebuilt.storage.graph_env.read_txn().unwrap();
+    assert_eq!(rebuilt.storage.expires_at(&txn, session).unwrap(), expires_at);
+    drop(txn);
+
+    let mut exported = Vec::new();
+    engine.export_jsonl(&mut exported).unwrap();
+    let (restored, _restored_dir) = setup_test_engine();
+    restored.import_jsonl(exported.as_slice()).unwrap();
+    let txn = restored.storage.graph_env.read_txn().unwrap();
+    assert_eq!(restored.storage.expires_at(&txn, session).unwrap(), expires_at);
+    assert_eq!(restored.storage.expires_at(&txn, user).unwrap(), None);
+}
+
+#[test]
+fn test_scans_are_lazy_ranges_in_id_order() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut ids: Vec<u128> = engine
+        .add_nodes((0..10).map(|_| ("item", None)).collect())
+        .unwrap()
+        .iter()
+        .map(|node| node.id)
+        .collect();
+    ids.sort_unstable();
+    add_edge(&engine, ids[0], ids[1]);
+    add_edge(&engine, ids[1], ids[2]);
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let scanned = |range: std::ops::Range<u128>| {
+        engine
+            .storage
+            .scan_nodes(&txn, range)
+            .unwrap()
+            .map(|node| node.unwrap().id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(scanned(ids[3]..ids[6]), ids[3..6].to_vec());
+    assert_eq!(scanned(ids[9] + 1..u128::MAX), Vec::<u128>::new());
+
+    let first_two = engine
+        .storage
+        .scan_nodes(&txn, ..)
+        .unwrap()
+        .take(2)
+        .map(|node| node.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(first_two, ids[..2].to_vec());
+    let found = engine
+        .storage
+        .scan_nodes(&txn, ids[2]..)
+        .unwrap()
+        .find(|node| node.as_ref().is_ok_and(|node| node.id > ids[7]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, ids[8]);
+
+    let edges = engine
+        .storage
+        .scan_edges(&txn, ..)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(edges.len(), 2);
+    assert!(edges.windows(2).all(|pair| pair[0].id < pair[1].id));
+}
+
+#[test]
+fn test_read_only_engine_reads_but_rejects_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let (alice, bob) = {
+        let engine = HelixGraphEngine::new(HelixGraphEngineOpts {
+            path: path.to_string(),
+            config: Config::default(),
+        })
+        .unwrap();
+        let alice = add_person(&engine, "alice");
+        let bob = add_person(&engine, "bob");
+        add_edge(&engine, alice, bob);
+        (alice, bob)
+    };
+
+    let engine = HelixGraphEngine::open_read_only(path).unwrap();
+    assert!(engine.storage.read_only);
+    assert_eq!(engine.node_count().unwrap(), 2);
+    let neighbors = engine
+        .neighbors_with_props(alice, Direction::Out, &["knows"], None)
+        .unwrap();
+    assert_eq!(neighbors.iter().map(|node| node.id).collect::<Vec<_>>(), vec![bob]);
+
+    let is_read_only = |result: Result<(), GraphError>| {
+        matches!(result, Err(GraphError::ReadOnly))
+    };
+    assert!(is_read_only(engine.insert_node(None, "person", None).map(|_| ())));
+    assert!(is_read_only(engine.drop_node(bob)));
+    assert!(is_read_only(engine.update_node_properties(alice, HashMap::new()).map(|_| ())));
+    assert!(is_read_only(engine.set_alias("first", alice).map(|_| ())));
+    assert_eq!(engine.node_count().unwrap(), 2);
+
+    // there is nothing to open read-only in a directory that was never written to
+    let empty = TempDir::new().unwrap();
+    assert!(HelixGraphEngine::open_read_only(empty.path().to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_insert_edges_validates_every_endpoint() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let knows = |from, to| EdgeInput {
+        label: "knows",
+        properties: Some(props! { "since" => 2020 }),
+        from,
+        to,
+        direction: EdgeDirection::Directed,
+    };
+
+    let inserted = engine
+        .insert_edges(&[knows(alice, bob), knows(bob, alice)])
+        .unwrap();
+    assert_eq!(inserted.len(), 2);
+    assert_eq!((inserted[0].from_node, inserted[0].to_node), (alice, bob));
+    assert_eq!(engine.edge_count().unwrap(), 2);
+
+    // the second edge points at a node that doesn't exist, so neither is written
+    let missing = uuid::Uuid::new_v4().as_u128();
+    let err = engine
+        .insert_edges(&[knows(alice, bob), knows(alice, missing)])
+        .unwrap_err();
+    assert!(matches!(err, GraphError::DanglingEdge(_)));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Dangling edge: edge 1 (knows) has no to node {}",
+            uuid::Uuid::from_u128(missing)
+        )
+    );
+    assert_eq!(err.status_code(), 404);
+    assert_eq!(engine.edge_count().unwrap(), 2);
+}
+
+#[test]
+fn test_undirected_edges_are_traversed_from_both_ends() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+
+    let friends = engine
+        .insert_edge("friends", None, alice, bob, EdgeDirection::Undirected)
+        .unwrap();
+    assert_eq!(EdgeDirection::of(&friends), EdgeDirection::Undirected);
+    engine
+        .insert_edge("friends", None, carol, bob, EdgeDirection::Directed)
+        .unwrap();
+    // the edge is stored once however it is indexed
+    assert_eq!(engine.edge_count().unwrap(), 2);
+
+    let neighbor_ids = |id, direction| -> Vec<u128> {
+        let mut ids: Vec<u128> = engine
+            .neighbors_with_props(id, direction, &["friends"], None)
+            .unwrap()
+            .iter()
+            .map(|node| node.id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    };
+    let mut alice_and_carol = vec![alice, carol];
+    alice_and_carol.sort_unstable();
+    for direction in [Direction::Out, Direction::In, Direction::Both] {
+        assert_eq!(neighbor_ids(alice, direction), vec![bob]);
+    }
+    assert_eq!(neighbor_ids(bob, Direction::Out), vec![alice]);
+    assert_eq!(neighbor_ids(bob, Direction::In), alice_and_carol);
+    assert_eq!(neighbor_ids(bob, Direction::Both), alice_and_carol);
+
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let stored = engine.storage.get_edge(&txn, &friends.id).unwrap();
+    assert_eq!(
+        stored.properties.unwrap().get(UNDIRECTED),
+        Some(&Value::Boolean(true))
+    );
+    drop(txn);
+
+    // dropping either end drops the edge and its entries under the other end
+    engine.drop_node(bob).unwrap();
+    assert_eq!(engine.edge_count().unwrap(), 0);
+    assert!(neighbor_ids(alice, Direction::Both).is_empty());
+    assert!(neighbor_ids(carol, Direction::Both).is_empty());
+
+    let err = engine
+        .insert_edge("friends", None, alice, bob, EdgeDirection::Undirected)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Dangling edge: friends edge has no to node {}",
+            uuid::Uuid::from_u128(bob)
+        )
+    );
+}
+
+#[test]
+fn test_drop_edge_keeps_other_edges_with_the_same_label() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+    let to_bob = engine
+        .insert_edge("friends", None, alice, bob, EdgeDirection::Undirected)
+        .unwrap();
+    engine
+        .insert_edge("friends", None, alice, carol, EdgeDirection::Undirected)
+        .unwrap();
+
+    let mut txn = engine.storage.graph_env.write_txn().unwrap();
+    engine.storage.drop_edge(&mut txn, &to_bob.id).unwrap();
+    txn.commit().unwrap();
+
+    let neighbors = engine
+        .neighbors_with_props(alice, Direction::Both, &["friends"], None)
+        .unwrap();
+    assert_eq!(neighbors.len(), 1);
+    assert_eq!(neighbors[0].id, carol);
+    assert!(engine
+        .neighbors_with_props(bob, Direction::Both, &["friends"], None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_neighbors_with_edges_returns_connecting_edge_properties() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+
+    let follows = |from, to, weight: f64, direction| {
+        engine
+            .insert_edge(
+                "follows",
+                Some(HashMap::from([("weight".to_string(), Value::F64(weight))])),
+                from,
+                to,
+                direction,
+            )
+            .unwrap()
+    };
+    let to_bob = follows(alice, bob, 0.5, EdgeDirection::Directed);
+    follows(alice, carol, 2.0, EdgeDirection::Undirected);
+
+    let stored = engine.get_edge(to_bob.id).unwrap();
+    assert_eq!(stored.properties.unwrap()["weight"], Value::F64(0.5));
+    assert!(matches!(engine.get_edge(u128::MAX), Err(GraphError::EdgeNotFound)));
+
+    let mut neighbors = engine
+        .neighbors_with_edges(alice, Direction::Both, &["follows"], None)
+        .unwrap();
+    neighbors.sort_by(|(a, _), (b, _)| {
+        a.check_property("weight")
+            .unwrap()
+            .partial_cmp(b.check_property("weight").unwrap())
+            .unwrap()
+    });
+    let found: Vec<(u128, Value)> = neighbors
+        .iter()
+        .map(|(edge, node)| (node.id, edge.check_property("weight").unwrap().clone()))
+        .collect();
+    // the undirected edge is found in both tables but only returned once
+    assert_eq!(
+        found,
+        vec![(bob, Value::F64(0.5)), (carol, Value::F64(2.0))]
+    );
+
+    let from_carol = engine
+        .neighbors_with_edges(carol, Direction::Out, &["follows"], None)
+        .unwrap();
+    assert_eq!(from_carol.len(), 1);
+    assert_eq!(from_carol[0].1.id, alice);
+}
+
+#[test]
+fn test_extract_subgraph_within_radius() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|name| add_person(&engine, name));
+    let edge = |label, from, to| {
+        engine
+            .insert_edge(label, None, from, to, EdgeDirection::Directed)
+            .unwrap()
+            .id
+    };
+    let a_b = edge("FOLLOWS", a, b);
+    let a_c = edge("FOLLOWS", a, c);
+    let b_c = edge("FOLLOWS", c, b);
+    edge("FOLLOWS", d, c);
+    edge("LIKES", a, e);
+    let ids = |subgraph: &Subgraph| {
+        let mut nodes: Vec<u128> = subgraph.nodes.iter().map(|node| node.id).collect();
+        let mut edges: Vec<u128> = subgraph.edges.iter().map(|edge| edge.id).collect();
+        nodes.sort();
+        edges.sort();
+        (nodes, edges)
+    };
+    let sorted = |mut ids: Vec<u128>| {
+        ids.sort();
+        ids
+    };
+
+    // the edge between the two nodes at the edge of the radius is kept, either way it points
+    let subgraph = engine
+        .extract_subgraph(&[a, a], 1, &["FOLLOWS"], None)
+        .unwrap();
+    assert_eq!(subgraph.nodes[0].id, a);
+    assert_eq!(
+        ids(&subgraph),
+        (sorted(vec![a, b, c]), sorted(vec![a_b, a_c, b_c]))
+    );
+    assert!(!subgraph.truncated);
+
+    let subgraph = engine.extract_subgraph(&[a], 2, &[], None).unwrap();
+    assert_eq!(subgraph.nodes.len(), 5);
+    assert_eq!(subgraph.edges.len(), 5);
+    let subgraph = engine.extract_subgraph(&[a], 0, &[], None).unwrap();
+    assert_eq!(ids(&subgraph), (vec![a], vec![]));
+
+    // edges to nodes left out by the cap are left out with them
+    let subgraph = engine
+        .extract_subgraph(&[a], 2, &["FOLLOWS"], Some(2))
+        .unwrap();
+    assert_eq!(subgraph.nodes.len(), 2);
+    assert_eq!(subgraph.edges.len(), 1);
+    assert!(subgraph.truncated);
+
+    let json: sonic_rs::Value = sonic_rs::from_slice(&sonic_rs::to_vec(&subgraph).unwrap()).unwrap();
+    let a_id = uuid::Uuid::from_u128(a).to_string();
+    assert_eq!(json["nodes"][0]["id"].as_str(), Some(a_id.as_str()));
+    assert_eq!(json["nodes"][0]["name"].as_str(), Some("a"));
+    assert_eq!(json["edges"][0]["label"].as_str(), Some("FOLLOWS"));
+    assert_eq!(json["truncated"].as_bool(), Some(true));
+
+    assert!(matches!(
+        engine.extract_subgraph(&[a, u128::MAX], 1, &[], None),
+        Err(GraphError::NodeNotFound)
+    ));
+}
+
+#[test]
+fn test_degree_counts_edges_once() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice");
+    let bob = add_person(&engine, "bob");
+    let carol = add_person(&engine, "carol");
+    let edge = |label, from, to, direction| {
+        engine.insert_edge(label, None, from, to, direction).unwrap();
+    };
+    edge("FOLLOWS", alice, bob, EdgeDirection::Directed);
+    edge("FOLLOWS", alice, carol, EdgeDirection::Directed);
+    edge("FOLLOWS", bob, alice, EdgeDirection::Directed);
+    edge("KNOWS", alice, carol, EdgeDirection::Undirected);
+    edge("LIKES", alice, alice, EdgeDirection::Directed);
+
+    // the undirected edge and the self loop are both out and in edges, but count once both ways
+    for (direction, degree) in [(Direction::Out, 4), (Direction::In, 3), (Direction::Both, 5)] {
+        assert_eq!(engine.degree(alice, direction).unwrap(), degree);
+        let neighbors = engine.neighbors_with_edges(alice, direction, &[], None).unwrap();
+        assert_eq!(neighbors.len() as u64, degree);
+    }
+    assert_eq!(engine.degree_by_type(alice, Direction::Out, "FOLLOWS").unwrap(), 2);
+    assert_eq!(engine.degree_by_type(alice, Direction::In, "FOLLOWS").unwrap(), 1);
+    assert_eq!(engine.degree_by_type(alice, Direction::Both, "FOLLOWS").unwrap(), 3);
+    assert_eq!(engine.degree_by_type(carol, Direction::Out, "KNOWS").unwrap(), 1);
+    assert_eq!(engine.degree_by_type(carol, Direction::Both, "KNOWS").unwrap(), 1);
+    assert_eq!(engine.degree_by_type(bob, Direction::Out, "KNOWS").unwrap(), 0);
+    assert!(matches!(
+        engine.degree(u128::MAX, Direction::Both),
+        Err(GraphError::NodeNotFound)
+    ));
+}
+
+#[test]
+fn test_delete_nodes_where_drops_matches_and_their_edges() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let nodes = engine
+        .add_nodes(vec![
+            ("person", Some(props! { "name" => "alice", "age" => 25 })),
+            ("person", Some(props! { "name" => "bob", "age" => 40 })),
+            ("person", Some(props! { "name" => "carol", "age" => 19 })),
+            ("company", Some(props! { "name" => "acme", "age" => 5 })),
+        ])
+        .unwrap();
+    let (alice, bob, carol, acme) = (nodes[0].id, nodes[1].id, nodes[2].id, nodes[3].id);
+    engine
+        .insert_edge("knows", None, alice, bob, EdgeDirection::Directed)
+        .unwrap();
+    engine
+        .insert_edge("works_at", None, bob, acme, EdgeDirection::Directed)
+        .unwrap();
+    engine
+        .insert_edge("knows", None, carol, alice, EdgeDirection::Directed)
+        .unwrap();
+
+    assert!(matches!(
+        engine.delete_nodes_where("person", &[]),
+        Err(GraphError::New(_))
+    ));
+    assert_eq!(engine.node_count().unwrap(), 4);
+
+    let young = [Predicate::Lt("age".to_string(), Value::I32(30))];
+    assert_eq!(engine.delete_nodes_where("person", &young).unwrap(), 2);
+    assert_eq!(engine.delete_nodes_where("person", &young).unwrap(), 0);
+
+    // acme is younger than 30 too, but isn't a person
+    let txn = engine.storage.graph_env.read_txn().unwrap();
+    let mut left: Vec<u128> = engine
+        .storage
+        .scan_nodes(&txn, ..)
+        .unwrap()
+        .map(|node| node.unwrap().id)
+        .collect();
+    left.sort();
+    let mut expected = vec![bob, acme];
+    expected.sort();
+    assert_eq!(left, expected);
+    assert!(engine.storage.get_node(&txn, &carol).is_err());
+    drop(txn);
+    assert_eq!(engine.edge_count().unwrap(), 1);
+
+    // every predicate has to hold
+    let predicates = [
+        Predicate::Gt("age".to_string(), Value::I32(29)),
+        Predicate::Eq("name".to_string(), Value::from("nobody")),
+    ];
+    assert_eq!(engine.delete_nodes_where("person", &predicates).unwrap(), 0);
+
+    // a predicate of the wrong type fails rather than matching nothing
+    let mistyped = [Predicate::Lt("name".to_string(), Value::I32(30))];
+    assert!(matches!(
+        engine.delete_nodes_where("person", &mistyped),
+        Err(GraphError::TraversalError(_))
+    ));
+    assert_eq!(engine.node_count().unwrap(), 2);
+}
+
+#[test]
+fn test_delete_nodes_where_spans_batches() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let total = DELETE_BATCH_SIZE * 2 + 5;
+    let nodes = (0..total)
+        .map(|i| ("item", Some(props! { "stale" => i % 3 != 0 })))
+        .collect();
+    engine.add_nodes(nodes).unwrap();
+
+    let stale = [Predicate::Eq("stale".to_string(), Value::Boolean(true))];
+    let expected = (0..total).filter(|i| i % 3 != 0).count();
+    assert_eq!(engine.delete_nodes_where("item", &stale).unwrap(), expected);
+    assert_eq!(engine.node_count().unwrap(), (total - expected) as u64);
+
+    let all = [Predicate::Eq("stale".to_string(), Value::Boolean(false))];
+    assert_eq!(engine.delete_nodes_where("item", &all).unwrap(), total - expected);
+    assert_eq!(engine.node_count().unwrap(), 0);
+}
+
+#[test]
+fn test_predicate_matches() {
+    let node = Node {
+        id: 1,
+        label: "person".to_string(),
+        properties: Some(HashMap::from([
+            ("name".to_string(), Value::from("alice smith")),
+            ("age".to_string(), Value::I32(30)),
+            (
+                "tags".to_string(),
+                Value::Array(vec![Value::from("admin"), Value::from("ops")]),
+            ),
+        ])),
+    };
+    let matches = |predicate: Predicate| predicate.matches(&node).unwrap();
+
+    assert!(matches(Predicate::Eq("age".to_string(), Value::F64(30.0))));
+    assert!(matches(Predicate::Ne("age".to_string(), Value::U8(18))));
+    assert!(matches(Predicate::Gt("age".to_string(), Value::I64(18))));
+    assert!(!matches(Predicate::Lt("age".to_string(), Value::I64(18))));
+    assert!(matches(Predicate::In(
+        "name".to_string(),
+        vec![Value::from("bob"), Value::from("alice smith")]
+    )));
+    assert!(!matches(Predicate::In("name".to_string(), Vec::new())));
+    assert!(matches(Predicate::Exists("tags".to_string())));
+    assert!(matches(Predicate::Contains("name".to_string(), Value::from("smith"))));
+    assert!(matches(Predicate::Contains("tags".to_string(), Value::from("ops"))));
+    assert!(!matches(Predicate::Contains("tags".to_string(), Value::from("dev"))));
+
+    // a missing property only passes `Ne`
+    assert!(!matches(Predicate::Exists("email".to_string())));
+    assert!(!matches(Predicate::Eq("email".to_string(), Value::from("a@b.c"))));
+    assert!(matches(Predicate::Ne("email".to_string(), Value::from("a@b.c"))));
+
+    for predicate in [
+        Predicate::Gt("name".to_string(), Value::I32(1)),
+        Predicate::In("age".to_string(), vec![Value::from("30")]),
+        Predicate::Contains("age".to_string(), Value::from("3")),
+    ] {
+        assert!(matches!(
+            predicate.matches(&node),
+            Err(GraphError::TraversalError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_nodes_and_neighbors_where() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let nodes = engine
+        .add_nodes(vec![
+            ("person", Some(props! { "name" => "alice", "age" => 40 })),
+            ("person", Some(props! { "name" => "bob", "age" => 17, "email" => "bob@example.com" })),
+            ("person", Some(props! { "name" => "carol", "age" => 25 })),
+            ("person", Some(props! { "name" => "dave", "age" => "unknown" })),
+            ("pet", Some(props! { "name" => "rex", "age" => 3 })),
+        ])
+        .unwrap();
+    let ids: Vec<u128> = nodes.iter().map(|node| node.id).collect();
+    for &to in &ids[1..3] {
+        engine
+            .insert_edge("knows", None, ids[0], to, EdgeDirection::Directed)
+            .unwrap();
+    }
+    engine
+        .insert_edge("owns", None, ids[0], ids[4], EdgeDirection::Directed)
+        .unwrap();
+
+    let names = |nodes: Vec<Node>| -> Vec<String> {
+        let mut names: Vec<String> = nodes
+            .into_iter()
+            .map(|node| node.check_property("name").unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let adults = Predicate::Gt("age".to_string(), Value::I32(18));
+    let neighbors = engine
+        .neighbors_where(ids[0], Direction::Out, &[], &adults, None)
+        .unwrap();
+    assert_eq!(names(neighbors), ["carol"]);
+    let neighbors = engine
+        .neighbors_where(
+            ids[0],
+            Direction::Out,
+            &["knows"],
+            &Predicate::Exists("email".to_string()),
+            None,
+        )
+        .unwrap();
+    assert_eq!(names(neighbors), ["bob"]);
+
+    let named = Predicate::In(
+        "name".to_string(),
+        vec![Value::from("alice"), Value::from("carol"), Value::from("rex")],
+    );
+    assert_eq!(names(engine.nodes_where("person", &named, None).unwrap()), ["alice", "carol"]);
+    assert_eq!(engine.nodes_where("person", &named, Some(1)).unwrap().len(), 1);
+
+    // dave's age is a string
+    assert!(matches!(
+        engine.nodes_where("person", &adults, None),
+        Err(GraphError::TraversalError(_))
+    ));
+
+    // neighbors stop once enough have matched
+    let anyone = Predicate::Exists("name".to_string());
+    let neighbors = engine
+        .neighbors_where(ids[0], Direction::Out, &[], &anyone, Some(2))
+        .unwrap();
+    assert_eq!(neighbors.len(), 2);
+
+    // with a range index the candidates come from it, leaving out dave's string age
+    engine.create_range_index("person", "age").unwrap();
+    let adults = engine.nodes_where("person", &adults, None).unwrap();
+    let adult_ids: Vec<u128> = adults.iter().map(|node| node.id).collect();
+    let mut expected = vec![ids[0], ids[2]];
+    expected.sort();
+    assert_eq!(adult_ids, expected);
+    let exactly = Predicate::Eq("age".to_string(), Value::I32(17));
+    assert_eq!(names(engine.nodes_where("person", &exactly, None).unwrap()), ["bob"]);
+    let minors = Predicate::Lt("age".to_string(), Value::I32(18));
+    assert_eq!(names(engine.nodes_where("person", &minors, Some(1)).unwrap()), ["bob"]);
+}
@@ -3,5 +3,7 @@ pub mod graph_core;
 pub mod ops;
 pub mod traversal_iter;
 
+#[cfg(test)]
+mod graph_core_tests;
 #[cfg(test)]
 mod traversal_tests;
@@ -4,7 +4,14 @@ use super::super::tr_val::TraversalVal;
 use crate::{
     helix_engine::{
         graph_core::traversal_iter::RwTraversalIterator,
-        storage_core::storage_core::HelixGraphStorage, types::GraphError, vector_core::hnsw::HNSW,
+        storage_core::{
+            changes::{ChangeEvent, ChangeKind},
+            oplog::Operation,
+            storage_core::HelixGraphStorage,
+            storage_methods::CountMethods,
+        },
+        types::GraphError,
+        vector_core::hnsw::HNSW,
     },
     protocol::value::Value,
     utils::{id::v6_uuid, items::Edge, label_hash::hash_label},
@@ -77,6 +84,17 @@ impl<'a, 'b, I: Iterator<Item = Result<TraversalVal, GraphError>>> AddEAdapter<'
             to_node,
         };
 
+        if let Err(e) = self.storage.check_quota(self.txn, 0, 1).and_then(|_| {
+            self.storage
+                .validate_schema(self.txn, &edge.label, edge.properties.as_ref())
+        }) {
+            return RwTraversalIterator {
+                inner: std::iter::once(Err(e)),
+                storage: self.storage,
+                txn: self.txn,
+            };
+        }
+
         let mut result: Result<TraversalVal, GraphError> = Ok(TraversalVal::Empty);
 
         /*
@@ -114,7 +132,7 @@ impl<'a, 'b, I: Iterator<Item = Result<TraversalVal, GraphError>>> AddEAdapter<'
         }
         */
 
-        match edge.encode_edge() {
+        match self.storage.encode_edge(&edge) {
             Ok(bytes) => {
                 if let Err(e) = self.storage.edges_db.put_with_flags(
                     self.txn,
@@ -123,6 +141,13 @@ impl<'a, 'b, I: Iterator<Item = Result<TraversalVal, GraphError>>> AddEAdapter<'
                     &bytes,
                 ) {
                     result = Err(GraphError::from(e));
+                } else if let Err(e) = self.storage.record_edge_added(self.txn) {
+                    result = Err(e);
+                } else if let Err(e) = self.storage.log_operation(|| Operation::add_edge(&edge)) {
+                    result = Err(e);
+                } else {
+                    self.storage
+                        .record_change(|| ChangeEvent::edge(ChangeKind::Insert, &edge));
                 }
             }
             Err(e) => result = Err(GraphError::from(e)),
@@ -3,6 +3,11 @@ use crate::{
     helix_engine::{
         bm25::bm25::{BM25Flatten, BM25},
         graph_core::traversal_iter::RwTraversalIterator,
+        storage_core::{
+            changes::{ChangeEvent, ChangeKind},
+            oplog::Operation,
+            storage_methods::CountMethods,
+        },
         types::GraphError,
     },
     protocol::value::Value,
@@ -45,10 +50,26 @@ impl<'a, 'b, I: Iterator<Item = Result<TraversalVal, GraphError>>> AddNAdapter<'
             label: label.to_string(), // TODO: just &str or Cow<'a, str>
             properties: properties.map(|props| props.into_iter().collect()),
         };
+        if let Err(e) = self
+            .storage
+            .check_quota(self.txn, 1, 0)
+            .and_then(|_| {
+                self.storage
+                    .validate_schema(self.txn, &node.label, node.properties.as_ref())
+            })
+            .and_then(|_| self.storage.update_node_indices(self.txn, None, Some(&node)))
+        {
+            return RwTraversalIterator {
+                inner: std::iter::once(Err(e)),
+                storage: self.storage,
+                txn: self.txn,
+            };
+        }
+
         let secondary_indices = secondary_indices.unwrap_or(&[]).to_vec();
         let mut result: Result<TraversalVal, GraphError> = Ok(TraversalVal::Empty);
 
-        match node.encode_node() {
+        match self.storage.encode_node(&node) {
             Ok(bytes) => {
                 if let Err(e) = self.storage.nodes_db.put_with_flags(
                     self.txn,
@@ -57,6 +78,13 @@ impl<'a, 'b, I: Iterator<Item = Result<TraversalVal, GraphError>>> AddNAdapter<'
                     &bytes,
                 ) {
                     result = Err(GraphError::from(e));
+                } else if let Err(e) = self.storage.record_node_added(self.txn, &node.label) {
+                    result = Err(e);
+                } else if let Err(e) = self.storage.log_operation(|| Operation::add_node(&node)) {
+                    result = Err(e);
+                } else {
+                    self.storage
+                        .record_change(|| ChangeEvent::node(ChangeKind::Insert, &node));
                 }
             }
             Err(e) => result = Err(GraphError::from(e)),
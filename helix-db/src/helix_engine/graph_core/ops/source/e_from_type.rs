@@ -1,31 +1,48 @@
 use crate::{
     helix_engine::{
         graph_core::{ops::tr_val::TraversalVal, traversal_iter::RoTraversalIterator},
+        storage_core::timestamps::now_millis,
         types::GraphError,
     },
     utils::items::Edge,
 };
 use heed3::{
     byteorder::BE,
-    types::{Bytes, U128},
+    types::{Bytes, Lazy, LazyDecode, U128},
 };
+use std::{collections::HashSet, ops::Bound};
 use helix_macros::debug_trace;
 
-pub struct EFromType<'a> {
-    pub iter: heed3::RoIter<'a, U128<BE>, heed3::types::LazyDecode<Bytes>>,
+pub struct EFromType<'a, I = heed3::RoIter<'a, U128<BE>, LazyDecode<Bytes>>> {
+    pub iter: I,
     pub label: &'a str,
+    /// Ids of the nodes that had expired when the scan started, which it skips the edges of.
+    /// An error reading them is returned in their place, which ends the scan.
+    pub expired: Option<Result<HashSet<u128>, GraphError>>,
 }
 
-impl<'a> Iterator for EFromType<'a> {
+impl<'a, I> Iterator for EFromType<'a, I>
+where
+    I: Iterator<Item = heed3::Result<(u128, Lazy<'a, Bytes>)>>,
+{
     type Item = Result<TraversalVal, GraphError>;
 
     #[debug_trace("E_FROM_TYPE")]
     fn next(&mut self) -> Option<Self::Item> {
+        let expired = match &self.expired {
+            Some(Ok(expired)) => expired,
+            _ => return self.expired.take().and_then(Result::err).map(Err),
+        };
         while let Some(value) = self.iter.next() {
             let (key, value) = value.unwrap();
             match value.decode() {
                 Ok(value) => match Edge::decode_edge(&value, key) {
                     Ok(edge) => match &edge.label {
+                        _ if expired.contains(&edge.from_node)
+                            || expired.contains(&edge.to_node) =>
+                        {
+                            continue;
+                        }
                         label if label == self.label => return Some(Ok(TraversalVal::Edge(edge))),
                         _ => continue,
                     },
@@ -42,6 +59,16 @@ pub trait EFromTypeAdapter<'a>: Iterator<Item = Result<TraversalVal, GraphError>
         self,
         label: &'a str,
     ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>>;
+
+    /// Returns an iterator containing the edges with the given label whose ids come after `after`.
+    ///
+    /// Seeks straight to the first id after `after` rather than scanning from the start,
+    /// which is what makes cursor based pagination cheap.
+    fn e_from_type_after(
+        self,
+        label: &'a str,
+        after: Option<u128>,
+    ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>>;
 }
 impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> EFromTypeAdapter<'a>
     for RoTraversalIterator<'a, I>
@@ -58,7 +85,38 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> EFromTypeAdapter<
             .iter(self.txn)
             .unwrap();
         RoTraversalIterator {
-            inner: EFromType { iter, label },
+            inner: EFromType {
+                iter,
+                label,
+                expired: Some(self.storage.expired_ids(self.txn, now_millis())),
+            },
+            storage: self.storage,
+            txn: self.txn,
+        }
+    }
+
+    #[inline]
+    fn e_from_type_after(
+        self,
+        label: &'a str,
+        after: Option<u128>,
+    ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        let iter = self
+            .storage
+            .edges_db
+            .lazily_decode_data()
+            .range(self.txn, &(start, Bound::Unbounded))
+            .unwrap();
+        RoTraversalIterator {
+            inner: EFromType {
+                iter,
+                label,
+                expired: Some(self.storage.expired_ids(self.txn, now_millis())),
+            },
             storage: self.storage,
             txn: self.txn,
         }
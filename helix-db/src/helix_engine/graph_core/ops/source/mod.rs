@@ -3,6 +3,7 @@ pub mod add_n;
 
 pub mod e_from_id;
 pub mod e_from_type;
+pub mod n_from_alias;
 pub mod n_from_id;
 pub mod n_from_index;
 pub mod n_from_type;
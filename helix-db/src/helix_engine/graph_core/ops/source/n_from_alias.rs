@@ -0,0 +1,66 @@
+use crate::{
+    helix_engine::{
+        graph_core::{ops::tr_val::TraversalVal, traversal_iter::RoTraversalIterator},
+        storage_core::{
+            storage_core::HelixGraphStorage,
+            storage_methods::{AliasMethods, StorageMethods},
+        },
+        types::GraphError,
+    },
+    utils::items::Node,
+};
+use helix_macros::debug_trace;
+use heed3::RoTxn;
+use std::{iter::Once, sync::Arc};
+
+pub struct NFromAlias<'a, T> {
+    iter: Once<Result<TraversalVal, GraphError>>,
+    storage: Arc<HelixGraphStorage>,
+    txn: &'a T,
+    alias: &'a str,
+}
+
+impl<'a> Iterator for NFromAlias<'a, RoTxn<'a>> {
+    type Item = Result<TraversalVal, GraphError>;
+
+    #[debug_trace("N_FROM_ALIAS")]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|_| {
+            // alias and node are read in the same txn so a concurrent swap can't be observed halfway
+            let id = self.storage.resolve_alias(self.txn, self.alias)?;
+            let node: Node = self.storage.get_node(self.txn, &id)?;
+            Ok(TraversalVal::Node(node))
+        })
+    }
+}
+
+pub trait NFromAliasAdapter<'a>: Iterator<Item = Result<TraversalVal, GraphError>> {
+    type OutputIter: Iterator<Item = Result<TraversalVal, GraphError>>;
+
+    /// Returns an iterator containing the node the given alias currently points at.
+    ///
+    /// Yields `GraphError::AliasNotFound` if the alias has not been set.
+    fn n_from_alias(self, alias: &'a str) -> Self::OutputIter;
+}
+
+impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> NFromAliasAdapter<'a>
+    for RoTraversalIterator<'a, I>
+{
+    type OutputIter = RoTraversalIterator<'a, NFromAlias<'a, RoTxn<'a>>>;
+
+    #[inline]
+    fn n_from_alias(self, alias: &'a str) -> Self::OutputIter {
+        let n_from_alias = NFromAlias {
+            iter: std::iter::once(Ok(TraversalVal::Empty)),
+            storage: Arc::clone(&self.storage),
+            txn: self.txn,
+            alias,
+        };
+
+        RoTraversalIterator {
+            inner: n_from_alias,
+            storage: self.storage,
+            txn: self.txn,
+        }
+    }
+}
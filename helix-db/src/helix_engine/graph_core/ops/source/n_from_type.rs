@@ -1,6 +1,7 @@
 use crate::{
     helix_engine::{
         graph_core::{ops::tr_val::TraversalVal, traversal_iter::RoTraversalIterator},
+        storage_core::timestamps::now_millis,
         types::GraphError,
     },
     utils::items::Node,
@@ -8,21 +9,35 @@ use crate::{
 use helix_macros::debug_trace;
 use heed3::{
     byteorder::BE,
-    types::{Bytes, U128},
+    types::{Bytes, Lazy, LazyDecode, U128},
 };
+use std::{collections::HashSet, ops::Bound};
 
-pub struct NFromType<'a> {
-    pub iter: heed3::RoIter<'a, U128<BE>, heed3::types::LazyDecode<Bytes>>,
+pub struct NFromType<'a, I = heed3::RoIter<'a, U128<BE>, LazyDecode<Bytes>>> {
+    pub iter: I,
     pub label: &'a str,
+    /// Ids of the nodes that had expired when the scan started, which it skips.
+    /// An error reading them is returned in their place, which ends the scan.
+    pub expired: Option<Result<HashSet<u128>, GraphError>>,
 }
 
-impl<'a> Iterator for NFromType<'a> {
+impl<'a, I> Iterator for NFromType<'a, I>
+where
+    I: Iterator<Item = heed3::Result<(u128, Lazy<'a, Bytes>)>>,
+{
     type Item = Result<TraversalVal, GraphError>;
 
     #[debug_trace("N_FROM_TYPE")]
     fn next(&mut self) -> Option<Self::Item> {
+        let expired = match &self.expired {
+            Some(Ok(expired)) => expired,
+            _ => return self.expired.take().and_then(Result::err).map(Err),
+        };
         while let Some(value) = self.iter.next() {
             let (key_, value) = value.unwrap();
+            if expired.contains(&key_) {
+                continue;
+            }
             match value.decode() {
                 Ok(value) => match Node::decode_node(&value, key_) {
                     Ok(node) => match &node.label {
@@ -48,6 +63,16 @@ pub trait NFromTypeAdapter<'a>: Iterator<Item = Result<TraversalVal, GraphError>
         self,
         label: &'a str,
     ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>>;
+
+    /// Returns an iterator containing the nodes with the given label whose ids come after `after`.
+    ///
+    /// Seeks straight to the first id after `after` rather than scanning from the start,
+    /// which is what makes cursor based pagination cheap.
+    fn n_from_type_after(
+        self,
+        label: &'a str,
+        after: Option<u128>,
+    ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>>;
 }
 impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> NFromTypeAdapter<'a>
     for RoTraversalIterator<'a, I>
@@ -64,7 +89,38 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> NFromTypeAdapter<
             .iter(self.txn)
             .unwrap();
         RoTraversalIterator {
-            inner: NFromType { iter, label },
+            inner: NFromType {
+                iter,
+                label,
+                expired: Some(self.storage.expired_ids(self.txn, now_millis())),
+            },
+            storage: self.storage,
+            txn: self.txn,
+        }
+    }
+
+    #[inline]
+    fn n_from_type_after(
+        self,
+        label: &'a str,
+        after: Option<u128>,
+    ) -> RoTraversalIterator<'a, impl Iterator<Item = Result<TraversalVal, GraphError>>> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        let iter = self
+            .storage
+            .nodes_db
+            .lazily_decode_data()
+            .range(self.txn, &(start, Bound::Unbounded))
+            .unwrap();
+        RoTraversalIterator {
+            inner: NFromType {
+                iter,
+                label,
+                expired: Some(self.storage.expired_ids(self.txn, now_millis())),
+            },
             storage: self.storage,
             txn: self.txn,
         }
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    helix_engine::{
+        graph_core::{
+            ops::tr_val::{Traversable, TraversalVal},
+            traversal_iter::{RoTraversalIterator, RwTraversalIterator},
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+};
+
+pub trait AggregateAdapter<'a>: Iterator<Item = Result<TraversalVal, GraphError>> {
+    /// Counts the items in the traversal grouped by the value of `property`.
+    ///
+    /// Items without the property are not counted.
+    fn group_count(self, property: &str) -> Result<BTreeMap<Value, usize>, GraphError>;
+
+    /// Sums the numeric values of `property` over the items in the traversal.
+    ///
+    /// Items without the property are skipped. Non-numeric values are skipped
+    /// unless `strict` is set, in which case they return an error.
+    fn sum_of(self, property: &str, strict: bool) -> Result<f64, GraphError>;
+
+    /// Averages the numeric values of `property` over the items in the traversal.
+    ///
+    /// Returns `None` if no item has a numeric value for the property.
+    /// Non-numeric values are handled in the same way as in [`AggregateAdapter::sum_of`].
+    fn avg_of(self, property: &str, strict: bool) -> Result<Option<f64>, GraphError>;
+}
+
+fn group_count<I>(iter: I, property: &str) -> Result<BTreeMap<Value, usize>, GraphError>
+where
+    I: Iterator<Item = Result<TraversalVal, GraphError>>,
+{
+    let mut groups = BTreeMap::new();
+    for item in iter {
+        if let Ok(value) = item?.check_property(property) {
+            *groups.entry(value.clone()).or_insert(0) += 1;
+        }
+    }
+    Ok(groups)
+}
+
+/// Returns the sum and number of the numeric values of `property`.
+fn numeric_totals<I>(iter: I, property: &str, strict: bool) -> Result<(f64, usize), GraphError>
+where
+    I: Iterator<Item = Result<TraversalVal, GraphError>>,
+{
+    let mut total = 0.0;
+    let mut count = 0;
+    for item in iter {
+        let item = item?;
+        let value = match item.check_property(property) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        match value.as_f64() {
+            Some(n) => {
+                total += n;
+                count += 1;
+            }
+            None if strict => {
+                return Err(GraphError::ConversionError(format!(
+                    "Property {} is not numeric: {:?}",
+                    property, value
+                )));
+            }
+            None => continue,
+        }
+    }
+    Ok((total, count))
+}
+
+fn average((total, count): (f64, usize)) -> Option<f64> {
+    match count {
+        0 => None,
+        _ => Some(total / count as f64),
+    }
+}
+
+impl<'a, I> AggregateAdapter<'a> for RoTraversalIterator<'a, I>
+where
+    I: Iterator<Item = Result<TraversalVal, GraphError>>,
+{
+    fn group_count(self, property: &str) -> Result<BTreeMap<Value, usize>, GraphError> {
+        group_count(self.inner, property)
+    }
+
+    fn sum_of(self, property: &str, strict: bool) -> Result<f64, GraphError> {
+        numeric_totals(self.inner, property, strict).map(|(total, _)| total)
+    }
+
+    fn avg_of(self, property: &str, strict: bool) -> Result<Option<f64>, GraphError> {
+        numeric_totals(self.inner, property, strict).map(average)
+    }
+}
+
+impl<'scope, 'env, I> AggregateAdapter<'env> for RwTraversalIterator<'scope, 'env, I>
+where
+    I: Iterator<Item = Result<TraversalVal, GraphError>>,
+{
+    fn group_count(self, property: &str) -> Result<BTreeMap<Value, usize>, GraphError> {
+        group_count(self.inner, property)
+    }
+
+    fn sum_of(self, property: &str, strict: bool) -> Result<f64, GraphError> {
+        numeric_totals(self.inner, property, strict).map(|(total, _)| total)
+    }
+
+    fn avg_of(self, property: &str, strict: bool) -> Result<Option<f64>, GraphError> {
+        numeric_totals(self.inner, property, strict).map(average)
+    }
+}
@@ -1,10 +1,13 @@
+pub mod aggregate;
 pub mod dedup;
 pub mod drop;
 pub mod exist;
 pub mod filter_mut;
 pub mod filter_ref;
 pub mod map;
+pub mod paginate;
 pub mod paths;
+pub mod predicate;
 pub mod props;
 pub mod range;
 pub mod update;
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+use crate::helix_engine::{
+    graph_core::{
+        ops::tr_val::{Traversable, TraversalVal},
+        traversal_iter::RoTraversalIterator,
+    },
+    types::GraphError,
+};
+
+/// Which page of a result set to return.
+///
+/// The cursor is opaque to clients and is taken from the `next_cursor` of the previous page.
+/// It encodes the id of the last item returned, so items inserted after a page was read
+/// sort after it and are picked up by later pages without shifting earlier ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    pub limit: usize,
+    pub cursor: Option<String>,
+}
+
+/// A page of results along with the cursor for the page after it.
+///
+/// `next_cursor` is `None` once the last page has been returned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Paged<T> {
+    pub items: T,
+    pub next_cursor: Option<String>,
+}
+
+impl Page {
+    /// The first page of a result set
+    pub fn first(limit: usize) -> Self {
+        Self {
+            limit,
+            cursor: None,
+        }
+    }
+
+    /// The page following the given page of results
+    pub fn next<T>(limit: usize, previous: &Paged<T>) -> Self {
+        Self {
+            limit,
+            cursor: previous.next_cursor.clone(),
+        }
+    }
+
+    /// Decodes the cursor into the id of the last item already returned
+    pub fn after(&self) -> Result<Option<u128>, GraphError> {
+        match &self.cursor {
+            Some(cursor) => u128::from_str_radix(cursor, 16)
+                .map(Some)
+                .map_err(|_| GraphError::ConversionError(format!("Invalid cursor: {}", cursor))),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes the cursor into the ids of the last item already returned, for results such as
+    /// query rows that are ordered by several ids rather than one
+    pub fn after_ids(&self) -> Result<Option<Vec<u128>>, GraphError> {
+        match &self.cursor {
+            Some(cursor) => cursor
+                .split('.')
+                .map(|id| u128::from_str_radix(id, 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+                .map_err(|_| GraphError::ConversionError(format!("Invalid cursor: {}", cursor))),
+            None => Ok(None),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn encode_cursor(id: u128) -> String {
+        format!("{:032x}", id)
+    }
+
+    pub(crate) fn encode_ids_cursor(ids: &[u128]) -> String {
+        ids.iter()
+            .map(|id| Self::encode_cursor(*id))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+pub trait PaginateAdapter<'a>: Iterator<Item = Result<TraversalVal, GraphError>> {
+    /// Collects a single page of the traversal.
+    ///
+    /// Items at or before the page's cursor are skipped, so the traversal must yield items
+    /// in ascending id order. Scans such as `n_from_type_after` and `e_from_type_after` do,
+    /// as they walk the keys in order and seek straight past the cursor.
+    fn paginate(self, page: &Page) -> Result<Paged<Vec<TraversalVal>>, GraphError>;
+}
+
+impl<'a, I> PaginateAdapter<'a> for RoTraversalIterator<'a, I>
+where
+    I: Iterator<Item = Result<TraversalVal, GraphError>>,
+{
+    fn paginate(self, page: &Page) -> Result<Paged<Vec<TraversalVal>>, GraphError> {
+        let after = page.after()?;
+        let mut items = Vec::with_capacity(page.limit);
+        let mut has_more = false;
+        for item in self.inner {
+            let item = item?;
+            if after.is_some_and(|after| item.id() <= after) {
+                continue;
+            }
+            if items.len() == page.limit {
+                has_more = true;
+                break;
+            }
+            items.push(item);
+        }
+
+        let next_cursor = match (has_more, items.last()) {
+            (true, Some(last)) => Some(Page::encode_cursor(last.id())),
+            _ => None,
+        };
+        Ok(Paged { items, next_cursor })
+    }
+}
@@ -18,10 +18,15 @@ pub enum PathType {
     To(u128),
 }
 
+/// Decides whether a shortest path may follow an edge, e.g. by its properties
+pub type EdgePredicate<'a> = Box<dyn Fn(&Edge) -> Result<bool, GraphError> + 'a>;
+
 pub struct ShortestPathIterator<'a, I> {
     iter: I,
     path_type: PathType,
     edge_label: Option<&'a str>,
+    max_depth: Option<usize>,
+    edge_filter: Option<EdgePredicate<'a>>,
     storage: Arc<HelixGraphStorage>,
     txn: &'a RoTxn<'a>,
 }
@@ -40,10 +45,15 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> Iterator
                     PathType::To(to) => (node.id, to),
                 };
 
+                let max_depth = match self.storage.traversal_depth(self.max_depth) {
+                    Ok(max_depth) => max_depth,
+                    Err(e) => return Some(Err(e)),
+                };
+
                 let mut queue = VecDeque::with_capacity(32);
                 let mut visited = HashSet::with_capacity(64);
                 let mut parent: HashMap<u128, (u128, Edge)> = HashMap::with_capacity(32);
-                queue.push_back(from);
+                queue.push_back((from, 0));
                 visited.insert(from);
 
                 let reconstruct_path = |parent: &HashMap<u128, (u128, Edge)>,
@@ -71,7 +81,10 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> Iterator
                     Ok(TraversalVal::Path((nodes, edges)))
                 };
 
-                while let Some(current_id) = queue.pop_front() {
+                while let Some((current_id, depth)) = queue.pop_front() {
+                    if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                        continue;
+                    }
                     let out_prefix = self.edge_label.map_or_else(
                         || current_id.to_be_bytes().to_vec(),
                         |label| {
@@ -92,15 +105,23 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>>> Iterator
                             HelixGraphStorage::unpack_adj_edge_data(value).unwrap(); // TODO: handle error
 
                         if !visited.contains(&to_node) {
-                            visited.insert(to_node);
                             let edge = self.storage.get_edge(self.txn, &edge_id).unwrap(); // TODO: handle error
+                            if let Some(edge_filter) = &self.edge_filter {
+                                match edge_filter(&edge) {
+                                    Ok(true) => {}
+                                    // the node may still be reached along another edge
+                                    Ok(false) => continue,
+                                    Err(e) => return Some(Err(e)),
+                                }
+                            }
+                            visited.insert(to_node);
                             parent.insert(to_node, (current_id, edge));
 
                             if to_node == to {
                                 return Some(reconstruct_path(&parent, &from, &to));
                             }
 
-                            queue.push_back(to_node);
+                            queue.push_back((to_node, depth + 1));
                         }
                     }
                 }
@@ -128,6 +149,8 @@ pub trait ShortestPathAdapter<'a, I>: Iterator<Item = Result<TraversalVal, Graph
     /// let node2 = Node { id: 2, label: "Person".to_string(), properties: None };
     /// let traversal = G::new(storage, &txn).shortest_path(Some("knows"), Some(&node1.id), Some(&node2.id));
     /// ```
+    ///
+    /// The path is limited to the graph's `max_traversal_depth` if one is configured.
     fn shortest_path(
         self,
         edge_label: Option<&'a str>,
@@ -136,6 +159,33 @@ pub trait ShortestPathAdapter<'a, I>: Iterator<Item = Result<TraversalVal, Graph
     ) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>>
     where
         I: 'a;
+
+    /// ShortestPath limited to paths of at most `max_depth` edges
+    ///
+    /// A `max_depth` over the graph's `max_traversal_depth` is clamped to it or
+    /// returns a `GraphError::TraversalError`, depending on `clamp_traversal_depth`.
+    fn shortest_path_within(
+        self,
+        edge_label: Option<&'a str>,
+        from: Option<&'a u128>,
+        to: Option<&'a u128>,
+        max_depth: usize,
+    ) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>>
+    where
+        I: 'a;
+
+    /// ShortestPath following only the edges `edge_filter` accepts, e.g. those with a
+    /// `weight` under some limit
+    fn shortest_path_where<F>(
+        self,
+        edge_label: Option<&'a str>,
+        from: Option<&'a u128>,
+        to: Option<&'a u128>,
+        edge_filter: F,
+    ) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>>
+    where
+        I: 'a,
+        F: Fn(&Edge) -> Result<bool, GraphError> + 'a;
 }
 
 impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>> + 'a> ShortestPathAdapter<'a, I>
@@ -151,23 +201,66 @@ impl<'a, I: Iterator<Item = Result<TraversalVal, GraphError>> + 'a> ShortestPath
     where
         I: 'a,
     {
-        let storage = Arc::clone(&self.storage);
-        let txn = self.txn;
-
-        RoTraversalIterator {
-            inner: ShortestPathIterator {
-                iter: self.inner,
-                path_type: match (from, to) {
-                    (Some(from), None) => PathType::From(*from),
-                    (None, Some(to)) => PathType::To(*to),
-                    _ => panic!("Invalid shortest path"),
-                },
-                edge_label,
-                storage,
-                txn,
+        shortest_path_iter(self, edge_label, from, to, None)
+    }
+
+    #[inline]
+    fn shortest_path_within(
+        self,
+        edge_label: Option<&'a str>,
+        from: Option<&'a u128>,
+        to: Option<&'a u128>,
+        max_depth: usize,
+    ) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>>
+    where
+        I: 'a,
+    {
+        shortest_path_iter(self, edge_label, from, to, Some(max_depth))
+    }
+
+    #[inline]
+    fn shortest_path_where<F>(
+        self,
+        edge_label: Option<&'a str>,
+        from: Option<&'a u128>,
+        to: Option<&'a u128>,
+        edge_filter: F,
+    ) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>>
+    where
+        I: 'a,
+        F: Fn(&Edge) -> Result<bool, GraphError> + 'a,
+    {
+        let mut traversal = shortest_path_iter(self, edge_label, from, to, None);
+        traversal.inner.edge_filter = Some(Box::new(edge_filter));
+        traversal
+    }
+}
+
+fn shortest_path_iter<'a, I: Iterator<Item = Result<TraversalVal, GraphError>> + 'a>(
+    traversal: RoTraversalIterator<'a, I>,
+    edge_label: Option<&'a str>,
+    from: Option<&'a u128>,
+    to: Option<&'a u128>,
+    max_depth: Option<usize>,
+) -> RoTraversalIterator<'a, ShortestPathIterator<'a, I>> {
+    let storage = Arc::clone(&traversal.storage);
+    let txn = traversal.txn;
+
+    RoTraversalIterator {
+        inner: ShortestPathIterator {
+            iter: traversal.inner,
+            path_type: match (from, to) {
+                (Some(from), None) => PathType::From(*from),
+                (None, Some(to)) => PathType::To(*to),
+                _ => panic!("Invalid shortest path"),
             },
-            storage: Arc::clone(&self.storage),
-            txn: self.txn,
-        }
+            edge_label,
+            max_depth,
+            edge_filter: None,
+            storage,
+            txn,
+        },
+        storage: Arc::clone(&traversal.storage),
+        txn: traversal.txn,
     }
 }
@@ -0,0 +1,114 @@
+use crate::{
+    helix_engine::types::GraphError,
+    protocol::value::Value,
+    utils::items::Node,
+};
+use std::cmp::Ordering;
+
+/// A test of one property of a node, for filtering nodes inside the engine, e.g. with
+/// [`HelixGraphEngine::nodes_where`], [`HelixGraphEngine::neighbors_where`] or
+/// [`HelixGraphEngine::delete_nodes_where`].
+///
+/// Numbers compare with each other whatever their types. A node without the property fails
+/// every predicate but `Ne`, while comparing a property with a value of another type, such as
+/// a string with a number, fails with `GraphError::TraversalError`.
+///
+/// [`HelixGraphEngine::nodes_where`]: crate::helix_engine::graph_core::graph_core::HelixGraphEngine::nodes_where
+/// [`HelixGraphEngine::neighbors_where`]: crate::helix_engine::graph_core::graph_core::HelixGraphEngine::neighbors_where
+/// [`HelixGraphEngine::delete_nodes_where`]: crate::helix_engine::graph_core::graph_core::HelixGraphEngine::delete_nodes_where
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Eq(String, Value),
+    Ne(String, Value),
+    Gt(String, Value),
+    Lt(String, Value),
+    /// The property equals any of the values
+    In(String, Vec<Value>),
+    /// The node has the property, whatever its value
+    Exists(String),
+    /// A string property contains the string as a substring, or an array property contains
+    /// the value as an element
+    Contains(String, Value),
+}
+
+impl Predicate {
+    /// The property the predicate tests
+    pub fn property(&self) -> &str {
+        match self {
+            Predicate::Eq(property, _)
+            | Predicate::Ne(property, _)
+            | Predicate::Gt(property, _)
+            | Predicate::Lt(property, _)
+            | Predicate::In(property, _)
+            | Predicate::Exists(property)
+            | Predicate::Contains(property, _) => property,
+        }
+    }
+
+    /// Whether the predicate holds for `node`
+    pub fn matches(&self, node: &Node) -> Result<bool, GraphError> {
+        let value = node
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.get(self.property()));
+        let Some(value) = value else {
+            return Ok(matches!(self, Predicate::Ne(..)));
+        };
+        let ordering = |literal: &Value| {
+            value.compare(literal).ok_or_else(|| mismatch(self.property(), value, literal))
+        };
+        Ok(match self {
+            Predicate::Eq(_, literal) => ordering(literal)? == Ordering::Equal,
+            Predicate::Ne(_, literal) => ordering(literal)? != Ordering::Equal,
+            Predicate::Gt(_, literal) => ordering(literal)? == Ordering::Greater,
+            Predicate::Lt(_, literal) => ordering(literal)? == Ordering::Less,
+            Predicate::In(_, literals) => {
+                for literal in literals {
+                    if ordering(literal)? == Ordering::Equal {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            Predicate::Exists(_) => true,
+            Predicate::Contains(_, literal) => match (value, literal) {
+                (Value::String(value), Value::String(literal)) => value.contains(literal.as_str()),
+                (Value::Array(values), literal) => values
+                    .iter()
+                    .any(|value| value.compare(literal) == Some(Ordering::Equal)),
+                _ => return Err(mismatch(self.property(), value, literal)),
+            },
+        })
+    }
+
+    /// Whether every one of `predicates` holds for `node`, testing them in order until one
+    /// doesn't
+    pub fn all_match(predicates: &[Predicate], node: &Node) -> Result<bool, GraphError> {
+        for predicate in predicates {
+            if !predicate.matches(node)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn mismatch(property: &str, value: &Value, literal: &Value) -> GraphError {
+    GraphError::TraversalError(format!(
+        "Can't compare {} property {} with {}",
+        type_name(value),
+        property,
+        type_name(literal)
+    ))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "string",
+        Value::Boolean(_) => "boolean",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+        Value::Empty => "empty",
+        _ => "number",
+    }
+}
@@ -3,7 +3,12 @@ use std::collections::HashMap;
 use crate::{
     helix_engine::{
         graph_core::traversal_iter::RwTraversalIterator,
-        storage_core::{storage_core::HelixGraphStorage, storage_methods::StorageMethods},
+        storage_core::{
+            changes::{ChangeEvent, ItemKind},
+            oplog::Operation,
+            storage_core::HelixGraphStorage,
+            storage_methods::StorageMethods,
+        },
         types::GraphError,
     },
     protocol::value::Value,
@@ -54,6 +59,7 @@ impl<'scope, 'env, I: Iterator<Item = Result<TraversalVal, GraphError>>> UpdateA
             match item {
                 Ok(TraversalVal::Node(node)) => match storage.get_node(self.txn, &node.id) {
                     Ok(mut old_node) => {
+                        let previous = old_node.clone();
                         if let Some(mut properties) = old_node.properties {
                             if let Some(ref props) = props {
                                 for (k, v) in props.iter() {
@@ -100,14 +106,44 @@ impl<'scope, 'env, I: Iterator<Item = Result<TraversalVal, GraphError>>> UpdateA
                                 old_node.properties = None;
                             }
                         }
-                        match old_node.encode_node() {
+                        if let Err(e) = storage
+                            .validate_schema(self.txn, &old_node.label, old_node.properties.as_ref())
+                            .and_then(|_| {
+                                storage.update_node_indices(
+                                    self.txn,
+                                    Some(&previous),
+                                    Some(&old_node),
+                                )
+                            })
+                        {
+                            vec.push(Err(e));
+                            continue;
+                        }
+                        match storage.encode_node(&old_node) {
                             Ok(serialized) => {
                                 match storage.nodes_db.put(
                                     self.txn,
                                     &HelixGraphStorage::node_key(&node.id),
                                     &serialized,
                                 ) {
-                                    Ok(_) => vec.push(Ok(TraversalVal::Node(old_node))),
+                                    Ok(_) => match storage.log_operation(|| Operation::UpdateNode {
+                                        id: old_node.id,
+                                        properties: old_node.properties.clone(),
+                                    }) {
+                                        Ok(_) => {
+                                            storage.record_change(|| {
+                                                ChangeEvent::update(
+                                                    ItemKind::Node,
+                                                    old_node.id,
+                                                    &old_node.label,
+                                                    previous.properties.as_ref(),
+                                                    old_node.properties.as_ref(),
+                                                )
+                                            });
+                                            vec.push(Ok(TraversalVal::Node(old_node)))
+                                        }
+                                        Err(e) => vec.push(Err(e)),
+                                    },
                                     Err(e) => vec.push(Err(GraphError::from(e))),
                                 }
                             }
@@ -119,6 +155,7 @@ impl<'scope, 'env, I: Iterator<Item = Result<TraversalVal, GraphError>>> UpdateA
                 Ok(TraversalVal::Edge(edge)) => match storage.get_edge(self.txn, &edge.id) {
                     Ok(old_edge) => {
                         let mut old_edge = old_edge.clone();
+                        let previous = old_edge.properties.clone();
                         if let Some(mut properties) = old_edge.properties.clone() {
                             if let Some(ref props) = props {
                                 for (k, v) in props.iter() {
@@ -127,14 +164,39 @@ impl<'scope, 'env, I: Iterator<Item = Result<TraversalVal, GraphError>>> UpdateA
                                 old_edge.properties = Some(properties);
                             }
                         }
-                        match old_edge.encode_edge() {
+                        if let Err(e) = storage.validate_schema(
+                            self.txn,
+                            &old_edge.label,
+                            old_edge.properties.as_ref(),
+                        ) {
+                            vec.push(Err(e));
+                            continue;
+                        }
+                        match storage.encode_edge(&old_edge) {
                             Ok(serialized) => {
-                                match storage.nodes_db.put(
+                                match storage.edges_db.put(
                                     self.txn,
                                     &HelixGraphStorage::edge_key(&edge.id),
                                     &serialized,
                                 ) {
-                                    Ok(_) => vec.push(Ok(TraversalVal::Edge(old_edge))),
+                                    Ok(_) => match storage.log_operation(|| Operation::UpdateEdge {
+                                        id: old_edge.id,
+                                        properties: old_edge.properties.clone(),
+                                    }) {
+                                        Ok(_) => {
+                                            storage.record_change(|| {
+                                                ChangeEvent::update(
+                                                    ItemKind::Edge,
+                                                    old_edge.id,
+                                                    &old_edge.label,
+                                                    previous.as_ref(),
+                                                    old_edge.properties.as_ref(),
+                                                )
+                                            });
+                                            vec.push(Ok(TraversalVal::Edge(old_edge)))
+                                        }
+                                        Err(e) => vec.push(Err(e)),
+                                    },
                                     Err(e) => vec.push(Err(GraphError::from(e))),
                                 }
                             }
@@ -7,7 +7,10 @@ use crate::helix_engine::{
         out::{from_n::FromNAdapter, from_v::FromVAdapter, out::OutAdapter},
         source::{add_n::AddNAdapter, e_from_id::EFromIdAdapter, n_from_id::NFromIdAdapter},
         tr_val::{Traversable, TraversalVal},
-        util::{dedup::DedupAdapter, props::PropsAdapter, range::RangeAdapter},
+        util::{
+            aggregate::AggregateAdapter, dedup::DedupAdapter, props::PropsAdapter,
+            range::RangeAdapter,
+        },
         vectors::brute_force_search::BruteForceSearchVAdapter,
     },
     storage_core::storage_core::HelixGraphStorage,
@@ -491,6 +494,73 @@ fn test_count_mixed_steps() {
     assert_eq!(count, 2);
 }
 
+#[test]
+fn test_group_count() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    for team in ["red", "blue", "red", "red"] {
+        G::new_mut(Arc::clone(&storage), &mut txn)
+            .add_n("person", Some(props! { "team" => team }), None)
+            .collect_to::<Vec<_>>();
+    }
+    G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_n("person", Some(props!()), None)
+        .collect_to::<Vec<_>>();
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let groups = G::new(Arc::clone(&storage), &txn)
+        .n_from_type("person")
+        .group_count("team")
+        .unwrap();
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[&Value::from("red")], 3);
+    assert_eq!(groups[&Value::from("blue")], 1);
+}
+
+#[test]
+fn test_sum_and_avg() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    for age in [20, 30, 40] {
+        G::new_mut(Arc::clone(&storage), &mut txn)
+            .add_n("person", Some(props! { "age" => age }), None)
+            .collect_to::<Vec<_>>();
+    }
+    G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_n("person", Some(props! { "age" => 2.5 }), None)
+        .collect_to::<Vec<_>>();
+    G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_n("person", Some(props! { "age" => "unknown" }), None)
+        .collect_to::<Vec<_>>();
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let sum = G::new(Arc::clone(&storage), &txn)
+        .n_from_type("person")
+        .sum_of("age", false)
+        .unwrap();
+    assert_eq!(sum, 92.5);
+
+    let avg = G::new(Arc::clone(&storage), &txn)
+        .n_from_type("person")
+        .avg_of("age", false)
+        .unwrap();
+    assert_eq!(avg, Some(92.5 / 4.0));
+
+    let strict = G::new(Arc::clone(&storage), &txn)
+        .n_from_type("person")
+        .sum_of("age", true);
+    assert!(matches!(strict, Err(GraphError::ConversionError(_))));
+
+    let missing = G::new(Arc::clone(&storage), &txn)
+        .n_from_type("person")
+        .avg_of("height", true)
+        .unwrap();
+    assert_eq!(missing, None);
+}
+
 #[test]
 fn test_range_subset() {
     let (storage, _temp_dir) = setup_test_db();
@@ -1338,6 +1408,54 @@ fn test_update_node() {
     );
 }
 
+#[test]
+fn test_update_edge() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let node1 = G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_n("person", Some(props!("name" => "node1")), None)
+        .collect_to_val();
+    let node2 = G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_n("person", Some(props!("name" => "node2")), None)
+        .collect_to_val();
+    let edge = G::new_mut(Arc::clone(&storage), &mut txn)
+        .add_e(
+            "knows",
+            Some(props!("since" => "2019")),
+            node1.id(),
+            node2.id(),
+            false,
+            EdgeType::Node,
+        )
+        .collect_to_val();
+
+    txn.commit().unwrap();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let _ = {
+        let update_tr = G::new(Arc::clone(&storage), &txn)
+            .e_from_id(&edge.id())
+            .collect_to::<Vec<_>>();
+        G::new_mut_from(Arc::clone(&storage), &mut txn, update_tr)
+            .update(Some(props! { "since" => "2020"}))
+            .collect_to::<Vec<_>>()
+    };
+    txn.commit().unwrap();
+    let txn = storage.graph_env.read_txn().unwrap();
+    let updated_edges = G::new(Arc::clone(&storage), &txn)
+        .e_from_id(&edge.id())
+        .collect_to::<Vec<_>>();
+    assert_eq!(updated_edges.len(), 1);
+    assert_eq!(
+        updated_edges[0].check_property("since").unwrap().to_string(),
+        "2020"
+    );
+    // the update isn't written to the nodes table under the edge's id
+    let edge_id = edge.id();
+    let node_key = HelixGraphStorage::node_key(&edge_id);
+    assert!(storage.nodes_db.get(&txn, &node_key).unwrap().is_none());
+}
+
 #[test]
 fn test_shortest_path() {
     let (storage, _temp_dir) = setup_test_db();
@@ -1418,6 +1536,117 @@ fn test_shortest_path() {
         }
     }
 }
+
+/// Builds a path of `len` nodes joined by "knows" edges in a graph with the given depth cap
+fn setup_depth_capped_chain(
+    len: usize,
+    max_traversal_depth: usize,
+    clamp_traversal_depth: bool,
+) -> (Arc<HelixGraphStorage>, TempDir, Vec<TraversalVal>) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = super::config::Config::default();
+    config.graph_config.max_traversal_depth = Some(max_traversal_depth);
+    config.graph_config.clamp_traversal_depth = clamp_traversal_depth;
+    let storage =
+        Arc::new(HelixGraphStorage::new(temp_dir.path().to_str().unwrap(), config).unwrap());
+
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let nodes = (0..len)
+        .map(|_| {
+            G::new_mut(Arc::clone(&storage), &mut txn)
+                .add_n("person", None, None)
+                .collect_to_val()
+        })
+        .collect::<Vec<_>>();
+    for pair in nodes.windows(2) {
+        G::new_mut(Arc::clone(&storage), &mut txn)
+            .add_e("knows", None, pair[0].id(), pair[1].id(), false, EdgeType::Node)
+            .collect_to::<Vec<_>>();
+    }
+    txn.commit().unwrap();
+    (storage, temp_dir, nodes)
+}
+
+fn path_len(result: Option<Result<TraversalVal, GraphError>>) -> Result<usize, GraphError> {
+    match result.unwrap()? {
+        TraversalVal::Path((_, edges)) => Ok(edges.len()),
+        other => panic!("Expected Path value, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_shortest_path_respects_global_depth_cap() {
+    let (storage, _temp_dir, nodes) = setup_depth_capped_chain(4, 2, false);
+    let ids = nodes.iter().map(|node| node.id()).collect::<Vec<_>>();
+    let txn = storage.graph_env.read_txn().unwrap();
+
+    let mut within_cap = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path(Some("knows"), None, Some(&ids[2]));
+    assert_eq!(path_len(within_cap.next()).unwrap(), 2);
+
+    let mut beyond_cap = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path(Some("knows"), None, Some(&ids[3]));
+    assert!(matches!(
+        path_len(beyond_cap.next()),
+        Err(GraphError::ShortestPathNotFound)
+    ));
+
+    let mut requested_over_cap = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_within(Some("knows"), None, Some(&ids[3]), 3);
+    assert!(matches!(
+        path_len(requested_over_cap.next()),
+        Err(GraphError::TraversalError(_))
+    ));
+
+    let mut requested_under_cap = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_within(Some("knows"), None, Some(&ids[2]), 1);
+    assert!(matches!(
+        path_len(requested_under_cap.next()),
+        Err(GraphError::ShortestPathNotFound)
+    ));
+}
+
+#[test]
+fn test_shortest_path_depth_clamped_to_global_cap() {
+    let (storage, _temp_dir, nodes) = setup_depth_capped_chain(4, 2, true);
+    let ids = nodes.iter().map(|node| node.id()).collect::<Vec<_>>();
+    let txn = storage.graph_env.read_txn().unwrap();
+
+    let mut clamped = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_within(Some("knows"), None, Some(&ids[2]), 10);
+    assert_eq!(path_len(clamped.next()).unwrap(), 2);
+
+    let mut beyond_cap = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_within(Some("knows"), None, Some(&ids[3]), 10);
+    assert!(matches!(
+        path_len(beyond_cap.next()),
+        Err(GraphError::ShortestPathNotFound)
+    ));
+}
+
+#[test]
+fn test_subgraph_radius_held_to_global_cap() {
+    let (storage, _temp_dir, nodes) = setup_depth_capped_chain(4, 2, false);
+    let txn = storage.graph_env.read_txn().unwrap();
+    let subgraph = storage
+        .extract_subgraph(&txn, &[nodes[0].id()], 2, &[], None)
+        .unwrap();
+    assert_eq!(subgraph.nodes.len(), 3);
+    assert!(matches!(
+        storage.extract_subgraph(&txn, &[nodes[0].id()], 3, &[], None),
+        Err(GraphError::TraversalError(_))
+    ));
+    drop(txn);
+
+    let (storage, _temp_dir, nodes) = setup_depth_capped_chain(4, 2, true);
+    let txn = storage.graph_env.read_txn().unwrap();
+    let subgraph = storage
+        .extract_subgraph(&txn, &[nodes[0].id()], 10, &[], None)
+        .unwrap();
+    assert_eq!(subgraph.nodes.len(), 3);
+    assert_eq!(subgraph.edges.len(), 2);
+}
+
 // #[test]
 // fn test_shortest_mutual_path() {
 //     let (storage, _temp_dir) = setup_test_db();
@@ -2206,3 +2435,61 @@ fn test_drop_traversal() {
 
     assert_eq!(traversal.len(), 0);
 }
+
+#[test]
+fn test_shortest_path_where_filters_on_edge_properties() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let nodes = (0..5)
+        .map(|_| {
+            G::new_mut(Arc::clone(&storage), &mut txn)
+                .add_n("person", None, None)
+                .collect_to_val()
+        })
+        .collect::<Vec<_>>();
+    // a short path over heavy edges and a longer one over light edges
+    for (from, to, weight) in [(0, 1, 10), (1, 4, 10), (0, 2, 1), (2, 3, 1), (3, 4, 1)] {
+        G::new_mut(Arc::clone(&storage), &mut txn)
+            .add_e(
+                "knows",
+                Some(props!("weight" => weight)),
+                nodes[from].id(),
+                nodes[to].id(),
+                false,
+                EdgeType::Node,
+            )
+            .collect_to::<Vec<_>>();
+    }
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let shortest = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path(Some("knows"), None, Some(&nodes[4].id()))
+        .next();
+    assert_eq!(path_len(shortest).unwrap(), 2);
+
+    let light = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_where(Some("knows"), None, Some(&nodes[4].id()), |edge| {
+            Ok(*edge.check_property("weight")? < Value::I32(5))
+        })
+        .next();
+    assert_eq!(path_len(light).unwrap(), 3);
+
+    let none = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .shortest_path_where(Some("knows"), None, Some(&nodes[4].id()), |edge| {
+            Ok(*edge.check_property("weight")? > Value::I32(100))
+        })
+        .next();
+    assert!(matches!(path_len(none), Err(GraphError::ShortestPathNotFound)));
+
+    // traversal filters see the edges' properties too
+    let heavy = G::new_from(Arc::clone(&storage), &txn, vec![nodes[0].clone()])
+        .out_e("knows")
+        .filter_ref(|val, _| match val {
+            Ok(TraversalVal::Edge(edge)) => Ok(*edge.check_property("weight")? == Value::I32(10)),
+            _ => Ok(false),
+        })
+        .collect_to::<Vec<_>>();
+    assert_eq!(heavy.len(), 1);
+}
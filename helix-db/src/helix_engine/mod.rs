@@ -1,6 +1,7 @@
 pub mod bm25;
 pub mod graph_core;
 pub mod macros;
+pub mod query;
 pub mod storage_core;
 pub mod types;
 pub mod vector_core;
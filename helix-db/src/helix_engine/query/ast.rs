@@ -0,0 +1,94 @@
+use crate::{
+    helix_engine::storage_core::storage_methods::Direction,
+    protocol::value::Value,
+    utils::items::{Edge, Node},
+};
+
+/// `MATCH <pattern> [WHERE <predicate>] RETURN <item>, ...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub pattern: Pattern,
+    pub predicate: Option<Comparison>,
+    pub returns: Vec<ReturnItem>,
+}
+
+/// A path of nodes joined by edges, e.g. `(a:Person)-[FOLLOWS]->(b)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub start: NodePattern,
+    /// Each edge followed from the node before it, and the node it leads to
+    pub hops: Vec<(EdgePattern, NodePattern)>,
+}
+
+/// `(a)`, `(a:Person)`, `(:Person)` or `()`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodePattern {
+    pub variable: Option<String>,
+    pub label: Option<String>,
+}
+
+/// `-[FOLLOWS]->`, `<-[FOLLOWS]-` or `-[FOLLOWS]-`, with `[e:FOLLOWS]` binding the edge
+/// to `e` and `[]` matching an edge of any label
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgePattern {
+    pub variable: Option<String>,
+    pub label: Option<String>,
+    pub direction: Direction,
+}
+
+/// `a.name = "x"`, comparing a property of a matched node or edge with a literal.
+///
+/// Values that can't be compared, such as a string and a number, are never equal, unlike with
+/// the engine's [`Predicate`], which fails on them.
+///
+/// [`Predicate`]: crate::helix_engine::graph_core::ops::util::predicate::Predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub variable: String,
+    pub property: String,
+    pub op: CompareOp,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// `b`, returning the whole node or edge, or `b.name`, returning one of its properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnItem {
+    pub variable: String,
+    pub property: Option<String>,
+}
+
+impl ReturnItem {
+    /// The column this item is returned under, as it was written
+    pub fn column(&self) -> String {
+        match &self.property {
+            Some(property) => format!("{}.{}", self.variable, property),
+            None => self.variable.clone(),
+        }
+    }
+}
+
+/// A value in a row of a query's result
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Node(Node),
+    Edge(Edge),
+    /// A property, `Value::Empty` if the node or edge doesn't have it
+    Value(Value),
+}
+
+/// The rows a query matched, with a cell for each of its `RETURN` items
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
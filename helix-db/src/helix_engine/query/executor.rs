@@ -0,0 +1,358 @@
+use crate::{
+    helix_engine::{
+        graph_core::ops::{
+            source::n_from_type::NFromType,
+            tr_val::TraversalVal,
+            util::paginate::{Page, Paged},
+        },
+        query::ast::{Cell, CompareOp, Comparison, NodePattern, Query, QueryResult},
+        storage_core::{
+            storage_core::HelixGraphStorage, storage_methods::StorageMethods,
+            timestamps::now_millis,
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::items::{Edge, Node},
+};
+use heed3::RoTxn;
+use std::{cmp::Ordering, collections::HashMap, ops::Bound};
+
+/// A node or edge matched by the pattern, in the order the pattern names them:
+/// the start node, then the edge and node of each hop
+#[derive(Debug, Clone)]
+enum Matched {
+    Node(Node),
+    Edge(Edge),
+}
+
+impl Matched {
+    fn id(&self) -> u128 {
+        match self {
+            Matched::Node(node) => node.id,
+            Matched::Edge(edge) => edge.id,
+        }
+    }
+
+    /// A property of the node or edge, falling back to its `id` and `label`
+    fn property(&self, key: &str) -> Value {
+        match self {
+            Matched::Node(node) => property(node.id, &node.label, &node.properties, key),
+            Matched::Edge(edge) => property(edge.id, &edge.label, &edge.properties, key),
+        }
+    }
+}
+
+/// A property of a node or edge, falling back to its `id` and `label`
+fn property(
+    id: u128,
+    label: &str,
+    properties: &Option<HashMap<String, Value>>,
+    key: &str,
+) -> Value {
+    match properties.as_ref().and_then(|props| props.get(key)) {
+        Some(value) => value.clone(),
+        None if key == "id" => Value::U128(id),
+        None if key == "label" => Value::String(label.to_string()),
+        None => Value::Empty,
+    }
+}
+
+/// Whether `value` compared with `literal` by `op` holds
+fn holds(value: &Value, op: CompareOp, literal: &Value) -> bool {
+    let Some(ordering) = value.compare(literal) else {
+        // nothing equals a value it can't be compared with
+        return op == CompareOp::Ne;
+    };
+    match op {
+        CompareOp::Eq => ordering == Ordering::Equal,
+        CompareOp::Ne => ordering != Ordering::Equal,
+        CompareOp::Lt => ordering == Ordering::Less,
+        CompareOp::Le => ordering != Ordering::Greater,
+        CompareOp::Gt => ordering == Ordering::Greater,
+        CompareOp::Ge => ordering != Ordering::Less,
+    }
+}
+
+impl Comparison {
+    fn matches(&self, matched: &Matched) -> bool {
+        holds(&matched.property(&self.property), self.op, &self.value)
+    }
+}
+
+impl NodePattern {
+    fn matches(&self, node: &Node) -> bool {
+        self.label.as_ref().is_none_or(|label| *label == node.label)
+    }
+}
+
+/// A row of the result along with the ids that place it: the start node's, then the edge's
+/// of each hop. Rows are found in the order of these ids, so a page's cursor holds those of
+/// its last row.
+struct Row {
+    key: Vec<u128>,
+    cells: Vec<Cell>,
+}
+
+/// Runs a parsed query, holding the variables of the pattern by their position in it
+struct Executor<'a> {
+    storage: &'a HelixGraphStorage,
+    txn: &'a RoTxn<'a>,
+    query: &'a Query,
+    // the variable at each position of the pattern, if it has one
+    variables: Vec<Option<&'a str>>,
+    // the position the WHERE predicate is checked at, where its variable is first bound
+    predicate_at: Option<usize>,
+    // stops matching once this many rows have been found
+    max_rows: Option<usize>,
+}
+
+impl<'a> Executor<'a> {
+    fn new(
+        storage: &'a HelixGraphStorage,
+        txn: &'a RoTxn<'a>,
+        query: &'a Query,
+        max_rows: Option<usize>,
+    ) -> Self {
+        let mut variables = vec![query.pattern.start.variable.as_deref()];
+        for (edge, node) in &query.pattern.hops {
+            variables.push(edge.variable.as_deref());
+            variables.push(node.variable.as_deref());
+        }
+        let predicate_at = query.predicate.as_ref().and_then(|predicate| {
+            variables
+                .iter()
+                .position(|variable| *variable == Some(predicate.variable.as_str()))
+        });
+        Self {
+            storage,
+            txn,
+            query,
+            variables,
+            predicate_at,
+            max_rows,
+        }
+    }
+
+    fn is_full(&self, rows: &[Row]) -> bool {
+        self.max_rows.is_some_and(|max_rows| rows.len() >= max_rows)
+    }
+
+    /// Whether the node or edge just matched at the end of `path` fits with the rest of it:
+    /// the same as anything earlier bound to the same variable, and passing the predicate
+    fn accepts(&self, path: &[Matched]) -> bool {
+        let position = path.len() - 1;
+        let matched = &path[position];
+        if let Some(variable) = self.variables[position]
+            && let Some(first) = self.variables.iter().position(|v| *v == Some(variable))
+            && first < position
+            && path[first].id() != matched.id()
+        {
+            return false;
+        }
+        match (self.predicate_at, &self.query.predicate) {
+            (Some(at), Some(predicate)) if at == position => predicate.matches(matched),
+            _ => true,
+        }
+    }
+
+    /// Follows the pattern's hops on from the node at the end of `path`, adding a row for
+    /// each full match.
+    ///
+    /// `after` holds the rest of the key of the row to resume after when `path` is the start
+    /// of it, in which case only the rows after that one are added.
+    fn extend(
+        &self,
+        path: &mut Vec<Matched>,
+        rows: &mut Vec<Row>,
+        after: Option<&[u128]>,
+    ) -> Result<(), GraphError> {
+        let hop = (path.len() - 1) / 2;
+        let Some((edge_pattern, node_pattern)) = self.query.pattern.hops.get(hop) else {
+            // a path that is all of `after`'s row was returned before
+            if after.is_none() {
+                rows.push(Row {
+                    key: self.key(path),
+                    cells: self.project(path),
+                });
+            }
+            return Ok(());
+        };
+        let Some(Matched::Node(from)) = path.last() else {
+            unreachable!("a hop always starts from a node");
+        };
+        let labels: Vec<&str> = edge_pattern.label.as_deref().into_iter().collect();
+        let mut neighbors = self.storage.neighbors_with_edges(
+            self.txn,
+            &from.id,
+            edge_pattern.direction,
+            &labels,
+            None,
+        )?;
+        neighbors.sort_by_key(|(edge, _)| edge.id);
+        for (edge, node) in neighbors {
+            let after = match after {
+                Some(after) if edge.id < after[0] => continue,
+                Some(after) if edge.id == after[0] => Some(&after[1..]),
+                _ => None,
+            };
+            if !node_pattern.matches(&node) {
+                continue;
+            }
+            path.push(Matched::Edge(edge));
+            if self.accepts(path) {
+                path.push(Matched::Node(node));
+                if self.accepts(path) {
+                    self.extend(path, rows, after)?;
+                }
+                path.pop();
+            }
+            path.pop();
+            if self.is_full(rows) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&self, path: &[Matched]) -> Vec<u128> {
+        std::iter::once(path[0].id())
+            .chain(path.iter().skip(1).step_by(2).map(Matched::id))
+            .collect()
+    }
+
+    fn project(&self, path: &[Matched]) -> Vec<Cell> {
+        self.query
+            .returns
+            .iter()
+            .map(|item| {
+                let position = self
+                    .variables
+                    .iter()
+                    .position(|variable| *variable == Some(item.variable.as_str()))
+                    .expect("return items are checked to be bound when parsing");
+                match (&path[position], &item.property) {
+                    (matched, Some(property)) => Cell::Value(matched.property(property)),
+                    (Matched::Node(node), None) => Cell::Node(node.clone()),
+                    (Matched::Edge(edge), None) => Cell::Edge(edge.clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// Finds the rows in order, starting after the row with the key `after` if given.
+    ///
+    /// A labelled start node is found by scanning that label, otherwise every node is tried.
+    fn run(&self, after: Option<&[u128]>) -> Result<Vec<Row>, GraphError> {
+        let from = match after {
+            Some(after) => Bound::Included(after[0]),
+            None => Bound::Unbounded,
+        };
+        let starts: Box<dyn Iterator<Item = Result<Node, GraphError>>> =
+            match &self.query.pattern.start.label {
+                Some(label) => Box::new(
+                    NFromType {
+                        iter: self
+                            .storage
+                            .nodes_db
+                            .lazily_decode_data()
+                            .range(self.txn, &(from, Bound::Unbounded))?,
+                        label,
+                        expired: Some(self.storage.expired_ids(self.txn, now_millis())),
+                    }
+                    .filter_map(|item| match item {
+                        Ok(TraversalVal::Node(node)) => Some(Ok(node)),
+                        Ok(_) => None,
+                        Err(e) => Some(Err(e)),
+                    }),
+                ),
+                None => Box::new(self.storage.scan_nodes(self.txn, (from, Bound::Unbounded))?),
+            };
+
+        let mut rows = Vec::new();
+        for node in starts {
+            let node = node?;
+            let after = after
+                .filter(|after| after[0] == node.id)
+                .map(|after| &after[1..]);
+            let mut path = vec![Matched::Node(node)];
+            if self.accepts(&path) {
+                self.extend(&mut path, &mut rows, after)?;
+            }
+            if self.is_full(&rows) {
+                break;
+            }
+        }
+        Ok(rows)
+    }
+
+    fn result(&self, rows: Vec<Row>) -> QueryResult {
+        QueryResult {
+            columns: self
+                .query
+                .returns
+                .iter()
+                .map(|item| item.column())
+                .collect(),
+            rows: rows.into_iter().map(|row| row.cells).collect(),
+        }
+    }
+}
+
+impl Query {
+    /// Runs the query in `txn`, returning a row for every way the pattern matches.
+    ///
+    /// Starts from each node matching the pattern's first node, in id order, then follows each
+    /// hop with [`StorageMethods::neighbors_with_edges`] in edge id order. The predicate is
+    /// checked as soon as its variable is matched, so paths it rules out aren't followed any
+    /// further.
+    pub fn execute(
+        &self,
+        storage: &HelixGraphStorage,
+        txn: &RoTxn,
+    ) -> Result<QueryResult, GraphError> {
+        let executor = Executor::new(storage, txn, self, None);
+        let rows = executor.run(None)?;
+        Ok(executor.result(rows))
+    }
+
+    /// Runs the query like [`Query::execute`], returning one page of its rows.
+    ///
+    /// Rows are ordered by the id of their start node, then by the id of the edge of each hop,
+    /// and the cursor holds those ids for the last row returned. The next page seeks to that
+    /// start node and skips the edges before the cursor's, so earlier rows aren't matched
+    /// again, and rows added or removed before the cursor don't shift the pages after it.
+    ///
+    /// Fails with `GraphError::ConversionError` if the cursor isn't one returned for this
+    /// query's pattern.
+    pub fn execute_page(
+        &self,
+        storage: &HelixGraphStorage,
+        txn: &RoTxn,
+        page: &Page,
+    ) -> Result<Paged<QueryResult>, GraphError> {
+        let after = page.after_ids()?;
+        if let Some(after) = &after
+            && after.len() != self.pattern.hops.len() + 1
+        {
+            return Err(GraphError::ConversionError(format!(
+                "Invalid cursor: {}",
+                page.cursor.as_deref().unwrap_or_default()
+            )));
+        }
+        // one row past the page shows whether there is another page after it
+        let max_rows = page.limit.saturating_add(1);
+        let executor = Executor::new(storage, txn, self, Some(max_rows));
+        let mut rows = executor.run(after.as_deref())?;
+        let has_more = page.limit > 0 && rows.len() == max_rows;
+        rows.truncate(page.limit);
+        let next_cursor = match (has_more, rows.last()) {
+            (true, Some(last)) => Some(Page::encode_ids_cursor(&last.key)),
+            _ => None,
+        };
+        Ok(Paged {
+            items: executor.result(rows),
+            next_cursor,
+        })
+    }
+}
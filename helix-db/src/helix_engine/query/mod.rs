@@ -0,0 +1,6 @@
+pub mod ast;
+pub mod executor;
+pub mod parser;
+
+#[cfg(test)]
+mod query_tests;
@@ -0,0 +1,379 @@
+use crate::{
+    helix_engine::{
+        query::ast::{CompareOp, Comparison, EdgePattern, NodePattern, Pattern, Query, ReturnItem},
+        storage_core::storage_methods::Direction,
+        types::GraphError,
+    },
+    protocol::value::Value,
+};
+use std::{collections::HashMap, iter::Peekable, str::CharIndices};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Colon,
+    Comma,
+    Dot,
+    Dash,
+    /// `->`
+    Arrow,
+    /// `<-`, only when it starts an edge so `a.x <-1` still compares with `-1`
+    LeftArrow,
+    Op(CompareOp),
+}
+
+fn invalid(offset: usize, message: impl std::fmt::Display) -> GraphError {
+    GraphError::InvalidQuery(format!("{} at offset {}", message, offset))
+}
+
+/// Splits a query into tokens, each with the byte offset it starts at
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, GraphError> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = query.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            '.' => Token::Dot,
+            '=' => Token::Op(CompareOp::Eq),
+            '-' if next == Some('>') => {
+                chars.next();
+                Token::Arrow
+            }
+            '-' => Token::Dash,
+            '<' if next == Some('-') && query[offset + 2..].trim_start().starts_with('[') => {
+                chars.next();
+                Token::LeftArrow
+            }
+            '<' | '>' | '!' => {
+                let op = match (c, next) {
+                    ('<', Some('=')) => CompareOp::Le,
+                    ('>', Some('=')) => CompareOp::Ge,
+                    ('!', Some('=')) => CompareOp::Ne,
+                    ('<', _) => CompareOp::Lt,
+                    ('>', _) => CompareOp::Gt,
+                    _ => return Err(invalid(offset, "Expected != after !")),
+                };
+                if next == Some('=') {
+                    chars.next();
+                }
+                Token::Op(op)
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(invalid(offset, "Unterminated string")),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, c)) => value.push(c),
+                        None => return Err(invalid(offset, "Unterminated string")),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = offset + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    // a `.` only continues the number if a digit follows it
+                    let fraction =
+                        c == '.' && query[i + 1..].starts_with(|c: char| c.is_ascii_digit());
+                    if !c.is_ascii_digit() && !fraction {
+                        break;
+                    }
+                    chars.next();
+                    end = i + c.len_utf8();
+                }
+                let number = &query[offset..end];
+                match number.contains('.') {
+                    true => Token::Float(number.parse().map_err(|e| invalid(offset, e))?),
+                    false => Token::Int(number.parse().map_err(|e| invalid(offset, e))?),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = offset + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !c.is_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    chars.next();
+                    end = i + c.len_utf8();
+                }
+                Token::Ident(query[offset..end].to_string())
+            }
+            c => return Err(invalid(offset, format!("Unexpected character {:?}", c))),
+        };
+        tokens.push((offset, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    // byte length of the query, where running out of tokens is reported
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), GraphError> {
+        match self.eat(&token) {
+            true => Ok(()),
+            false => Err(invalid(self.offset(), format!("Expected {}", what))),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), GraphError> {
+        match self.is_keyword(keyword) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => Err(invalid(self.offset(), format!("Expected {}", keyword))),
+        }
+    }
+
+    fn ident(&mut self, what: &str) -> Result<String, GraphError> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            _ => Err(invalid(offset, format!("Expected {}", what))),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, GraphError> {
+        self.expect_keyword("MATCH")?;
+        let pattern = self.pattern()?;
+        let predicate = match self.is_keyword("WHERE") {
+            true => {
+                self.position += 1;
+                Some(self.predicate()?)
+            }
+            false => None,
+        };
+        self.expect_keyword("RETURN")?;
+        let mut returns = vec![self.return_item()?];
+        while self.eat(&Token::Comma) {
+            returns.push(self.return_item()?);
+        }
+        if self.peek().is_some() {
+            return Err(invalid(self.offset(), "Expected the end of the query"));
+        }
+        Ok(Query {
+            pattern,
+            predicate,
+            returns,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, GraphError> {
+        let start = self.node()?;
+        let mut hops = Vec::new();
+        while matches!(self.peek(), Some(Token::Dash | Token::LeftArrow)) {
+            hops.push((self.edge()?, self.node()?));
+        }
+        Ok(Pattern { start, hops })
+    }
+
+    fn node(&mut self) -> Result<NodePattern, GraphError> {
+        self.expect(Token::LParen, "(")?;
+        let mut node = NodePattern::default();
+        if let Some(Token::Ident(_)) = self.peek() {
+            node.variable = Some(self.ident("a variable")?);
+        }
+        if self.eat(&Token::Colon) {
+            node.label = Some(self.ident("a node label")?);
+        }
+        self.expect(Token::RParen, ")")?;
+        Ok(node)
+    }
+
+    fn edge(&mut self) -> Result<EdgePattern, GraphError> {
+        let incoming = self.eat(&Token::LeftArrow);
+        if !incoming {
+            self.expect(Token::Dash, "-")?;
+        }
+        self.expect(Token::LBracket, "[")?;
+        // a lone name is the label, as in `[FOLLOWS]`, and binding one takes `[e:FOLLOWS]`
+        let (variable, label) = match self.peek() {
+            Some(Token::Ident(_)) => {
+                let name = self.ident("an edge label")?;
+                match self.eat(&Token::Colon) {
+                    true => (Some(name), Some(self.ident("an edge label")?)),
+                    false => (None, Some(name)),
+                }
+            }
+            Some(Token::Colon) => {
+                self.position += 1;
+                (None, Some(self.ident("an edge label")?))
+            }
+            _ => (None, None),
+        };
+        self.expect(Token::RBracket, "]")?;
+        let direction = match (incoming, self.next()) {
+            (true, Some(Token::Dash)) => Direction::In,
+            (false, Some(Token::Arrow)) => Direction::Out,
+            (false, Some(Token::Dash)) => Direction::Both,
+            _ => {
+                self.position -= 1;
+                let expected = if incoming { "-" } else { "-> or -" };
+                return Err(invalid(self.offset(), format!("Expected {}", expected)));
+            }
+        };
+        Ok(EdgePattern {
+            variable,
+            label,
+            direction,
+        })
+    }
+
+    fn predicate(&mut self) -> Result<Comparison, GraphError> {
+        let variable = self.ident("a variable")?;
+        self.expect(Token::Dot, ".")?;
+        let property = self.ident("a property")?;
+        let offset = self.offset();
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(invalid(offset, "Expected a comparison")),
+        };
+        let value = self.literal()?;
+        Ok(Comparison {
+            variable,
+            property,
+            op,
+            value,
+        })
+    }
+
+    fn literal(&mut self) -> Result<Value, GraphError> {
+        let offset = self.offset();
+        let negative = self.eat(&Token::Dash);
+        let value = match (self.next(), negative) {
+            (Some(Token::Int(n)), _) => Value::I64(if negative { -n } else { n }),
+            (Some(Token::Float(n)), _) => Value::F64(if negative { -n } else { n }),
+            (Some(Token::Str(s)), false) => Value::String(s),
+            (Some(Token::Ident(ident)), false) if ident.eq_ignore_ascii_case("true") => {
+                Value::Boolean(true)
+            }
+            (Some(Token::Ident(ident)), false) if ident.eq_ignore_ascii_case("false") => {
+                Value::Boolean(false)
+            }
+            _ => return Err(invalid(offset, "Expected a string, number or boolean")),
+        };
+        Ok(value)
+    }
+
+    fn return_item(&mut self) -> Result<ReturnItem, GraphError> {
+        let variable = self.ident("a variable to return")?;
+        let property = match self.eat(&Token::Dot) {
+            true => Some(self.ident("a property")?),
+            false => None,
+        };
+        Ok(ReturnItem { variable, property })
+    }
+}
+
+/// Whether a variable names a node or an edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    Node,
+    Edge,
+}
+
+/// Checks every variable is bound by the pattern, and never to both a node and an edge
+fn check_variables(query: &Query) -> Result<(), GraphError> {
+    let mut bound = HashMap::new();
+    let nodes = std::iter::once(&query.pattern.start)
+        .chain(query.pattern.hops.iter().map(|(_, node)| node));
+    let edges = query.pattern.hops.iter().map(|(edge, _)| edge);
+    let variables = nodes
+        .filter_map(|node| node.variable.as_ref().map(|v| (v, Binding::Node)))
+        .chain(edges.filter_map(|edge| edge.variable.as_ref().map(|v| (v, Binding::Edge))));
+    for (variable, binding) in variables {
+        if *bound.entry(variable.as_str()).or_insert(binding) != binding {
+            return Err(GraphError::InvalidQuery(format!(
+                "{} is bound to both a node and an edge",
+                variable
+            )));
+        }
+    }
+    let used = query
+        .predicate
+        .iter()
+        .map(|predicate| &predicate.variable)
+        .chain(query.returns.iter().map(|item| &item.variable));
+    for variable in used {
+        if !bound.contains_key(variable.as_str()) {
+            return Err(GraphError::InvalidQuery(format!(
+                "{} is not bound by the MATCH pattern",
+                variable
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl Query {
+    /// Parses a query such as `MATCH (a)-[FOLLOWS]->(b) WHERE a.name = "x" RETURN b`.
+    ///
+    /// Keywords and `true`/`false` are case insensitive, strings can be quoted with `"` or `'`,
+    /// and integers and decimals compare with numeric properties of any type.
+    /// Fails with `GraphError::InvalidQuery` naming the byte offset the query went wrong at,
+    /// or the variable that isn't bound by the pattern.
+    pub fn parse(query: &str) -> Result<Self, GraphError> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            position: 0,
+            end: query.len(),
+        };
+        let query = parser.query()?;
+        check_variables(&query)?;
+        Ok(query)
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use tempfile::TempDir;
+
+use super::ast::{Cell, CompareOp, NodePattern, Query};
+use crate::{
+    helix_engine::{
+        graph_core::{
+            config::Config,
+            graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+            ops::util::paginate::Page,
+        },
+        storage_core::{direction::EdgeDirection, storage_methods::Direction},
+        types::GraphError,
+    },
+    protocol::value::Value,
+};
+
+fn setup_test_engine() -> (HelixGraphEngine, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    (HelixGraphEngine::new(opts).unwrap(), temp_dir)
+}
+
+fn add_person(engine: &HelixGraphEngine, name: &str, age: i32) -> u128 {
+    let properties = HashMap::from([
+        ("name".to_string(), Value::String(name.to_string())),
+        ("age".to_string(), Value::I32(age)),
+    ]);
+    engine
+        .insert_node(None, "person", Some(properties))
+        .unwrap()
+}
+
+fn follows(engine: &HelixGraphEngine, from: u128, to: u128, since: u64) {
+    let properties = HashMap::from([("since".to_string(), Value::U64(since))]);
+    engine
+        .insert_edge(
+            "FOLLOWS",
+            Some(properties),
+            from,
+            to,
+            EdgeDirection::Directed,
+        )
+        .unwrap();
+}
+
+/// The `name` in each row's first cell, sorted
+fn names(rows: &[Vec<Cell>]) -> Vec<String> {
+    let mut names: Vec<String> = rows
+        .iter()
+        .map(|row| match &row[0] {
+            Cell::Node(node) => match node.properties.as_ref().unwrap().get("name") {
+                Some(Value::String(name)) => name.clone(),
+                other => panic!("unexpected name {:?}", other),
+            },
+            Cell::Value(Value::String(name)) => name.clone(),
+            other => panic!("unexpected cell {:?}", other),
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_parse_match_where_return() {
+    let query =
+        Query::parse(r#"match (a:person)-[FOLLOWS]->(b) WHERE a.name = "x" RETURN b, b.name"#)
+            .unwrap();
+    assert_eq!(
+        query.pattern.start,
+        NodePattern {
+            variable: Some("a".to_string()),
+            label: Some("person".to_string()),
+        }
+    );
+    let (edge, node) = &query.pattern.hops[0];
+    assert_eq!(edge.label.as_deref(), Some("FOLLOWS"));
+    assert_eq!(edge.variable, None);
+    assert_eq!(edge.direction, Direction::Out);
+    assert_eq!(node.variable.as_deref(), Some("b"));
+    let predicate = query.predicate.unwrap();
+    assert_eq!(predicate.op, CompareOp::Eq);
+    assert_eq!(predicate.value, Value::String("x".to_string()));
+    let columns: Vec<String> = query.returns.iter().map(|item| item.column()).collect();
+    assert_eq!(columns, ["b", "b.name"]);
+
+    let query =
+        Query::parse("MATCH (a)<-[e:FOLLOWS]-(b)-[]-(c) WHERE e.since <-1 RETURN c").unwrap();
+    assert_eq!(query.pattern.hops[0].0.direction, Direction::In);
+    assert_eq!(query.pattern.hops[0].0.variable.as_deref(), Some("e"));
+    assert_eq!(query.pattern.hops[1].0.direction, Direction::Both);
+    assert_eq!(query.pattern.hops[1].0.label, None);
+    let predicate = query.predicate.unwrap();
+    assert_eq!(predicate.op, CompareOp::Lt);
+    assert_eq!(predicate.value, Value::I64(-1));
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |query: &str| match Query::parse(query) {
+        Err(GraphError::InvalidQuery(message)) => message,
+        other => panic!("expected an invalid query, got {:?}", other.map(|_| ())),
+    };
+    assert_eq!(error("MATCH (a RETURN a"), "Expected ) at offset 9");
+    assert_eq!(
+        error("MATCH (a)-[FOLLOWS]>(b) RETURN b"),
+        "Expected -> or - at offset 19"
+    );
+    assert_eq!(
+        error("MATCH (a) WHERE a.name = RETURN a"),
+        "Expected a string, number or boolean at offset 25"
+    );
+    assert_eq!(
+        error("MATCH (a) RETURN a b"),
+        "Expected the end of the query at offset 19"
+    );
+    assert_eq!(
+        error("MATCH (a) RETURN"),
+        "Expected a variable to return at offset 16"
+    );
+    assert_eq!(
+        error("MATCH (a) WHERE a.name = \"x RETURN a"),
+        "Unterminated string at offset 25"
+    );
+    assert_eq!(
+        error("MATCH (a) RETURN b"),
+        "b is not bound by the MATCH pattern"
+    );
+    assert_eq!(
+        error("MATCH (a)-[a:FOLLOWS]->(b) RETURN b"),
+        "a is bound to both a node and an edge"
+    );
+}
+
+#[test]
+fn test_query_follows_edges_from_filtered_nodes() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice", 30);
+    let bob = add_person(&engine, "bob", 25);
+    let carol = add_person(&engine, "carol", 41);
+    let dave = add_person(&engine, "dave", 19);
+    follows(&engine, alice, bob, 2020);
+    follows(&engine, alice, carol, 2022);
+    follows(&engine, bob, dave, 2021);
+    let other = engine.insert_node(None, "company", None).unwrap();
+    engine
+        .insert_edge("FOLLOWS", None, alice, other, EdgeDirection::Directed)
+        .unwrap();
+
+    let result = engine
+        .query(r#"MATCH (a)-[FOLLOWS]->(b:person) WHERE a.name = "alice" RETURN b"#)
+        .unwrap();
+    assert_eq!(result.columns, ["b"]);
+    assert_eq!(names(&result.rows), ["bob", "carol"]);
+
+    // integer literals compare with properties of any numeric type
+    let result = engine
+        .query("MATCH (a:person)-[FOLLOWS]->(b) WHERE b.age >= 25 RETURN a.name, b.name")
+        .unwrap();
+    assert_eq!(result.columns, ["a.name", "b.name"]);
+    let mut pairs: Vec<(Cell, Cell)> = result
+        .rows
+        .into_iter()
+        .map(|row| (row[0].clone(), row[1].clone()))
+        .collect();
+    pairs.sort_by_key(|(_, b)| format!("{:?}", b));
+    let name = |name: &str| Cell::Value(Value::String(name.to_string()));
+    assert_eq!(
+        pairs,
+        [(name("alice"), name("bob")), (name("alice"), name("carol"))]
+    );
+
+    // edge properties, incoming edges and hops chained on
+    let result = engine
+        .query("MATCH (d)<-[e:FOLLOWS]-(b)<-[FOLLOWS]-(a) WHERE e.since = 2021 RETURN a.name, e")
+        .unwrap();
+    assert_eq!(result.rows.len(), 1);
+    assert_eq!(result.rows[0][0], name("alice"));
+    match &result.rows[0][1] {
+        Cell::Edge(edge) => assert_eq!((edge.from_node, edge.to_node), (bob, dave)),
+        other => panic!("unexpected cell {:?}", other),
+    }
+
+    // a missing property matches nothing but `!=`
+    let result = engine
+        .query("MATCH (a) WHERE a.nickname = 1 RETURN a")
+        .unwrap();
+    assert!(result.rows.is_empty());
+    let result = engine
+        .query("MATCH (a:person) WHERE a.nickname != 1 RETURN a.nickname")
+        .unwrap();
+    assert_eq!(result.rows.len(), 4);
+    assert!(
+        result
+            .rows
+            .iter()
+            .all(|row| row[0] == Cell::Value(Value::Empty))
+    );
+
+    assert!(matches!(
+        engine.query("MATCH (a) RETURN b"),
+        Err(GraphError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_query_repeated_variable_matches_the_same_node() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let alice = add_person(&engine, "alice", 30);
+    let bob = add_person(&engine, "bob", 25);
+    let carol = add_person(&engine, "carol", 41);
+    follows(&engine, alice, bob, 2020);
+    follows(&engine, bob, alice, 2021);
+    follows(&engine, bob, carol, 2021);
+
+    let result = engine
+        .query("MATCH (a)-[FOLLOWS]->(b)-[FOLLOWS]->(a) RETURN a")
+        .unwrap();
+    assert_eq!(names(&result.rows), ["alice", "bob"]);
+}
+
+#[test]
+fn test_query_pages_resume_after_the_last_row() {
+    let (engine, _temp_dir) = setup_test_engine();
+    let mut people: Vec<u128> = ["alice", "bob", "carol", "dave"]
+        .into_iter()
+        .enumerate()
+        .map(|(age, name)| add_person(&engine, name, age as i32))
+        .collect();
+    people.sort();
+    for (i, &from) in people.iter().enumerate() {
+        follows(&engine, from, people[(i + 1) % people.len()], 2020);
+        follows(&engine, from, people[(i + 2) % people.len()], 2021);
+    }
+
+    let query = "MATCH (a:person)-[e:FOLLOWS]->(b) RETURN a.name, b.name";
+    let all = engine.query(query).unwrap().rows;
+    assert_eq!(all.len(), 8);
+
+    let first = engine.query_page(query, &Page::first(3)).unwrap();
+    assert_eq!(first.items.rows, all[..3]);
+
+    // a row added before the cursor doesn't shift the pages after it
+    follows(&engine, people[0], people[0], 2022);
+    let mut rows = first.items.rows.clone();
+    let mut page = Page::next(3, &first);
+    loop {
+        let paged = engine.query_page(query, &page).unwrap();
+        rows.extend(paged.items.rows.iter().cloned());
+        if paged.next_cursor.is_none() {
+            break;
+        }
+        page = Page::next(3, &paged);
+    }
+    assert_eq!(rows, all);
+
+    // a cursor has to name a row of the same pattern
+    let cursor = Page {
+        limit: 3,
+        cursor: Some(format!("{:032x}", people[0])),
+    };
+    assert!(matches!(
+        engine.query_page(query, &cursor),
+        Err(GraphError::ConversionError(_))
+    ));
+}
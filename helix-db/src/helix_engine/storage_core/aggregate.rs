@@ -0,0 +1,105 @@
+use crate::{
+    helix_engine::{
+        storage_core::{
+            storage_core::HelixGraphStorage, timestamps::now_millis,
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::items::Node,
+};
+use heed3::RoTxn;
+use uuid::Uuid;
+
+/// How to combine a property across the nodes with a label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// The number of nodes that have the property, whatever its type
+    Count,
+}
+
+/// The result of an [`AggregateOp`], of the same kind as the op.
+///
+/// `Avg`, `Min` and `Max` are `None` if no node has the property.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateResult {
+    Sum(f64),
+    Avg(Option<f64>),
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Count(u64),
+}
+
+impl AggregateResult {
+    /// The result as a number, `None` if there was nothing to aggregate
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AggregateResult::Sum(sum) => Some(*sum),
+            AggregateResult::Avg(value) | AggregateResult::Min(value) | AggregateResult::Max(value) => {
+                *value
+            }
+            AggregateResult::Count(count) => Some(*count as f64),
+        }
+    }
+}
+
+impl HelixGraphStorage {
+    /// Combines `property` across the nodes with `label` in a single scan of the nodes table.
+    ///
+    /// Nodes without the property are skipped, so they aren't counted towards `Avg` either.
+    /// Returns `GraphError::ConversionError` if a node has a non-numeric value for any op
+    /// other than `Count`.
+    pub fn aggregate(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        property: &str,
+        op: AggregateOp,
+    ) -> Result<AggregateResult, GraphError> {
+        let mut count = 0u64;
+        let mut sum = 0.0;
+        let mut min: Option<f64> = None;
+        let mut max: Option<f64> = None;
+
+        let expired = self.expired_ids(txn, now_millis())?;
+        for result in self.nodes_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let node = Node::decode_node(bytes, id)?;
+            if node.label != label || expired.contains(&id) {
+                continue;
+            }
+            let value = match node.properties.as_ref().and_then(|props| props.get(property)) {
+                Some(Value::Empty) | None => continue,
+                Some(value) => value,
+            };
+            count += 1;
+            if op == AggregateOp::Count {
+                continue;
+            }
+
+            let value = value.as_f64().ok_or_else(|| {
+                GraphError::ConversionError(format!(
+                    "{}.{} of node {} is not a number",
+                    label,
+                    property,
+                    Uuid::from_u128(id)
+                ))
+            })?;
+            sum += value;
+            min = Some(min.map_or(value, |min| min.min(value)));
+            max = Some(max.map_or(value, |max| max.max(value)));
+        }
+
+        Ok(match op {
+            AggregateOp::Sum => AggregateResult::Sum(sum),
+            AggregateOp::Avg => AggregateResult::Avg((count > 0).then(|| sum / count as f64)),
+            AggregateOp::Min => AggregateResult::Min(min),
+            AggregateOp::Max => AggregateResult::Max(max),
+            AggregateOp::Count => AggregateResult::Count(count),
+        })
+    }
+}
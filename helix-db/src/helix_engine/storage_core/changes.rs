@@ -0,0 +1,355 @@
+use crate::{
+    helix_engine::{
+        storage_core::{storage_core::HelixGraphStorage, timestamps::now_millis},
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::items::{Edge, Node},
+};
+use flume::{Receiver, Sender, TrySendError};
+use heed3::RwTxn;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Events a subscriber can fall behind by before newer ones are dropped for it
+pub const SUBSCRIPTION_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Node,
+    Edge,
+}
+
+/// A committed change to a single node or edge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub item: ItemKind,
+    pub id: u128,
+    pub label: String,
+    /// The properties the change touched, which for an update are only those whose value changed
+    pub properties: Vec<String>,
+}
+
+/// The names of `properties`, in order
+fn property_names(properties: Option<&HashMap<String, Value>>) -> Vec<String> {
+    let mut names: Vec<String> = properties
+        .map(|props| props.keys().cloned().collect())
+        .unwrap_or_default();
+    names.sort_unstable();
+    names
+}
+
+impl ChangeEvent {
+    /// A node being inserted or deleted, touching every property it has
+    pub fn node(kind: ChangeKind, node: &Node) -> Self {
+        Self {
+            kind,
+            item: ItemKind::Node,
+            id: node.id,
+            label: node.label.clone(),
+            properties: property_names(node.properties.as_ref()),
+        }
+    }
+
+    /// An edge being inserted or deleted, touching every property it has
+    pub fn edge(kind: ChangeKind, edge: &Edge) -> Self {
+        Self {
+            kind,
+            item: ItemKind::Edge,
+            id: edge.id,
+            label: edge.label.clone(),
+            properties: property_names(edge.properties.as_ref()),
+        }
+    }
+
+    /// An update, touching the properties added, removed or changed between `old` and `new`
+    pub fn update(
+        item: ItemKind,
+        id: u128,
+        label: &str,
+        old: Option<&HashMap<String, Value>>,
+        new: Option<&HashMap<String, Value>>,
+    ) -> Self {
+        let empty = HashMap::new();
+        let (old, new) = (old.unwrap_or(&empty), new.unwrap_or(&empty));
+        let mut properties: Vec<String> = old
+            .keys()
+            .chain(new.keys().filter(|key| !old.contains_key(*key)))
+            .filter(|key| old.get(*key) != new.get(*key))
+            .cloned()
+            .collect();
+        properties.sort_unstable();
+        Self {
+            kind: ChangeKind::Update,
+            item,
+            id,
+            label: label.to_string(),
+            properties,
+        }
+    }
+}
+
+/// Narrows a subscription to the changes it matches, everything by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeFilter {
+    pub label: Option<String>,
+    pub property: Option<String>,
+}
+
+impl ChangeFilter {
+    /// Only changes to nodes and edges with `label`
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Only changes that touch `property`
+    pub fn property(mut self, property: &str) -> Self {
+        self.property = Some(property.to_string());
+        self
+    }
+
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        self.label.as_ref().is_none_or(|label| *label == event.label)
+            && self
+                .property
+                .as_ref()
+                .is_none_or(|property| event.properties.contains(property))
+    }
+}
+
+/// Gives every feed its own id, so events pending on a thread are only published by their feed
+static NEXT_FEED_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // changes made in the current thread's write txn, by feed, waiting for it to commit
+    static PENDING: RefCell<Vec<(usize, ChangeEvent)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Publishes committed changes to subscribers.
+///
+/// Changes are held back for the thread making them, as LMDB ties a write txn to the thread
+/// that opened it, and published once [`HelixGraphStorage::commit`] commits the txn.
+pub struct ChangeFeed {
+    id: usize,
+    subscribers: Mutex<Vec<(ChangeFilter, Sender<ChangeEvent>)>>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self {
+            id: NEXT_FEED_ID.fetch_add(1, Ordering::Relaxed),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl ChangeFeed {
+    pub fn subscribe(&self, filter: ChangeFilter) -> Receiver<ChangeEvent> {
+        let (tx, rx) = flume::bounded(SUBSCRIPTION_CAPACITY);
+        self.subscribers.lock().unwrap().push((filter, tx));
+        rx
+    }
+
+    /// Number of subscriptions, counting those dropped since the last change was published
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    fn record(&self, event: ChangeEvent) {
+        PENDING.with_borrow_mut(|pending| pending.push((self.id, event)));
+    }
+
+    fn pending_mark(&self) -> usize {
+        PENDING.with_borrow(|pending| pending.len())
+    }
+
+    fn discard_since(&self, mark: usize) {
+        PENDING.with_borrow_mut(|pending| {
+            let mut index = 0;
+            pending.retain(|(id, _)| {
+                index += 1;
+                index <= mark || *id != self.id
+            });
+        });
+    }
+
+    fn take_pending(&self) -> Vec<ChangeEvent> {
+        PENDING.with_borrow_mut(|pending| {
+            let (own, others) = std::mem::take(pending)
+                .into_iter()
+                .partition(|(id, _)| *id == self.id);
+            *pending = others;
+            own.into_iter().map(|(_, event)| event).collect()
+        })
+    }
+
+    /// Sends events to the subscribers whose filter matches them without ever blocking,
+    /// dropping events for subscribers that are full and removing those that are gone
+    fn publish(&self, events: Vec<ChangeEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        self.subscribers.lock().unwrap().retain(|(filter, tx)| {
+            for event in events.iter().filter(|event| filter.matches(event)) {
+                if let Err(TrySendError::Disconnected(_)) = tx.try_send(event.clone()) {
+                    return false;
+                }
+            }
+            !tx.is_disconnected()
+        });
+    }
+}
+
+/// A write txn opened by [`HelixGraphStorage::write_txn`], which derefs to the [`RwTxn`] it wraps.
+///
+/// Holds the storage's writer lock until it is committed, aborted or dropped. LMDB only
+/// finishes ending a write txn after letting the next one begin, which can corrupt the heap
+/// when write txns on several threads follow each other closely, so the next one waits for
+/// this one to have ended completely.
+pub struct WriteTxn<'a> {
+    // fields drop in order, so the txn has ended before the lock is released
+    txn: RwTxn<'a>,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl<'a> Deref for WriteTxn<'a> {
+    type Target = RwTxn<'a>;
+
+    fn deref(&self) -> &RwTxn<'a> {
+        &self.txn
+    }
+}
+
+impl DerefMut for WriteTxn<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.txn
+    }
+}
+
+impl HelixGraphStorage {
+    /// Opens a write txn whose changes are published to subscribers, and whose operations are
+    /// appended to the operation log, by [`HelixGraphStorage::commit`].
+    ///
+    /// Changes and operations left over from a txn on this thread that was aborted are discarded.
+    /// Nodes that have expired are purged in the txn before it is returned.
+    /// Opening one waits until any other thread's write txn has ended, see [`WriteTxn`].
+    /// Fails with `GraphError::ReadOnly` if the storage was opened read-only.
+    pub fn write_txn(&self) -> Result<WriteTxn<'_>, GraphError> {
+        Ok(self.open_write_txn()?.0)
+    }
+
+    /// Opens a write txn like [`HelixGraphStorage::write_txn`], along with how many expired
+    /// nodes were purged in it
+    pub(crate) fn open_write_txn(&self) -> Result<(WriteTxn<'_>, usize), GraphError> {
+        if self.read_only {
+            return Err(GraphError::ReadOnly);
+        }
+        // a txn on this thread panicking while it held the lock has ended all the same
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut txn = WriteTxn {
+            txn: self.graph_env.write_txn()?,
+            _writer: writer,
+        };
+        self.discard_pending();
+        let purged = self.purge_expired(&mut txn, now_millis())?;
+        Ok((txn, purged))
+    }
+
+    /// Appends the operations applied in a write txn to the operation log, then commits it and
+    /// publishes the changes made in it to subscribers.
+    ///
+    /// The txn is aborted if its operations can't be logged, and they are taken back out of the
+    /// log if it fails to commit.
+    pub fn commit(&self, txn: WriteTxn) -> Result<(), GraphError> {
+        // logged first, so a crash can't leave a committed txn out of the log
+        let logged = match &self.oplog {
+            Some(oplog) => match oplog.append(&oplog.take_pending()) {
+                Ok(length) => Some((oplog, length)),
+                Err(e) => {
+                    self.abort(txn);
+                    return Err(e);
+                }
+            },
+            None => None,
+        };
+        if let Err(e) = txn.txn.commit() {
+            if let Some((oplog, length)) = logged
+                && let Err(e) = oplog.truncate(length)
+            {
+                eprintln!("Error taking a txn that failed to commit out of the log: {}", e);
+            }
+            self.discard_pending();
+            return Err(e.into());
+        }
+        self.changes.publish(self.changes.take_pending());
+        Ok(())
+    }
+
+    /// Aborts a write txn, discarding the changes and operations recorded in it
+    pub fn abort(&self, txn: WriteTxn) {
+        txn.txn.abort();
+        self.discard_pending();
+    }
+
+    /// Runs `write` in a txn nested in `txn`, which is committed into `txn` if `write` succeeds.
+    ///
+    /// Otherwise the nested txn is aborted, along with the changes and operations recorded in
+    /// it, while `txn` keeps everything from before it.
+    pub fn nested_write<T>(
+        &self,
+        txn: &mut RwTxn,
+        write: impl FnOnce(&mut RwTxn) -> Result<T, GraphError>,
+    ) -> Result<T, GraphError> {
+        let changes = self.changes.pending_mark();
+        let operations = self.oplog.as_ref().map(|oplog| oplog.pending_mark());
+        let mut nested = self.graph_env.nested_write_txn(txn)?;
+        let written = write(&mut nested).and_then(|written| {
+            nested.commit()?;
+            Ok(written)
+        });
+        if written.is_err() {
+            self.changes.discard_since(changes);
+            if let (Some(oplog), Some(operations)) = (&self.oplog, operations) {
+                oplog.discard_since(operations);
+            }
+        }
+        written
+    }
+
+    fn discard_pending(&self) {
+        self.changes.take_pending();
+        if let Some(oplog) = &self.oplog {
+            oplog.take_pending();
+        }
+    }
+
+    /// Records a change made in the current write txn if anything is subscribed.
+    ///
+    /// Takes a closure so nothing is built when nothing is subscribed.
+    #[inline]
+    pub fn record_change(&self, event: impl FnOnce() -> ChangeEvent) {
+        if self.changes.has_subscribers() {
+            self.changes.record(event());
+        }
+    }
+}
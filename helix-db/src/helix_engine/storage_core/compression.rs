@@ -0,0 +1,83 @@
+use crate::{
+    helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError},
+    utils::items::{Edge, Node},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
+
+/// Level zstd compresses with, which favours speed as every write pays it
+const ZSTD_LEVEL: i32 = 3;
+
+/// Magic numbers starting zstd and LZ4 frames.
+///
+/// Neither can start a bincoded node or edge, whose first 8 bytes are the length of its label,
+/// so values written uncompressed are told apart from compressed ones without a header.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+
+/// How node and edge values are compressed before they are stored.
+///
+/// Every read decompresses the whole value, so this trades CPU on reads and writes for
+/// less disk and page cache. Values that don't shrink are stored as they are.
+/// Values are decompressed by the frame they start with rather than by this setting,
+/// so it can be changed at any time and values already stored stay readable.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    /// Fast to compress and decompress, but shrinks values less
+    Lz4,
+    /// Shrinks values more, at a higher cost to writes
+    Zstd,
+}
+
+impl Compression {
+    /// Compresses `bytes`, keeping them as they are if that doesn't make them smaller
+    pub fn compress(self, bytes: Vec<u8>) -> Result<Vec<u8>, GraphError> {
+        let compressed = match self {
+            Compression::None => return Ok(bytes),
+            Compression::Lz4 => {
+                let mut encoder = lz4::EncoderBuilder::new().build(Vec::new())?;
+                encoder.write_all(&bytes)?;
+                let (compressed, result) = encoder.finish();
+                result?;
+                compressed
+            }
+            Compression::Zstd => zstd::encode_all(bytes.as_slice(), ZSTD_LEVEL)?,
+        };
+        Ok(if compressed.len() < bytes.len() {
+            compressed
+        } else {
+            bytes
+        })
+    }
+
+    /// Decompresses a stored value, borrowing it if it was stored uncompressed
+    pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, GraphError> {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            Ok(Cow::Owned(zstd::decode_all(bytes)?))
+        } else if bytes.starts_with(&LZ4_MAGIC) {
+            let mut decompressed = Vec::with_capacity(bytes.len() * 2);
+            lz4::Decoder::new(bytes)?.read_to_end(&mut decompressed)?;
+            Ok(Cow::Owned(decompressed))
+        } else {
+            Ok(Cow::Borrowed(bytes))
+        }
+    }
+}
+
+impl HelixGraphStorage {
+    /// Encodes a node to be stored, compressing it as configured
+    pub fn encode_node(&self, node: &Node) -> Result<Vec<u8>, GraphError> {
+        self.compression.compress(node.encode_node()?)
+    }
+
+    /// Encodes an edge to be stored, compressing it as configured
+    pub fn encode_edge(&self, edge: &Edge) -> Result<Vec<u8>, GraphError> {
+        self.compression.compress(edge.encode_edge()?)
+    }
+}
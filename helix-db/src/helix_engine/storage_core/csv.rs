@@ -0,0 +1,370 @@
+use crate::{
+    helix_engine::{
+        storage_core::{
+            storage_core::HelixGraphStorage,
+            storage_methods::StorageMethods,
+            timestamps::{now_millis, stamp_created},
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::{
+        id::v6_uuid,
+        items::{Edge, Node},
+    },
+};
+use heed3::RwTxn;
+use std::{collections::HashMap, io::BufRead};
+use twox_hash::XxHash3_128;
+use uuid::Uuid;
+
+/// A row that wasn't imported, by the line of the file it starts on
+#[derive(Debug)]
+pub struct CsvRowError {
+    pub line: usize,
+    pub error: GraphError,
+}
+
+/// What a CSV import did: how many rows were imported, and why each of the others wasn't
+#[derive(Debug, Default)]
+pub struct CsvImportSummary {
+    pub imported: usize,
+    pub failed: Vec<CsvRowError>,
+}
+
+/// The node id an id column value maps to, so edges can refer to nodes by the same value.
+///
+/// A UUID is used as it is, and anything else is hashed to 128 bits, e.g. `42` or `alice`.
+pub fn csv_node_id(value: &str) -> u128 {
+    match Uuid::parse_str(value) {
+        Ok(uuid) => uuid.as_u128(),
+        Err(_) => XxHash3_128::oneshot(value.as_bytes()),
+    }
+}
+
+/// The property value a field holds, or `None` for an empty field, which sets no property.
+///
+/// `true` and `false` are booleans, integers that read back the same are `I64`, so `007`
+/// stays a string, numbers with a decimal point or exponent are `F64`, and everything else
+/// is a string.
+pub fn csv_value(field: &str) -> Option<Value> {
+    if field.is_empty() {
+        return None;
+    }
+    if let Ok(boolean) = field.parse::<bool>() {
+        return Some(Value::Boolean(boolean));
+    }
+    if let Ok(integer) = field.parse::<i64>()
+        && integer.to_string() == field
+    {
+        return Some(Value::I64(integer));
+    }
+    // `f64` also parses `inf`, `NaN` and plain integers, which are left as strings
+    if field.contains(['.', 'e', 'E'])
+        && field.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+        && let Ok(float) = field.parse::<f64>()
+    {
+        return Some(Value::F64(float));
+    }
+    Some(Value::String(field.to_string()))
+}
+
+/// Reads the records of an RFC 4180 CSV file, with fields separated by commas and quoted with
+/// `"` to hold commas, line breaks or `""` for a quote.
+struct CsvRecords<R> {
+    reader: R,
+    // line number of the last line read
+    line: usize,
+    done: bool,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self, buf: &mut String) -> Result<bool, GraphError> {
+        buf.clear();
+        let read = self.reader.read_line(buf)?;
+        if read > 0 {
+            self.line += 1;
+        }
+        if buf.ends_with('\n') {
+            buf.pop();
+            if buf.ends_with('\r') {
+                buf.pop();
+            }
+        }
+        Ok(read > 0)
+    }
+
+    /// The next record and the line it starts on, skipping blank lines.
+    ///
+    /// A malformed record is returned as an `Err` for its line, and reading carries on after it,
+    /// except for an unterminated quote, which runs to the end of the file.
+    fn next_record(&mut self) -> Option<(usize, Result<Vec<String>, GraphError>)> {
+        let mut buf = String::new();
+        loop {
+            if self.done {
+                return None;
+            }
+            match self.read_line(&mut buf) {
+                Ok(true) if buf.is_empty() => continue,
+                Ok(true) => break,
+                Ok(false) => return None,
+                Err(e) => {
+                    self.done = true;
+                    return Some((self.line + 1, Err(e)));
+                }
+            }
+        }
+        let start = self.line;
+
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut malformed = None;
+        let mut line = std::mem::take(&mut buf);
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => {
+                        quoted = false;
+                        if !matches!(chars.peek(), None | Some(',')) {
+                            malformed.get_or_insert("Unexpected text after a closing quote");
+                        }
+                    }
+                    (true, c) => field.push(c),
+                    (false, '"') if field.is_empty() => quoted = true,
+                    (false, '"') => {
+                        malformed.get_or_insert("Unexpected quote in an unquoted field");
+                        field.push(c);
+                    }
+                    (false, ',') => fields.push(std::mem::take(&mut field)),
+                    (false, c) => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            // a quoted field carries on over the line break
+            match self.read_line(&mut buf) {
+                Ok(true) => {
+                    field.push('\n');
+                    line = std::mem::take(&mut buf);
+                }
+                Ok(false) => {
+                    self.done = true;
+                    malformed = Some("Unterminated quote");
+                    break;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some((start, Err(e)));
+                }
+            }
+        }
+        fields.push(field);
+
+        match malformed {
+            Some(message) => Some((start, Err(GraphError::ConversionError(message.to_string())))),
+            None => Some((start, Ok(fields))),
+        }
+    }
+}
+
+/// The header row of a CSV file, naming each column
+struct Header {
+    columns: Vec<String>,
+}
+
+impl Header {
+    fn read<R: BufRead>(records: &mut CsvRecords<R>) -> Result<Self, GraphError> {
+        match records.next_record() {
+            Some((_, Ok(columns))) => Ok(Self { columns }),
+            Some((line, Err(e))) => Err(GraphError::ConversionError(format!(
+                "Invalid CSV header on line {}: {}",
+                line, e
+            ))),
+            None => Err(GraphError::ConversionError(
+                "CSV file has no header row".to_string(),
+            )),
+        }
+    }
+
+    fn position(&self, column: &str) -> Result<usize, GraphError> {
+        self.columns
+            .iter()
+            .position(|name| name == column)
+            .ok_or_else(|| {
+                GraphError::ConversionError(format!("CSV header has no {} column", column))
+            })
+    }
+
+    /// Checks a row has a field for every column
+    fn check<'r>(&self, row: &'r [String]) -> Result<&'r [String], GraphError> {
+        match row.len() == self.columns.len() {
+            true => Ok(row),
+            false => Err(GraphError::ConversionError(format!(
+                "Expected {} fields, found {}",
+                self.columns.len(),
+                row.len()
+            ))),
+        }
+    }
+
+    /// The row's fields as properties by column name, leaving out the `skip` columns
+    /// and empty fields
+    fn properties(&self, row: &[String], skip: &[usize]) -> HashMap<String, Value> {
+        self.columns
+            .iter()
+            .zip(row)
+            .enumerate()
+            .filter(|(index, _)| !skip.contains(index))
+            .filter_map(|(_, (column, field))| Some((column.clone(), csv_value(field)?)))
+            .collect()
+    }
+}
+
+/// A field that has to be set, e.g. an edge's label
+fn required<'r>(header: &Header, row: &'r [String], index: usize) -> Result<&'r str, GraphError> {
+    match row[index].as_str() {
+        "" => Err(GraphError::ConversionError(format!(
+            "Missing {}",
+            header.columns[index]
+        ))),
+        field => Ok(field),
+    }
+}
+
+pub trait CsvMethods {
+    /// Imports each row after the header as a node with `label` and a property per column.
+    ///
+    /// The node's id comes from its `id_column` field through [`csv_node_id`], and the field is
+    /// kept as a property too. Each row is written in a txn nested in `txn`, so a row that fails,
+    /// e.g. as its id is taken or it breaks the label's schema, is left out and recorded in the
+    /// summary without undoing the others.
+    ///
+    /// Fails without importing anything if the header is missing or has no `id_column`.
+    fn import_csv_nodes<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        label: &str,
+        id_column: &str,
+    ) -> Result<CsvImportSummary, GraphError>;
+
+    /// Imports each row after the header as a directed edge labelled by its `type_column`,
+    /// from and to the nodes whose ids its `from_column` and `to_column` map to through
+    /// [`csv_node_id`], with a property for each of its other columns and a generated
+    /// time-ordered v6 UUID like the engine's other edges.
+    ///
+    /// Rows are written like [`CsvMethods::import_csv_nodes`] writes them, with a row
+    /// whose nodes don't exist failing with `GraphError::DanglingEdge`.
+    fn import_csv_edges<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        type_column: &str,
+        from_column: &str,
+        to_column: &str,
+    ) -> Result<CsvImportSummary, GraphError>;
+}
+
+impl CsvMethods for HelixGraphStorage {
+    fn import_csv_nodes<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        label: &str,
+        id_column: &str,
+    ) -> Result<CsvImportSummary, GraphError> {
+        let mut records = CsvRecords::new(reader);
+        let header = Header::read(&mut records)?;
+        let id_index = header.position(id_column)?;
+        self.create_timestamp_index(txn, label)?;
+
+        let mut summary = CsvImportSummary::default();
+        while let Some((line, row)) = records.next_record() {
+            let node = row.and_then(|row| {
+                let row = header.check(&row)?;
+                let mut properties = header.properties(row, &[]);
+                stamp_created(&mut properties, now_millis());
+                Ok(Node {
+                    id: csv_node_id(required(&header, row, id_index)?),
+                    label: label.to_string(),
+                    properties: Some(properties),
+                })
+            });
+            let imported = node.and_then(|node| {
+                self.nested_write(txn, |row_txn| self.insert_node_with_id(row_txn, &node))
+            });
+            match imported {
+                Ok(()) => summary.imported += 1,
+                Err(error) => summary.failed.push(CsvRowError { line, error }),
+            }
+        }
+        Ok(summary)
+    }
+
+    fn import_csv_edges<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        type_column: &str,
+        from_column: &str,
+        to_column: &str,
+    ) -> Result<CsvImportSummary, GraphError> {
+        let mut records = CsvRecords::new(reader);
+        let header = Header::read(&mut records)?;
+        let columns = [
+            header.position(type_column)?,
+            header.position(from_column)?,
+            header.position(to_column)?,
+        ];
+
+        let mut summary = CsvImportSummary::default();
+        while let Some((line, row)) = records.next_record() {
+            let edge = row.and_then(|row| {
+                let row = header.check(&row)?;
+                let [label, from, to] = columns.map(|index| required(&header, row, index));
+                let properties = header.properties(row, &columns);
+                Ok(Edge {
+                    id: v6_uuid(),
+                    label: label?.to_string(),
+                    from_node: csv_node_id(from?),
+                    to_node: csv_node_id(to?),
+                    properties: (!properties.is_empty()).then_some(properties),
+                })
+            });
+            let imported = edge.and_then(|edge| {
+                self.nested_write(txn, |row_txn| {
+                    for (end, id) in [("from", edge.from_node), ("to", edge.to_node)] {
+                        if let Err(GraphError::NodeNotFound) = self.get_node(row_txn, &id) {
+                            return Err(GraphError::DanglingEdge(format!(
+                                "{} node {}",
+                                end,
+                                Uuid::from_u128(id)
+                            )));
+                        }
+                    }
+                    self.insert_edge_with_id(row_txn, &edge)
+                })
+            });
+            match imported {
+                Ok(()) => summary.imported += 1,
+                Err(error) => summary.failed.push(CsvRowError { line, error }),
+            }
+        }
+        Ok(summary)
+    }
+}
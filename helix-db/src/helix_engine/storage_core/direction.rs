@@ -0,0 +1,95 @@
+use crate::{
+    helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError},
+    protocol::value::Value,
+    utils::{items::Edge, label_hash::hash_label},
+};
+use heed3::{Database, RwTxn, types::Bytes};
+use std::collections::HashMap;
+
+/// Property marking an edge as undirected, set to `true`
+pub const UNDIRECTED: &str = "undirected";
+
+/// Whether an edge goes from one node to the other or joins them both ways.
+///
+/// Either way the edge is stored once. An undirected edge is also indexed the other way round,
+/// under its `to_node` as an out edge and its `from_node` as an in edge, so following out,
+/// in or both edges from either end finds it, and finds the node at the other end.
+/// Dropping a node drops its undirected edges like its directed ones, along with the entries
+/// indexing them under the node at the other end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdgeDirection {
+    #[default]
+    Directed,
+    Undirected,
+}
+
+impl EdgeDirection {
+    /// The direction of a stored edge, from its `undirected` property
+    pub fn of(edge: &Edge) -> Self {
+        match edge.properties.as_ref().and_then(|props| props.get(UNDIRECTED)) {
+            Some(Value::Boolean(true)) => EdgeDirection::Undirected,
+            _ => EdgeDirection::Directed,
+        }
+    }
+
+    /// Sets the `undirected` property of an edge about to be stored with this direction
+    pub fn mark(self, properties: &mut Option<HashMap<String, Value>>) {
+        if self == EdgeDirection::Undirected {
+            properties
+                .get_or_insert_with(HashMap::new)
+                .insert(UNDIRECTED.to_string(), Value::Boolean(true));
+        }
+    }
+}
+
+/// An adjacency table, a key in it and the packed edge data stored under the key
+type AdjacencyEntry = (Database<Bytes, Bytes>, [u8; 20], [u8; 32]);
+
+impl HelixGraphStorage {
+    /// The adjacency entries indexing `edge`
+    fn adjacency(&self, edge: &Edge) -> Vec<AdjacencyEntry> {
+        let label_hash = hash_label(edge.label.as_str(), None);
+        let mut entries = vec![
+            (
+                self.out_edges_db,
+                Self::out_edge_key(&edge.from_node, &label_hash),
+                Self::pack_edge_data(&edge.id, &edge.to_node),
+            ),
+            (
+                self.in_edges_db,
+                Self::in_edge_key(&edge.to_node, &label_hash),
+                Self::pack_edge_data(&edge.id, &edge.from_node),
+            ),
+        ];
+        // a self loop is already found from its only end
+        if EdgeDirection::of(edge) == EdgeDirection::Undirected && edge.from_node != edge.to_node {
+            entries.push((
+                self.out_edges_db,
+                Self::out_edge_key(&edge.to_node, &label_hash),
+                Self::pack_edge_data(&edge.id, &edge.from_node),
+            ));
+            entries.push((
+                self.in_edges_db,
+                Self::in_edge_key(&edge.from_node, &label_hash),
+                Self::pack_edge_data(&edge.id, &edge.to_node),
+            ));
+        }
+        entries
+    }
+
+    /// Writes the adjacency entries of `edge`, which for an undirected edge index it both ways
+    pub fn put_adjacency(&self, txn: &mut RwTxn, edge: &Edge) -> Result<(), GraphError> {
+        for (db, key, data) in self.adjacency(edge) {
+            db.put(txn, &key, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the adjacency entries of `edge`, leaving those of other edges under the same keys
+    pub fn delete_adjacency(&self, txn: &mut RwTxn, edge: &Edge) -> Result<(), GraphError> {
+        for (db, key, data) in self.adjacency(edge) {
+            db.delete_one_duplicate(txn, &key, &data)?;
+        }
+        Ok(())
+    }
+}
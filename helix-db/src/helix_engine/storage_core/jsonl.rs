@@ -0,0 +1,284 @@
+use crate::{
+    helix_engine::{
+        bm25::bm25::{BM25Flatten, BM25},
+        storage_core::{
+            changes::{ChangeEvent, ChangeKind},
+            oplog::Operation,
+            storage_core::HelixGraphStorage,
+            storage_methods::CountMethods,
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::{
+        filterable::Filterable,
+        items::{Edge, Node},
+        json_stream::for_each_array_element,
+    },
+};
+use heed3::{RoTxn, RwTxn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+use uuid::Uuid;
+
+/// A single line of a JSONL export.
+///
+/// Ids are written as hyphenated UUID strings, e.g.
+/// `{"type":"node","id":"...","label":"person","properties":{"name":"alice"}}` or
+/// `{"type":"edge","id":"...","label":"knows","from_node":"...","to_node":"...","properties":null}`.
+///
+/// Properties are plain JSON values, so numbers are read back as whichever numeric type
+/// fits them rather than the exact width they were stored with.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonlRecord {
+    Node {
+        id: String,
+        label: String,
+        #[serde(default)]
+        properties: Option<HashMap<String, Value>>,
+        /// When the node expires, in unix millis, if it was inserted with a TTL
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+    },
+    Edge {
+        id: String,
+        label: String,
+        from_node: String,
+        to_node: String,
+        #[serde(default)]
+        properties: Option<HashMap<String, Value>>,
+    },
+}
+
+/// Set of functions to stream the graph to and from JSON lines
+pub trait JsonlMethods {
+    /// Writes every node and then every edge to `writer` as one JSON object per line.
+    ///
+    /// Items are read straight from the table iterators so memory use does not grow with the graph.
+    /// Each line is serialized in full before it is written, so if an error is returned
+    /// the writer holds only complete lines.
+    ///
+    /// Returns the number of lines written.
+    fn export_jsonl<W: Write>(&self, txn: &RoTxn, writer: W) -> Result<usize, GraphError>;
+
+    /// Reads nodes and edges written by [`JsonlMethods::export_jsonl`], keeping their ids.
+    ///
+    /// Edges whose nodes have not been read yet are held back until the end of the stream
+    /// so they never point at missing nodes. Exports put every node before every edge,
+    /// so importing them never holds anything back.
+    ///
+    /// Returns the number of nodes and edges imported.
+    fn import_jsonl<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+    ) -> Result<(usize, usize), GraphError>;
+
+    /// Reads nodes and edges from a JSON array of the same objects an export writes one per line.
+    ///
+    /// The array is parsed one element at a time so memory use does not grow with its size,
+    /// and edges are held back the same way as [`JsonlMethods::import_jsonl`] does.
+    /// `max_size` limits the number of bytes read.
+    ///
+    /// Returns the number of nodes and edges imported.
+    fn import_json_array<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        max_size: Option<usize>,
+    ) -> Result<(usize, usize), GraphError>;
+}
+
+impl JsonlMethods for HelixGraphStorage {
+    fn export_jsonl<W: Write>(&self, txn: &RoTxn, mut writer: W) -> Result<usize, GraphError> {
+        let mut lines = 0;
+
+        for node in self.scan_nodes(txn, ..)? {
+            let node = node?;
+            let record = JsonlRecord::Node {
+                id: Uuid::from_u128(node.id).to_string(),
+                expires_at: self.expires_at(txn, node.id)?,
+                label: node.label,
+                properties: node.properties,
+            };
+            write_line(&mut writer, &record)?;
+            lines += 1;
+        }
+
+        for edge in self.scan_edges(txn, ..)? {
+            let edge = edge?;
+            let record = JsonlRecord::Edge {
+                id: Uuid::from_u128(edge.id).to_string(),
+                label: edge.label,
+                from_node: Uuid::from_u128(edge.from_node).to_string(),
+                to_node: Uuid::from_u128(edge.to_node).to_string(),
+                properties: edge.properties,
+            };
+            write_line(&mut writer, &record)?;
+            lines += 1;
+        }
+
+        writer.flush()?;
+        Ok(lines)
+    }
+
+    fn import_jsonl<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+    ) -> Result<(usize, usize), GraphError> {
+        self.import_records(txn, |import| {
+            for line in reader.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    import(sonic_rs::from_str::<JsonlRecord>(&line)?)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn import_json_array<R: BufRead>(
+        &self,
+        txn: &mut RwTxn,
+        reader: R,
+        max_size: Option<usize>,
+    ) -> Result<(usize, usize), GraphError> {
+        self.import_records(txn, |import| {
+            for_each_array_element(reader, max_size, import).map(|_| ())
+        })
+    }
+}
+
+impl HelixGraphStorage {
+    /// Imports each record `read` passes to the callback it's given
+    fn import_records(
+        &self,
+        txn: &mut RwTxn,
+        read: impl FnOnce(
+            &mut dyn FnMut(JsonlRecord) -> Result<(), GraphError>,
+        ) -> Result<(), GraphError>,
+    ) -> Result<(usize, usize), GraphError> {
+        let mut nodes = 0;
+        let mut edges = 0;
+        let mut deferred = Vec::new();
+
+        read(&mut |record| {
+            match record {
+                JsonlRecord::Node {
+                    id,
+                    label,
+                    properties,
+                    expires_at,
+                } => {
+                    let node = Node {
+                        id: Uuid::parse_str(&id)?.as_u128(),
+                        label,
+                        properties,
+                    };
+                    self.insert_node_with_id(txn, &node)?;
+                    if let Some(expires_at) = expires_at {
+                        self.set_expiry(txn, node.id, &node.label, expires_at)?;
+                    }
+                    nodes += 1;
+                }
+                JsonlRecord::Edge {
+                    id,
+                    label,
+                    from_node,
+                    to_node,
+                    properties,
+                } => {
+                    let edge = Edge {
+                        id: Uuid::parse_str(&id)?.as_u128(),
+                        label,
+                        from_node: Uuid::parse_str(&from_node)?.as_u128(),
+                        to_node: Uuid::parse_str(&to_node)?.as_u128(),
+                        properties,
+                    };
+                    match self.has_endpoints(txn, &edge)? {
+                        true => self.insert_edge_with_id(txn, &edge)?,
+                        false => deferred.push(edge),
+                    }
+                    edges += 1;
+                }
+            }
+            Ok(())
+        })?;
+
+        for edge in deferred {
+            if !self.has_endpoints(txn, &edge)? {
+                return Err(GraphError::NodeNotFound);
+            }
+            self.insert_edge_with_id(txn, &edge)?;
+        }
+
+        Ok((nodes, edges))
+    }
+
+    fn has_endpoints(&self, txn: &RoTxn, edge: &Edge) -> Result<bool, GraphError> {
+        Ok(self
+            .nodes_db
+            .get(txn, Self::node_key(&edge.from_node))?
+            .is_some()
+            && self.nodes_db.get(txn, Self::node_key(&edge.to_node))?.is_some())
+    }
+
+    /// Writes a node with its existing id, keeping the indices and counters in sync
+    pub fn insert_node_with_id(&self, txn: &mut RwTxn, node: &Node) -> Result<(), GraphError> {
+        if self.nodes_db.get(txn, Self::node_key(&node.id))?.is_some() {
+            return Err(GraphError::MultipleNodesWithSameId);
+        }
+        self.check_quota(txn, 1, 0)?;
+        self.validate_schema(txn, &node.label, node.properties.as_ref())?;
+        self.update_node_indices(txn, None, Some(node))?;
+        self.nodes_db
+            .put(txn, Self::node_key(&node.id), &self.encode_node(node)?)?;
+
+        for (index, db) in self.secondary_indices.iter() {
+            if let Ok(value) = node.check_property(index) {
+                db.put(txn, &bincode::serialize(value)?, &node.id)?;
+            }
+        }
+
+        if let Some(properties) = &node.properties {
+            let mut data = properties.flatten_bm25();
+            data.push_str(&node.label);
+            self.bm25.insert_doc(txn, node.id, &data)?;
+        }
+
+        self.record_node_added(txn, &node.label)?;
+        self.log_operation(|| Operation::add_node(node))?;
+        self.record_change(|| ChangeEvent::node(ChangeKind::Insert, node));
+        Ok(())
+    }
+
+    /// Writes an edge with its existing id along with its adjacency entries
+    pub fn insert_edge_with_id(&self, txn: &mut RwTxn, edge: &Edge) -> Result<(), GraphError> {
+        if self.edges_db.get(txn, Self::edge_key(&edge.id))?.is_some() {
+            return Err(GraphError::MultipleEdgesWithSameId);
+        }
+        self.check_quota(txn, 0, 1)?;
+        self.validate_schema(txn, &edge.label, edge.properties.as_ref())?;
+        self.edges_db
+            .put(txn, Self::edge_key(&edge.id), &self.encode_edge(edge)?)?;
+
+        self.put_adjacency(txn, edge)?;
+
+        self.record_edge_added(txn)?;
+        self.log_operation(|| Operation::add_edge(edge))?;
+        self.record_change(|| ChangeEvent::edge(ChangeKind::Insert, edge));
+        Ok(())
+    }
+}
+
+fn write_line<W: Write>(writer: &mut W, record: &JsonlRecord) -> Result<(), GraphError> {
+    let mut line = sonic_rs::to_vec(record)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    Ok(())
+}
@@ -1,4 +1,20 @@
 pub mod storage_core;
 pub mod storage_methods;
 pub mod graph_visualization;
+pub mod aggregate;
+pub mod changes;
+pub mod compression;
+pub mod csv;
+pub mod direction;
+pub mod jsonl;
+pub mod oplog;
+pub mod range_index;
+pub mod scan;
+pub mod schema;
+pub mod setup;
+pub mod subgraph;
+pub mod text_index;
+pub mod timestamps;
+pub mod ttl;
+pub mod unique;
 
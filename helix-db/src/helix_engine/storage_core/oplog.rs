@@ -0,0 +1,375 @@
+use crate::{
+    helix_engine::{
+        storage_core::{
+            changes::{ChangeEvent, ItemKind},
+            storage_core::HelixGraphStorage,
+            storage_methods::{AliasMethods, StorageMethods},
+        },
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::items::{Edge, Node},
+};
+use heed3::RwTxn;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// A single graph mutation as recorded in the operation log.
+///
+/// Each operation carries the ids and full resulting state of what it touched rather than
+/// a diff, so applying the same operation twice leaves the graph as applying it once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    AddNode {
+        #[serde(with = "uuid_string")]
+        id: u128,
+        label: String,
+        properties: Option<HashMap<String, Value>>,
+    },
+    AddEdge {
+        #[serde(with = "uuid_string")]
+        id: u128,
+        label: String,
+        #[serde(with = "uuid_string")]
+        from_node: u128,
+        #[serde(with = "uuid_string")]
+        to_node: u128,
+        properties: Option<HashMap<String, Value>>,
+    },
+    /// The properties of a node after an update
+    UpdateNode {
+        #[serde(with = "uuid_string")]
+        id: u128,
+        properties: Option<HashMap<String, Value>>,
+    },
+    /// The properties of an edge after an update
+    UpdateEdge {
+        #[serde(with = "uuid_string")]
+        id: u128,
+        properties: Option<HashMap<String, Value>>,
+    },
+    /// Dropping a node also drops its edges, as it does when first applied
+    DropNode {
+        #[serde(with = "uuid_string")]
+        id: u128,
+    },
+    DropEdge {
+        #[serde(with = "uuid_string")]
+        id: u128,
+    },
+    /// When a node inserted with a TTL expires, in unix millis
+    ExpireNode {
+        #[serde(with = "uuid_string")]
+        id: u128,
+        expires_at: u64,
+    },
+    SetAlias {
+        name: String,
+        #[serde(with = "uuid_string")]
+        node_id: u128,
+    },
+    DropAlias {
+        name: String,
+    },
+}
+
+impl Operation {
+    pub fn add_node(node: &Node) -> Self {
+        Operation::AddNode {
+            id: node.id,
+            label: node.label.clone(),
+            properties: node.properties.clone(),
+        }
+    }
+
+    pub fn add_edge(edge: &Edge) -> Self {
+        Operation::AddEdge {
+            id: edge.id,
+            label: edge.label.clone(),
+            from_node: edge.from_node,
+            to_node: edge.to_node,
+            properties: edge.properties.clone(),
+        }
+    }
+}
+
+/// Gives every log its own id, so operations pending on a thread are only appended to their log
+static NEXT_LOG_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // operations applied in the current thread's write txn, by log, waiting for it to commit
+    static PENDING: RefCell<Vec<(usize, Operation)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Append-only log of every graph mutation, one JSON object per line.
+///
+/// Operations are held back for the thread applying them, like changes for subscribers, and
+/// only appended by [`HelixGraphStorage::commit`], so the log never holds operations of a txn
+/// that was aborted. They are flushed to disk before their txn is committed, so every committed
+/// txn is in the log even after a crash, though a crash before the commit can leave the last
+/// txn in the log without it having been committed. Vectors are not recorded.
+pub struct OpLog {
+    pub path: PathBuf,
+    id: usize,
+    file: Mutex<File>,
+}
+
+impl OpLog {
+    /// Opens the log at `path` for appending, creating it if it doesn't exist.
+    ///
+    /// A last line left partly written by a crash is cut off first.
+    pub fn open(path: &str) -> Result<Self, GraphError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        drop_torn_line(&mut file)?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            id: NEXT_LOG_ID.fetch_add(1, Ordering::Relaxed),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Durably appends operations to the log, syncing once after all of them.
+    ///
+    /// Returns the length of the log before them, to take them back out with
+    /// [`OpLog::truncate`] if their txn fails to commit. A write that fails is taken back out
+    /// before this returns, so it can't leave part of a line behind.
+    pub fn append(&self, operations: &[Operation]) -> Result<u64, GraphError> {
+        let mut lines = Vec::new();
+        for operation in operations {
+            lines.extend(sonic_rs::to_vec(operation)?);
+            lines.push(b'\n');
+        }
+        let mut file = self.file.lock().unwrap();
+        let length = file.metadata()?.len();
+        if lines.is_empty() {
+            return Ok(length);
+        }
+        if let Err(e) = file.write_all(&lines).and_then(|_| file.sync_data()) {
+            let _ = file.set_len(length);
+            return Err(e.into());
+        }
+        Ok(length)
+    }
+
+    /// Cuts the log back to `length`, taking out the operations appended since
+    pub fn truncate(&self, length: u64) -> Result<(), GraphError> {
+        let file = self.file.lock().unwrap();
+        file.set_len(length)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn record(&self, operation: Operation) {
+        PENDING.with_borrow_mut(|pending| pending.push((self.id, operation)));
+    }
+
+    pub(crate) fn take_pending(&self) -> Vec<Operation> {
+        PENDING.with_borrow_mut(|pending| {
+            let (own, others) = std::mem::take(pending)
+                .into_iter()
+                .partition(|(id, _)| *id == self.id);
+            *pending = others;
+            own.into_iter().map(|(_, operation)| operation).collect()
+        })
+    }
+
+    /// How many operations are pending on this thread, to discard those recorded after it
+    /// with [`OpLog::discard_since`]
+    pub(crate) fn pending_mark(&self) -> usize {
+        PENDING.with_borrow(|pending| pending.len())
+    }
+
+    pub(crate) fn discard_since(&self, mark: usize) {
+        PENDING.with_borrow_mut(|pending| {
+            let mut index = 0;
+            pending.retain(|(id, _)| {
+                index += 1;
+                index <= mark || *id != self.id
+            });
+        });
+    }
+
+    /// Reads back the operations in the log at `path` in the order they were recorded
+    pub fn read(path: &str) -> Result<impl Iterator<Item = Result<Operation, GraphError>>, GraphError> {
+        let reader = BufReader::new(File::open(Path::new(path))?);
+        Ok(reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Ok(sonic_rs::from_str::<Operation>(&line?)?)))
+    }
+}
+
+/// Cuts off a last line that doesn't end in a newline, which a crash left partly written
+fn drop_torn_line(file: &mut File) -> std::io::Result<()> {
+    let length = file.metadata()?.len();
+    let mut block = [0u8; 4096];
+    let mut end = length;
+    while end > 0 {
+        let start = end.saturating_sub(block.len() as u64);
+        let block = &mut block[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+        if let Some(newline) = block.iter().rposition(|byte| *byte == b'\n') {
+            end = start + newline as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if end < length {
+        file.set_len(end)?;
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+impl HelixGraphStorage {
+    /// Records an operation applied in the current write txn if the operation log is enabled,
+    /// to be appended once the txn commits.
+    ///
+    /// Takes a closure so nothing is cloned when it isn't.
+    #[inline]
+    pub fn log_operation(&self, operation: impl FnOnce() -> Operation) -> Result<(), GraphError> {
+        if let Some(oplog) = &self.oplog {
+            oplog.record(operation());
+        }
+        Ok(())
+    }
+
+    /// Applies a logged operation, skipping it if its effect is already present
+    /// or the item it updates no longer exists.
+    ///
+    /// Applied operations are recorded to this storage's own log if it has one,
+    /// so a rebuilt graph keeps a complete log of its own.
+    pub fn apply_operation(&self, txn: &mut RwTxn, operation: Operation) -> Result<(), GraphError> {
+        match operation {
+            Operation::AddNode {
+                id,
+                label,
+                properties,
+            } => {
+                if self.nodes_db.get(txn, Self::node_key(&id))?.is_none() {
+                    self.insert_node_with_id(
+                        txn,
+                        &Node {
+                            id,
+                            label,
+                            properties,
+                        },
+                    )?;
+                }
+            }
+            Operation::AddEdge {
+                id,
+                label,
+                from_node,
+                to_node,
+                properties,
+            } => {
+                if self.edges_db.get(txn, Self::edge_key(&id))?.is_none() {
+                    self.insert_edge_with_id(
+                        txn,
+                        &Edge {
+                            id,
+                            label,
+                            from_node,
+                            to_node,
+                            properties,
+                        },
+                    )?;
+                }
+            }
+            Operation::UpdateNode { id, properties } => {
+                let current = match self.get_node(txn, &id) {
+                    Ok(node) => node,
+                    Err(GraphError::NodeNotFound) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                let mut patch = properties.unwrap_or_default();
+                // keys the update removed are nulled out so the result matches exactly
+                for key in current.properties.unwrap_or_default().into_keys() {
+                    patch.entry(key).or_insert(Value::Empty);
+                }
+                self.update_node_properties(txn, &id, patch)?;
+            }
+            Operation::UpdateEdge { id, properties } => {
+                let mut edge = match self.get_edge(txn, &id) {
+                    Ok(edge) => edge,
+                    Err(GraphError::EdgeNotFound) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                let previous = std::mem::replace(&mut edge.properties, properties);
+                self.validate_schema(txn, &edge.label, edge.properties.as_ref())?;
+                self.edges_db
+                    .put(txn, Self::edge_key(&id), &self.encode_edge(&edge)?)?;
+                self.log_operation(|| Operation::UpdateEdge {
+                    id,
+                    properties: edge.properties.clone(),
+                })?;
+                self.record_change(|| {
+                    ChangeEvent::update(
+                        ItemKind::Edge,
+                        id,
+                        &edge.label,
+                        previous.as_ref(),
+                        edge.properties.as_ref(),
+                    )
+                });
+            }
+            Operation::DropNode { id } => self.drop_node(txn, &id)?,
+            Operation::DropEdge { id } => {
+                if self.edges_db.get(txn, Self::edge_key(&id))?.is_some() {
+                    self.drop_edge(txn, &id)?;
+                }
+            }
+            Operation::ExpireNode { id, expires_at } => {
+                if let Some(bytes) = self.nodes_db.get(txn, Self::node_key(&id))? {
+                    let label = Node::decode_node(bytes, id)?.label;
+                    self.set_expiry(txn, id, &label, expires_at)?;
+                }
+            }
+            Operation::SetAlias { name, node_id } => {
+                if self.nodes_db.get(txn, Self::node_key(&node_id))?.is_some() {
+                    self.set_alias(txn, &name, &node_id)?;
+                }
+            }
+            Operation::DropAlias { name } => {
+                if self.aliases_db.get(txn, &name)?.is_some() {
+                    self.drop_alias(txn, &name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes `u128` ids as hyphenated UUID strings
+mod uuid_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use uuid::Uuid;
+
+    pub fn serialize<S: Serializer>(id: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&Uuid::from_u128(*id).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        Uuid::parse_str(&id)
+            .map(|uuid| uuid.as_u128())
+            .map_err(serde::de::Error::custom)
+    }
+}
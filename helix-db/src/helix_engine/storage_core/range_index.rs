@@ -0,0 +1,213 @@
+use crate::{
+    helix_engine::{
+        storage_core::{storage_core::HelixGraphStorage, timestamps::UPDATED_AT},
+        types::GraphError,
+    },
+    utils::items::Node,
+};
+use heed3::{RoTxn, RwTxn};
+use std::ops::Bound;
+
+/// Key of a range index, every entry key under it starts with this.
+///
+/// key = `label` | `0x00` | `property` | `0x00`
+#[inline(always)]
+fn index_key(label: &str, property: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(label.len() + property.len() + 2 + 8 + 16);
+    key.extend_from_slice(label.as_bytes());
+    key.push(0);
+    key.extend_from_slice(property.as_bytes());
+    key.push(0);
+    key
+}
+
+/// Encodes a float so its big-endian bytes sort in the same order as the number.
+///
+/// Positive numbers get their sign bit set and negative numbers have every bit flipped,
+/// so negatives sort below positives and larger magnitudes sort further from zero.
+#[inline(always)]
+fn sortable(value: f64) -> [u8; 8] {
+    // -0.0 is stored as 0.0 so both fall inside a range starting or ending at zero
+    let value = if value == 0.0 { 0.0 } else { value };
+    let bits = value.to_bits();
+    let bits = match bits >> 63 {
+        1 => !bits,
+        _ => bits | 1 << 63,
+    };
+    bits.to_be_bytes()
+}
+
+/// key = `label` | `0x00` | `property` | `0x00` | `sortable(value)(8)` | `node-id(16)`
+#[inline(always)]
+fn entry_key(label: &str, property: &str, value: f64, id: u128) -> Vec<u8> {
+    let mut key = index_key(label, property);
+    key.extend_from_slice(&sortable(value));
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+impl HelixGraphStorage {
+    /// Creates an ordered index over the numeric values of `property` on the nodes with `label`,
+    /// indexing the nodes that already have it.
+    ///
+    /// Values are indexed as `f64`, so integers beyond 2^53 may lose precision.
+    /// Non-numeric and NaN values aren't indexed. Creating an index that already exists does nothing.
+    pub fn create_range_index(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        property: &str,
+    ) -> Result<(), GraphError> {
+        let key = index_key(label, property);
+        if self.range_indices_db.get(txn, &key)?.is_some() {
+            return Ok(());
+        }
+
+        let mut entries = Vec::new();
+        for result in self.nodes_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let node = Node::decode_node(bytes, id)?;
+            if node.label == label
+                && let Some(value) = indexed_value(&node, property)
+            {
+                entries.push(entry_key(label, property, value, id));
+            }
+        }
+        for entry in entries {
+            self.range_values_db.put(txn, &entry, &())?;
+        }
+
+        self.range_indices_db.put(txn, &key, &())?;
+        Ok(())
+    }
+
+    /// Creates the range index over `updated_at` on the nodes with `label` if it doesn't exist,
+    /// as the engine does before stamping one of them.
+    ///
+    /// Labels whose index has been seen in a committed txn are remembered, so after that this
+    /// doesn't touch the database. One created by `txn` is only remembered once a later txn
+    /// finds it, as `txn` may still be aborted.
+    pub fn create_timestamp_index(&self, txn: &mut RwTxn, label: &str) -> Result<(), GraphError> {
+        if self.timestamp_indexed.read().unwrap().contains(label) {
+            return Ok(());
+        }
+        if self.range_indices_db.get(txn, &index_key(label, UPDATED_AT))?.is_none() {
+            return self.create_range_index(txn, label, UPDATED_AT);
+        }
+        self.timestamp_indexed.write().unwrap().insert(label.to_string());
+        Ok(())
+    }
+
+    /// Gets the properties with a range index on the nodes with `label`
+    pub fn range_indexed_properties(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+    ) -> Result<Vec<String>, GraphError> {
+        let mut prefix = label.as_bytes().to_vec();
+        prefix.push(0);
+
+        let mut properties = Vec::new();
+        for result in self.range_indices_db.prefix_iter(txn, &prefix)? {
+            let (key, _) = result?;
+            let property = &key[prefix.len()..key.len() - 1];
+            properties.push(String::from_utf8_lossy(property).into_owned());
+        }
+        Ok(properties)
+    }
+
+    /// Moves a node's range index entries from `old` to `new`.
+    ///
+    /// `old` is the node as currently stored, or `None` if it is being added,
+    /// and `new` is the node about to be stored, or `None` if it is being dropped.
+    pub fn update_range_values(
+        &self,
+        txn: &mut RwTxn,
+        old: Option<&Node>,
+        new: Option<&Node>,
+    ) -> Result<(), GraphError> {
+        let Some(label) = new.or(old).map(|node| node.label.as_str()) else {
+            return Ok(());
+        };
+        let properties = self.range_indexed_properties(txn, label)?;
+        for property in properties.iter() {
+            let old_value = old.and_then(|node| indexed_value(node, property));
+            let new_value = new.and_then(|node| indexed_value(node, property));
+            if old_value == new_value {
+                continue;
+            }
+            if let (Some(node), Some(value)) = (old, old_value) {
+                self.range_values_db
+                    .delete(txn, &entry_key(label, property, value, node.id))?;
+            }
+            if let (Some(node), Some(value)) = (new, new_value) {
+                self.range_values_db
+                    .put(txn, &entry_key(label, property, value, node.id), &())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the ids of the nodes with `label` whose `property` is between `min` and `max`,
+    /// in ascending order of the value.
+    ///
+    /// Both bounds are inclusive and either can be left open.
+    /// Returns `GraphError::New` if there is no range index on the property.
+    pub fn node_ids_in_range(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        property: &str,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<Vec<u128>, GraphError> {
+        let prefix = index_key(label, property);
+        if self.range_indices_db.get(txn, &prefix)?.is_none() {
+            return Err(GraphError::New(format!(
+                "No range index on {}.{}",
+                label, property
+            )));
+        }
+        if min.is_some_and(f64::is_nan) || max.is_some_and(f64::is_nan) {
+            return Err(GraphError::New("Range bounds can't be NaN".to_string()));
+        }
+        if let (Some(min), Some(max)) = (min, max)
+            && min > max
+        {
+            return Ok(Vec::new());
+        }
+
+        let mut start = prefix.clone();
+        if let Some(min) = min {
+            start.extend_from_slice(&sortable(min));
+        }
+        let mut end = prefix.clone();
+        match max {
+            Some(max) => {
+                end.extend_from_slice(&sortable(max));
+                end.extend_from_slice(&[0xff; 16]);
+            }
+            None => end.extend_from_slice(&[0xff; 24]),
+        }
+
+        let range = (Bound::Included(start.as_slice()), Bound::Included(end.as_slice()));
+        let mut ids = Vec::new();
+        for result in self.range_values_db.range(txn, &range)? {
+            let (key, _) = result?;
+            let id = key[key.len() - 16..]
+                .try_into()
+                .map_err(|_| GraphError::SliceLengthError)?;
+            ids.push(u128::from_be_bytes(id));
+        }
+        Ok(ids)
+    }
+}
+
+/// The value of `property` on `node` as it is stored in a range index, if it can be
+fn indexed_value(node: &Node, property: &str) -> Option<f64> {
+    node.properties
+        .as_ref()
+        .and_then(|props| props.get(property))
+        .and_then(|value| value.as_f64())
+        .filter(|value| !value.is_nan())
+}
@@ -0,0 +1,56 @@
+use crate::{
+    helix_engine::{
+        storage_core::{storage_core::HelixGraphStorage, timestamps::now_millis},
+        types::GraphError,
+    },
+    utils::items::{Edge, Node},
+};
+use heed3::RoTxn;
+use std::ops::RangeBounds;
+
+impl HelixGraphStorage {
+    /// Scans the nodes with ids in `range`, in id order, reading and decoding each one only
+    /// when it is pulled.
+    ///
+    /// Stopping early, e.g. with `take` or `find`, leaves the rest of the range unread, so
+    /// memory stays flat however large the range is. Expired nodes are skipped.
+    pub fn scan_nodes<'a>(
+        &'a self,
+        txn: &'a RoTxn,
+        range: impl RangeBounds<u128>,
+    ) -> Result<impl Iterator<Item = Result<Node, GraphError>> + 'a, GraphError> {
+        let expired = self.expired_ids(txn, now_millis())?;
+        let iter = self.nodes_db.range(txn, &range)?;
+        Ok(iter.filter_map(move |result| match result {
+            Ok((id, _)) if expired.contains(&id) => None,
+            Ok((id, bytes)) => Some(Node::decode_node(bytes, id)),
+            Err(e) => Some(Err(GraphError::from(e))),
+        }))
+    }
+
+    /// Scans the edges with ids in `range`, in id order, reading and decoding each one only
+    /// when it is pulled.
+    ///
+    /// Edges of expired nodes are skipped.
+    pub fn scan_edges<'a>(
+        &'a self,
+        txn: &'a RoTxn,
+        range: impl RangeBounds<u128>,
+    ) -> Result<impl Iterator<Item = Result<Edge, GraphError>> + 'a, GraphError> {
+        let expired = self.expired_ids(txn, now_millis())?;
+        let iter = self.edges_db.range(txn, &range)?;
+        Ok(iter.filter_map(move |result| {
+            let edge = result
+                .map_err(GraphError::from)
+                .and_then(|(id, bytes)| Edge::decode_edge(bytes, id));
+            match edge {
+                Ok(edge)
+                    if expired.contains(&edge.from_node) || expired.contains(&edge.to_node) =>
+                {
+                    None
+                }
+                edge => Some(edge),
+            }
+        }))
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    helix_engine::{
+        storage_core::{storage_core::HelixGraphStorage, timestamps::RESERVED_PROPERTIES},
+        types::GraphError,
+    },
+    protocol::value::Value,
+    utils::items::{Edge, Node},
+};
+use heed3::{RoTxn, RwTxn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// The type a property under a schema must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    String,
+    /// Any signed or unsigned integer
+    Integer,
+    /// Any number, since integers are valid wherever a float is
+    Float,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl FieldType {
+    pub fn matches(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (FieldType::String, Value::String(_))
+                | (
+                    FieldType::Integer | FieldType::Float,
+                    Value::I8(_)
+                        | Value::I16(_)
+                        | Value::I32(_)
+                        | Value::I64(_)
+                        | Value::U8(_)
+                        | Value::U16(_)
+                        | Value::U32(_)
+                        | Value::U64(_)
+                        | Value::U128(_),
+                )
+                | (FieldType::Float, Value::F32(_) | Value::F64(_))
+                | (FieldType::Boolean, Value::Boolean(_))
+                | (FieldType::Array, Value::Array(_))
+                | (FieldType::Object, Value::Object(_))
+        )
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A property allowed on the nodes and edges with a registered label
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    pub field_type: FieldType,
+    pub required: bool,
+}
+
+impl FieldSchema {
+    pub fn required(name: &str, field_type: FieldType) -> Self {
+        Self {
+            name: name.to_string(),
+            field_type,
+            required: true,
+        }
+    }
+
+    pub fn optional(name: &str, field_type: FieldType) -> Self {
+        Self {
+            name: name.to_string(),
+            field_type,
+            required: false,
+        }
+    }
+}
+
+/// Checks `properties` against the fields of `label`'s schema
+fn check_fields(
+    label: &str,
+    fields: &[FieldSchema],
+    properties: Option<&HashMap<String, Value>>,
+) -> Result<(), GraphError> {
+    let value = |name: &str| {
+        properties
+            .and_then(|props| props.get(name))
+            .filter(|value| !matches!(value, Value::Empty))
+    };
+
+    for field in fields {
+        match value(&field.name) {
+            Some(value) if !field.field_type.matches(value) => {
+                return Err(GraphError::SchemaViolation(format!(
+                    "{}.{} must be {}, found {:?}",
+                    label, field.name, field.field_type, value
+                )));
+            }
+            None if field.required => {
+                return Err(GraphError::SchemaViolation(format!(
+                    "{}.{} is required",
+                    label, field.name
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    for name in properties.into_iter().flat_map(|props| props.keys()) {
+        if !RESERVED_PROPERTIES.contains(&name.as_str())
+            && !fields.iter().any(|field| &field.name == name)
+        {
+            return Err(GraphError::SchemaViolation(format!(
+                "{}.{} is not in the schema",
+                label, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl HelixGraphStorage {
+    /// Registers the properties allowed on the nodes and edges with `label`, replacing any
+    /// schema it already had.
+    ///
+    /// Fails with `GraphError::SchemaViolation`, leaving the old schema in place,
+    /// if an existing node or edge with the label doesn't match.
+    pub fn register_schema(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        fields: &[FieldSchema],
+    ) -> Result<(), GraphError> {
+        for result in self.nodes_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let node = Node::decode_node(bytes, id)?;
+            if node.label == label {
+                check_fields(label, fields, node.properties.as_ref())?;
+            }
+        }
+        for result in self.edges_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let edge = Edge::decode_edge(bytes, id)?;
+            if edge.label == label {
+                check_fields(label, fields, edge.properties.as_ref())?;
+            }
+        }
+
+        self.schemas_db
+            .put(txn, label, &bincode::serialize(fields)?)?;
+        Ok(())
+    }
+
+    /// Gets the schema registered for `label`, if it has one
+    pub fn get_schema(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+    ) -> Result<Option<Vec<FieldSchema>>, GraphError> {
+        match self.schemas_db.get(txn, label)? {
+            Some(bytes) => Ok(Some(bincode::deserialize(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks the properties of a node or edge about to be written with `label`.
+    ///
+    /// Labels without a schema accept any properties.
+    /// Otherwise every required field must be present, every field must have its type,
+    /// and properties that aren't fields are rejected so misspelled names are caught.
+    /// The engine's own timestamp properties are always accepted.
+    pub fn validate_schema(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        properties: Option<&HashMap<String, Value>>,
+    ) -> Result<(), GraphError> {
+        match self.get_schema(txn, label)? {
+            Some(fields) => check_fields(label, &fields, properties),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use heed3::{
+    Comparator, Database, DatabaseOpenOptions, MdbError, RoTxn, RwTxn, WithTls,
+};
+
+/// The txn the databases are opened in while the storage is being set up.
+///
+/// A writable one creates the databases that don't exist yet, while one on a read-only
+/// environment can only open those that do.
+pub enum SetupTxn<'e> {
+    Write(RwTxn<'e>),
+    ReadOnly(RoTxn<'e, WithTls>),
+}
+
+impl<'e> SetupTxn<'e> {
+    /// The txn to read through, whichever kind it is
+    pub fn read(&self) -> &RoTxn<'e> {
+        match self {
+            SetupTxn::Write(txn) => txn,
+            SetupTxn::ReadOnly(txn) => txn,
+        }
+    }
+
+    /// Commits the txn, which keeps the databases opened in it open afterwards
+    pub fn commit(self) -> heed3::Result<()> {
+        match self {
+            SetupTxn::Write(txn) => txn.commit(),
+            SetupTxn::ReadOnly(txn) => txn.commit(),
+        }
+    }
+}
+
+/// Opens a database in a [`SetupTxn`], in place of `create`
+pub trait OpenOrCreate<KC, DC, C, CDUP> {
+    /// Creates the database if it doesn't exist and the txn is writable.
+    ///
+    /// Fails with `MdbError::NotFound` if it doesn't exist and the txn is read-only,
+    /// as LMDB does when it is asked to open a database without creating it.
+    fn open_or_create(&self, txn: &mut SetupTxn) -> heed3::Result<Database<KC, DC, C, CDUP>>;
+}
+
+impl<KC, DC, C, CDUP> OpenOrCreate<KC, DC, C, CDUP>
+    for DatabaseOpenOptions<'_, '_, WithTls, KC, DC, C, CDUP>
+where
+    KC: 'static,
+    DC: 'static,
+    C: Comparator + 'static,
+    CDUP: Comparator + 'static,
+{
+    fn open_or_create(&self, txn: &mut SetupTxn) -> heed3::Result<Database<KC, DC, C, CDUP>> {
+        match txn {
+            SetupTxn::Write(txn) => self.create(txn),
+            SetupTxn::ReadOnly(txn) => self
+                .open(txn)?
+                .ok_or(heed3::Error::Mdb(MdbError::NotFound)),
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use super::storage_methods::DBMethods;
+use super::{
+    changes::{ChangeEvent, ChangeFeed, ChangeKind, ItemKind},
+    compression::Compression,
+    oplog::{OpLog, Operation},
+    setup::{OpenOrCreate, SetupTxn},
+    storage_methods::{AliasMethods, CountMethods, DBMethods, Direction},
+    timestamps::now_millis,
+};
 use crate::{
     helix_engine::{
-        bm25::bm25::HBM25Config,
-        graph_core::config::Config,
+        bm25::bm25::{BM25Flatten, HBM25Config, BM25},
+        graph_core::{config::Config, ops::util::predicate::Predicate},
         storage_core::storage_methods::StorageMethods,
         types::GraphError,
         vector_core::{
@@ -11,6 +18,7 @@ use crate::{
             vector_core::{HNSWConfig, VectorCore},
         },
     },
+    protocol::value::Value,
     utils::{
         items::{Edge, Node},
         label_hash::hash_label,
@@ -18,15 +26,17 @@ use crate::{
 };
 use heed3::{
     types::*,
-    Database, DatabaseFlags,
-    Env, EnvOpenOptions,
+    CompactionOption, Database, DatabaseFlags,
+    Env, EnvFlags, EnvOpenOptions,
     RoTxn, RwTxn,
     byteorder::BE,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
+    ops::Bound,
     path::Path,
+    sync::{Mutex, RwLock},
 };
 
 // database names for different stores
@@ -34,10 +44,33 @@ const DB_NODES: &str = "nodes"; // for node data (n:)
 const DB_EDGES: &str = "edges"; // for edge data (e:)
 const DB_OUT_EDGES: &str = "out_edges"; // for outgoing edge indices (o:)
 const DB_IN_EDGES: &str = "in_edges"; // for incoming edge indices (i:)
+const DB_ALIASES: &str = "aliases"; // for node aliases (a:)
+const DB_COUNTS: &str = "counts"; // for node and edge counters
+const DB_UNIQUE_CONSTRAINTS: &str = "unique_constraints"; // for unique constraints on node properties
+const DB_UNIQUE_VALUES: &str = "unique_values"; // for the values held under unique constraints
+const DB_RANGE_INDICES: &str = "range_indices"; // for range indices on numeric node properties
+const DB_RANGE_VALUES: &str = "range_values"; // for the ordered entries of range indices
+const DB_TEXT_INDICES: &str = "text_indices"; // for text indices on string node properties
+const DB_TEXT_POSTINGS: &str = "text_postings"; // for the token postings of text indices
+const DB_SCHEMAS: &str = "schemas"; // for the registered property schemas of labels
+const DB_EXPIRIES: &str = "expiries"; // for when nodes inserted with a TTL expire
+const DB_NODE_EXPIRIES: &str = "node_expiries"; // for when each node inserted with a TTL expires
+
+// name of the single LMDB data file in a data or snapshot directory
+const DATA_FILE: &str = "data.mdb";
+
+// keys for the counters in the counts database
+const NODE_COUNT_KEY: &str = "nodes";
+const EDGE_COUNT_KEY: &str = "edges";
 
 pub type NodeId = u128;
 pub type EdgeId = u128;
 
+/// The graph's LMDB environment and the named databases in it.
+///
+/// Nodes, edges, each direction of adjacency and every kind of index are kept in databases
+/// of their own, so a scan over one never reads keys of another. Adjacency keys start with
+/// the node id, so a node's neighbours are a single prefix scan.
 pub struct HelixGraphStorage {
     // TODO: maybe make not public?
     pub graph_env: Env,
@@ -45,17 +78,44 @@ pub struct HelixGraphStorage {
     pub edges_db: Database<U128<BE>, Bytes>,
     pub out_edges_db: Database<Bytes, Bytes>,
     pub in_edges_db: Database<Bytes, Bytes>,
+    pub aliases_db: Database<Str, U128<BE>>,
+    pub counts_db: Database<Str, U64<BE>>,
+    pub unique_constraints_db: Database<Bytes, Unit>,
+    pub unique_values_db: Database<Bytes, U128<BE>>,
+    pub range_indices_db: Database<Bytes, Unit>,
+    pub range_values_db: Database<Bytes, Unit>,
+    pub text_indices_db: Database<Bytes, Unit>,
+    pub text_postings_db: Database<Bytes, Unit>,
+    pub schemas_db: Database<Str, Bytes>,
+    pub expiries_db: Database<Bytes, Str>,
+    pub node_expiries_db: Database<U128<BE>, U64<BE>>,
     pub secondary_indices: HashMap<String, Database<Bytes, U128<BE>>>,
     pub vectors: VectorCore,
     pub bm25: HBM25Config,
     pub schema: String,
     pub graphvis_node_label: Option<String>,
     pub embedding_model: Option<String>,
+    pub oplog: Option<OpLog>,
+    pub changes: ChangeFeed,
+    // labels whose `updated_at` range index is known to be committed
+    pub(crate) timestamp_indexed: RwLock<HashSet<String>>,
+    // held by each write txn from `write_txn` until it has ended, see `WriteTxn`
+    pub(crate) writer: Mutex<()>,
+    pub compression: Compression,
+    pub read_only: bool,
+    pub max_traversal_depth: Option<usize>,
+    pub clamp_traversal_depth: bool,
+    pub max_nodes: Option<u64>,
+    pub max_edges: Option<u64>,
 }
 
 impl HelixGraphStorage {
     pub fn new(path: &str, config: Config) -> Result<HelixGraphStorage, GraphError> {
-        fs::create_dir_all(path)?;
+        let storage_config = config.storage_config;
+        let read_only = storage_config.read_only;
+        if !read_only {
+            fs::create_dir_all(path)?;
+        }
 
         let db_size = if config.db_max_size_gb.unwrap_or(100) >= 9999 {
             9998
@@ -63,15 +123,32 @@ impl HelixGraphStorage {
             config.db_max_size_gb.unwrap_or(100)
         };
 
+        let mut env_flags = EnvFlags::empty();
+        if storage_config.no_sync {
+            env_flags |= EnvFlags::NO_SYNC;
+        }
+        if storage_config.no_read_ahead {
+            env_flags |= EnvFlags::NO_READ_AHEAD;
+        }
+        if read_only {
+            env_flags |= EnvFlags::READ_ONLY;
+        }
+
         let graph_env = unsafe {
             EnvOpenOptions::new()
                 .map_size(db_size * 1024 * 1024 * 1024) // Sets max size of the database in GB
-                .max_dbs(20) // Sets max number of databases
-                .max_readers(200) // Sets max number of readers
+                .max_dbs(40) // Sets max number of databases, leaving room for secondary indices
+                .max_readers(storage_config.max_readers) // Sets max number of readers
+                // NO_SYNC only gives up durability of the latest commits, which the config opts into
+                .flags(env_flags)
                 .open(Path::new(path))?
         };
 
-        let mut wtxn = graph_env.write_txn()?;
+        // a read-only environment can't write, so its databases are only opened
+        let mut txn = match read_only {
+            true => SetupTxn::ReadOnly(graph_env.read_txn()?),
+            false => SetupTxn::Write(graph_env.write_txn()?),
+        };
 
         // creates the lmdb databases (tables)
         // Table: [key]->[value]
@@ -83,7 +160,7 @@ impl HelixGraphStorage {
             .database_options()
             .types::<U128<BE>, Bytes>()
             .name(DB_NODES)
-            .create(&mut wtxn)?;
+            .open_or_create(&mut txn)?;
 
         // Edges: [edge_id]->[bytes array of edge data]
         //        [16 bytes]->[dynamic]
@@ -91,7 +168,7 @@ impl HelixGraphStorage {
             .database_options()
             .types::<U128<BE>, Bytes>()
             .name(DB_EDGES)
-            .create(&mut wtxn)?;
+            .open_or_create(&mut txn)?;
 
         // Out edges: [from_node_id + label]->[edge_id + to_node_id]  (edge first because value is ordered by byte size)
         //                    [20 + 4 bytes]->[16 + 16 bytes]
@@ -103,7 +180,7 @@ impl HelixGraphStorage {
             .types::<Bytes, Bytes>()
             .flags(DatabaseFlags::DUP_SORT | DatabaseFlags::DUP_FIXED)
             .name(DB_OUT_EDGES)
-            .create(&mut wtxn)?;
+            .open_or_create(&mut txn)?;
 
         // In edges: [to_node_id + label]->[edge_id + from_node_id]  (edge first because value is ordered by byte size)
         //                 [20 + 4 bytes]->[16 + 16 bytes]
@@ -115,10 +192,135 @@ impl HelixGraphStorage {
             .types::<Bytes, Bytes>()
             .flags(DatabaseFlags::DUP_SORT | DatabaseFlags::DUP_FIXED)
             .name(DB_IN_EDGES)
-            .create(&mut wtxn)?;
+            .open_or_create(&mut txn)?;
+
+        // Aliases: [alias name]->[node_id]
+        //          [dynamic]->[16 bytes]
+        //
+        // Repointing an alias is a single put inside a write txn so readers either
+        // see the old target or the new one, never a missing alias.
+        let aliases_db: Database<Str, U128<BE>> = graph_env
+            .database_options()
+            .types::<Str, U128<BE>>()
+            .name(DB_ALIASES)
+            .open_or_create(&mut txn)?;
+
+        // Counts: [counter name]->[count]
+        //         [dynamic]->[8 bytes]
+        //
+        // Kept up to date on every insert and delete so counting is a single read.
+        let counts_db: Database<Str, U64<BE>> = graph_env
+            .database_options()
+            .types::<Str, U64<BE>>()
+            .name(DB_COUNTS)
+            .open_or_create(&mut txn)?;
+
+        // Unique constraints: [label + property]->[]
+        //                     [dynamic]->[0 bytes]
+        let unique_constraints_db: Database<Bytes, Unit> = graph_env
+            .database_options()
+            .types::<Bytes, Unit>()
+            .name(DB_UNIQUE_CONSTRAINTS)
+            .open_or_create(&mut txn)?;
+
+        // Unique values: [label + property + value]->[node_id]
+        //                [dynamic]->[16 bytes]
+        //
+        // Holds the one node allowed to have each value under a unique constraint.
+        let unique_values_db: Database<Bytes, U128<BE>> = graph_env
+            .database_options()
+            .types::<Bytes, U128<BE>>()
+            .name(DB_UNIQUE_VALUES)
+            .open_or_create(&mut txn)?;
+
+        // Range indices: [label + property]->[]
+        //                [dynamic]->[0 bytes]
+        let range_indices_db: Database<Bytes, Unit> = graph_env
+            .database_options()
+            .types::<Bytes, Unit>()
+            .name(DB_RANGE_INDICES)
+            .open_or_create(&mut txn)?;
+
+        // Range values: [label + property + sortable value + node_id]->[]
+        //               [dynamic + 8 + 16 bytes]->[0 bytes]
+        //
+        // The value is encoded so keys sort in numeric order, making a range query one bounded scan.
+        let range_values_db: Database<Bytes, Unit> = graph_env
+            .database_options()
+            .types::<Bytes, Unit>()
+            .name(DB_RANGE_VALUES)
+            .open_or_create(&mut txn)?;
+
+        // Text indices: [label + property]->[]
+        //               [dynamic]->[0 bytes]
+        let text_indices_db: Database<Bytes, Unit> = graph_env
+            .database_options()
+            .types::<Bytes, Unit>()
+            .name(DB_TEXT_INDICES)
+            .open_or_create(&mut txn)?;
+
+        // Text postings: [label + property + token + node_id]->[]
+        //                [dynamic + 16 bytes]->[0 bytes]
+        //
+        // All postings of a token share a prefix, so looking one up is a single prefix scan.
+        let text_postings_db: Database<Bytes, Unit> = graph_env
+            .database_options()
+            .types::<Bytes, Unit>()
+            .name(DB_TEXT_POSTINGS)
+            .open_or_create(&mut txn)?;
+
+        // Schemas: [label]->[bincode of the label's fields]
+        //          [dynamic]->[dynamic]
+        let schemas_db: Database<Str, Bytes> = graph_env
+            .database_options()
+            .types::<Str, Bytes>()
+            .name(DB_SCHEMAS)
+            .open_or_create(&mut txn)?;
+
+        // Expiries: [expires_at + node_id]->[label]
+        //           [8 + 16 bytes]->[dynamic]
+        //
+        // Keys sort by expiry, so the expired nodes are always a single scan from the start.
+        let expiries_db: Database<Bytes, Str> = graph_env
+            .database_options()
+            .types::<Bytes, Str>()
+            .name(DB_EXPIRIES)
+            .open_or_create(&mut txn)?;
+
+        // Node expiries: [node_id]->[expires_at]
+        //                [16 bytes]->[8 bytes]
+        let node_expiries_db: Database<U128<BE>, U64<BE>> = graph_env
+            .database_options()
+            .types::<U128<BE>, U64<BE>>()
+            .name(DB_NODE_EXPIRIES)
+            .open_or_create(&mut txn)?;
+
+        // Backfills the counters for databases created before they existed
+        if let SetupTxn::Write(wtxn) = &mut txn
+            && counts_db.is_empty(wtxn)?
+            && !(nodes_db.is_empty(wtxn)? && edges_db.is_empty(wtxn)?)
+        {
+            let mut label_counts: HashMap<String, u64> = HashMap::new();
+            for result in nodes_db.iter(wtxn)? {
+                let (id, bytes) = result?;
+                let node = Node::decode_node(bytes, id)?;
+                *label_counts.entry(node.label).or_insert(0) += 1;
+            }
+            for (label, count) in label_counts {
+                counts_db.put(wtxn, &Self::node_label_count_key(&label), &count)?;
+            }
+            let node_count = nodes_db.len(wtxn)?;
+            let edge_count = edges_db.len(wtxn)?;
+            counts_db.put(wtxn, NODE_COUNT_KEY, &node_count)?;
+            counts_db.put(wtxn, EDGE_COUNT_KEY, &edge_count)?;
+        }
 
         // Creates the secondary indices databases if there are any
         let mut secondary_indices = HashMap::new();
+        let max_traversal_depth = config.graph_config.max_traversal_depth;
+        let clamp_traversal_depth = config.graph_config.clamp_traversal_depth;
+        let max_nodes = config.graph_config.max_nodes;
+        let max_edges = config.graph_config.max_edges;
         if let Some(indexes) = config.graph_config.secondary_indices {
             for index in indexes {
                 secondary_indices.insert(
@@ -128,7 +330,7 @@ impl HelixGraphStorage {
                         .types::<Bytes, U128<BE>>()
                         .flags(DatabaseFlags::DUP_SORT) // DUP_SORT used to store all duplicated node keys under a single key. Saves on space and requires a single read to get all values.
                         .name(&index)
-                        .create(&mut wtxn)?,
+                        .open_or_create(&mut txn)?,
                 );
             }
         }
@@ -136,7 +338,7 @@ impl HelixGraphStorage {
         // Creates the vector database
         let vectors = VectorCore::new(
             &graph_env,
-            &mut wtxn,
+            &mut txn,
             HNSWConfig::new(
                 config.vector_config.m,
                 config.vector_config.ef_construction,
@@ -144,27 +346,94 @@ impl HelixGraphStorage {
             ),
         )?;
 
-        let bm25 = HBM25Config::new(&graph_env, &mut wtxn)?;
+        let bm25 = HBM25Config::new(&graph_env, &mut txn)?;
         let schema = config.schema.unwrap_or("".to_string());
         let graphvis_node_label = config.graphvis_node_label;
         let embedding_model = config.embedding_model;
+        // nothing is written to a read-only storage, so there is nothing to log
+        let oplog = match config.oplog_path {
+            Some(path) if !read_only => Some(OpLog::open(&path)?),
+            _ => None,
+        };
 
-        wtxn.commit()?;
+        txn.commit()?;
         Ok(Self {
             graph_env,
             nodes_db,
             edges_db,
             out_edges_db,
             in_edges_db,
+            aliases_db,
+            counts_db,
+            unique_constraints_db,
+            unique_values_db,
+            range_indices_db,
+            range_values_db,
+            text_indices_db,
+            text_postings_db,
+            schemas_db,
+            expiries_db,
+            node_expiries_db,
             secondary_indices,
             vectors,
             bm25,
             schema,
             graphvis_node_label,
             embedding_model,
+            oplog,
+            changes: ChangeFeed::default(),
+            timestamp_indexed: RwLock::new(HashSet::new()),
+            writer: Mutex::new(()),
+            compression: storage_config.compression,
+            read_only,
+            max_traversal_depth,
+            clamp_traversal_depth,
+            max_nodes,
+            max_edges,
         })
     }
 
+    /// Resolves how many hops a traversal may take given the depth its caller asked for.
+    ///
+    /// Without a caller depth the global `max_traversal_depth` applies.
+    /// A caller depth over the global cap is clamped to it if `clamp_traversal_depth` is set
+    /// and otherwise rejected with `GraphError::TraversalError`.
+    /// Returns `None` if the traversal is unlimited.
+    pub fn traversal_depth(&self, requested: Option<usize>) -> Result<Option<usize>, GraphError> {
+        match (requested, self.max_traversal_depth) {
+            (Some(depth), Some(max)) if depth > max => match self.clamp_traversal_depth {
+                true => Ok(Some(max)),
+                false => Err(GraphError::TraversalError(format!(
+                    "Traversal depth {} exceeds the maximum of {}",
+                    depth, max
+                ))),
+            },
+            (Some(depth), _) => Ok(Some(depth)),
+            (None, max) => Ok(max),
+        }
+    }
+
+    /// Checks a node's unique values, then moves its unique, range and text index entries
+    /// from `old` to `new`, and drops its expiry along with it.
+    ///
+    /// `old` is the node as currently stored, or `None` if it is being added,
+    /// and `new` is the node about to be stored, or `None` if it is being dropped.
+    #[inline]
+    pub fn update_node_indices(
+        &self,
+        txn: &mut RwTxn,
+        old: Option<&Node>,
+        new: Option<&Node>,
+    ) -> Result<(), GraphError> {
+        self.update_unique_values(txn, old, new)?;
+        self.update_range_values(txn, old, new)?;
+        self.update_text_postings(txn, old, new)?;
+        match (old, new) {
+            (Some(old), None) => self.clear_expiry(txn, old.id),
+            _ => Ok(()),
+        }
+    }
+
     /// Used because in the case the key changes in the future.
     /// Believed to not introduce any overhead being inline and using a reference.
     #[must_use]
@@ -181,6 +450,140 @@ impl HelixGraphStorage {
         id
     }
 
+    /// Writes a point-in-time snapshot of the whole graph to the directory at `path`.
+    ///
+    /// The copy is taken inside a read txn so it is consistent even while writes continue,
+    /// and writes are not blocked while it runs.
+    ///
+    /// The snapshot is a directory holding a single compacted `data.mdb`, which is the same
+    /// LMDB file a live data directory uses (there is no lock file, it is recreated on open).
+    /// Free pages are dropped, so the file can be smaller than the live one. Like any LMDB file
+    /// it can be moved between machines with the same endianness and page size.
+    pub fn snapshot(&self, path: &str) -> Result<(), GraphError> {
+        fs::create_dir_all(path)?;
+        let file = Path::new(path).join(DATA_FILE);
+        if file.exists() {
+            return Err(GraphError::New(format!(
+                "Snapshot already exists at {}",
+                file.display()
+            )));
+        }
+        self.graph_env.copy_to_path(&file, CompactionOption::Enabled)?;
+        Ok(())
+    }
+
+    /// Restores a snapshot written by [`HelixGraphStorage::snapshot`] into the data directory
+    /// at `target_path`, which can then be opened as normal.
+    ///
+    /// The target must not already hold a graph.
+    pub fn restore(backup_path: &str, target_path: &str) -> Result<(), GraphError> {
+        let backup = Path::new(backup_path).join(DATA_FILE);
+        if !backup.is_file() {
+            return Err(GraphError::New(format!(
+                "No snapshot found at {}",
+                backup.display()
+            )));
+        }
+        fs::create_dir_all(target_path)?;
+        let target = Path::new(target_path).join(DATA_FILE);
+        if target.exists() {
+            return Err(GraphError::New(format!(
+                "A graph already exists at {}",
+                target.display()
+            )));
+        }
+        fs::copy(&backup, &target)?;
+        Ok(())
+    }
+
+    /// Key of the counter for nodes with the given label.
+    #[inline(always)]
+    pub fn node_label_count_key(label: &str) -> String {
+        format!("{}:{}", NODE_COUNT_KEY, label)
+    }
+
+    /// Adds `delta` to the counter stored under `key`, saturating at zero.
+    ///
+    /// The read and the write are in the caller's write txn, and LMDB only lets one write txn
+    /// run at a time, so concurrent inserts and deletes can't lose each other's updates the
+    /// way a read-modify-write across separate transactions would.
+    #[inline]
+    fn adjust_count(&self, txn: &mut RwTxn, key: &str, delta: i64) -> Result<(), GraphError> {
+        let current = self.counts_db.get(txn, key)?.unwrap_or(0);
+        self.counts_db
+            .put(txn, key, &current.saturating_add_signed(delta))?;
+        Ok(())
+    }
+
+    /// The adjacency tables to follow in `direction` and the key prefixes to scan in them
+    /// for edges of a node with any of `labels`, or of every label if it is empty
+    fn adjacency_prefixes(
+        &self,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+    ) -> (Vec<&Database<Bytes, Bytes>>, Vec<Vec<u8>>) {
+        let dbs = match direction {
+            Direction::Out => vec![&self.out_edges_db],
+            Direction::In => vec![&self.in_edges_db],
+            Direction::Both => vec![&self.out_edges_db, &self.in_edges_db],
+        };
+        // both adjacency keys are the node id followed by the label hash
+        let prefixes = match labels.is_empty() {
+            true => vec![id.to_be_bytes().to_vec()],
+            false => labels
+                .iter()
+                .map(|label| Self::out_edge_key(id, &hash_label(label, None)).to_vec())
+                .collect(),
+        };
+        (dbs, prefixes)
+    }
+
+    /// Gets the distinct neighbors of a node for which `predicate` holds, reading each one as
+    /// its edge is followed and stopping once `limit` have matched.
+    ///
+    /// An empty `labels` slice follows edges of every label.
+    pub fn neighbors_where(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+        predicate: &Predicate,
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError> {
+        self.get_node(txn, id)?;
+
+        let (dbs, prefixes) = self.adjacency_prefixes(id, direction, labels);
+        let limit = limit.unwrap_or(usize::MAX);
+        let now = now_millis();
+
+        let mut seen = HashSet::new();
+        let mut matched = Vec::new();
+        for db in dbs {
+            for prefix in prefixes.iter() {
+                for result in db.prefix_iter(txn, prefix)? {
+                    if matched.len() >= limit {
+                        return Ok(matched);
+                    }
+                    let (_, value) = result?;
+                    let (_, node_id) = Self::unpack_adj_edge_data(value)?;
+                    if !seen.insert(node_id) || self.is_expired(txn, node_id, now)? {
+                        continue;
+                    }
+                    let Some(data) = self.nodes_db.get(txn, Self::node_key(&node_id))? else {
+                        continue;
+                    };
+                    let node = Node::decode_node(data, node_id)?;
+                    if predicate.matches(&node)? {
+                        matched.push(node);
+                    }
+                }
+            }
+        }
+        Ok(matched)
+    }
+
     /// Out edge key generator. Creates a 20 byte array and copies in the node id and 4 byte label.
     ///
     /// key = `from-node(16)` | `label-id(4)`                 ← 20 B
@@ -252,16 +655,16 @@ impl HelixGraphStorage {
 impl DBMethods for HelixGraphStorage {
     // Creates a secondary index lmdb db (table) for a given index name
     fn create_secondary_index(&mut self, name: &str) -> Result<(), GraphError> {
-        let mut wtxn = self.graph_env.write_txn()?;
+        let mut wtxn = self.write_txn()?;
         let db = self.graph_env.create_database(&mut wtxn, Some(name))?;
-        wtxn.commit()?;
+        self.commit(wtxn)?;
         self.secondary_indices.insert(name.to_string(), db);
         Ok(())
     }
 
     // Drops a secondary index lmdb db (table) for a given index name
     fn drop_secondary_index(&mut self, name: &str) -> Result<(), GraphError> {
-        let mut wtxn = self.graph_env.write_txn()?;
+        let mut wtxn = self.write_txn()?;
         let db = self
             .secondary_indices
             .get(name)
@@ -270,7 +673,7 @@ impl DBMethods for HelixGraphStorage {
                 name
             )))?;
         db.clear(&mut wtxn)?;
-        wtxn.commit()?;
+        self.commit(wtxn)?;
         self.secondary_indices.remove(name);
         Ok(())
     }
@@ -293,6 +696,10 @@ impl StorageMethods for HelixGraphStorage {
             Ok(node) => node,
             Err(e) => return Err(e),
         };
+        // expired nodes are absent until they are purged
+        if self.is_expired(txn, *id, now_millis())? {
+            return Err(GraphError::NodeNotFound);
+        }
         Ok(node)
     }
 
@@ -306,70 +713,191 @@ impl StorageMethods for HelixGraphStorage {
             Ok(edge) => edge,
             Err(e) => return Err(e),
         };
+        if self.is_edge_expired(txn, &edge, now_millis())? {
+            return Err(GraphError::EdgeNotFound);
+        }
         Ok(edge)
     }
 
-    fn drop_node(&self, txn: &mut RwTxn, id: &u128) -> Result<(), GraphError> {
-        // Get node to get its label
-        //let node = self.get_node(txn, id)?;
-
-        // Delete outgoing edges
-        let out_edges = {
-            let iter = self.out_edges_db.prefix_iter(&txn, &id.to_be_bytes())?;
-            let capacity = match iter.size_hint() {
-                (_, Some(upper)) => upper,
-                (lower, None) => lower,
-            };
-            let mut out_edges = Vec::with_capacity(capacity);
+    fn get_nodes(&self, txn: &RoTxn, ids: &[u128]) -> Result<Vec<Node>, GraphError> {
+        let mut sorted = ids.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
 
-            for result in iter {
-                let (key, value) = result?;
-                assert_eq!(key.len(), 20);
-                let mut label = [0u8; 4];
-                label.copy_from_slice(&key[16..20]);
-                let (edge_id, _) = Self::unpack_adj_edge_data(&value)?;
-                out_edges.push((edge_id, label));
+        let expired = self.expired_ids(txn, now_millis())?;
+        let mut nodes = HashMap::with_capacity(sorted.len());
+        for id in sorted {
+            if expired.contains(&id) {
+                continue;
             }
-            out_edges
-        };
+            if let Some(data) = self.nodes_db.get(txn, Self::node_key(&id))? {
+                nodes.insert(id, Node::decode_node(data, id)?);
+            }
+        }
 
-        // Delete incoming edges
+        Ok(ids.iter().filter_map(|id| nodes.get(id).cloned()).collect())
+    }
 
-        let in_edges = {
-            let iter = self.in_edges_db.prefix_iter(&txn, &id.to_be_bytes())?;
-            let capacity = match iter.size_hint() {
-                (_, Some(c)) => c,
-                (c, None) => c,
-            };
-            let mut in_edges = Vec::with_capacity(capacity);
+    fn neighbors_with_props(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError> {
+        self.get_node(txn, id)?;
 
-            for result in iter {
-                let (key, value) = result?;
-                assert_eq!(key.len(), 20);
-                let mut label = [0u8; 4];
-                label.copy_from_slice(&key[16..20]);
-                let (edge_id, node_id) = Self::unpack_adj_edge_data(&value)?;
-                in_edges.push((edge_id, label, node_id));
+        let (dbs, prefixes) = self.adjacency_prefixes(id, direction, labels);
+        let limit = limit.unwrap_or(usize::MAX);
+
+        let mut seen = HashSet::new();
+        let mut neighbor_ids = Vec::new();
+        'gather: for db in dbs {
+            for prefix in prefixes.iter() {
+                for result in db.prefix_iter(txn, prefix)? {
+                    if neighbor_ids.len() >= limit {
+                        break 'gather;
+                    }
+                    let (_, value) = result?;
+                    let (_, node_id) = Self::unpack_adj_edge_data(value)?;
+                    if seen.insert(node_id) {
+                        neighbor_ids.push(node_id);
+                    }
+                }
+            }
+        }
+
+        self.get_nodes(txn, &neighbor_ids)
+    }
+
+    fn neighbors_with_edges(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<(Edge, Node)>, GraphError> {
+        self.get_node(txn, id)?;
+
+        let (dbs, prefixes) = self.adjacency_prefixes(id, direction, labels);
+        let limit = limit.unwrap_or(usize::MAX);
+
+        // an undirected edge is found under both tables, but is only returned once
+        let mut seen = HashSet::new();
+        let mut adjacent = Vec::new();
+        'gather: for db in dbs {
+            for prefix in prefixes.iter() {
+                for result in db.prefix_iter(txn, prefix)? {
+                    if adjacent.len() >= limit {
+                        break 'gather;
+                    }
+                    let (_, value) = result?;
+                    let (edge_id, node_id) = Self::unpack_adj_edge_data(value)?;
+                    if seen.insert(edge_id) {
+                        adjacent.push((edge_id, node_id));
+                    }
+                }
+            }
+        }
+
+        let node_ids: Vec<u128> = adjacent.iter().map(|(_, node_id)| *node_id).collect();
+        let nodes: HashMap<u128, Node> = self
+            .get_nodes(txn, &node_ids)?
+            .into_iter()
+            .map(|node| (node.id, node))
+            .collect();
+        let mut neighbors = Vec::with_capacity(adjacent.len());
+        for (edge_id, node_id) in adjacent {
+            // edges to expired nodes are skipped along with them
+            if let Some(node) = nodes.get(&node_id) {
+                neighbors.push((self.get_edge(txn, &edge_id)?, node.clone()));
             }
+        }
+        Ok(neighbors)
+    }
+
+    fn degree(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+    ) -> Result<u64, GraphError> {
+        self.get_node(txn, id)?;
 
-            in_edges
+        let (dbs, prefixes) = self.adjacency_prefixes(id, direction, labels);
+        // a node's edges are only indexed under it once in each table, but both tables hold
+        // an undirected edge or a self loop, so following both ways has to tell them apart
+        let mut seen = HashSet::new();
+        let mut degree = 0;
+        for db in dbs {
+            for prefix in prefixes.iter() {
+                for result in db.prefix_iter(txn, prefix)? {
+                    let (_, value) = result?;
+                    if direction != Direction::Both
+                        || seen.insert(Self::unpack_adj_edge_data(value)?.0)
+                    {
+                        degree += 1;
+                    }
+                }
+            }
+        }
+        Ok(degree)
+    }
+
+    fn drop_node(&self, txn: &mut RwTxn, id: &u128) -> Result<(), GraphError> {
+        // Get node to get its label and unique values
+        let node = match self.nodes_db.get(txn, Self::node_key(id))? {
+            Some(data) => Some(Node::decode_node(data, *id)?),
+            None => None,
         };
+        self.update_node_indices(txn, node.as_ref(), None)?;
 
-        // Delete all related data
-        for (out_edge_id, label_bytes) in out_edges.iter() {
-            // Delete edge data
-            self.edges_db.delete(txn, &Self::edge_key(out_edge_id))?;
-            self.out_edges_db
-                .delete(txn, &Self::out_edge_key(id, label_bytes))?;
+        // Gather the node's edges, each once even if it shows up as both an in and out edge
+        let mut edge_ids = Vec::new();
+        for db in [&self.out_edges_db, &self.in_edges_db] {
+            for result in db.prefix_iter(txn, &id.to_be_bytes())? {
+                let (key, value) = result?;
+                assert_eq!(key.len(), 20);
+                let (edge_id, _) = Self::unpack_adj_edge_data(value)?;
+                edge_ids.push(edge_id);
+            }
         }
-        for (in_edge_id, label_bytes, other_id) in in_edges.iter() {
-            self.edges_db.delete(txn, &Self::edge_key(in_edge_id))?;
-            self.in_edges_db
-                .delete(txn, &Self::in_edge_key(other_id, label_bytes))?;
+        edge_ids.sort_unstable();
+        edge_ids.dedup();
+
+        // Delete each edge along with its entries under the node at the other end,
+        // which for an undirected edge are both an in and an out entry
+        for edge_id in edge_ids.iter() {
+            let edge = match self.edges_db.get(txn, Self::edge_key(edge_id))? {
+                Some(data) => Edge::decode_edge(data, *edge_id)?,
+                None => continue,
+            };
+            self.edges_db.delete(txn, Self::edge_key(edge_id))?;
+            self.record_edge_removed(txn)?;
+            self.delete_adjacency(txn, &edge)?;
+            // not logged, as the node's drop is replayed along with its edges
+            self.record_change(|| ChangeEvent::edge(ChangeKind::Delete, &edge));
+        }
+        // anything left under the node's own keys points at edges that are already gone
+        let (first, last) = (
+            Self::out_edge_key(id, &[0; 4]),
+            Self::out_edge_key(id, &[u8::MAX; 4]),
+        );
+        let own_keys = (Bound::Included(&first[..]), Bound::Included(&last[..]));
+        for db in [&self.out_edges_db, &self.in_edges_db] {
+            db.delete_range(txn, &own_keys)?;
         }
 
         // Delete node data and label
         self.nodes_db.delete(txn, Self::node_key(id))?;
+        if let Some(node) = node {
+            self.record_node_removed(txn, &node.label)?;
+            self.log_operation(|| Operation::DropNode { id: *id })?;
+            self.record_change(|| ChangeEvent::node(ChangeKind::Delete, &node));
+        }
 
         Ok(())
     }
@@ -380,15 +908,188 @@ impl StorageMethods for HelixGraphStorage {
             Some(data) => data,
             None => return Err(GraphError::EdgeNotFound),
         };
-        let edge: Edge = bincode::deserialize(edge_data)?;
-        let label_hash = hash_label(&edge.label, None);
+        let edge = Edge::decode_edge(edge_data, *edge_id)?;
         // Delete all edge-related data
-        self.edges_db.delete(txn, &Self::edge_key(edge_id))?;
-        self.out_edges_db
-            .delete(txn, &Self::out_edge_key(&edge.from_node, &label_hash))?;
-        self.in_edges_db
-            .delete(txn, &Self::in_edge_key(&edge.to_node, &label_hash))?;
+        if self.edges_db.delete(txn, Self::edge_key(edge_id))? {
+            self.record_edge_removed(txn)?;
+            self.log_operation(|| Operation::DropEdge { id: *edge_id })?;
+            self.record_change(|| ChangeEvent::edge(ChangeKind::Delete, &edge));
+        }
+        self.delete_adjacency(txn, &edge)?;
+
+        Ok(())
+    }
 
+    fn update_node_properties(
+        &self,
+        txn: &mut RwTxn,
+        id: &u128,
+        patch: HashMap<String, Value>,
+    ) -> Result<Node, GraphError> {
+        let mut node = self.get_node(txn, id)?;
+        let previous = node.clone();
+        let mut properties = node.properties.take().unwrap_or_default();
+
+        for (key, value) in patch {
+            let old = match value {
+                Value::Empty => properties.remove(&key),
+                value => properties.insert(key.clone(), value),
+            };
+
+            // keep secondary indices pointing at the current value only
+            if let Some(db) = self.secondary_indices.get(&key) {
+                if let Some(old) = old {
+                    db.delete_one_duplicate(txn, &bincode::serialize(&old)?, id)?;
+                }
+                if let Some(new) = properties.get(&key) {
+                    db.put(txn, &bincode::serialize(new)?, id)?;
+                }
+            }
+        }
+
+        node.properties = match properties.is_empty() {
+            true => None,
+            false => Some(properties),
+        };
+        self.validate_schema(txn, &node.label, node.properties.as_ref())?;
+        self.update_node_indices(txn, Some(&previous), Some(&node))?;
+        self.nodes_db
+            .put(txn, Self::node_key(id), &self.encode_node(&node)?)?;
+
+        let mut data = node
+            .properties
+            .as_ref()
+            .map(|props| props.flatten_bm25())
+            .unwrap_or_default();
+        data.push_str(&node.label);
+        self.bm25.update_doc(txn, node.id, &data)?;
+
+        self.log_operation(|| Operation::UpdateNode {
+            id: *id,
+            properties: node.properties.clone(),
+        })?;
+        self.record_change(|| {
+            ChangeEvent::update(
+                ItemKind::Node,
+                node.id,
+                &node.label,
+                previous.properties.as_ref(),
+                node.properties.as_ref(),
+            )
+        });
+        Ok(node)
+    }
+}
+
+impl AliasMethods for HelixGraphStorage {
+    fn set_alias(
+        &self,
+        txn: &mut RwTxn,
+        name: &str,
+        node_id: &u128,
+    ) -> Result<Option<u128>, GraphError> {
+        // an alias must always resolve to a live node
+        if self.nodes_db.get(txn, Self::node_key(node_id))?.is_none() {
+            return Err(GraphError::NodeNotFound);
+        }
+        let previous = self.aliases_db.get(txn, name)?;
+        self.aliases_db.put(txn, name, node_id)?;
+        self.log_operation(|| Operation::SetAlias {
+            name: name.to_string(),
+            node_id: *node_id,
+        })?;
+        Ok(previous)
+    }
+
+    fn resolve_alias(&self, txn: &RoTxn, name: &str) -> Result<u128, GraphError> {
+        match self.aliases_db.get(txn, name)? {
+            Some(node_id) => Ok(node_id),
+            None => Err(GraphError::AliasNotFound),
+        }
+    }
+
+    fn swap_aliases(&self, txn: &mut RwTxn, first: &str, second: &str) -> Result<(), GraphError> {
+        let first_id = self.resolve_alias(txn, first)?;
+        let second_id = self.resolve_alias(txn, second)?;
+        self.aliases_db.put(txn, first, &second_id)?;
+        self.aliases_db.put(txn, second, &first_id)?;
+        self.log_operation(|| Operation::SetAlias {
+            name: first.to_string(),
+            node_id: second_id,
+        })?;
+        self.log_operation(|| Operation::SetAlias {
+            name: second.to_string(),
+            node_id: first_id,
+        })?;
+        Ok(())
+    }
+
+    fn drop_alias(&self, txn: &mut RwTxn, name: &str) -> Result<(), GraphError> {
+        match self.aliases_db.delete(txn, name)? {
+            true => self.log_operation(|| Operation::DropAlias {
+                name: name.to_string(),
+            }),
+            false => Err(GraphError::AliasNotFound),
+        }
+    }
+}
+
+impl CountMethods for HelixGraphStorage {
+    fn node_count(&self, txn: &RoTxn) -> Result<u64, GraphError> {
+        let count = self.counts_db.get(txn, NODE_COUNT_KEY)?.unwrap_or(0);
+        Ok(count.saturating_sub(self.expired_count(txn, None, now_millis())?))
+    }
+
+    fn node_count_by_label(&self, txn: &RoTxn, label: &str) -> Result<u64, GraphError> {
+        let count = self
+            .counts_db
+            .get(txn, &Self::node_label_count_key(label))?
+            .unwrap_or(0);
+        Ok(count.saturating_sub(self.expired_count(txn, Some(label), now_millis())?))
+    }
+
+    fn edge_count(&self, txn: &RoTxn) -> Result<u64, GraphError> {
+        let count = self.counts_db.get(txn, EDGE_COUNT_KEY)?.unwrap_or(0);
+        Ok(count.saturating_sub(self.expired_edge_count(txn, now_millis())?))
+    }
+
+    fn check_quota(&self, txn: &RoTxn, new_nodes: u64, new_edges: u64) -> Result<(), GraphError> {
+        if let Some(max_nodes) = self.max_nodes
+            && new_nodes > 0
+            && self.node_count(txn)?.saturating_add(new_nodes) > max_nodes
+        {
+            return Err(GraphError::QuotaExceeded(format!(
+                "graph is limited to {} nodes",
+                max_nodes
+            )));
+        }
+        if let Some(max_edges) = self.max_edges
+            && new_edges > 0
+            && self.edge_count(txn)?.saturating_add(new_edges) > max_edges
+        {
+            return Err(GraphError::QuotaExceeded(format!(
+                "graph is limited to {} edges",
+                max_edges
+            )));
+        }
         Ok(())
     }
+
+    fn record_node_added(&self, txn: &mut RwTxn, label: &str) -> Result<(), GraphError> {
+        self.adjust_count(txn, NODE_COUNT_KEY, 1)?;
+        self.adjust_count(txn, &Self::node_label_count_key(label), 1)
+    }
+
+    fn record_node_removed(&self, txn: &mut RwTxn, label: &str) -> Result<(), GraphError> {
+        self.adjust_count(txn, NODE_COUNT_KEY, -1)?;
+        self.adjust_count(txn, &Self::node_label_count_key(label), -1)
+    }
+
+    fn record_edge_added(&self, txn: &mut RwTxn) -> Result<(), GraphError> {
+        self.adjust_count(txn, EDGE_COUNT_KEY, 1)
+    }
+
+    fn record_edge_removed(&self, txn: &mut RwTxn) -> Result<(), GraphError> {
+        self.adjust_count(txn, EDGE_COUNT_KEY, -1)
+    }
 }
@@ -1,6 +1,8 @@
 use crate::helix_engine::types::GraphError;
+use crate::protocol::value::Value;
 use crate::utils::items::{Edge, Node};
 use heed3::{RoTxn, RwTxn};
+use std::collections::HashMap;
 
 pub trait DBMethods {
     /// Creates a new database with a given name for a secondary index
@@ -23,6 +25,14 @@ pub trait BasicStorageMethods {
     /// as underlying data is pinned.
     fn get_temp_edge<'a>(&self, txn: &'a RoTxn, id: &u128) -> Result<&'a [u8], GraphError>;
 }
+/// Which edges of a node to follow when expanding its neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
 pub trait StorageMethods {
     /// Checks whether an entry with a given id exists.
     /// Works for nodes or edges.
@@ -34,8 +44,69 @@ pub trait StorageMethods {
     /// Gets a edge object for a given edge id
     fn get_edge(&self, txn: &RoTxn, id: &u128) -> Result<Edge, GraphError>;
 
+    /// Gets the nodes for the given ids in one pass over the nodes table.
+    ///
+    /// Lookups are done in key order and the nodes are returned in the order of `ids`.
+    /// Ids without a node are skipped.
+    fn get_nodes(&self, txn: &RoTxn, ids: &[u128]) -> Result<Vec<Node>, GraphError>;
+
+    /// Gets the distinct neighbors of a node along edges with any of the given labels,
+    /// fully hydrated with their properties.
+    ///
+    /// An empty `labels` slice follows edges of every label.
+    /// The neighbor ids are gathered from the adjacency tables first and then fetched with
+    /// [`StorageMethods::get_nodes`] rather than one lookup per edge.
+    fn neighbors_with_props(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<Node>, GraphError>;
+
+    /// Gets the neighbors of a node like [`StorageMethods::neighbors_with_props`], each along
+    /// with the edge connecting it, so the edge's properties can be read or filtered on.
+    ///
+    /// A neighbor connected by more than one edge is returned once for each of them.
+    fn neighbors_with_edges(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+        limit: Option<usize>,
+    ) -> Result<Vec<(Edge, Node)>, GraphError>;
+
+    /// Counts the edges of a node from its adjacency entries, without reading the edges or the
+    /// nodes at their other ends.
+    ///
+    /// Each edge is counted once, like [`StorageMethods::neighbors_with_edges`] returns it,
+    /// but edges to expired nodes are counted until the nodes are dropped.
+    /// An empty `labels` slice counts edges of every label.
+    fn degree(
+        &self,
+        txn: &RoTxn,
+        id: &u128,
+        direction: Direction,
+        labels: &[&str],
+    ) -> Result<u64, GraphError>;
+
+    /// Drops a node along with every edge it is an end of, directed or undirected,
+    /// and the entries indexing those edges under the nodes at their other ends.
     fn drop_node(&self, txn: &mut RwTxn, id: &u128) -> Result<(), GraphError>;
     fn drop_edge(&self, txn: &mut RwTxn, id: &u128) -> Result<(), GraphError>;
+
+    /// Merges `patch` into the properties of an existing node and returns the updated node.
+    ///
+    /// A `Value::Empty` (null) in the patch removes that key.
+    /// Secondary indices and the bm25 index are kept in sync.
+    fn update_node_properties(
+        &self,
+        txn: &mut RwTxn,
+        id: &u128,
+        patch: HashMap<String, Value>,
+    ) -> Result<Node, GraphError>;
 }
 
 pub trait SearchMethods {
@@ -57,3 +128,50 @@ pub trait SearchMethods {
     ) -> Result<(Vec<Node>, Vec<Edge>), GraphError>;
 }
 
+pub trait AliasMethods {
+    /// Points an alias at a node, replacing whatever it pointed at before.
+    ///
+    /// Returns the previous target of the alias if there was one.
+    fn set_alias(
+        &self,
+        txn: &mut RwTxn,
+        name: &str,
+        node_id: &u128,
+    ) -> Result<Option<u128>, GraphError>;
+
+    /// Gets the node id an alias currently points at
+    fn resolve_alias(&self, txn: &RoTxn, name: &str) -> Result<u128, GraphError>;
+
+    /// Exchanges the targets of two existing aliases in the same transaction
+    fn swap_aliases(&self, txn: &mut RwTxn, first: &str, second: &str) -> Result<(), GraphError>;
+
+    /// Removes an alias, leaving the node it pointed at untouched
+    fn drop_alias(&self, txn: &mut RwTxn, name: &str) -> Result<(), GraphError>;
+}
+
+pub trait CountMethods {
+    /// Gets the number of nodes in the graph
+    fn node_count(&self, txn: &RoTxn) -> Result<u64, GraphError>;
+
+    /// Gets the number of nodes with the given label
+    fn node_count_by_label(&self, txn: &RoTxn, label: &str) -> Result<u64, GraphError>;
+
+    /// Gets the number of edges in the graph
+    fn edge_count(&self, txn: &RoTxn) -> Result<u64, GraphError>;
+
+    /// Checks that adding `new_nodes` nodes and `new_edges` edges keeps the graph within
+    /// its `max_nodes` and `max_edges`, returning `GraphError::QuotaExceeded` if it wouldn't
+    fn check_quota(&self, txn: &RoTxn, new_nodes: u64, new_edges: u64) -> Result<(), GraphError>;
+
+    /// Increments the total and per-label node counters
+    fn record_node_added(&self, txn: &mut RwTxn, label: &str) -> Result<(), GraphError>;
+
+    /// Decrements the total and per-label node counters
+    fn record_node_removed(&self, txn: &mut RwTxn, label: &str) -> Result<(), GraphError>;
+
+    /// Increments the edge counter
+    fn record_edge_added(&self, txn: &mut RwTxn) -> Result<(), GraphError>;
+
+    /// Decrements the edge counter
+    fn record_edge_removed(&self, txn: &mut RwTxn) -> Result<(), GraphError>;
+}
@@ -0,0 +1,120 @@
+use crate::{
+    helix_engine::{
+        storage_core::{
+            storage_core::HelixGraphStorage,
+            storage_methods::{Direction, StorageMethods},
+        },
+        types::GraphError,
+    },
+    protocol::return_values::ReturnValue,
+    utils::{
+        filterable::Filterable,
+        items::{Edge, Node},
+    },
+};
+use heed3::RoTxn;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::collections::HashSet;
+
+/// The nodes within some hops of a set of seeds and the edges between them.
+///
+/// Serializes to JSON as `{"nodes": [...], "edges": [...], "truncated": false}`, with each node
+/// and edge written with its `id`, `label` and properties the way query results are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subgraph {
+    /// Each node once, in the order they were reached, starting with the seeds
+    pub nodes: Vec<Node>,
+    /// Each edge between two of the nodes once
+    pub edges: Vec<Edge>,
+    /// Whether nodes were left out to keep to the `max_nodes` the subgraph was extracted with
+    pub truncated: bool,
+}
+
+/// Serializes nodes or edges the way [`ReturnValue`] writes them, with their ids
+struct Items<'a, T>(&'a [T]);
+
+impl<T: Filterable + Clone> Serialize for Items<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|item| ReturnValue::from(item.clone())))
+    }
+}
+
+impl Serialize for Subgraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Subgraph", 3)?;
+        state.serialize_field("nodes", &Items(&self.nodes))?;
+        state.serialize_field("edges", &Items(&self.edges))?;
+        state.serialize_field("truncated", &self.truncated)?;
+        state.end()
+    }
+}
+
+impl HelixGraphStorage {
+    /// Extracts the nodes up to `radius` hops from any of `seeds`, following edges either way,
+    /// along with every edge between them.
+    ///
+    /// Only edges with one of `labels` are followed and returned, or edges of every label if it's
+    /// empty. Once there are `max_nodes` nodes no more are added and the subgraph is marked as
+    /// truncated, but edges between the nodes already in it are still returned.
+    ///
+    /// A `radius` over the graph's `max_traversal_depth` is clamped to it or returns a
+    /// `GraphError::TraversalError`, depending on `clamp_traversal_depth`.
+    /// Fails with `GraphError::NodeNotFound` if a seed doesn't exist.
+    pub fn extract_subgraph(
+        &self,
+        txn: &RoTxn,
+        seeds: &[u128],
+        radius: usize,
+        labels: &[&str],
+        max_nodes: Option<usize>,
+    ) -> Result<Subgraph, GraphError> {
+        let radius = self.traversal_depth(Some(radius))?.unwrap_or(radius);
+        let max_nodes = max_nodes.unwrap_or(usize::MAX);
+        let mut subgraph = Subgraph::default();
+        let mut included = HashSet::new();
+        let mut edges = HashSet::new();
+
+        for seed in seeds {
+            let node = self.get_node(txn, seed)?;
+            if included.contains(seed) {
+                continue;
+            }
+            if included.len() >= max_nodes {
+                subgraph.truncated = true;
+                continue;
+            }
+            included.insert(node.id);
+            subgraph.nodes.push(node);
+        }
+
+        // nodes are visited in the order they were reached, and no node is added once those
+        // at `radius` are being visited, so an edge between two nodes of the subgraph is always
+        // found from whichever of its ends is visited second
+        let mut depths = vec![0; subgraph.nodes.len()];
+        let mut next = 0;
+        while next < subgraph.nodes.len() {
+            let (id, depth) = (subgraph.nodes[next].id, depths[next]);
+            next += 1;
+            for (edge, node) in
+                self.neighbors_with_edges(txn, &id, Direction::Both, labels, None)?
+            {
+                if !included.contains(&node.id) {
+                    if depth >= radius {
+                        continue;
+                    }
+                    if included.len() >= max_nodes {
+                        subgraph.truncated = true;
+                        continue;
+                    }
+                    included.insert(node.id);
+                    subgraph.nodes.push(node);
+                    depths.push(depth + 1);
+                }
+                if edges.insert(edge.id) {
+                    subgraph.edges.push(edge);
+                }
+            }
+        }
+        Ok(subgraph)
+    }
+}
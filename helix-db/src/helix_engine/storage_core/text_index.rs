@@ -0,0 +1,184 @@
+use crate::{
+    helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError},
+    protocol::value::Value,
+    utils::items::Node,
+};
+use heed3::{RoTxn, RwTxn};
+use std::collections::{BTreeSet, HashMap};
+
+/// Longest token that is indexed, in bytes, keeping posting keys under LMDB's key size limit
+const MAX_TOKEN_LEN: usize = 256;
+
+/// Key of a text index, every posting key under it starts with this.
+///
+/// key = `label` | `0x00` | `property` | `0x00`
+#[inline(always)]
+fn index_key(label: &str, property: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(label.len() + property.len() + 2);
+    key.extend_from_slice(label.as_bytes());
+    key.push(0);
+    key.extend_from_slice(property.as_bytes());
+    key.push(0);
+    key
+}
+
+/// key = `label` | `0x00` | `property` | `0x00` | `token` | `0x00`
+#[inline(always)]
+fn token_key(label: &str, property: &str, token: &str) -> Vec<u8> {
+    let mut key = index_key(label, property);
+    key.extend_from_slice(token.as_bytes());
+    key.push(0);
+    key
+}
+
+/// key = `label` | `0x00` | `property` | `0x00` | `token` | `0x00` | `node-id(16)`
+#[inline(always)]
+fn posting_key(label: &str, property: &str, token: &str, id: u128) -> Vec<u8> {
+    let mut key = token_key(label, property, token);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+/// Splits text into its distinct lowercased whitespace separated tokens.
+///
+/// Tokens longer than [`MAX_TOKEN_LEN`] are dropped.
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| c.is_whitespace() || c == '\0')
+        .map(|token| token.to_lowercase())
+        .filter(|token| !token.is_empty() && token.len() <= MAX_TOKEN_LEN)
+        .collect()
+}
+
+/// The tokens of `property` on `node`, empty if it isn't a string
+fn node_tokens(node: Option<&Node>, property: &str) -> BTreeSet<String> {
+    match node
+        .and_then(|node| node.properties.as_ref())
+        .and_then(|props| props.get(property))
+    {
+        Some(Value::String(text)) => tokenize(text),
+        _ => BTreeSet::new(),
+    }
+}
+
+impl HelixGraphStorage {
+    /// Creates an inverted index over the tokens of the string `property` on the nodes with `label`,
+    /// indexing the nodes that already have it.
+    ///
+    /// Creating an index that already exists does nothing.
+    pub fn create_text_index(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        property: &str,
+    ) -> Result<(), GraphError> {
+        let key = index_key(label, property);
+        if self.text_indices_db.get(txn, &key)?.is_some() {
+            return Ok(());
+        }
+
+        let mut postings = Vec::new();
+        for result in self.nodes_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let node = Node::decode_node(bytes, id)?;
+            if node.label != label {
+                continue;
+            }
+            for token in node_tokens(Some(&node), property) {
+                postings.push(posting_key(label, property, &token, id));
+            }
+        }
+        for posting in postings {
+            self.text_postings_db.put(txn, &posting, &())?;
+        }
+
+        self.text_indices_db.put(txn, &key, &())?;
+        Ok(())
+    }
+
+    /// Gets the properties with a text index on the nodes with `label`
+    pub fn text_indexed_properties(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+    ) -> Result<Vec<String>, GraphError> {
+        let mut prefix = label.as_bytes().to_vec();
+        prefix.push(0);
+
+        let mut properties = Vec::new();
+        for result in self.text_indices_db.prefix_iter(txn, &prefix)? {
+            let (key, _) = result?;
+            let property = &key[prefix.len()..key.len() - 1];
+            properties.push(String::from_utf8_lossy(property).into_owned());
+        }
+        Ok(properties)
+    }
+
+    /// Moves a node's postings from `old` to `new`, so no stale tokens are left behind.
+    ///
+    /// `old` is the node as currently stored, or `None` if it is being added,
+    /// and `new` is the node about to be stored, or `None` if it is being dropped.
+    pub fn update_text_postings(
+        &self,
+        txn: &mut RwTxn,
+        old: Option<&Node>,
+        new: Option<&Node>,
+    ) -> Result<(), GraphError> {
+        let Some(node) = new.or(old) else {
+            return Ok(());
+        };
+        let (label, id) = (node.label.as_str(), node.id);
+
+        for property in self.text_indexed_properties(txn, label)? {
+            let old_tokens = node_tokens(old, &property);
+            let new_tokens = node_tokens(new, &property);
+            for token in old_tokens.difference(&new_tokens) {
+                self.text_postings_db
+                    .delete(txn, &posting_key(label, &property, token, id))?;
+            }
+            for token in new_tokens.difference(&old_tokens) {
+                self.text_postings_db
+                    .put(txn, &posting_key(label, &property, token, id), &())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the ids of the nodes with `label` whose `property` shares a token with `query`.
+    ///
+    /// Nodes matching more of the query's tokens come first, with ties in id order.
+    /// Returns `GraphError::New` if there is no text index on the property.
+    pub fn search_text(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        property: &str,
+        query: &str,
+    ) -> Result<Vec<u128>, GraphError> {
+        if self
+            .text_indices_db
+            .get(txn, &index_key(label, property))?
+            .is_none()
+        {
+            return Err(GraphError::New(format!(
+                "No text index on {}.{}",
+                label, property
+            )));
+        }
+
+        let mut overlap: HashMap<u128, usize> = HashMap::new();
+        for token in tokenize(query) {
+            let prefix = token_key(label, property, &token);
+            for result in self.text_postings_db.prefix_iter(txn, &prefix)? {
+                let (key, _) = result?;
+                let id = key[prefix.len()..]
+                    .try_into()
+                    .map_err(|_| GraphError::SliceLengthError)?;
+                *overlap.entry(u128::from_be_bytes(id)).or_insert(0) += 1;
+            }
+        }
+
+        let mut ranked: Vec<(u128, usize)> = overlap.into_iter().collect();
+        ranked.sort_unstable_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+        Ok(ranked.into_iter().map(|(id, _)| id).collect())
+    }
+}
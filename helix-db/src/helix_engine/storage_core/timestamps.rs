@@ -0,0 +1,34 @@
+use crate::{helix_engine::storage_core::direction::UNDIRECTED, protocol::value::Value};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Property holding when a node was inserted, in unix millis
+pub const CREATED_AT: &str = "created_at";
+
+/// Property holding when a node was last inserted or updated, in unix millis
+pub const UPDATED_AT: &str = "updated_at";
+
+/// Properties managed by the engine, which schemas accept without declaring them
+pub const RESERVED_PROPERTIES: [&str; 3] = [CREATED_AT, UPDATED_AT, UNDIRECTED];
+
+/// The current time in unix millis
+#[inline]
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Sets both timestamps of a node being inserted to `now`
+pub fn stamp_created(properties: &mut HashMap<String, Value>, now: u64) {
+    properties.insert(CREATED_AT.to_string(), Value::U64(now));
+    properties.insert(UPDATED_AT.to_string(), Value::U64(now));
+}
+
+/// Sets the `updated_at` of a node being updated to `now`
+pub fn stamp_updated(properties: &mut HashMap<String, Value>, now: u64) {
+    properties.insert(UPDATED_AT.to_string(), Value::U64(now));
+}
@@ -0,0 +1,163 @@
+use crate::{
+    helix_engine::{
+        storage_core::{
+            oplog::Operation, storage_core::HelixGraphStorage, storage_methods::StorageMethods,
+        },
+        types::GraphError,
+    },
+    utils::items::Edge,
+};
+use heed3::{RoTxn, RwTxn};
+use std::{collections::HashSet, ops::Bound};
+
+/// key = `expires_at(8)` | `node-id(16)`
+#[inline(always)]
+fn expiry_key(expires_at: u64, id: u128) -> [u8; 24] {
+    let mut key = [0u8; 24];
+    key[..8].copy_from_slice(&expires_at.to_be_bytes());
+    key[8..].copy_from_slice(&id.to_be_bytes());
+    key
+}
+
+/// Node expiries are kept in tables of their own rather than in the nodes' properties, so they
+/// can't be read, set or dropped through them.
+impl HelixGraphStorage {
+    /// The unix millis the node with `id` expires at, if it was inserted with a TTL
+    pub fn expires_at(&self, txn: &RoTxn, id: u128) -> Result<Option<u64>, GraphError> {
+        Ok(self.node_expiries_db.get(txn, &id)?)
+    }
+
+    /// Whether the node with `id` has expired by the unix millis `now`
+    #[inline]
+    pub fn is_expired(&self, txn: &RoTxn, id: u128, now: u64) -> Result<bool, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(false);
+        }
+        Ok(self
+            .expires_at(txn, id)?
+            .is_some_and(|expires_at| expires_at <= now))
+    }
+
+    /// Makes the node with `id` and `label` expire at the unix millis `expires_at`, replacing
+    /// any expiry it had
+    pub fn set_expiry(
+        &self,
+        txn: &mut RwTxn,
+        id: u128,
+        label: &str,
+        expires_at: u64,
+    ) -> Result<(), GraphError> {
+        self.clear_expiry(txn, id)?;
+        self.node_expiries_db.put(txn, &id, &expires_at)?;
+        self.expiries_db.put(txn, &expiry_key(expires_at, id), label)?;
+        self.log_operation(|| Operation::ExpireNode { id, expires_at })
+    }
+
+    /// Drops the expiry of the node with `id`, if it has one
+    pub fn clear_expiry(&self, txn: &mut RwTxn, id: u128) -> Result<(), GraphError> {
+        if let Some(expires_at) = self.expires_at(txn, id)? {
+            self.node_expiries_db.delete(txn, &id)?;
+            self.expiries_db.delete(txn, &expiry_key(expires_at, id))?;
+        }
+        Ok(())
+    }
+
+    /// Whether any node has expired by `now` without having been purged yet.
+    ///
+    /// A single read of the earliest expiry, so reads only look further when it is true.
+    pub fn has_expired(&self, txn: &RoTxn, now: u64) -> Result<bool, GraphError> {
+        Ok(match self.expiries_db.first(txn)? {
+            Some((key, _)) => key[..8] <= now.to_be_bytes()[..],
+            None => false,
+        })
+    }
+
+    /// Gets the ids and labels of the nodes that have expired by `now`, earliest first
+    pub fn expired_nodes(&self, txn: &RoTxn, now: u64) -> Result<Vec<(u128, String)>, GraphError> {
+        let end = expiry_key(now, u128::MAX);
+        let range = (Bound::Unbounded, Bound::Included(&end[..]));
+        let mut expired = Vec::new();
+        for result in self.expiries_db.range(txn, &range)? {
+            let (key, label) = result?;
+            let id = key[8..]
+                .try_into()
+                .map_err(|_| GraphError::SliceLengthError)?;
+            expired.push((u128::from_be_bytes(id), label.to_string()));
+        }
+        Ok(expired)
+    }
+
+    /// Gets the ids of the nodes that have expired by `now`, for scans to skip
+    pub fn expired_ids(&self, txn: &RoTxn, now: u64) -> Result<HashSet<u128>, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(HashSet::new());
+        }
+        Ok(self
+            .expired_nodes(txn, now)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect())
+    }
+
+    /// Number of nodes, with `label` if given, that have expired by `now` without being purged
+    pub fn expired_count(
+        &self,
+        txn: &RoTxn,
+        label: Option<&str>,
+        now: u64,
+    ) -> Result<u64, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(0);
+        }
+        Ok(self
+            .expired_nodes(txn, now)?
+            .iter()
+            .filter(|(_, expired_label)| label.is_none_or(|label| label == expired_label))
+            .count() as u64)
+    }
+
+    /// Number of edges hidden because a node at either end of them has expired by `now` without
+    /// being purged, each counted once even if both of its nodes have
+    pub fn expired_edge_count(&self, txn: &RoTxn, now: u64) -> Result<u64, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(0);
+        }
+        let mut edge_ids = HashSet::new();
+        for (id, _) in self.expired_nodes(txn, now)? {
+            for db in [&self.out_edges_db, &self.in_edges_db] {
+                for result in db.prefix_iter(txn, &id.to_be_bytes())? {
+                    let (_, value) = result?;
+                    let (edge_id, _) = Self::unpack_adj_edge_data(value)?;
+                    edge_ids.insert(edge_id);
+                }
+            }
+        }
+        Ok(edge_ids.len() as u64)
+    }
+
+    /// Whether `edge` is hidden because a node at either end of it has expired by `now`
+    pub fn is_edge_expired(&self, txn: &RoTxn, edge: &Edge, now: u64) -> Result<bool, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(false);
+        }
+        for id in [edge.from_node, edge.to_node] {
+            if self.is_expired(txn, id, now)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Drops the nodes that have expired by `now`, along with their edges,
+    /// returning how many were dropped
+    pub fn purge_expired(&self, txn: &mut RwTxn, now: u64) -> Result<usize, GraphError> {
+        if !self.has_expired(txn, now)? {
+            return Ok(0);
+        }
+        let expired = self.expired_nodes(txn, now)?;
+        for (id, _) in expired.iter() {
+            self.drop_node(txn, id)?;
+        }
+        Ok(expired.len())
+    }
+}
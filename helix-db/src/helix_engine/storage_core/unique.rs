@@ -0,0 +1,145 @@
+use crate::{
+    helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError},
+    protocol::value::Value,
+    utils::items::Node,
+};
+use heed3::{RoTxn, RwTxn};
+
+/// Key of a unique constraint, every value key under it starts with this.
+///
+/// key = `label` | `0x00` | `property` | `0x00`
+#[inline(always)]
+fn constraint_key(label: &str, property: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(label.len() + property.len() + 2);
+    key.extend_from_slice(label.as_bytes());
+    key.push(0);
+    key.extend_from_slice(property.as_bytes());
+    key.push(0);
+    key
+}
+
+/// key = `label` | `0x00` | `property` | `0x00` | `bincode(value)`
+#[inline(always)]
+fn value_key(label: &str, property: &str, value: &Value) -> Result<Vec<u8>, GraphError> {
+    let mut key = constraint_key(label, property);
+    key.extend_from_slice(&bincode::serialize(value)?);
+    Ok(key)
+}
+
+/// A node's id, one of its unique properties and the key of its value for it
+type HeldValue<'a> = (u128, &'a str, Vec<u8>);
+
+fn violated(label: &str, property: &str) -> GraphError {
+    GraphError::UniqueViolation {
+        label: label.to_string(),
+        property: property.to_string(),
+    }
+}
+
+impl HelixGraphStorage {
+    /// Makes `property` unique across the nodes with `label`, indexing the nodes that already have it.
+    ///
+    /// Fails without creating the constraint if two of those nodes already share a value.
+    /// Creating a constraint that already exists does nothing.
+    pub fn create_unique_constraint(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        property: &str,
+    ) -> Result<(), GraphError> {
+        let key = constraint_key(label, property);
+        if self.unique_constraints_db.get(txn, &key)?.is_some() {
+            return Ok(());
+        }
+
+        let mut existing = Vec::new();
+        for result in self.nodes_db.iter(txn)? {
+            let (id, bytes) = result?;
+            let node = Node::decode_node(bytes, id)?;
+            if node.label != label {
+                continue;
+            }
+            if let Some(value) = node.properties.as_ref().and_then(|props| props.get(property)) {
+                existing.push((value_key(label, property, value)?, id));
+            }
+        }
+        for (value_key, id) in existing {
+            if self.unique_values_db.get(txn, &value_key)?.is_some() {
+                return Err(violated(label, property));
+            }
+            self.unique_values_db.put(txn, &value_key, &id)?;
+        }
+
+        self.unique_constraints_db.put(txn, &key, &())?;
+        Ok(())
+    }
+
+    /// Gets the properties that must be unique across the nodes with `label`
+    pub fn unique_properties(&self, txn: &RoTxn, label: &str) -> Result<Vec<String>, GraphError> {
+        let mut prefix = label.as_bytes().to_vec();
+        prefix.push(0);
+
+        let mut properties = Vec::new();
+        for result in self.unique_constraints_db.prefix_iter(txn, &prefix)? {
+            let (key, _) = result?;
+            let property = &key[prefix.len()..key.len() - 1];
+            properties.push(String::from_utf8_lossy(property).into_owned());
+        }
+        Ok(properties)
+    }
+
+    /// Moves the unique values held by a node from `old` to `new`.
+    ///
+    /// `old` is the node as currently stored, or `None` if it is being added,
+    /// and `new` is the node about to be stored, or `None` if it is being dropped.
+    /// Every value is checked before anything is written, so a violation leaves the index untouched.
+    /// The check and the write happen in the caller's write txn, and LMDB allows only one of those
+    /// at a time, so two txns can never both claim the same value.
+    pub fn update_unique_values(
+        &self,
+        txn: &mut RwTxn,
+        old: Option<&Node>,
+        new: Option<&Node>,
+    ) -> Result<(), GraphError> {
+        let Some(label) = new.or(old).map(|node| node.label.as_str()) else {
+            return Ok(());
+        };
+        let properties = self.unique_properties(txn, label)?;
+        if properties.is_empty() {
+            return Ok(());
+        }
+
+        let values = |node: Option<&Node>| -> Result<Vec<HeldValue>, GraphError> {
+            let Some(node) = node else {
+                return Ok(Vec::new());
+            };
+            let mut keys = Vec::new();
+            for property in properties.iter() {
+                if let Some(value) = node.properties.as_ref().and_then(|props| props.get(property)) {
+                    let key = value_key(&node.label, property, value)?;
+                    keys.push((node.id, property.as_str(), key));
+                }
+            }
+            Ok(keys)
+        };
+        let released = values(old)?;
+        let claimed = values(new)?;
+
+        for (id, property, key) in claimed.iter() {
+            match self.unique_values_db.get(txn, key)? {
+                Some(holder) if holder != *id => return Err(violated(label, property)),
+                _ => {}
+            }
+        }
+
+        for (id, _, key) in released.iter() {
+            if self.unique_values_db.get(txn, key)? == Some(*id) {
+                self.unique_values_db.delete(txn, key)?;
+            }
+        }
+        for (id, _, key) in claimed.iter() {
+            self.unique_values_db.put(txn, key, id)?;
+        }
+        Ok(())
+    }
+}
@@ -27,6 +27,13 @@ pub enum GraphError {
     SliceLengthError,
     ShortestPathNotFound,
     EmbeddingError(String),
+    AliasNotFound,
+    QuotaExceeded(String),
+    SchemaViolation(String),
+    DanglingEdge(String),
+    InvalidQuery(String),
+    UniqueViolation { label: String, property: String },
+    ReadOnly,
 }
 
 impl fmt::Display for GraphError {
@@ -57,6 +64,98 @@ impl fmt::Display for GraphError {
             GraphError::VectorError(msg) => write!(f, "Vector error: {}", msg),
             GraphError::ShortestPathNotFound => write!(f, "Shortest path not found"),
             GraphError::EmbeddingError(msg) => write!(f, "Error while embedding text: {}", msg),
+            GraphError::AliasNotFound => write!(f, "Alias not found"),
+            GraphError::QuotaExceeded(msg) => write!(f, "Quota exceeded: {}", msg),
+            GraphError::SchemaViolation(msg) => write!(f, "Schema violation: {}", msg),
+            GraphError::DanglingEdge(msg) => write!(f, "Dangling edge: {}", msg),
+            GraphError::InvalidQuery(msg) => write!(f, "Invalid query: {}", msg),
+            GraphError::UniqueViolation { label, property } => write!(
+                f,
+                "Unique constraint violated: another {} node has the same {}",
+                label, property
+            ),
+            GraphError::ReadOnly => write!(f, "Storage is read-only"),
+        }
+    }
+}
+
+impl GraphError {
+    /// The HTTP status code a request failing with this error is answered with
+    pub fn status_code(&self) -> u16 {
+        match self {
+            GraphError::NodeNotFound
+            | GraphError::EdgeNotFound
+            | GraphError::LabelNotFound
+            | GraphError::AliasNotFound
+            | GraphError::ShortestPathNotFound
+            | GraphError::DanglingEdge(_) => 404,
+            GraphError::TraversalError(_)
+            | GraphError::ConversionError(_)
+            | GraphError::DecodeError(_)
+            | GraphError::InvalidNode
+            | GraphError::SliceLengthError
+            | GraphError::SchemaViolation(_)
+            | GraphError::InvalidQuery(_) => 400,
+            GraphError::ReadOnly => 403,
+            GraphError::MultipleNodesWithSameId
+            | GraphError::MultipleEdgesWithSameId
+            | GraphError::UniqueViolation { .. } => 409,
+            GraphError::EmbeddingError(_) => 502,
+            GraphError::QuotaExceeded(_) => 507,
+            GraphError::Io(_)
+            | GraphError::GraphConnectionError(_, _)
+            | GraphError::StorageConnectionError(_, _)
+            | GraphError::StorageError(_)
+            | GraphError::VectorError(_)
+            | GraphError::ConfigFileNotFound
+            | GraphError::Default
+            | GraphError::New(_)
+            | GraphError::Empty => 500,
+        }
+    }
+
+    /// A stable name for the kind of error, sent as the `code` of an error response so clients
+    /// can tell errors apart without parsing their messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            GraphError::Io(_) => "io_error",
+            GraphError::GraphConnectionError(_, _) => "graph_connection_error",
+            GraphError::StorageConnectionError(_, _) => "storage_connection_error",
+            GraphError::StorageError(_) => "storage_error",
+            GraphError::TraversalError(_) => "traversal_error",
+            GraphError::ConversionError(_) => "conversion_error",
+            GraphError::DecodeError(_) => "decode_error",
+            GraphError::EdgeNotFound => "edge_not_found",
+            GraphError::NodeNotFound => "node_not_found",
+            GraphError::LabelNotFound => "label_not_found",
+            GraphError::VectorError(_) => "vector_error",
+            GraphError::Default | GraphError::New(_) => "graph_error",
+            GraphError::Empty => "empty",
+            GraphError::MultipleNodesWithSameId => "duplicate_node_id",
+            GraphError::MultipleEdgesWithSameId => "duplicate_edge_id",
+            GraphError::InvalidNode => "invalid_node",
+            GraphError::ConfigFileNotFound => "config_file_not_found",
+            GraphError::SliceLengthError => "slice_length_error",
+            GraphError::ShortestPathNotFound => "shortest_path_not_found",
+            GraphError::EmbeddingError(_) => "embedding_error",
+            GraphError::AliasNotFound => "alias_not_found",
+            GraphError::QuotaExceeded(_) => "quota_exceeded",
+            GraphError::SchemaViolation(_) => "schema_violation",
+            GraphError::DanglingEdge(_) => "dangling_edge",
+            GraphError::InvalidQuery(_) => "invalid_query",
+            GraphError::UniqueViolation { .. } => "unique_violation",
+            GraphError::ReadOnly => "read_only",
+        }
+    }
+}
+
+impl std::error::Error for GraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphError::Io(e)
+            | GraphError::GraphConnectionError(_, e)
+            | GraphError::StorageConnectionError(_, e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -156,6 +255,8 @@ impl fmt::Display for VectorError {
     }
 }
 
+impl std::error::Error for VectorError {}
+
 impl From<HeedError> for VectorError {
     fn from(error: HeedError) -> Self {
         VectorError::VectorCoreError(format!("heed error: {}", error.to_string()))
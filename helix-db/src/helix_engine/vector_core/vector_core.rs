@@ -1,4 +1,5 @@
 use crate::helix_engine::{
+    storage_core::setup::{OpenOrCreate, SetupTxn},
     types::VectorError,
     vector_core::{hnsw::HNSW, vector::HVector},
 };
@@ -11,8 +12,9 @@ use itertools::Itertools;
 use rand::prelude::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
-    cmp::Ordering,
+    cmp::{Ordering, Reverse},
     collections::{BinaryHeap, HashSet},
+    sync::RwLock,
 };
 
 const DB_VECTORS: &str = "vectors"; // for vector data (v:)
@@ -21,14 +23,17 @@ const DB_VECTOR_DATA: &str = "vector_data"; // for vector data (v:)
 const DB_HNSW_OUT_EDGES: &str = "hnsw_out_nodes"; // for hnsw out node data
 const VECTOR_PREFIX: &[u8] = b"v:";
 const ENTRY_POINT_KEY: &str = "entry_point";
+const INDEX_CONFIG_KEY: &str = "index_config";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct HNSWConfig {
     pub m: usize,            // max num of bi-directional links per element
     pub m_max_0: usize,      // max num of links for lower layers
     pub ef_construct: usize, // size of the dynamic candidate list for construction
     pub m_l: f64,            // level generation factor
     pub ef: usize,           // search param, num of cands to search
+    #[serde(default)]
+    pub dimension: Option<usize>, // enforced vector length, set once an index is built
 }
 
 impl HNSWConfig {
@@ -40,6 +45,7 @@ impl HNSWConfig {
             ef_construct: ef_construct.unwrap_or(128),
             m_l: 1.0 / (m as f64).ln(),
             ef: ef.unwrap_or(768),
+            dimension: None,
         }
     }
 }
@@ -169,23 +175,177 @@ pub struct VectorCore {
     pub vectors_db: Database<Bytes, Bytes>,
     pub vector_data_db: Database<Bytes, Bytes>,
     pub out_edges_db: Database<Bytes, Unit>,
-    pub config: HNSWConfig,
+    pub config: RwLock<HNSWConfig>,
 }
 
 impl VectorCore {
-    pub fn new(env: &Env, txn: &mut RwTxn, config: HNSWConfig) -> Result<Self, VectorError> {
-        let vectors_db = env.create_database(txn, Some(DB_VECTORS))?;
-        let vector_data_db = env.create_database(txn, Some(DB_VECTOR_DATA))?;
-        let out_edges_db = env.create_database(txn, Some(DB_HNSW_OUT_EDGES))?;
+    pub fn new(env: &Env, txn: &mut SetupTxn, config: HNSWConfig) -> Result<Self, VectorError> {
+        let vectors_db = env.database_options().types().name(DB_VECTORS).open_or_create(txn)?;
+        let vector_data_db = env
+            .database_options()
+            .types()
+            .name(DB_VECTOR_DATA)
+            .open_or_create(txn)?;
+        let out_edges_db = env
+            .database_options()
+            .types()
+            .name(DB_HNSW_OUT_EDGES)
+            .open_or_create(txn)?;
+
+        // an index built with explicit settings is persisted and takes precedence over the config file
+        let config = match vectors_db.get(txn.read(), INDEX_CONFIG_KEY.as_bytes())? {
+            Some(bytes) => bincode::deserialize(bytes)?,
+            None => config,
+        };
 
         Ok(Self {
             vectors_db,
             vector_data_db,
             out_edges_db,
-            config,
+            config: RwLock::new(config),
         })
     }
 
+    /// Returns a copy of the current index settings
+    #[inline(always)]
+    pub fn config(&self) -> HNSWConfig {
+        *self.config.read().unwrap()
+    }
+
+    /// Returns the index settings as of `txn`, i.e. the ones persisted by the last committed
+    /// `rebuild_index`, or the in memory ones if no index has been built with explicit settings.
+    ///
+    /// Searches read these rather than [`Self::config`] so they never pair a rebuilt index with
+    /// the settings it replaced.
+    pub fn config_in(&self, txn: &RoTxn) -> Result<HNSWConfig, VectorError> {
+        match self.vectors_db.get(txn, INDEX_CONFIG_KEY.as_bytes())? {
+            Some(bytes) => Ok(bincode::deserialize(bytes)?),
+            None => Ok(self.config()),
+        }
+    }
+
+    /// Replaces the in memory index settings.
+    ///
+    /// Should only be called once the txn that persisted them via `rebuild_index` has committed.
+    pub fn set_config(&self, config: HNSWConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    #[inline(always)]
+    fn check_dimension(config: &HNSWConfig, data: &[f64]) -> Result<(), VectorError> {
+        match config.dimension {
+            Some(dim) if dim != data.len() => Err(VectorError::InvalidVectorLength),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether an hnsw entry point exists, i.e. at least one vector has been indexed
+    pub fn has_index(&self, txn: &RoTxn) -> Result<bool, VectorError> {
+        Ok(self.vectors_db.get(txn, ENTRY_POINT_KEY.as_bytes())?.is_some())
+    }
+
+    /// Gets every vector stored at level 0 (where all vectors live) without their properties
+    fn get_base_vectors(&self, txn: &RoTxn) -> Result<Vec<HVector>, VectorError> {
+        let mut vectors = Vec::new();
+        for result in self.vectors_db.prefix_iter(txn, VECTOR_PREFIX)? {
+            let (key, value) = result?;
+            let (id, level) = Self::parse_vector_key(key)?;
+            if level == 0 {
+                vectors.push(HVector::from_bytes(id, level, value)?);
+            }
+        }
+        Ok(vectors)
+    }
+
+    /// Splits a vector key back into its id and level
+    #[inline(always)]
+    fn parse_vector_key(key: &[u8]) -> Result<(u128, usize), VectorError> {
+        let prefix_len = VECTOR_PREFIX.len();
+        let id = key
+            .get(prefix_len..prefix_len + 16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u128::from_be_bytes)
+            .ok_or(VectorError::InvalidVectorData)?;
+        let level = key
+            .get(prefix_len + 16..)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(usize::from_be_bytes)
+            .ok_or(VectorError::InvalidVectorData)?;
+        Ok((id, level))
+    }
+
+    /// Drops every hnsw link and upper level entry and re-inserts all stored vectors with the given settings.
+    ///
+    /// Vector ids and properties are kept so edges to vectors stay valid.
+    /// Used after bulk loads or to change `m`/`ef_construct` on an existing index.
+    ///
+    /// Returns the number of vectors that were re-indexed.
+    pub fn rebuild_index(&self, txn: &mut RwTxn, config: HNSWConfig) -> Result<usize, VectorError> {
+        let base_vectors = self.get_base_vectors(txn)?;
+        if let Some(dim) = config.dimension
+            && base_vectors.iter().any(|v| v.len() != dim)
+        {
+            return Err(VectorError::InvalidVectorLength);
+        }
+
+        let upper_keys = self
+            .vectors_db
+            .prefix_iter(txn, VECTOR_PREFIX)?
+            .filter_map(|result| result.ok().map(|(key, _)| key.to_vec()))
+            .filter(|key| matches!(Self::parse_vector_key(key), Ok((_, level)) if level > 0))
+            .collect::<Vec<_>>();
+        for key in upper_keys {
+            self.vectors_db.delete(txn, &key)?;
+        }
+        self.out_edges_db.clear(txn)?;
+        self.vectors_db.delete(txn, ENTRY_POINT_KEY.as_bytes())?;
+        self.vectors_db
+            .put(txn, INDEX_CONFIG_KEY.as_bytes(), &bincode::serialize(&config)?)?;
+
+        let count = base_vectors.len();
+        for vector in base_vectors {
+            self.index_vector(txn, vector, &config)?;
+        }
+        Ok(count)
+    }
+
+    /// Exact k nearest neighbours by scanning every stored vector.
+    ///
+    /// Used as a fallback when no index exists and as the ground truth for recall.
+    pub fn brute_force_search(
+        &self,
+        txn: &RoTxn,
+        query: &[f64],
+        k: usize,
+    ) -> Result<Vec<HVector>, VectorError> {
+        let query = HVector::from_slice(0, query.to_vec());
+        let mut results = self
+            .get_base_vectors(txn)?
+            .into_iter()
+            .map(|mut vector| {
+                vector.set_distance(vector.distance_to(&query)?);
+                Ok(vector)
+            })
+            .collect::<Result<BinaryHeap<HVector>, VectorError>>()?
+            .to_vec(k);
+        self.load_properties(txn, &mut results)?;
+        Ok(results)
+    }
+
+    #[inline(always)]
+    fn load_properties(&self, txn: &RoTxn, results: &mut [HVector]) -> Result<(), VectorError> {
+        for result in results.iter_mut() {
+            result.properties = match self
+                .vector_data_db
+                .get(txn, &result.get_id().to_be_bytes())?
+            {
+                Some(bytes) => Some(bincode::deserialize(&bytes).map_err(VectorError::from)?),
+                None => None, // Maybe should be an error?
+            };
+        }
+        Ok(())
+    }
+
     #[inline(always)]
     fn vector_key(id: u128, level: usize) -> Vec<u8> {
         [VECTOR_PREFIX, &id.to_be_bytes(), &level.to_be_bytes()].concat()
@@ -211,13 +371,13 @@ impl VectorCore {
     }
 
     #[inline]
-    fn get_new_level(&self) -> usize {
+    fn get_new_level(&self, m_l: f64) -> usize {
         // TODO: look at using the XOR shift algorithm for random number generation
         // Storing global rng will not be threadsafe or possible as thread rng needs to be mutable
         // Should instead using an atomic mutable seed and the XOR shift algorithm
         let mut rng = rand::rng();
         let r: f64 = rng.random::<f64>();
-        let level = (-r.ln() * self.config.m_l).floor() as usize;
+        let level = (-r.ln() * m_l).floor() as usize;
         level
     }
 
@@ -274,7 +434,7 @@ impl VectorCore {
         F: Fn(&HVector, &RoTxn) -> bool,
     {
         let out_key = Self::out_edges_key(id, level, None);
-        let mut neighbors = Vec::with_capacity(self.config.m_max_0.min(512)); // TODO: why 512?
+        let mut neighbors = Vec::with_capacity(self.config().m_max_0.min(512)); // TODO: why 512?
 
         let iter = self
             .out_edges_db
@@ -355,14 +515,15 @@ impl VectorCore {
         level: usize,
         should_extend: bool,
         filter: Option<&[F]>,
+        config: &HNSWConfig,
     ) -> Result<BinaryHeap<HVector>, VectorError>
     where
         F: Fn(&HVector, &RoTxn) -> bool,
     {
         let m: usize = if level == 0 {
-            self.config.m
+            config.m
         } else {
-            self.config.m_max_0
+            config.m_max_0
         };
         let mut visited: HashSet<String> = HashSet::new();
         if should_extend {
@@ -399,30 +560,28 @@ impl VectorCore {
     {
         let mut visited: HashSet<u128> = HashSet::new();
         let mut candidates: BinaryHeap<Candidate> = BinaryHeap::new();
-        let mut results: BinaryHeap<HVector> = BinaryHeap::new();
+        // HVector orders closest first, so reversed the furthest result is on top
+        let mut results: BinaryHeap<Reverse<HVector>> = BinaryHeap::new();
 
         entry_point.set_distance(entry_point.distance_to(query)?);
         candidates.push(Candidate {
             id: entry_point.get_id(),
             distance: entry_point.get_distance(),
         });
-        results.push(entry_point.clone());
+        results.push(Reverse(entry_point.clone()));
         visited.insert(entry_point.get_id());
 
         while let Some(curr_cand) = candidates.pop() {
-            if results.len() >= ef
-                && results
-                    .get_max()
-                    .map_or(false, |f| curr_cand.distance > f.get_distance())
-            {
+            let furthest = match results.len() >= ef {
+                true => results.peek().map(|Reverse(f)| f.get_distance()),
+                false => None,
+            };
+
+            if furthest.map_or(false, |max| curr_cand.distance > max) {
                 break;
             }
 
-            let max_distance = if results.len() >= ef {
-                results.get_max().map(|f| f.get_distance())
-            } else {
-                None
-            };
+            let max_distance = furthest;
 
             self.get_neighbors(txn, curr_cand.id, level, filter)?
                 .into_iter()
@@ -441,67 +600,37 @@ impl VectorCore {
                         id: neighbor.get_id(),
                         distance,
                     });
-                    results.push(neighbor);
+                    results.push(Reverse(neighbor));
                     if results.len() > ef {
-                        results = results.take_inord(ef);
+                        results.pop();
                     }
                 });
         }
-        Ok(results)
-    }
-}
-
-impl HNSW for VectorCore {
-    #[inline(always)]
-    fn get_vector(
-        &self,
-        txn: &RoTxn,
-        id: u128,
-        level: usize,
-        with_data: bool,
-    ) -> Result<HVector, VectorError> {
-        let key = Self::vector_key(id, level);
-        let vector = match self.vectors_db.get(txn, key.as_ref())? {
-            Some(bytes) => {
-                let vector = match with_data {
-                    true => {
-                        let mut vector = HVector::from_bytes(id, level, &bytes)?;
-                        vector.properties = match self.vector_data_db.get(txn, &id.to_be_bytes())? {
-                            Some(bytes) => {
-                                Some(bincode::deserialize(&bytes).map_err(VectorError::from)?)
-                            }
-                            None => None,
-                        };
-
-                        vector
-                    }
-                    false => HVector::from_bytes(id, level, &bytes)?,
-                };
-                Ok(vector)
-            }
-            None if level > 0 => self.get_vector(txn, id, 0, with_data),
-            None => Err(VectorError::VectorNotFound(id.to_string())),
-        }?;
-
-        Ok(vector)
+        Ok(results.into_iter().map(|Reverse(result)| result).collect())
     }
 
-    fn search<F>(
+    /// Same as `HNSW::search` but with an explicit size for the dynamic candidate list.
+    ///
+    /// A larger `ef` trades latency for recall.
+    pub fn search_ef<F>(
         &self,
         txn: &RoTxn,
         query: &[f64],
         k: usize,
+        ef: usize,
         filter: Option<&[F]>,
         should_trickle: bool,
     ) -> Result<Vec<HVector>, VectorError>
     where
         F: Fn(&HVector, &RoTxn) -> bool,
     {
+        Self::check_dimension(&self.config_in(txn)?, query)?;
         let query = HVector::from_slice(0, query.to_vec());
 
         let mut entry_point = self.get_entry_point(txn)?;
 
-        let ef = self.config.ef;
+        // ef below k would cut off results before the filters are applied
+        let ef = ef.max(k);
         let curr_level = entry_point.get_level();
 
         for level in (1..=curr_level).rev() {
@@ -534,32 +663,22 @@ impl HNSW for VectorCore {
         )?;
 
         let mut results = candidates.to_vec_with_filter(k, filter, txn);
-
-        for result in results.iter_mut() {
-            result.properties = match self
-                .vector_data_db
-                .get(txn, &result.get_id().to_be_bytes())?
-            {
-                Some(bytes) => Some(bincode::deserialize(&bytes).map_err(VectorError::from)?),
-                None => None, // Maybe should be an error?
-            };
-        }
+        self.load_properties(txn, &mut results)?;
 
         Ok(results)
     }
 
-    fn insert<F>(
+    /// Links a vector into the hnsw graph, keeping its id.
+    fn index_vector(
         &self,
         txn: &mut RwTxn,
-        data: &[f64],
-        fields: Option<Vec<(String, Value)>>,
-    ) -> Result<HVector, VectorError>
-    where
-        F: Fn(&HVector, &RoTxn) -> bool,
-    {
-        let new_level = self.get_new_level();
+        mut query: HVector,
+        config: &HNSWConfig,
+    ) -> Result<HVector, VectorError> {
+        type F = fn(&HVector, &RoTxn) -> bool;
+        let new_level = self.get_new_level(config.m_l);
 
-        let mut query = HVector::from_slice(0, data.to_vec());
+        query.level = 0;
         self.put_vector(txn, &query)?;
 
         query.level = new_level;
@@ -588,30 +707,25 @@ impl HNSW for VectorCore {
                 txn,
                 &query,
                 &mut curr_ep,
-                self.config.ef_construct,
+                config.ef_construct,
                 level,
                 None,
             )?;
 
             curr_ep = nearest.peek().unwrap().clone();
 
-            let neighbors = self.select_neighbors::<F>(txn, &query, nearest, level, true, None)?;
+            let neighbors =
+                self.select_neighbors::<F>(txn, &query, nearest, level, true, None, config)?;
 
             self.set_neighbours(txn, query.get_id(), &neighbors, level)?;
 
             for e in neighbors {
                 let id = e.get_id();
                 let e_conns = self.get_neighbors::<F>(txn, id, level, None)?;
-                if e_conns.len()
-                    > if level == 0 {
-                        self.config.m_max_0
-                    } else {
-                        self.config.m_max_0
-                    }
-                {
+                if e_conns.len() > config.m_max_0 {
                     let e_conns = BinaryHeap::from(e_conns);
-                    let e_new_conn =
-                        self.select_neighbors::<F>(txn, &query, e_conns, level, true, None)?;
+                    let e_new_conn = self
+                        .select_neighbors::<F>(txn, &query, e_conns, level, true, None, config)?;
                     self.set_neighbours(txn, id, &e_new_conn, level)?;
                 }
             }
@@ -621,6 +735,75 @@ impl HNSW for VectorCore {
             self.set_entry_point(txn, &query)?;
         }
 
+        Ok(query)
+    }
+}
+
+impl HNSW for VectorCore {
+    #[inline(always)]
+    fn get_vector(
+        &self,
+        txn: &RoTxn,
+        id: u128,
+        level: usize,
+        with_data: bool,
+    ) -> Result<HVector, VectorError> {
+        let key = Self::vector_key(id, level);
+        let vector = match self.vectors_db.get(txn, key.as_ref())? {
+            Some(bytes) => {
+                let vector = match with_data {
+                    true => {
+                        let mut vector = HVector::from_bytes(id, level, &bytes)?;
+                        vector.properties = match self.vector_data_db.get(txn, &id.to_be_bytes())? {
+                            Some(bytes) => {
+                                Some(bincode::deserialize(&bytes).map_err(VectorError::from)?)
+                            }
+                            None => None,
+                        };
+
+                        vector
+                    }
+                    false => HVector::from_bytes(id, level, &bytes)?,
+                };
+                Ok(vector)
+            }
+            None if level > 0 => self.get_vector(txn, id, 0, with_data),
+            None => Err(VectorError::VectorNotFound(id.to_string())),
+        }?;
+
+        Ok(vector)
+    }
+
+    fn search<F>(
+        &self,
+        txn: &RoTxn,
+        query: &[f64],
+        k: usize,
+        filter: Option<&[F]>,
+        should_trickle: bool,
+    ) -> Result<Vec<HVector>, VectorError>
+    where
+        F: Fn(&HVector, &RoTxn) -> bool,
+    {
+        let ef = self.config_in(txn)?.ef;
+        self.search_ef(txn, query, k, ef, filter, should_trickle)
+    }
+
+    fn insert<F>(
+        &self,
+        txn: &mut RwTxn,
+        data: &[f64],
+        fields: Option<Vec<(String, Value)>>,
+    ) -> Result<HVector, VectorError>
+    where
+        F: Fn(&HVector, &RoTxn) -> bool,
+    {
+        let config = self.config_in(txn)?;
+        Self::check_dimension(&config, data)?;
+
+        let query = HVector::from_slice(0, data.to_vec());
+        let query = self.index_vector(txn, query, &config)?;
+
         if let Some(fields) = fields {
             self.vector_data_db.put(
                 txn,
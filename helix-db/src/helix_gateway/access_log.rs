@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use std::{net::SocketAddr, time::Duration};
+
+use crate::protocol::request::Request;
+
+/// Called with a record of each request once its response has been sent
+pub type AccessLogFn = fn(&RequestLog);
+
+/// Record of a single request and the response it was sent
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    pub addr: SocketAddr,
+    /// When the request finished being parsed
+    pub time: DateTime<Utc>,
+    pub method: String,
+    /// The path as sent in the request line, with its query and percent-encoding,
+    /// see [`Request::raw_path`]
+    pub path: String,
+    /// The HTTP version from the request line, e.g. `HTTP/1.1`
+    pub version: String,
+    pub status: u16,
+    /// Size of the response body
+    pub bytes: usize,
+    /// Time from the request being parsed to the response being flushed
+    pub duration: Duration,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    /// The request's ID, see [`Request::request_id`]
+    pub request_id: String,
+}
+
+impl RequestLog {
+    /// Starts a record for `request`, with the response fields left empty
+    pub fn start(addr: SocketAddr, request: &Request) -> Self {
+        Self {
+            addr,
+            time: Utc::now(),
+            method: request.method.clone(),
+            path: request.raw_path.clone(),
+            version: request.version.clone(),
+            status: 0,
+            bytes: 0,
+            duration: Duration::ZERO,
+            referer: request.headers.get("referer").cloned(),
+            user_agent: request.headers.get("user-agent").cloned(),
+            request_id: request.request_id.clone(),
+        }
+    }
+
+    /// Formats the record as a line of the Apache combined log format.
+    ///
+    /// The fields taken from the request are escaped as Apache does, so a client can't
+    /// break out of a quoted field or write a line of its own.
+    pub fn combined(&self) -> String {
+        let bytes = match self.bytes {
+            0 => "-".to_string(),
+            bytes => bytes.to_string(),
+        };
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\"",
+            self.addr.ip(),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            escape(&self.method),
+            escape(&self.path),
+            escape(&self.version),
+            self.status,
+            bytes,
+            escape(self.referer.as_deref().unwrap_or("-")),
+            escape(self.user_agent.as_deref().unwrap_or("-")),
+        )
+    }
+}
+
+/// Escapes `"` and `\` with a backslash, and control and non-ASCII bytes as `\xhh`
+/// or their C escape, like Apache's `ap_escape_logitem`
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for &byte in field.as_bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b'\x08' => escaped.push_str("\\b"),
+            b'\n' => escaped.push_str("\\n"),
+            b'\r' => escaped.push_str("\\r"),
+            b'\t' => escaped.push_str("\\t"),
+            b'\x0b' => escaped.push_str("\\v"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Writes each request to stdout in the Apache combined log format
+pub fn log_combined(log: &RequestLog) {
+    println!("{}", log.combined());
+}
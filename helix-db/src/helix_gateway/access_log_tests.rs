@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+
+use super::access_log::RequestLog;
+
+fn request_log() -> RequestLog {
+    RequestLog {
+        addr: "192.168.1.20:51234".parse().unwrap(),
+        time: Utc.with_ymd_and_hms(2025, 3, 7, 14, 5, 9).unwrap(),
+        method: "POST".to_string(),
+        path: "/add_user".to_string(),
+        version: "HTTP/1.1".to_string(),
+        status: 200,
+        bytes: 42,
+        duration: Duration::from_millis(3),
+        referer: None,
+        user_agent: Some("curl/8.5.0".to_string()),
+        request_id: "0b6c1d1e-8f0e-4b8e-9a51-5d3f2a4c7e10".to_string(),
+    }
+}
+
+#[test]
+fn test_combined_log_line() {
+    assert_eq!(
+        request_log().combined(),
+        "192.168.1.20 - - [07/Mar/2025:14:05:09 +0000] \"POST /add_user HTTP/1.1\" 200 42 \"-\" \"curl/8.5.0\""
+    );
+}
+
+#[test]
+fn test_combined_log_line_without_body() {
+    let log = RequestLog {
+        status: 204,
+        bytes: 0,
+        ..request_log()
+    };
+    assert!(log.combined().contains("\" 204 - \""));
+}
+
+#[test]
+fn test_combined_log_line_escapes_request_fields() {
+    let log = RequestLog {
+        path: "/find?name=a%20b&x=\"y\"".to_string(),
+        version: "HTTP/1.0".to_string(),
+        referer: Some("http://example.com/\\".to_string()),
+        user_agent: Some("evil\" 200 1 \"-\n\x1bé".to_string()),
+        ..request_log()
+    };
+    assert_eq!(
+        log.combined(),
+        "192.168.1.20 - - [07/Mar/2025:14:05:09 +0000] \"POST /find?name=a%20b&x=\\\"y\\\" HTTP/1.0\" 200 42 \"http://example.com/\\\\\" \"evil\\\" 200 1 \\\"-\\n\\x1b\\xc3\\xa9\""
+    );
+}
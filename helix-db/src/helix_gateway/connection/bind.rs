@@ -0,0 +1,181 @@
+#[cfg(unix)]
+use socket2::SockAddr;
+use socket2::{Domain, Protocol, Socket, Type};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Duration,
+};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+/// How a listener's socket is set up, and how long binding it keeps being retried for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindOpts {
+    /// Lets an IPv6 listener bound to `[::]` accept IPv4 connections on the same port too,
+    /// where the OS supports it. Has no effect on an IPv4 listener.
+    pub dual_stack: bool,
+    /// Sets `SO_REUSEADDR` on Unix, as tokio does, so a restarted gateway can bind while
+    /// connections to the old one are in `TIME_WAIT`. It isn't set on Windows, where it would
+    /// let another process take over the port.
+    pub reuse_address: bool,
+    /// Sets `SO_REUSEPORT` on Unix, so a new gateway can bind the port while the old one is still
+    /// listening on it, with the OS sharing connections between them. The old one has to have
+    /// set it too.
+    pub reuse_port: bool,
+    /// Times a bind failing with `AddrInUse` is retried before giving up
+    pub retries: u32,
+    /// How long to wait before the first retry, doubling before each one after it
+    pub retry_backoff: Duration,
+    /// Connections that can wait to be accepted before new ones are refused, which the OS may
+    /// cap, e.g. at `net.core.somaxconn` on Linux
+    pub backlog: u32,
+}
+
+impl BindOpts {
+    pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+    pub const DEFAULT_BACKLOG: u32 = 1024;
+}
+
+impl Default for BindOpts {
+    fn default() -> Self {
+        Self {
+            dual_stack: false,
+            reuse_address: true,
+            reuse_port: false,
+            retries: 0,
+            retry_backoff: Self::DEFAULT_RETRY_BACKOFF,
+            backlog: Self::DEFAULT_BACKLOG,
+        }
+    }
+}
+
+/// Runs `bind` until it succeeds, fails with anything but `AddrInUse`, or has been retried
+/// `opts.retries` times, backing off between tries.
+pub async fn with_retries<T>(
+    address: &str,
+    opts: &BindOpts,
+    mut bind: impl AsyncFnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = opts.retry_backoff;
+    for _ in 0..opts.retries {
+        match bind().await {
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                eprintln!("{} is in use, retrying in {:?}", address, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    bind().await
+}
+
+/// Binds a listener to the first address `address` resolves to that can be bound,
+/// e.g. `0.0.0.0:6969`, `[::]:6969` or `localhost:6969`, with its socket set up by `opts`.
+///
+/// Binding isn't retried, see [`with_retries`].
+pub async fn bind(address: &str, opts: &BindOpts) -> io::Result<TcpListener> {
+    let mut last_error = None;
+    for addr in tokio::net::lookup_host(address).await? {
+        match bind_addr(addr, opts) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} didn't resolve to any address", address),
+        )
+    }))
+}
+
+fn bind_addr(addr: SocketAddr, opts: &BindOpts) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!opts.dual_stack)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(opts.reuse_address)?;
+    if opts.reuse_port {
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SO_REUSEPORT isn't supported on this platform",
+        ));
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(listen_backlog(opts))?;
+    TcpListener::from_std(socket.into())
+}
+
+fn listen_backlog(opts: &BindOpts) -> i32 {
+    i32::try_from(opts.backlog).unwrap_or(i32::MAX)
+}
+
+/// The peer address Unix socket clients are recorded under, e.g. in access logs and by the
+/// rate limiter, as they have no IP address of their own
+#[cfg(unix)]
+pub const UNIX_PEER_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+/// The socket path `address` names, if it is one rather than a host and port.
+///
+/// A path is written with a `unix:` prefix or contains a `/`, e.g. `unix:helix.sock`
+/// or `/run/helix/helix.sock`.
+#[cfg(unix)]
+pub fn unix_socket_path(address: &str) -> Option<&Path> {
+    match address.strip_prefix("unix:") {
+        Some(path) => Some(Path::new(path)),
+        None => address.contains('/').then(|| Path::new(address)),
+    }
+}
+
+/// A listener on a Unix socket, whose file is removed when it is dropped
+#[cfg(unix)]
+pub struct UnixSocket {
+    pub listener: UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Binds a Unix socket at `path`, which can then be secured with filesystem permissions.
+    /// Only the backlog of `opts` applies to it.
+    ///
+    /// A socket file left at `path` by a process that didn't clean up is replaced, while one
+    /// that is still being listened on fails with `AddrInUse`.
+    pub fn bind(path: &Path, opts: &BindOpts) -> io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if let Ok(metadata) = std::fs::symlink_metadata(path)
+            && metadata.file_type().is_socket()
+            && std::os::unix::net::UnixStream::connect(path).is_err()
+        {
+            std::fs::remove_file(path)?;
+        }
+        let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SockAddr::unix(path)?)?;
+        socket.listen(listen_backlog(opts))?;
+        Ok(Self {
+            listener: UnixListener::from_std(socket.into())?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!("Failed to remove socket {}: {}", self.path.display(), e);
+        }
+    }
+}
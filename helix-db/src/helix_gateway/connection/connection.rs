@@ -5,27 +5,340 @@ use uuid::Uuid;
 use std::{
     net::SocketAddr,
     collections::HashMap,
-    sync::{Arc, Mutex},
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, RawFd};
+#[cfg(unix)]
+use tokio::net::UnixStream;
 use tokio::{
-    net::TcpListener,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::{Semaphore, watch},
     task::JoinHandle,
+    time::Sleep,
 };
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
 
-use crate::helix_gateway::{router::router::HelixRouter, thread_pool::thread_pool::ThreadPool};
+use crate::helix_gateway::{
+    connection::{
+        bind::{BindOpts, bind, with_retries},
+        rate_limiter::RateLimiter,
+    },
+    gateway::{GatewayOpts, TlsOpts},
+    health::{livez_handler, readyz_handler},
+    metrics::GatewayMetrics,
+    query_endpoint::query_handler,
+    version::{set_server_header, version_handler},
+    router::router::HelixRouter,
+    thread_pool::thread_pool::{Serving, ThreadPool, serve_connection},
+};
+#[cfg(unix)]
+use crate::helix_gateway::connection::bind::{UNIX_PEER_ADDR, UnixSocket, unix_socket_path};
+use crate::protocol::{request::Request, response::Response};
 
 pub struct ConnectionHandler {
     pub address: String,
     pub active_connections: Arc<Mutex<HashMap<String, ClientConnection>>>,
     pub thread_pool: ThreadPool,
+    // already bound listener to accept on instead of binding to `address`
+    listener: Mutex<Option<std::net::TcpListener>>,
+    bind_opts: BindOpts,
+    // sets TCP_NODELAY on accepted connections
+    nodelay: bool,
+    // the address actually bound, once accepting has started
+    local_addr: Mutex<Option<SocketAddr>>,
+    // terminates TLS on every accepted connection when set
+    tls: Option<TlsAcceptor>,
+    pub metrics: Arc<GatewayMetrics>,
+    max_connections: Option<usize>,
+    // sends `Server: helix-db/<version>` with rejections too
+    server_header: bool,
+    // set to true to stop the accept loop
+    pub(crate) shutdown: watch::Sender<bool>,
+    // closed once the accept loop has stopped
+    accept_loop: Mutex<Option<watch::Receiver<()>>>,
+    drain_timeout: Duration,
+    write_timeout: Option<Duration>,
 }
 
+/// How long a client has to complete the TLS handshake before it is dropped
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a rejected client has to send its request before it is dropped without a response
+const REJECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many rejected connections are answered at once, past which they're closed straight away
+/// so a flood of them can't pile up tasks waiting on their requests
+pub(crate) const MAX_REJECTING: usize = 64;
+
+/// How often shutdown checks whether every connection has closed
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// First file descriptor passed by systemd socket activation
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Set once the socket activation fd has been taken, so it isn't owned twice
+#[cfg(unix)]
+static LISTEN_FD_TAKEN: AtomicBool = AtomicBool::new(false);
+
 pub struct ClientConnection {
     pub id: String,
     pub last_active: DateTime<Utc>,
     pub addr: SocketAddr,
 }
 
+/// Snapshot of the connections a handler is serving
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub active_connections: usize,
+    pub max_connections: Option<usize>,
+    /// Kept-alive connections waiting for their next request
+    pub idle_connections: usize,
+    /// Kept-alive connections closed for sending nothing within the keep-alive timeout
+    pub keep_alive_timeouts: u64,
+    /// Connections closed after serving at least one request, and the requests they served
+    /// between them, which averages out to the requests each connection served
+    pub closed_connections: u64,
+    pub closed_connection_requests: u64,
+}
+
+/// An accepted connection, either plain TCP, TLS over TCP or a Unix socket.
+///
+/// Holds the connection's slot, so it counts as active until it is dropped.
+/// A write, flush or shutdown that makes no progress for the write timeout fails with
+/// `ErrorKind::TimedOut`, so a client that stops reading can't hold a worker.
+pub struct ClientStream {
+    transport: Transport,
+    addr: SocketAddr,
+    slot: Option<ConnectionSlot>,
+    write_timeout: Option<Duration>,
+    // started when a write first returns pending, and cleared once one goes through
+    write_deadline: Option<Pin<Box<Sleep>>>,
+    // read ahead while waiting for the next request, and returned by reads before the transport
+    unread: Vec<u8>,
+}
+
+impl ClientStream {
+    fn new(
+        transport: Transport,
+        addr: SocketAddr,
+        slot: Option<ConnectionSlot>,
+        write_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            transport,
+            addr,
+            slot,
+            write_timeout,
+            write_deadline: None,
+            unread: Vec::new(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Gives back bytes read past the end of a request, so they're read again as the start
+    /// of the next one
+    pub(crate) fn unread(&mut self, mut bytes: Vec<u8>) {
+        bytes.append(&mut self.unread);
+        self.unread = bytes;
+    }
+
+    /// Counts a request read from the connection, recorded once it closes
+    pub(crate) fn count_request(&mut self) {
+        if let Some(slot) = &mut self.slot {
+            slot.requests += 1;
+        }
+    }
+
+    /// Waits for the client to send more, keeping what it sends to be read first.
+    ///
+    /// Returns `false` if the client closed the connection instead.
+    pub(crate) async fn wait_for_data(&mut self) -> io::Result<bool> {
+        if !self.unread.is_empty() {
+            return Ok(true);
+        }
+        let mut buf = [0u8; 4096];
+        let read = self.read(&mut buf).await?;
+        self.unread.extend_from_slice(&buf[..read]);
+        Ok(read > 0)
+    }
+
+    /// Passes on the result of polling a write, failing it if it has been pending too long
+    fn check_write_timeout<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let (Poll::Pending, Some(write_timeout)) = (&poll, self.write_timeout) else {
+            self.write_deadline = None;
+            return poll;
+        };
+        let deadline = self
+            .write_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(write_timeout)));
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.write_deadline = None;
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Timeout writing to {}", self.addr),
+                )))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+enum Transport {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// What a handler accepts connections on
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixSocket),
+}
+
+impl Listener {
+    /// Binds a Unix socket if `address` is a path, otherwise a TCP listener, retrying
+    /// while the address is in use as `opts` allows
+    async fn bind(address: &str, opts: &BindOpts) -> io::Result<Self> {
+        #[cfg(unix)]
+        if let Some(path) = unix_socket_path(address) {
+            return with_retries(address, opts, async || UnixSocket::bind(path, opts))
+                .await
+                .map(Listener::Unix);
+        }
+        with_retries(address, opts, async || bind(address, opts).await)
+            .await
+            .map(Listener::Tcp)
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(_) => None,
+        }
+    }
+
+    /// Accepts the next connection, setting `TCP_NODELAY` on it if `nodelay` and it's TCP
+    async fn accept(&self, nodelay: bool) -> io::Result<(Transport, SocketAddr)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                if let Err(e) = stream.set_nodelay(nodelay) {
+                    eprintln!("Failed to set TCP_NODELAY: {}", e);
+                }
+                Ok((Transport::Plain(stream), addr))
+            }
+            #[cfg(unix)]
+            Listener::Unix(socket) => {
+                let (stream, _) = socket.listener.accept().await?;
+                Ok((Transport::Unix(stream), UNIX_PEER_ADDR))
+            }
+        }
+    }
+}
+
+/// A connection's place in the active count and `active_connections`,
+/// both of which it is removed from when dropped
+struct ConnectionSlot {
+    id: String,
+    metrics: Arc<GatewayMetrics>,
+    active_connections: Arc<Mutex<HashMap<String, ClientConnection>>>,
+    // requests read from the connection so far
+    requests: u64,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if self.requests > 0 {
+            self.metrics.record_connection(self.requests);
+        }
+        self.metrics.active_connections.fetch_sub(1, Ordering::AcqRel);
+        self.active_connections.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.unread.is_empty() {
+            let len = this.unread.len().min(buf.remaining());
+            buf.put_slice(&this.unread[..len]);
+            this.unread.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+        match &mut this.transport {
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = match &mut this.transport {
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        };
+        this.check_write_timeout(cx, poll)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = match &mut this.transport {
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Tls(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        };
+        this.check_write_timeout(cx, poll)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = match &mut this.transport {
+            Transport::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        };
+        this.check_write_timeout(cx, poll)
+    }
+}
+
 impl ConnectionHandler {
     pub fn new(
         address: &str,
@@ -33,69 +346,466 @@ impl ConnectionHandler {
         size: usize,
         router: HelixRouter,
     ) -> Result<Self, GraphError> {
+        let opts = GatewayOpts::builder()
+            .address(address)
+            .pool_size(size)
+            .build();
+        Self::with_opts(graph, router, &opts)
+    }
+
+    /// Creates a handler that binds to `opts.address` when it starts accepting.
+    ///
+    /// Fails if an endpoint enabled in `opts`, such as `/metrics`, has the method and path of a
+    /// route already added to `router`.
+    pub fn with_opts(
+        graph: Arc<HelixGraphEngine>,
+        router: HelixRouter,
+        opts: &GatewayOpts,
+    ) -> Result<Self, GraphError> {
+        Self::build(opts.address.clone(), None, graph, router, opts)
+    }
+
+    /// Creates a handler that accepts connections on an inherited, already bound listener
+    /// rather than binding to an address itself.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor for a bound and listening TCP socket
+    /// that is not owned by anything else, as the handler takes ownership of it.
+    #[cfg(unix)]
+    pub unsafe fn from_raw_fd(
+        fd: RawFd,
+        graph: Arc<HelixGraphEngine>,
+        size: usize,
+        router: HelixRouter,
+    ) -> Result<Self, GraphError> {
+        let opts = GatewayOpts::builder().pool_size(size).build();
+        unsafe { Self::from_raw_fd_with_opts(fd, graph, router, &opts) }
+    }
+
+    /// Same as [`ConnectionHandler::from_raw_fd`], with `opts.address` unused
+    ///
+    /// # Safety
+    ///
+    /// Has the same requirements on `fd` as [`ConnectionHandler::from_raw_fd`].
+    #[cfg(unix)]
+    pub unsafe fn from_raw_fd_with_opts(
+        fd: RawFd,
+        graph: Arc<HelixGraphEngine>,
+        router: HelixRouter,
+        opts: &GatewayOpts,
+    ) -> Result<Self, GraphError> {
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        let inherited_error =
+            |e| GraphError::GraphConnectionError("Failed to use inherited listener".to_string(), e);
+        listener.set_nonblocking(true).map_err(inherited_error)?;
+        let address = listener.local_addr().map_err(inherited_error)?.to_string();
+        Self::build(address, Some(listener), graph, router, opts)
+    }
+
+    fn build(
+        address: String,
+        listener: Option<std::net::TcpListener>,
+        graph: Arc<HelixGraphEngine>,
+        mut router: HelixRouter,
+        opts: &GatewayOpts,
+    ) -> Result<Self, GraphError> {
+        let metrics = Arc::new(GatewayMetrics::default());
+        if opts.metrics_endpoint {
+            let endpoint_metrics = Arc::clone(&metrics);
+            router.try_insert_route(
+                "GET",
+                "/metrics",
+                Arc::new(move |_, response| {
+                    response.body = endpoint_metrics.render().into_bytes();
+                    response.headers.insert(
+                        "Content-Type".to_string(),
+                        "text/plain; version=0.0.4".to_string(),
+                    );
+                    Ok(())
+                }),
+            )?;
+        }
+
+        // the built in endpoints fail the build rather than replace a route of the same path
+        if opts.query_endpoint {
+            router.try_add_route("POST", "/query", query_handler)?;
+        }
+
+        if opts.version_endpoint {
+            router.try_add_route("GET", "/version", version_handler)?;
+        }
+
+        if opts.health_endpoints {
+            router.try_add_route("GET", "/livez", livez_handler)?;
+            router.try_add_route("GET", "/readyz", readyz_handler)?;
+        }
+
+        // checked before the pool's workers are started
+        let tls = opts.tls.as_ref().map(TlsOpts::acceptor).transpose()?;
+        let rate_limiter = opts.rate_limit.as_ref().map(RateLimiter::new).transpose()?;
+        let thread_pool = ThreadPool::with_opts(graph, Arc::new(router), opts, Arc::clone(&metrics))?;
+        if let Some(rate_limiter) = rate_limiter {
+            thread_pool.rate_limit(rate_limiter);
+        }
         Ok(Self {
-            address: address.to_string(),
+            address,
             active_connections: Arc::new(Mutex::new(HashMap::new())),
-            thread_pool: ThreadPool::new(size, graph, Arc::new(router))?,
+            thread_pool,
+            listener: Mutex::new(listener),
+            bind_opts: opts.bind_opts(),
+            nodelay: opts.nodelay,
+            local_addr: Mutex::new(None),
+            tls,
+            metrics,
+            max_connections: opts.max_connections,
+            server_header: opts.server_header,
+            shutdown: watch::channel(false).0,
+            accept_loop: Mutex::new(None),
+            drain_timeout: opts.drain_timeout,
+            write_timeout: opts.write_timeout,
         })
     }
 
+    /// Terminates TLS on every accepted connection with `acceptor`, in place of one built
+    /// from [`GatewayOpts::tls`].
+    ///
+    /// Connections that fail the handshake, including plaintext ones, are closed
+    /// without being passed to the thread pool.
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// The address accepting connections, with the port the OS picked if `address` asked for
+    /// port 0, or `None` until [`ConnectionHandler::accept_conns`] has bound it.
+    ///
+    /// Also `None` when `address` is a Unix socket path, which is then the socket's address.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        *self.local_addr.lock().unwrap()
+    }
+
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            active_connections: self.metrics.active_connections(),
+            max_connections: self.max_connections,
+            idle_connections: self.metrics.idle_connections(),
+            keep_alive_timeouts: self.metrics.keep_alive_timeouts(),
+            closed_connections: self.metrics.closed_connections(),
+            closed_connection_requests: self.metrics.closed_connection_requests(),
+        }
+    }
+
+    /// Takes the listener fd passed through systemd socket activation, if there is one.
+    ///
+    /// Follows the `sd_listen_fds` protocol: `LISTEN_PID` must be this process and
+    /// `LISTEN_FDS` must be at least one, in which case the first fd is used. The variables are
+    /// removed so child processes don't take the fd for theirs, and the fd is only handed out
+    /// once per process, so a second gateway binds its own address instead.
+    ///
+    /// # Safety
+    ///
+    /// Removing the variables is only sound while no other thread reads or writes the
+    /// environment, so this should be called at startup, before such threads are spawned.
+    #[cfg(unix)]
+    pub unsafe fn take_listen_fd_from_env() -> Option<RawFd> {
+        let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+        let fds: RawFd = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+        if pid != std::process::id() {
+            return None;
+        }
+        // SAFETY: upheld by the caller
+        unsafe {
+            std::env::remove_var("LISTEN_PID");
+            std::env::remove_var("LISTEN_FDS");
+            std::env::remove_var("LISTEN_FDNAMES");
+        }
+        let taken = LISTEN_FD_TAKEN.swap(true, Ordering::AcqRel);
+        (fds >= 1 && !taken).then_some(SD_LISTEN_FDS_START)
+    }
+
     /// accepts new connections and sends them to the thread pool
     pub async fn accept_conns(&self) -> Result<JoinHandle<()>, GraphError> {
-        // Create a new TcpListener for each accept_conns call
-        let listener = TcpListener::bind(&self.address).await.map_err(|e| {
-            eprintln!("Failed to bind to address {}: {}", self.address, e);
-            GraphError::GraphConnectionError("Failed to bind to address".to_string(), e)
-        })?;
+        // Use the inherited listener if there is one, otherwise bind to the address
+        let inherited = self.listener.lock().unwrap().take();
+        let listener = match inherited {
+            Some(listener) => TcpListener::from_std(listener).map(Listener::Tcp).map_err(|e| {
+                GraphError::GraphConnectionError("Failed to use inherited listener".to_string(), e)
+            })?,
+            None => Listener::bind(&self.address, &self.bind_opts).await.map_err(|e| {
+                eprintln!("Failed to bind to address {}: {}", self.address, e);
+                GraphError::GraphConnectionError("Failed to bind to address".to_string(), e)
+            })?,
+        };
+        *self.local_addr.lock().unwrap() = listener.local_addr();
 
         // Log binding success to stderr since stdout might be buffered
 
-        let active_connections = Arc::clone(&self.active_connections);
-        let thread_pool_sender = self.thread_pool.sender.clone();
+        let dispatcher = Dispatcher {
+            sender: self.thread_pool.sender.clone(),
+            connection_tasks: self.thread_pool.connection_tasks(),
+            active_connections: Arc::clone(&self.active_connections),
+            metrics: Arc::clone(&self.metrics),
+            max_connections: self.max_connections,
+            rejecting: Arc::new(Semaphore::new(MAX_REJECTING)),
+            server_header: self.server_header,
+        };
+        let write_timeout = self.write_timeout;
+        let nodelay = self.nodelay;
+        let tls = self.tls.clone();
         let _address = self.address.clone();
+        let mut shutdown = self.shutdown.subscribe();
+        let (stopped, accept_loop) = watch::channel(());
+        *self.accept_loop.lock().unwrap() = Some(accept_loop);
 
 
         let handle = tokio::spawn(async move {
+            // dropped when the loop ends, which closes the listener too
+            let _stopped = stopped;
 
             loop {
-                match listener.accept().await {
+                let accepted = tokio::select! {
+                    accepted = listener.accept(nodelay) => accepted,
+                    // errors if the handler is dropped, which leaves the loop running
+                    Ok(_) = shutdown.wait_for(|stop| *stop) => break,
+                };
+                match accepted {
                     Ok((stream, addr)) => {
-
-                        // Configure TCP stream
-                        if let Err(e) = stream.set_nodelay(true) {
-                            eprintln!("Failed to set TCP_NODELAY: {}", e);
-                        }
-
-                        // Create a client connection record
-                        let client_id = Uuid::new_v4().to_string();
-                        let client = ClientConnection {
-                            id: client_id.clone(),
-                            last_active: Utc::now(),
-                            addr,
+                        // Take a slot, which adds it to the active connections
+                        let Some(slot) = dispatcher.acquire_slot(addr) else {
+                            eprintln!("Rejecting connection from {}: too many connections", addr);
+                            let rejecting = Arc::clone(&dispatcher.rejecting);
+                            let Ok(rejecting) = rejecting.try_acquire_owned() else {
+                                // closed without a response
+                                continue;
+                            };
+                            let mut response = too_many_connections();
+                            if dispatcher.server_header {
+                                set_server_header(&mut response);
+                            }
+                            let metrics = Arc::clone(&dispatcher.metrics);
+                            let tls = tls.clone();
+                            tokio::spawn(async move {
+                                reject(tls, stream, addr, response, metrics).await;
+                                drop(rejecting);
+                            });
+                            continue;
                         };
 
-                        // Add to active connections
-                        active_connections
-                            .lock()
-                            .unwrap()
-                            .insert(client_id.clone(), client);
-
-                        // Send to thread pool
-                        match thread_pool_sender.send_async(stream).await {
-                            Ok(_) => (),
-                            Err(e) => {
-                                eprintln!("Error sending connection {} to thread pool: {}", client_id, e);
-                                active_connections.lock().unwrap().remove(&client_id);
+                        // Plain connections go straight to the thread pool, TLS ones once
+                        // their handshake is done so a slow client doesn't hold up accepting
+                        let Some(acceptor) = tls.clone() else {
+                            let stream = ClientStream::new(stream, addr, Some(slot), write_timeout);
+                            dispatcher.dispatch(stream, addr).await;
+                            continue;
+                        };
+                        let dispatcher = dispatcher.clone();
+                        tokio::spawn(async move {
+                            if let Some(transport) = handshake(&acceptor, stream, addr).await {
+                                let stream = ClientStream::new(transport, addr, Some(slot), write_timeout);
+                                dispatcher.dispatch(stream, addr).await;
                             }
-                        }
+                        });
                     }
                     Err(e) => {
                         eprintln!("Error accepting connection: {}", e);
                     }
                 }
             }
+            // closed, removing a Unix socket's file, before shutdown hears the loop has stopped
+            drop(listener);
         });
 
         Ok(handle)
     }
+
+    /// Stops accepting connections and waits for the open ones to be answered and closed.
+    ///
+    /// Connections still open after the drain timeout are closed without a response.
+    /// The workers, or the connection tasks, are stopped either way, so the handler can't serve
+    /// again afterwards.
+    ///
+    /// Returns the number of connections that were closed at the timeout.
+    pub async fn shutdown(&self) -> usize {
+        let _ = self.shutdown.send(true);
+        let accept_loop = self.accept_loop.lock().unwrap().take();
+        if let Some(mut accept_loop) = accept_loop {
+            // errors once the loop has stopped and dropped its sender
+            let _ = accept_loop.changed().await;
+        }
+        // kept-alive connections waiting for another request have nothing left to answer
+        self.thread_pool.close_idle();
+
+        let drained = tokio::time::timeout(self.drain_timeout, async {
+            while self.metrics.active_connections() > 0 {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        })
+        .await;
+        let force_closed = match drained {
+            Ok(()) => 0,
+            Err(_) => self.metrics.active_connections(),
+        };
+        if force_closed > 0 {
+            eprintln!("Closing {} connections left after draining", force_closed);
+        }
+
+        self.thread_pool.stop();
+        force_closed
+    }
+
+    /// Accepts connections until `SIGINT` or `SIGTERM` is received, then shuts down gracefully
+    pub async fn serve_until_signal(&self) -> Result<usize, GraphError> {
+        let handle = self.accept_conns().await?;
+        wait_for_signal().await?;
+        println!("Shutting down, draining connections");
+        let force_closed = self.shutdown().await;
+        let _ = handle.await;
+        Ok(force_closed)
+    }
+}
+
+async fn wait_for_signal() -> Result<(), GraphError> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Hands accepted connections to the thread pool, or to tasks of their own
+#[derive(Clone)]
+struct Dispatcher {
+    sender: flume::Sender<ClientStream>,
+    connection_tasks: Option<Arc<Serving>>,
+    active_connections: Arc<Mutex<HashMap<String, ClientConnection>>>,
+    metrics: Arc<GatewayMetrics>,
+    max_connections: Option<usize>,
+    // permits for the rejected connections being answered
+    rejecting: Arc<Semaphore>,
+    server_header: bool,
+}
+
+impl Dispatcher {
+    /// Takes a slot for a new connection, unless every slot is in use
+    fn acquire_slot(&self, addr: SocketAddr) -> Option<ConnectionSlot> {
+        let max_connections = self.max_connections.unwrap_or(usize::MAX);
+        self.metrics
+            .active_connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < max_connections).then_some(active + 1)
+            })
+            .ok()?;
+
+        // Create a client connection record
+        let client_id = Uuid::new_v4().to_string();
+        let client = ClientConnection {
+            id: client_id.clone(),
+            last_active: Utc::now(),
+            addr,
+        };
+        self.active_connections
+            .lock()
+            .unwrap()
+            .insert(client_id.clone(), client);
+
+        Some(ConnectionSlot {
+            id: client_id,
+            metrics: Arc::clone(&self.metrics),
+            active_connections: Arc::clone(&self.active_connections),
+            requests: 0,
+        })
+    }
+
+    async fn dispatch(&self, stream: ClientStream, addr: SocketAddr) {
+        if let Some(serving) = &self.connection_tasks {
+            tokio::spawn(serve_connection(stream, Arc::clone(serving)));
+            return;
+        }
+
+        // counted before sending so a worker picking it up straight away can't go below zero
+        self.metrics.queued.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = self.sender.send_async(stream).await {
+            self.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+            eprintln!("Error sending connection from {} to thread pool: {}", addr, e);
+        }
+    }
+}
+
+/// Does the TLS handshake, giving up on clients that fail it or take too long.
+///
+/// Unix socket connections are passed through as they are, as TLS is only served over TCP.
+async fn handshake(
+    acceptor: &TlsAcceptor,
+    transport: Transport,
+    addr: SocketAddr,
+) -> Option<Transport> {
+    let Transport::Plain(stream) = transport else {
+        return Some(transport);
+    };
+    match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => Some(Transport::Tls(Box::new(stream))),
+        Ok(Err(e)) => {
+            eprintln!("TLS handshake with {} failed: {}", addr, e);
+            None
+        }
+        Err(_) => {
+            eprintln!("TLS handshake with {} timed out", addr);
+            None
+        }
+    }
+}
+
+/// Answers a connection that was not given a slot
+async fn reject(
+    tls: Option<TlsAcceptor>,
+    stream: Transport,
+    addr: SocketAddr,
+    response: Response,
+    metrics: Arc<GatewayMetrics>,
+) {
+    let transport = match tls {
+        Some(acceptor) => match handshake(&acceptor, stream, addr).await {
+            Some(transport) => transport,
+            None => return,
+        },
+        None => stream,
+    };
+    let mut stream = ClientStream::new(transport, addr, None, None);
+    respond_and_close(&mut stream, response, &metrics).await;
+}
+
+/// Reads the request and answers it with `response` rather than passing it to the router
+async fn respond_and_close(
+    stream: &mut ClientStream,
+    mut response: Response,
+    metrics: &GatewayMetrics,
+) {
+    // reading the request first means closing the connection won't reset it
+    // before the client has read the response
+    match tokio::time::timeout(REJECT_TIMEOUT, Request::from_stream(stream)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            eprintln!("Error parsing request: {:?}", e);
+            return;
+        }
+        Err(_) => return,
+    }
+    let started = Instant::now();
+    if let Err(e) = response.send(stream).await {
+        eprintln!("Error sending response: {:?}", e);
+    }
+    metrics.record_request(response.status, started.elapsed());
+}
+
+/// `503 Service Unavailable`
+fn too_many_connections() -> Response {
+    Response::error(503, "too_many_connections", "Too many connections")
 }
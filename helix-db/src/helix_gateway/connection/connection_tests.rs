@@ -0,0 +1,1563 @@
+use std::{
+    future::Future,
+    io::{Read, Write},
+    os::fd::IntoRawFd,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{ClientConfig, RootCertStore, crypto::ring, pki_types::ServerName},
+};
+
+use super::connection::{ClientStream, ConnectionHandler, ConnectionStats, MAX_REJECTING};
+use crate::{
+    helix_engine::{
+        graph_core::{
+            config::Config,
+            graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+        },
+        types::GraphError,
+    },
+    helix_gateway::{
+        access_log::RequestLog,
+        cors::CorsOpts,
+        gateway::{GatewayOpts, RateLimitOpts, TlsOpts},
+        router::router::{HandlerInput, HelixRouter},
+    },
+    protocol::{
+        binary::{BinaryRequest, BinaryResponse, WireProtocol},
+        response::Response,
+        sse::SseEvent,
+        websocket::{Frame, WebSocket},
+    },
+};
+
+fn setup_test_engine() -> (Arc<HelixGraphEngine>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    (Arc::new(HelixGraphEngine::new(opts).unwrap()), temp_dir)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_from_raw_fd_accepts_on_inherited_listener() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd(listener.into_raw_fd(), graph, 1, HelixRouter::new(None, None))
+    }
+    .unwrap();
+    assert_eq!(handler.address, addr.to_string());
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..n]).to_string()
+    })
+    .await
+    .unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn test_listen_fd_taken_from_env_once() {
+    // SAFETY: no other test reads or writes these variables
+    unsafe {
+        std::env::set_var("LISTEN_PID", (std::process::id() + 1).to_string());
+        std::env::set_var("LISTEN_FDS", "1");
+        // passed to another process, so left for it
+        assert_eq!(ConnectionHandler::take_listen_fd_from_env(), None);
+        assert!(std::env::var("LISTEN_FDS").is_ok());
+
+        std::env::set_var("LISTEN_PID", std::process::id().to_string());
+        assert_eq!(ConnectionHandler::take_listen_fd_from_env(), Some(3));
+        assert!(std::env::var("LISTEN_PID").is_err());
+        assert!(std::env::var("LISTEN_FDS").is_err());
+
+        // a second gateway in the process can't take the same fd
+        std::env::set_var("LISTEN_PID", std::process::id().to_string());
+        std::env::set_var("LISTEN_FDS", "1");
+        assert_eq!(ConnectionHandler::take_listen_fd_from_env(), None);
+    }
+}
+
+const TEST_CERT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/helix_gateway/connection/test_certs/cert.pem"
+);
+const TEST_KEY: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/helix_gateway/connection/test_certs/key.pem"
+);
+
+async fn start_tls_handler(graph: Arc<HelixGraphEngine>) -> std::net::SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .tls(TlsOpts {
+            cert_path: TEST_CERT.to_string(),
+            key_path: TEST_KEY.to_string(),
+        })
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+    // the accept loop keeps running after the handler is dropped
+    addr
+}
+
+fn test_connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    let cert_file = std::fs::File::open(TEST_CERT).unwrap();
+    for cert in rustls_pemfile::certs(&mut std::io::BufReader::new(cert_file)) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls_connection_is_served() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let addr = start_tls_handler(graph).await;
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let domain = ServerName::try_from("localhost").unwrap();
+    let mut stream = test_connector().connect(domain, stream).await.unwrap();
+    stream
+        .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await.unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plaintext_rejected_when_tls_enabled() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let addr = start_tls_handler(graph).await;
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        // the server closes the connection after failing the handshake
+        let _ = stream.read_to_end(&mut response);
+        response
+    })
+    .await
+    .unwrap();
+
+    assert!(!response.starts_with(b"HTTP/"));
+}
+
+#[test]
+fn test_tls_opts_missing_cert() {
+    let tls = TlsOpts {
+        cert_path: "/nonexistent/cert.pem".to_string(),
+        key_path: TEST_KEY.to_string(),
+    };
+    assert!(tls.acceptor().is_err());
+}
+
+#[test]
+fn test_creating_handler_fails_on_bad_tls_or_rate_limit() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let missing_cert = GatewayOpts::builder()
+        .address("127.0.0.1:0")
+        .tls(TlsOpts {
+            cert_path: "/nonexistent/cert.pem".to_string(),
+            key_path: TEST_KEY.to_string(),
+        })
+        .build();
+    assert!(
+        ConnectionHandler::with_opts(Arc::clone(&graph), HelixRouter::new(None, None), &missing_cert)
+            .is_err()
+    );
+
+    let no_requests = GatewayOpts::builder()
+        .address("127.0.0.1:0")
+        .rate_limit(RateLimitOpts {
+            requests_per_second: 0.0,
+            burst: 1,
+        })
+        .build();
+    assert!(ConnectionHandler::with_opts(graph, HelixRouter::new(None, None), &no_requests).is_err());
+}
+
+fn send_plain_request(addr: std::net::SocketAddr) -> String {
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..n]).to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rate_limited_client_gets_429() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .rate_limit(RateLimitOpts {
+            requests_per_second: 0.1,
+            burst: 2,
+        })
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let responses = tokio::task::spawn_blocking(move || {
+        (0..3).map(|_| send_plain_request(addr)).collect::<Vec<_>>()
+    })
+    .await
+    .unwrap();
+
+    assert!(responses[0].starts_with("HTTP/1.1 404"));
+    assert!(responses[1].starts_with("HTTP/1.1 404"));
+    assert!(responses[2].starts_with("HTTP/1.1 429 Too Many Requests"));
+    assert!(responses[2].contains("Retry-After: 10\r\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rate_limit_applies_to_each_request_on_a_connection() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .keep_alive(std::time::Duration::from_secs(5))
+        .rate_limit(RateLimitOpts {
+            requests_per_second: 0.1,
+            burst: 3,
+        })
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let request = "GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        assert!(read_response(&mut stream).starts_with("HTTP/1.1 404"));
+
+        // pipelined on the kept-alive connection, one past the burst
+        stream.write_all(request.repeat(3).as_bytes()).unwrap();
+        assert!(read_response(&mut stream).starts_with("HTTP/1.1 404"));
+        assert!(read_response(&mut stream).starts_with("HTTP/1.1 404"));
+        let limited = read_response(&mut stream);
+        assert!(limited.starts_with("HTTP/1.1 429 Too Many Requests"));
+        assert!(limited.contains("Retry-After: 10\r\n"));
+        assert!(limited.contains("\r\nKeep-Alive: timeout=5\r\n"));
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connections_over_max_get_503() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let opts = GatewayOpts::builder().pool_size(1).max_connections(1).build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let (first, rejected) = tokio::task::spawn_blocking(move || {
+        // holds the only slot until it sends its request
+        let mut first = std::net::TcpStream::connect(addr).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let rejected = send_plain_request(addr);
+
+        first
+            .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let n = first.read(&mut buf).unwrap();
+        (String::from_utf8_lossy(&buf[..n]).to_string(), rejected)
+    })
+    .await
+    .unwrap();
+
+    assert!(rejected.starts_with("HTTP/1.1 503 Service Unavailable"));
+    assert!(first.starts_with("HTTP/1.1 404"));
+
+    // the slot is released once the worker drops the connection
+    for _ in 0..50 {
+        if handler.stats().active_connections == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        handler.stats(),
+        ConnectionStats {
+            active_connections: 0,
+            max_connections: Some(1),
+            idle_connections: 0,
+            keep_alive_timeouts: 0,
+            closed_connections: 1,
+            closed_connection_requests: 1,
+        }
+    );
+    assert!(handler.active_connections.lock().unwrap().is_empty());
+
+    let response = tokio::task::spawn_blocking(move || send_plain_request(addr))
+        .await
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rejected_connections_past_bound_closed_at_once() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let opts = GatewayOpts::builder().pool_size(1).max_connections(1).build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let _first = std::net::TcpStream::connect(addr).unwrap();
+        // rejected, left waiting on requests that are never sent
+        let _rejecting = (0..MAX_REJECTING)
+            .map(|_| std::net::TcpStream::connect(addr).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut dropped = std::net::TcpStream::connect(addr).unwrap();
+        dropped
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        // closed without waiting for a request, rather than timing out
+        match dropped.read(&mut [0u8; 1]) {
+            Ok(n) => assert_eq!(n, 0),
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset),
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_binds_ipv6_address() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder().address("[::1]:0").pool_size(1).build();
+    let handler = ConnectionHandler::with_opts(graph, HelixRouter::new(None, None), &opts).unwrap();
+    assert_eq!(handler.local_addr(), None);
+    handler.accept_conns().await.unwrap();
+
+    let addr = handler.local_addr().unwrap();
+    assert!(addr.is_ipv6());
+    assert_ne!(addr.port(), 0);
+    let response = tokio::task::spawn_blocking(move || send_plain_request(addr))
+        .await
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dual_stack_accepts_ipv4_on_ipv6_address() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let ipv4_on = |addr: std::net::SocketAddr| {
+        std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, addr.port()))
+    };
+
+    let opts = GatewayOpts::builder()
+        .address("[::]:0")
+        .dual_stack(true)
+        .pool_size(1)
+        .build();
+    let handler =
+        ConnectionHandler::with_opts(Arc::clone(&graph), HelixRouter::new(None, None), &opts)
+            .unwrap();
+    handler.accept_conns().await.unwrap();
+    let addr = handler.local_addr().unwrap();
+    let responses = tokio::task::spawn_blocking(move || {
+        let ipv6 = std::net::SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, addr.port()));
+        [send_plain_request(ipv4_on(addr)), send_plain_request(ipv6)]
+    })
+    .await
+    .unwrap();
+    assert!(responses.iter().all(|response| response.starts_with("HTTP/1.1 404")));
+
+    // without it the same address only takes IPv6 connections
+    let opts = GatewayOpts::builder().address("[::]:0").pool_size(1).build();
+    let handler = ConnectionHandler::with_opts(graph, HelixRouter::new(None, None), &opts).unwrap();
+    handler.accept_conns().await.unwrap();
+    let addr = handler.local_addr().unwrap();
+    assert!(std::net::TcpStream::connect(ipv4_on(addr)).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_serves_on_unix_socket_and_removes_it_on_shutdown() {
+    let (graph, temp_dir) = setup_test_engine();
+    let path = temp_dir.path().join("helix.sock");
+    let opts = GatewayOpts::builder()
+        .address(path.to_str().unwrap())
+        .pool_size(1)
+        .build();
+    let handler = ConnectionHandler::with_opts(graph, HelixRouter::new(None, None), &opts).unwrap();
+    handler.accept_conns().await.unwrap();
+    assert!(path.exists());
+    assert_eq!(handler.local_addr(), None);
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    assert_eq!(handler.shutdown().await, 0);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_unix_socket_path() {
+    use super::bind::unix_socket_path;
+    use std::path::Path;
+
+    assert_eq!(unix_socket_path("unix:helix.sock"), Some(Path::new("helix.sock")));
+    assert_eq!(unix_socket_path("/run/helix.sock"), Some(Path::new("/run/helix.sock")));
+    assert_eq!(unix_socket_path("0.0.0.0:6969"), None);
+    assert_eq!(unix_socket_path("[::]:6969"), None);
+}
+
+#[tokio::test]
+async fn test_reuse_port_lets_two_listeners_share_a_port() {
+    use super::bind::{BindOpts, bind};
+
+    let opts = BindOpts {
+        reuse_port: true,
+        ..BindOpts::default()
+    };
+    let first = bind("127.0.0.1:0", &opts).await.unwrap();
+    let address = first.local_addr().unwrap().to_string();
+    let second = bind(&address, &opts).await.unwrap();
+    assert_eq!(second.local_addr().unwrap(), first.local_addr().unwrap());
+
+    // both listeners have to set it
+    let error = bind(&address, &BindOpts::default()).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+}
+
+#[tokio::test]
+async fn test_bind_retries_while_address_is_in_use() {
+    use super::bind::{BindOpts, bind, with_retries};
+    use std::time::Duration;
+
+    let taken = bind("127.0.0.1:0", &BindOpts::default()).await.unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+    let error = with_retries(&address, &BindOpts::default(), async || {
+        bind(&address, &BindOpts::default()).await
+    })
+    .await
+    .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        drop(taken);
+    });
+    let opts = BindOpts {
+        retries: 5,
+        retry_backoff: Duration::from_millis(50),
+        ..BindOpts::default()
+    };
+    let listener = with_retries(&address, &opts, async || bind(&address, &opts).await)
+        .await
+        .unwrap();
+    assert_eq!(listener.local_addr().unwrap().to_string(), address);
+}
+
+#[test]
+fn test_gateway_opts_builder() {
+    let defaults = GatewayOpts::builder().build();
+    assert_eq!(defaults.address, GatewayOpts::DEFAULT_ADDRESS);
+    assert!(!defaults.dual_stack);
+    assert!(defaults.reuse_address);
+    assert!(!defaults.reuse_port);
+    assert_eq!(defaults.bind_retries, 0);
+    assert_eq!(defaults.listen_backlog, 1024);
+    assert!(defaults.nodelay);
+    assert!(!defaults.query_endpoint);
+    assert!(!defaults.version_endpoint);
+    assert!(!defaults.health_endpoints);
+    #[cfg(feature = "task-per-connection")]
+    assert!(!defaults.task_per_connection);
+    assert!(!defaults.socket_activation);
+    assert_eq!(defaults.compression, None);
+    assert!(!defaults.server_header);
+    assert_eq!(defaults.pool_size, GatewayOpts::DEFAULT_POOL_SIZE);
+    assert_eq!(defaults.max_body_size, None);
+    assert_eq!(defaults.read_timeout, None);
+    assert_eq!(defaults.header_timeout, Some(GatewayOpts::DEFAULT_HEADER_TIMEOUT));
+    assert_eq!(defaults.body_timeout, Some(GatewayOpts::DEFAULT_BODY_TIMEOUT));
+    assert_eq!(defaults.write_timeout, None);
+    assert_eq!(defaults.request_timeout, None);
+    assert_eq!(defaults.keep_alive, None);
+    assert_eq!(defaults.max_connections, None);
+
+    let opts = GatewayOpts::builder()
+        .address("127.0.0.1:7000")
+        .pool_size(2)
+        .max_body_size(1024)
+        .read_timeout(std::time::Duration::from_secs(1))
+        .header_timeout(std::time::Duration::from_millis(500))
+        .body_timeout(None)
+        .write_timeout(std::time::Duration::from_millis(200))
+        .request_timeout(std::time::Duration::from_secs(2))
+        .max_connections(10)
+        .reuse_port(true)
+        .bind_retries(3, std::time::Duration::from_millis(20))
+        .listen_backlog(4096)
+        .nodelay(false)
+        .build();
+    assert_eq!(opts.address, "127.0.0.1:7000");
+    assert_eq!(opts.pool_size, 2);
+    assert_eq!(opts.max_body_size, Some(1024));
+    assert_eq!(opts.read_timeout, Some(std::time::Duration::from_secs(1)));
+    assert_eq!(opts.header_timeout, Some(std::time::Duration::from_millis(500)));
+    assert_eq!(opts.body_timeout, None);
+    assert_eq!(opts.write_timeout, Some(std::time::Duration::from_millis(200)));
+    assert_eq!(opts.request_timeout, Some(std::time::Duration::from_secs(2)));
+    assert_eq!(opts.max_connections, Some(10));
+    let bind_opts = opts.bind_opts();
+    assert!(bind_opts.reuse_port);
+    assert_eq!(bind_opts.retries, 3);
+    assert_eq!(bind_opts.retry_backoff, std::time::Duration::from_millis(20));
+    assert_eq!(bind_opts.backlog, 4096);
+    assert!(!opts.nodelay);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_request_over_max_body_size_gets_413() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .max_body_size(4)
+        .max_connections(5)
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    assert_eq!(handler.stats().max_connections, Some(5));
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let (small, large) = tokio::task::spawn_blocking(move || {
+        let send = |body: &str| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            let request = format!(
+                "POST /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = Vec::new();
+            let _ = stream.read_to_end(&mut response);
+            String::from_utf8_lossy(&response).to_string()
+        };
+        (send("abcd"), send("abcdefgh"))
+    })
+    .await
+    .unwrap();
+
+    assert!(small.starts_with("HTTP/1.1 404"));
+    assert!(large.starts_with("HTTP/1.1 413 Payload Too Large"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invalid_percent_encoded_path_is_bad_request() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd(listener.into_raw_fd(), graph, 1, HelixRouter::new(None, None))
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /nodes/%zz HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        String::from_utf8_lossy(&response).to_string()
+    })
+    .await
+    .unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(response.ends_with(
+        r#"{"error":{"code":"invalid_path","message":"Invalid percent-encoding in path: /nodes/%zz"}}"#
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ambiguous_body_length_answered_then_connection_closed() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd(listener.into_raw_fd(), graph, 1, HelixRouter::new(None, None))
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let send = |request: &'static [u8]| {
+        tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(request).unwrap();
+            let mut response = Vec::new();
+            let _ = stream.read_to_end(&mut response);
+            String::from_utf8_lossy(&response).to_string()
+        })
+    };
+    // the request smuggled in what the second Content-Length counts as body is never answered
+    let duplicated = send(
+        b"POST /missing HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 24\r\n\r\n\
+          GET /missing HTTP/1.1\r\n\r\n",
+    );
+    let chunked = send(
+        b"POST /missing HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    );
+    let negative = send(b"POST /missing HTTP/1.1\r\nContent-Length: -1\r\n\r\n");
+
+    let duplicated = duplicated.await.unwrap();
+    assert!(duplicated.starts_with("HTTP/1.1 400"));
+    assert!(duplicated.contains("\r\nConnection: close\r\n"));
+    assert_eq!(duplicated.matches("HTTP/1.1 ").count(), 1);
+    assert!(chunked.await.unwrap().starts_with("HTTP/1.1 501 Not Implemented"));
+    let negative = negative.await.unwrap();
+    assert!(negative.starts_with("HTTP/1.1 400"));
+    assert!(negative.contains(r#""code":"invalid_content_length""#));
+}
+
+static ACCESS_LOGS: Mutex<Vec<RequestLog>> = Mutex::new(Vec::new());
+
+fn record_access_log(log: &RequestLog) {
+    ACCESS_LOGS.lock().unwrap().push(log.clone());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_access_log_called_after_response() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .access_log(record_access_log)
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let response = tokio::task::spawn_blocking(move || send_plain_request(addr))
+        .await
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    // the log is written after the response is flushed, so it may lag behind the read
+    let mut log = None;
+    for _ in 0..50 {
+        log = ACCESS_LOGS.lock().unwrap().first().cloned();
+        if log.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let log = log.unwrap();
+    assert_eq!(log.method, "GET");
+    assert_eq!(log.path, "/missing");
+    assert_eq!(log.version, "HTTP/1.1");
+    assert_eq!(log.status, 404);
+    let content_length = format!("Content-Length: {}\r\n", log.bytes);
+    assert!(response.contains(&content_length));
+    assert_eq!(log.addr.ip(), addr.ip());
+    // the request was sent without an ID, so the one it was given is echoed and logged
+    assert!(uuid::Uuid::parse_str(&log.request_id).is_ok());
+    assert!(response.contains(&format!("X-Request-Id: {}\r\n", log.request_id)));
+}
+
+/// Reads one response with a `Content-Length` body, leaving the connection open
+fn read_response(stream: &mut std::net::TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8(head).unwrap();
+    let length = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length: "))
+        .map_or(0, |length| length.parse().unwrap());
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).unwrap();
+    head + &String::from_utf8_lossy(&body)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keep_alive_serves_requests_on_one_connection() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .keep_alive(std::time::Duration::from_millis(300))
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let request = b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut kept = std::net::TcpStream::connect(addr).unwrap();
+        kept.write_all(request).unwrap();
+        let first = read_response(&mut kept);
+        assert!(first.starts_with("HTTP/1.1 404"));
+        assert!(first.contains("\r\nKeep-Alive: timeout=1\r\n"));
+
+        // the idle connection doesn't hold the only worker
+        let mut other = std::net::TcpStream::connect(addr).unwrap();
+        other.write_all(request).unwrap();
+        assert!(read_response(&mut other).starts_with("HTTP/1.1 404"));
+
+        kept.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let last = read_response(&mut kept);
+        assert!(last.starts_with("HTTP/1.1 404"));
+        assert!(last.contains("\r\nConnection: close\r\n"));
+        assert_eq!(kept.read(&mut [0u8; 1]).unwrap(), 0);
+
+        // closed once it has been idle for the keep-alive timeout
+        assert_eq!(other.read(&mut [0u8; 1]).unwrap(), 0);
+    })
+    .await
+    .unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+    while handler.stats().closed_connections < 2 && std::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let stats = handler.stats();
+    assert_eq!(stats.closed_connections, 2);
+    assert_eq!(stats.closed_connection_requests, 3);
+    assert_eq!(stats.keep_alive_timeouts, 1);
+    assert_eq!(stats.idle_connections, 0);
+}
+
+fn echo_body(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    response.body = input.request.body.clone();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipelined_requests_answered_in_order() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(2)
+        .keep_alive(std::time::Duration::from_secs(1))
+        .build();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/echo", echo_body);
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        // all three arrive in one write, so they're read into the same buffer
+        stream
+            .write_all(
+                b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nfirst\
+                  POST /echo HTTP/1.1\r\nContent-Length: 11\r\n\r\nsecond body\
+                  POST /echo HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\n\r\nthird",
+            )
+            .unwrap();
+
+        let first = read_response(&mut stream);
+        assert!(first.starts_with("HTTP/1.1 200"));
+        assert!(first.ends_with("\r\n\r\nfirst"));
+        let second = read_response(&mut stream);
+        assert!(second.ends_with("\r\n\r\nsecond body"));
+        let third = read_response(&mut stream);
+        assert!(third.ends_with("\r\n\r\nthird"));
+        assert!(third.contains("\r\nConnection: close\r\n"));
+        assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metrics_endpoint() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .metrics_endpoint(true)
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let metrics = tokio::task::spawn_blocking(move || {
+        assert!(send_plain_request(addr).starts_with("HTTP/1.1 404"));
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).to_string()
+    })
+    .await
+    .unwrap();
+
+    assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+    // the request for the metrics is counted once its response is sent
+    assert!(metrics.contains("helix_requests_total 1\n"));
+    assert!(metrics.contains("helix_responses_total{status_class=\"4xx\"} 1\n"));
+    assert!(metrics.contains("helix_active_connections 1\n"));
+    assert!(metrics.contains("helix_thread_pool_queue_depth 0\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_version_endpoint_and_server_header() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .version_endpoint(true)
+        .server_header(true)
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let (missing, version) = tokio::task::spawn_blocking(move || {
+        let missing = send_plain_request(addr);
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /version HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        (missing, String::from_utf8_lossy(&response).to_string())
+    })
+    .await
+    .unwrap();
+
+    let server = format!("\r\nServer: helix-db/{}\r\n", env!("CARGO_PKG_VERSION"));
+    assert!(missing.starts_with("HTTP/1.1 404"));
+    assert!(missing.contains(&server));
+    assert!(version.starts_with("HTTP/1.1 200"));
+    assert!(version.contains(&server));
+    assert!(version.contains(&format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION"))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_builtin_endpoint_conflicting_with_route_fails_build() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("GET", "/version", |_: &HandlerInput, _: &mut Response| Ok(()));
+    let opts = GatewayOpts::builder()
+        .address("127.0.0.1:0")
+        .pool_size(1)
+        .version_endpoint(true)
+        .build();
+
+    let result = ConnectionHandler::with_opts(graph, router, &opts);
+    assert!(matches!(result, Err(GraphError::New(msg)) if msg.contains("GET /version")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metrics_endpoint_disabled_by_default() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd(listener.into_raw_fd(), graph, 1, HelixRouter::new(None, None))
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..n]).to_string()
+    })
+    .await
+    .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cors_preflight_answered_by_gateway() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .cors(CorsOpts::default())
+        .build();
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            &opts,
+        )
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    let send = move |request: &'static str| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).to_string()
+    };
+    let (preflight, response) = tokio::task::spawn_blocking(move || {
+        (
+            send("OPTIONS /missing HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\n\r\n"),
+            send("GET /missing HTTP/1.1\r\nOrigin: https://app.example.com\r\nContent-Length: 0\r\n\r\n"),
+        )
+    })
+    .await
+    .unwrap();
+
+    assert!(preflight.starts_with("HTTP/1.1 204 No Content"));
+    assert!(preflight.contains("Access-Control-Allow-Methods: GET, POST, PUT, DELETE\r\n"));
+    // responses from the router get the headers too
+    assert!(response.starts_with("HTTP/1.1 404"));
+    assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+}
+
+async fn start_handler_with_opts(
+    graph: Arc<HelixGraphEngine>,
+    opts: &GatewayOpts,
+) -> (ConnectionHandler, std::net::SocketAddr) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(
+            listener.into_raw_fd(),
+            graph,
+            HelixRouter::new(None, None),
+            opts,
+        )
+    }
+    .unwrap();
+    handler.accept_conns().await.unwrap();
+    (handler, addr)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stalled_write_times_out_and_frees_worker() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.routes.insert(
+        ("GET".to_string(), "/large".to_string()),
+        Arc::new(|_, response| {
+            // far more than the socket buffers can hold
+            response.body = vec![0u8; 64 * 1024 * 1024];
+            Ok(())
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .write_timeout(std::time::Duration::from_millis(200))
+        .build();
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    handler.accept_conns().await.unwrap();
+
+    // sends its request, then never reads the response
+    let mut stalled = std::net::TcpStream::connect(addr).unwrap();
+    stalled
+        .write_all(b"GET /large HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+
+    for _ in 0..100 {
+        if handler.stats().active_connections == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(handler.stats().active_connections, 0);
+
+    // the only worker is free again
+    let response = tokio::task::spawn_blocking(move || send_plain_request(addr))
+        .await
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+    drop(stalled);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_request_timeout_bounds_slow_requests() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.routes.insert(
+        ("GET".to_string(), "/slow".to_string()),
+        Arc::new(|_, _| {
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            Ok(())
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .request_timeout(std::time::Duration::from_millis(200))
+        .build();
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    handler.accept_conns().await.unwrap();
+
+    let responses = tokio::task::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        let mut slow_handler = std::net::TcpStream::connect(addr).unwrap();
+        slow_handler
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = slow_handler.read_to_end(&mut response);
+        let slow_handler = String::from_utf8_lossy(&response).to_string();
+        let answered_in = started.elapsed();
+
+        // the only worker isn't held by the handler still running
+        let next = send_plain_request(addr);
+
+        // a client that never finishes its headers
+        let mut slow_client = std::net::TcpStream::connect(addr).unwrap();
+        slow_client.write_all(b"GET /missing HTTP/1.1\r\n").unwrap();
+        let mut response = Vec::new();
+        let _ = slow_client.read_to_end(&mut response);
+        let slow_client = String::from_utf8_lossy(&response).to_string();
+        (slow_handler, answered_in, next, slow_client)
+    })
+    .await
+    .unwrap();
+
+    let (slow_handler, answered_in, next, slow_client) = responses;
+    assert!(slow_handler.starts_with("HTTP/1.1 503"));
+    assert!(slow_handler.contains(r#""code":"handler_timeout""#));
+    assert!(answered_in < std::time::Duration::from_millis(900));
+    assert!(next.starts_with("HTTP/1.1 404"));
+    assert!(slow_client.starts_with("HTTP/1.1 408"));
+    assert!(slow_client.contains(r#""code":"request_timeout""#));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown_refuses_new_connections() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder().pool_size(1).build();
+    let (handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    assert_eq!(handler.shutdown().await, 0);
+    assert!(std::net::TcpStream::connect(addr).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown_waits_for_in_flight_request() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder().pool_size(1).build();
+    let (handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"POST /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\n")
+        .unwrap();
+    wait_for_active_connections(&handler, 1).await;
+
+    let mut stopping = handler.shutdown.subscribe();
+    let client = async move {
+        // the body arrives after shutdown has started
+        stopping.wait_for(|stopping| *stopping).await.unwrap();
+        tokio::task::spawn_blocking(move || {
+            stream.write_all(b"abcd").unwrap();
+            let mut response = Vec::new();
+            let _ = stream.read_to_end(&mut response);
+            String::from_utf8_lossy(&response).to_string()
+        })
+        .await
+    };
+    let (force_closed, response) = tokio::join!(handler.shutdown(), client);
+
+    assert_eq!(force_closed, 0);
+    assert!(response.unwrap().starts_with("HTTP/1.1 404"));
+    assert_eq!(handler.metrics.active_connections(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown_force_closes_after_drain_timeout() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .drain_timeout(std::time::Duration::from_millis(100))
+        .build();
+    assert_eq!(GatewayOpts::default().drain_timeout, GatewayOpts::DEFAULT_DRAIN_TIMEOUT);
+    let (handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    // never sends a request, so it can't finish on its own
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    wait_for_active_connections(&handler, 1).await;
+
+    assert_eq!(handler.shutdown().await, 1);
+    let mut response = Vec::new();
+    let _ = tokio::task::spawn_blocking(move || stream.read_to_end(&mut response).map(|_| response))
+        .await
+        .unwrap();
+}
+
+fn echo_websocket(
+    request: crate::protocol::request::Request,
+    mut websocket: WebSocket<ClientStream>,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        while let Ok(Some(frame)) = websocket.read_frame().await {
+            match frame {
+                Frame::Text(text) => {
+                    let echoed = format!("{} {}", request.path, text);
+                    if websocket.send_text(&echoed).await.is_err() {
+                        break;
+                    }
+                }
+                Frame::Close(_) => break,
+                _ => {}
+            }
+        }
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_websocket_upgrade_handed_to_callback() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .on_websocket(echo_websocket)
+        .build();
+    let (_handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"GET /live HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut handshake = Vec::new();
+    while !handshake.ends_with(b"\r\n\r\n") {
+        handshake.push(stream.read_u8().await.unwrap());
+    }
+    let handshake = String::from_utf8(handshake).unwrap();
+    assert!(handshake.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(handshake.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+    // the websocket runs off the only worker, which is still free for plain requests
+    let addr_copy = addr;
+    let plain = tokio::task::spawn_blocking(move || send_plain_request(addr_copy))
+        .await
+        .unwrap();
+    assert!(plain.starts_with("HTTP/1.1 404"));
+
+    // a masked text frame with an all zero mask
+    stream.write_all(&[0x81, 0x82, 0, 0, 0, 0, b'h', b'i']).await.unwrap();
+    let mut echoed = [0u8; 10];
+    stream.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed[..2], &[0x81, 8]);
+    assert_eq!(&echoed[2..], b"/live hi");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_websocket_upgrade_routed_without_callback() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder().pool_size(1).build();
+    let (_handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /live HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: a2V5\r\nSec-WebSocket-Version: 13\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        String::from_utf8_lossy(&response).to_string()
+    })
+    .await
+    .unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sse_streamed_off_the_worker() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let (release, wait_for_release) = std::sync::mpsc::channel::<()>();
+    let wait_for_release = Arc::new(Mutex::new(wait_for_release));
+    let mut router = HelixRouter::new(None, None);
+    router.routes.insert(
+        ("GET".to_string(), "/events".to_string()),
+        Arc::new(move |_, response| {
+            let (sse, events) = Response::sse();
+            *response = sse;
+            events.send(SseEvent::data("ready")).unwrap();
+            let wait_for_release = Arc::clone(&wait_for_release);
+            std::thread::spawn(move || {
+                wait_for_release.lock().unwrap().recv().unwrap();
+                events.send(SseEvent::data("released").event("done")).unwrap();
+            });
+            Ok(())
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder().pool_size(1).build();
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    handler.accept_conns().await.unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    while !received.ends_with(b"data: ready\n\n") {
+        received.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(received).unwrap();
+    assert!(head.contains("Content-Type: text/event-stream\r\n"));
+    assert!(!head.contains("Content-Length"));
+
+    // the stream is still open, and the only worker is free for other requests
+    let plain = tokio::task::spawn_blocking(move || send_plain_request(addr))
+        .await
+        .unwrap();
+    assert!(plain.starts_with("HTTP/1.1 404"));
+
+    release.send(()).unwrap();
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await.unwrap();
+    assert_eq!(rest, "event: done\ndata: released\n\n");
+}
+
+fn send_binary_request(
+    stream: &mut std::net::TcpStream,
+    request: &BinaryRequest,
+) -> BinaryResponse {
+    let payload = rmp_serde::to_vec_named(request).unwrap();
+    stream.write_all(&(payload.len() as u32).to_be_bytes()).unwrap();
+    stream.write_all(&payload).unwrap();
+    read_binary_response(stream)
+}
+
+fn read_binary_response(stream: &mut std::net::TcpStream) -> BinaryResponse {
+    let mut prefix = [0u8; 4];
+    stream.read_exact(&mut prefix).unwrap();
+    let mut payload = vec![0u8; u32::from_be_bytes(prefix) as usize];
+    stream.read_exact(&mut payload).unwrap();
+    rmp_serde::from_slice(&payload).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_binary_protocol_requests_on_one_connection() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .protocol(WireProtocol::Binary)
+        .keep_alive(std::time::Duration::from_secs(1))
+        .build();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/echo", echo_body);
+
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        let echoed = send_binary_request(
+            &mut stream,
+            &BinaryRequest {
+                method: "POST".to_string(),
+                path: "/echo".to_string(),
+                headers: vec![("X-Request-Id".to_string(), "binary-1".to_string())],
+                body: vec![0, 159, 146, 150],
+            },
+        );
+        assert_eq!(echoed.status, 200);
+        assert_eq!(echoed.body, vec![0, 159, 146, 150]);
+        assert!(echoed
+            .headers
+            .contains(&("X-Request-Id".to_string(), "binary-1".to_string())));
+
+        let missing = send_binary_request(
+            &mut stream,
+            &BinaryRequest {
+                method: "GET".to_string(),
+                path: "/missing".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(missing.status, 404);
+
+        // a frame that isn't a request is answered, then the connection is closed
+        stream.write_all(&[0, 0, 0, 2, 0xc1, 0xc1]).unwrap();
+        let rejected = read_binary_response(&mut stream);
+        assert_eq!(rejected.status, 400);
+        assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+    })
+    .await
+    .unwrap();
+}
+
+/// Waits up to a second for `handler` to have `count` connections open
+async fn wait_for_active_connections(handler: &ConnectionHandler, count: usize) {
+    let waited = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+        while handler.metrics.active_connections() != count {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "{} connections open", handler.metrics.active_connections());
+}
+
+#[cfg(feature = "task-per-connection")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_task_per_connection_answers_past_pool_size() {
+    const CLIENTS: usize = 4;
+    let (graph, _temp_dir) = setup_test_engine();
+    // each handler waits for all of them to have started, which only happens if they
+    // run side by side rather than one after another on the single worker
+    let started = Arc::new((Mutex::new(0), std::sync::Condvar::new()));
+    let mut router = HelixRouter::new(None, None);
+    router.routes.insert(
+        ("GET".to_string(), "/together".to_string()),
+        Arc::new(move |_, _| {
+            let (count, all_started) = &*started;
+            let mut count = count.lock().unwrap();
+            *count += 1;
+            all_started.notify_all();
+            let timeout = std::time::Duration::from_secs(5);
+            let (_count, waited) = all_started
+                .wait_timeout_while(count, timeout, |count| *count < CLIENTS)
+                .unwrap();
+            match waited.timed_out() {
+                true => Err(GraphError::New("handlers weren't run together".to_string())),
+                false => Ok(()),
+            }
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = GatewayOpts::builder()
+        .pool_size(1)
+        .task_per_connection(true)
+        .keep_alive(std::time::Duration::from_secs(1))
+        .build();
+    let handler = unsafe {
+        ConnectionHandler::from_raw_fd_with_opts(listener.into_raw_fd(), graph, router, &opts)
+    }
+    .unwrap();
+    assert!(handler.thread_pool.workers.is_empty());
+    let _handle = handler.accept_conns().await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+                    stream
+                        .write_all(b"GET /together HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                        .unwrap();
+                    let first = read_response(&mut stream);
+                    stream.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+                    (first, read_response(&mut stream))
+                })
+            })
+            .collect();
+        for client in clients {
+            let (first, second) = client.join().unwrap();
+            assert!(first.starts_with("HTTP/1.1 200"), "{}", first);
+            assert!(first.contains("\r\nKeep-Alive: timeout=1\r\n"));
+            assert!(second.starts_with("HTTP/1.1 404"));
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[cfg(feature = "task-per-connection")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown_ends_connection_tasks() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let opts = GatewayOpts::builder()
+        .task_per_connection(true)
+        .drain_timeout(std::time::Duration::from_millis(100))
+        .build();
+    let (handler, addr) = start_handler_with_opts(graph, &opts).await;
+
+    // never sends a request, so it can't finish on its own
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    wait_for_active_connections(&handler, 1).await;
+
+    assert_eq!(handler.shutdown().await, 1);
+    let response = tokio::task::spawn_blocking(move || {
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map(|_| response)
+    })
+    .await
+    .unwrap();
+    // closed without waiting out the header timeout for a 408
+    assert!(response.unwrap().is_empty());
+    // the task drops its connection once it has seen the shutdown, which can be after it returns
+    wait_for_active_connections(&handler, 0).await;
+}
@@ -1 +1,9 @@
-pub mod connection;
\ No newline at end of file
+pub mod bind;
+pub mod connection;
+pub mod rate_limiter;
+
+#[cfg(all(test, unix))]
+mod connection_tests;
+
+#[cfg(test)]
+mod rate_limiter_tests;
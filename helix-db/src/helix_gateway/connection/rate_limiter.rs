@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::helix_engine::types::GraphError;
+use crate::helix_gateway::gateway::RateLimitOpts;
+
+/// How often buckets of clients that have gone idle are evicted
+const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket rate limiter keyed by client IP.
+///
+/// Each IP starts with `burst` tokens, spends one per request and regains
+/// `requests_per_second` tokens a second up to `burst`.
+/// A bucket that has refilled is the same as a new one, so those are evicted
+/// periodically to keep the map from growing with every client ever seen.
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    buckets: HashMap<IpAddr, Bucket>,
+    last_evicted: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Fails unless `opts` allows more than 0 requests per second
+    pub fn new(opts: &RateLimitOpts) -> Result<Self, GraphError> {
+        if opts.requests_per_second.is_nan() || opts.requests_per_second <= 0.0 {
+            return Err(GraphError::New(format!(
+                "Expected rate limit to allow more than 0 requests per second, got {}",
+                opts.requests_per_second
+            )));
+        }
+        Ok(Self {
+            requests_per_second: opts.requests_per_second,
+            burst: opts.burst as f64,
+            state: Mutex::new(LimiterState {
+                buckets: HashMap::new(),
+                last_evicted: Instant::now(),
+            }),
+        })
+    }
+
+    /// Takes a token for a request from `ip`.
+    ///
+    /// Returns how long until the next token is available if there are none left.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    pub(crate) fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        if now.saturating_duration_since(state.last_evicted) >= EVICT_INTERVAL {
+            self.evict_idle(&mut state.buckets, now);
+            state.last_evicted = now;
+        }
+
+        let bucket = state.buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+
+        match bucket.tokens >= 1.0 {
+            true => {
+                bucket.tokens -= 1.0;
+                Ok(())
+            }
+            false => Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.requests_per_second,
+            )),
+        }
+    }
+
+    /// Number of IPs currently being tracked
+    pub fn tracked(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.requests_per_second).min(self.burst)
+    }
+
+    fn evict_idle(&self, buckets: &mut HashMap<IpAddr, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+    }
+}
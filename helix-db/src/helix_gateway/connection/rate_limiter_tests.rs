@@ -0,0 +1,86 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::{Duration, Instant},
+};
+
+use super::rate_limiter::RateLimiter;
+use crate::helix_gateway::gateway::RateLimitOpts;
+
+fn ip(last: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+}
+
+fn limiter(requests_per_second: f64, burst: u32) -> RateLimiter {
+    RateLimiter::new(&RateLimitOpts {
+        requests_per_second,
+        burst,
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_burst_then_limited() {
+    let limiter = limiter(2.0, 3);
+    let now = Instant::now();
+
+    for _ in 0..3 {
+        assert!(limiter.check_at(ip(1), now).is_ok());
+    }
+    let retry_after = limiter.check_at(ip(1), now).unwrap_err();
+    assert_eq!(retry_after, Duration::from_millis(500));
+
+    // other clients have their own bucket
+    assert!(limiter.check_at(ip(2), now).is_ok());
+}
+
+#[test]
+fn test_tokens_refill_over_time() {
+    let limiter = limiter(2.0, 2);
+    let now = Instant::now();
+
+    assert!(limiter.check_at(ip(1), now).is_ok());
+    assert!(limiter.check_at(ip(1), now).is_ok());
+    assert!(limiter.check_at(ip(1), now).is_err());
+
+    let later = now + Duration::from_millis(500);
+    assert!(limiter.check_at(ip(1), later).is_ok());
+    assert!(limiter.check_at(ip(1), later).is_err());
+
+    // refills never go over the burst size
+    let much_later = later + Duration::from_secs(60);
+    assert!(limiter.check_at(ip(1), much_later).is_ok());
+    assert!(limiter.check_at(ip(1), much_later).is_ok());
+    assert!(limiter.check_at(ip(1), much_later).is_err());
+}
+
+#[test]
+fn test_idle_clients_evicted() {
+    let limiter = limiter(1.0, 5);
+    let now = Instant::now();
+
+    for last in 0..100 {
+        assert!(limiter.check_at(ip(last), now).is_ok());
+    }
+    assert_eq!(limiter.tracked(), 100);
+
+    // one client keeps making requests up to the next eviction
+    let active = now + Duration::from_secs(59);
+    for _ in 0..5 {
+        let _ = limiter.check_at(ip(0), active);
+    }
+
+    let after_eviction = now + Duration::from_secs(61);
+    assert!(limiter.check_at(ip(200), after_eviction).is_ok());
+    assert_eq!(limiter.tracked(), 2);
+}
+
+#[test]
+fn test_rate_limit_must_allow_requests() {
+    for requests_per_second in [0.0, -1.0, f64::NAN] {
+        let opts = RateLimitOpts {
+            requests_per_second,
+            burst: 1,
+        };
+        assert!(RateLimiter::new(&opts).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::protocol::{request::Request, response::Response};
+
+/// Origins allowed to make cross-origin requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllowedOrigins {
+    /// Any origin, sent as `*` unless credentials are allowed
+    Any,
+    /// Only these origins, e.g. `https://app.example.com`, echoed back when they match
+    List(Vec<String>),
+}
+
+/// Cross-origin resource sharing settings for the gateway.
+///
+/// Preflight `OPTIONS` requests are answered by the gateway without reaching the router,
+/// and every other response to an allowed origin gets the `Access-Control-*` headers added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsOpts {
+    pub allowed_origins: AllowedOrigins,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsOpts {
+    fn default() -> Self {
+        Self {
+            allowed_origins: AllowedOrigins::Any,
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: vec!["Content-Type".to_string()],
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsOpts {
+    /// Whether the request is a CORS preflight rather than a plain `OPTIONS` request
+    pub fn is_preflight(request: &Request) -> bool {
+        request.method.eq_ignore_ascii_case("OPTIONS")
+            && request.headers.contains_key("Origin")
+            && request.headers.contains_key("Access-Control-Request-Method")
+    }
+
+    /// Answers a preflight with `204 No Content`, with the allowed methods and headers
+    /// only if the origin is allowed
+    pub fn preflight(&self, origin: Option<&str>, response: &mut Response) {
+        response.no_content();
+        if origin.is_some_and(|origin| self.allows(origin)) {
+            response.headers.insert(
+                "Access-Control-Allow-Methods",
+                self.allowed_methods.join(", "),
+            );
+            response.headers.insert(
+                "Access-Control-Allow-Headers",
+                self.allowed_headers.join(", "),
+            );
+        }
+        self.apply(origin, response);
+    }
+
+    /// Adds the headers allowing `origin` to read the response, if it is allowed
+    pub fn apply(&self, origin: Option<&str>, response: &mut Response) {
+        let Some(origin) = origin.filter(|origin| self.allows(origin)) else {
+            return;
+        };
+        // `*` can't be used with credentials, so the origin is echoed back instead
+        match (&self.allowed_origins, self.allow_credentials) {
+            (AllowedOrigins::Any, false) => {
+                response.headers.insert("Access-Control-Allow-Origin", "*");
+            }
+            _ => {
+                response
+                    .headers
+                    .insert("Access-Control-Allow-Origin", origin);
+                // the response differs by origin, so caches must not share it between them
+                response.headers.append("Vary", "Origin");
+            }
+        }
+        if self.allow_credentials {
+            response
+                .headers
+                .insert("Access-Control-Allow-Credentials", "true");
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        match &self.allowed_origins {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+        }
+    }
+}
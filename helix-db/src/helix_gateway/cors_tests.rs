@@ -0,0 +1,104 @@
+use super::cors::{AllowedOrigins, CorsOpts};
+use crate::protocol::{headers::Headers, request::Request, response::Response};
+
+fn request(method: &str, headers: &[(&str, &str)]) -> Request {
+    Request {
+        method: method.to_string(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Headers>(),
+        path: "/users".to_string(),
+        raw_path: "/users".to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: Vec::new(),
+        request_id: String::new(),
+    }
+}
+
+fn allow_list(allow_credentials: bool) -> CorsOpts {
+    CorsOpts {
+        allowed_origins: AllowedOrigins::List(vec!["https://app.example.com".to_string()]),
+        allow_credentials,
+        ..CorsOpts::default()
+    }
+}
+
+#[test]
+fn test_is_preflight() {
+    let preflight = request(
+        "OPTIONS",
+        &[
+            ("origin", "https://app.example.com"),
+            ("access-control-request-method", "POST"),
+        ],
+    );
+    assert!(CorsOpts::is_preflight(&preflight));
+    assert!(!CorsOpts::is_preflight(&request("OPTIONS", &[])));
+    assert!(!CorsOpts::is_preflight(&request(
+        "GET",
+        &[("origin", "https://app.example.com")]
+    )));
+}
+
+#[test]
+fn test_preflight_for_allowed_origin() {
+    let mut response = Response::new();
+    CorsOpts::default().preflight(Some("https://app.example.com"), &mut response);
+
+    assert_eq!(response.status, 204);
+    assert_eq!(response.headers["Access-Control-Allow-Origin"], "*");
+    assert_eq!(
+        response.headers["Access-Control-Allow-Methods"],
+        "GET, POST, PUT, DELETE"
+    );
+    assert_eq!(response.headers["Access-Control-Allow-Headers"], "Content-Type");
+    assert!(!response.headers.contains_key("Access-Control-Allow-Credentials"));
+}
+
+#[test]
+fn test_preflight_for_disallowed_origin() {
+    let mut response = Response::new();
+    allow_list(false).preflight(Some("https://evil.example.com"), &mut response);
+
+    assert_eq!(response.status, 204);
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+    assert!(!response.headers.contains_key("Access-Control-Allow-Methods"));
+}
+
+#[test]
+fn test_allow_list_echoes_origin() {
+    let mut response = Response::new();
+    allow_list(true).apply(Some("https://app.example.com"), &mut response);
+
+    assert_eq!(
+        response.headers["Access-Control-Allow-Origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(response.headers["Vary"], "Origin");
+    assert_eq!(response.headers["Access-Control-Allow-Credentials"], "true");
+
+    let mut response = Response::new();
+    allow_list(true).apply(Some("https://evil.example.com"), &mut response);
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+
+    let mut response = Response::new();
+    allow_list(true).apply(None, &mut response);
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+}
+
+#[test]
+fn test_wildcard_with_credentials_echoes_origin() {
+    let cors = CorsOpts {
+        allow_credentials: true,
+        ..CorsOpts::default()
+    };
+    let mut response = Response::new();
+    cors.apply(Some("https://app.example.com"), &mut response);
+
+    assert_eq!(
+        response.headers["Access-Control-Allow-Origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(response.headers["Access-Control-Allow-Credentials"], "true");
+}
@@ -1,15 +1,453 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap, fs::File, future::Future, io::BufReader, pin::Pin, sync::Arc,
+    time::Duration,
+};
 
-use super::connection::connection::ConnectionHandler;
+use super::connection::{
+    bind::BindOpts,
+    connection::{ClientStream, ConnectionHandler},
+};
 use super::router::router::{HandlerFn, HelixRouter};
 use crate::{
-    helix_engine::graph_core::graph_core::HelixGraphEngine, helix_gateway::mcp::mcp::MCPHandlerFn,
+    helix_engine::{graph_core::graph_core::HelixGraphEngine, types::GraphError},
+    helix_gateway::{access_log::AccessLogFn, cors::CorsOpts, mcp::mcp::MCPHandlerFn},
+    protocol::{
+        binary::WireProtocol,
+        request::{Request, RequestLimits},
+        websocket::WebSocket,
+    },
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{ServerConfig, crypto::ring},
 };
 
-pub struct GatewayOpts {}
+/// Takes over a connection once its websocket handshake has been answered,
+/// given the handshake request so it can route on its path
+pub type WebSocketHandlerFn =
+    fn(Request, WebSocket<ClientStream>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Options for the gateway's listener and its thread pool.
+///
+/// Built with [`GatewayOpts::builder`], with anything left unset taking its default.
+#[derive(Debug, Clone)]
+pub struct GatewayOpts {
+    pub address: String,
+    pub dual_stack: bool,
+    pub reuse_address: bool,
+    pub reuse_port: bool,
+    pub bind_retries: u32,
+    pub bind_retry_backoff: Duration,
+    pub listen_backlog: u32,
+    pub nodelay: bool,
+    pub pool_size: usize,
+    pub max_body_size: Option<usize>,
+    pub read_timeout: Option<Duration>,
+    pub header_timeout: Option<Duration>,
+    pub body_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub keep_alive: Option<Duration>,
+    pub max_connections: Option<usize>,
+    pub tls: Option<TlsOpts>,
+    pub rate_limit: Option<RateLimitOpts>,
+    pub access_log: Option<AccessLogFn>,
+    pub metrics_endpoint: bool,
+    pub query_endpoint: bool,
+    pub version_endpoint: bool,
+    pub health_endpoints: bool,
+    pub server_header: bool,
+    pub cors: Option<CorsOpts>,
+    pub compression: Option<usize>,
+    pub drain_timeout: Duration,
+    pub on_websocket: Option<WebSocketHandlerFn>,
+    pub protocol: WireProtocol,
+    #[cfg(feature = "task-per-connection")]
+    pub task_per_connection: bool,
+    pub socket_activation: bool,
+}
 
 impl GatewayOpts {
     pub const DEFAULT_POOL_SIZE: usize = 8;
+    pub const DEFAULT_ADDRESS: &str = "0.0.0.0:6969";
+    pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const DEFAULT_BODY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn builder() -> GatewayOptsBuilder {
+        GatewayOptsBuilder::default()
+    }
+
+    pub fn bind_opts(&self) -> BindOpts {
+        BindOpts {
+            dual_stack: self.dual_stack,
+            reuse_address: self.reuse_address,
+            reuse_port: self.reuse_port,
+            retries: self.bind_retries,
+            retry_backoff: self.bind_retry_backoff,
+            backlog: self.listen_backlog,
+        }
+    }
+
+    pub fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            max_body_size: self.max_body_size,
+            read_timeout: self.read_timeout,
+            header_timeout: self.header_timeout,
+            body_timeout: self.body_timeout,
+        }
+    }
+
+    /// Whether each connection is served on a task of its own, which is never the case
+    /// without the `task-per-connection` feature, see [`GatewayOptsBuilder::task_per_connection`]
+    pub(crate) fn serves_task_per_connection(&self) -> bool {
+        #[cfg(feature = "task-per-connection")]
+        return self.task_per_connection;
+        #[cfg(not(feature = "task-per-connection"))]
+        false
+    }
+}
+
+impl Default for GatewayOpts {
+    fn default() -> Self {
+        Self {
+            address: Self::DEFAULT_ADDRESS.to_string(),
+            dual_stack: false,
+            reuse_address: true,
+            reuse_port: false,
+            bind_retries: 0,
+            bind_retry_backoff: BindOpts::DEFAULT_RETRY_BACKOFF,
+            listen_backlog: BindOpts::DEFAULT_BACKLOG,
+            nodelay: true,
+            pool_size: Self::DEFAULT_POOL_SIZE,
+            max_body_size: None,
+            read_timeout: None,
+            header_timeout: Some(Self::DEFAULT_HEADER_TIMEOUT),
+            body_timeout: Some(Self::DEFAULT_BODY_TIMEOUT),
+            write_timeout: None,
+            request_timeout: None,
+            keep_alive: None,
+            max_connections: None,
+            tls: None,
+            rate_limit: None,
+            access_log: None,
+            metrics_endpoint: false,
+            query_endpoint: false,
+            version_endpoint: false,
+            health_endpoints: false,
+            server_header: false,
+            cors: None,
+            compression: None,
+            drain_timeout: Self::DEFAULT_DRAIN_TIMEOUT,
+            on_websocket: None,
+            protocol: WireProtocol::Http,
+            #[cfg(feature = "task-per-connection")]
+            task_per_connection: false,
+            socket_activation: false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GatewayOptsBuilder {
+    opts: GatewayOpts,
+}
+
+impl GatewayOptsBuilder {
+    /// Address to bind to, unless a listener is passed in through [`Self::socket_activation`].
+    ///
+    /// Either a host and port, or on Unix the path of a Unix socket to serve on instead,
+    /// written with a `unix:` prefix or containing a `/`. The socket file is removed on shutdown.
+    pub fn address(mut self, address: &str) -> Self {
+        self.opts.address = address.to_string();
+        self
+    }
+
+    /// Accepts IPv4 connections as well on an IPv6 address such as `[::]:6969`, where the OS
+    /// supports it. Without it an IPv6 address only accepts IPv6 connections.
+    pub fn dual_stack(mut self, enabled: bool) -> Self {
+        self.opts.dual_stack = enabled;
+        self
+    }
+
+    /// Sets `SO_REUSEADDR` on the listener on Unix, which it is by default, so a restarted
+    /// gateway can bind while connections to the old one are in `TIME_WAIT`
+    pub fn reuse_address(mut self, enabled: bool) -> Self {
+        self.opts.reuse_address = enabled;
+        self
+    }
+
+    /// Sets `SO_REUSEPORT` on the listener on Unix, so during a rolling restart the new gateway
+    /// can bind while the old one, started with it too, is still serving.
+    /// Binding fails on platforms without it.
+    pub fn reuse_port(mut self, enabled: bool) -> Self {
+        self.opts.reuse_port = enabled;
+        self
+    }
+
+    /// Retries binding up to `retries` times while the address is in use, waiting `backoff`
+    /// before the first retry and twice as long before each one after it
+    pub fn bind_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.opts.bind_retries = retries;
+        self.opts.bind_retry_backoff = backoff;
+        self
+    }
+
+    /// Connections that can wait to be accepted before new ones are refused, 1024 by default.
+    /// A larger backlog absorbs bursts of connections, up to the OS's own cap, e.g.
+    /// `net.core.somaxconn` on Linux. It has no effect on an inherited listener.
+    pub fn listen_backlog(mut self, backlog: u32) -> Self {
+        self.opts.listen_backlog = backlog;
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on accepted TCP connections, which it is by default, so small
+    /// responses are sent straight away rather than held back by Nagle's algorithm
+    pub fn nodelay(mut self, enabled: bool) -> Self {
+        self.opts.nodelay = enabled;
+        self
+    }
+
+    /// Number of workers handling requests
+    pub fn pool_size(mut self, pool_size: usize) -> Self {
+        self.opts.pool_size = pool_size;
+        self
+    }
+
+    /// Largest request body in bytes that will be read, with larger ones answered with
+    /// `413 Payload Too Large`
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.opts.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// How long a client has to send its whole request
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.opts.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// How long a client has to send its request line and headers before it is answered with
+    /// `408`, separately from the body
+    pub fn header_timeout(mut self, header_timeout: Duration) -> Self {
+        self.opts.header_timeout = Some(header_timeout);
+        self
+    }
+
+    /// How long a client's request body can stop arriving for once its headers have before it
+    /// is answered with `408`, or `None` to wait on it for as long as the connection is open.
+    /// Each read that finishes starts the wait again, so a large body sent slowly isn't cut off.
+    pub fn body_timeout(mut self, body_timeout: Option<Duration>) -> Self {
+        self.opts.body_timeout = body_timeout;
+        self
+    }
+
+    /// How long a write to a client can go without progress before the connection is closed,
+    /// so a client that stops reading its response doesn't hold a worker
+    pub fn write_timeout(mut self, write_timeout: Duration) -> Self {
+        self.opts.write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// How long a request has from when a worker picks up its connection until it is answered.
+    ///
+    /// A request still being read when it runs out is answered with `408`, and one whose
+    /// handler is still running with `503`. A handler can't be stopped, so it carries on in
+    /// the background and any writes it makes still happen, but the worker moves on.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.opts.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Keeps a connection open for another request after answering one, closing it if the client
+    /// sends nothing for `idle_timeout`. Connections are closed after each response by default.
+    ///
+    /// A connection waiting for its next request doesn't hold a worker, but it does count
+    /// towards `max_connections`. It isn't kept open if the client sends `Connection: close`,
+    /// or for a websocket or event stream.
+    pub fn keep_alive(mut self, idle_timeout: Duration) -> Self {
+        self.opts.keep_alive = Some(idle_timeout);
+        self
+    }
+
+    /// Number of connections served at once, with any over it answered with `503`
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.opts.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Serves every connection over TLS with the given certificate and key, closing ones that
+    /// fail the handshake, including plaintext ones. They are loaded when the gateway is created,
+    /// which fails if they can't be.
+    pub fn tls(mut self, tls: TlsOpts) -> Self {
+        self.opts.tls = Some(tls);
+        self
+    }
+
+    /// Rate limits each client IP, answering every request over the limit with
+    /// `429 Too Many Requests`, including ones on a kept-alive connection.
+    ///
+    /// Creating the gateway fails unless it allows more than 0 requests per second.
+    pub fn rate_limit(mut self, rate_limit: RateLimitOpts) -> Self {
+        self.opts.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Called with a record of each request after its response is sent,
+    /// e.g. [`log_combined`](crate::helix_gateway::access_log::log_combined)
+    pub fn access_log(mut self, access_log: AccessLogFn) -> Self {
+        self.opts.access_log = Some(access_log);
+        self
+    }
+
+    /// Serves the gateway's metrics at `GET /metrics` in the Prometheus text format
+    pub fn metrics_endpoint(mut self, enabled: bool) -> Self {
+        self.opts.metrics_endpoint = enabled;
+        self
+    }
+
+    /// Runs queries posted as JSON to `POST /query`, see
+    /// [`query_handler`](crate::helix_gateway::query_endpoint::query_handler)
+    pub fn query_endpoint(mut self, enabled: bool) -> Self {
+        self.opts.query_endpoint = enabled;
+        self
+    }
+
+    /// Serves the gateway's version, git commit and build time as JSON at `GET /version`, see
+    /// [`version_handler`](crate::helix_gateway::version::version_handler)
+    pub fn version_endpoint(mut self, enabled: bool) -> Self {
+        self.opts.version_endpoint = enabled;
+        self
+    }
+
+    /// Serves a liveness check at `GET /livez` and a readiness check at `GET /readyz`, which
+    /// answers `503` while the engine can't serve reads, see
+    /// [`livez_handler`](crate::helix_gateway::health::livez_handler) and
+    /// [`readyz_handler`](crate::helix_gateway::health::readyz_handler)
+    pub fn health_endpoints(mut self, enabled: bool) -> Self {
+        self.opts.health_endpoints = enabled;
+        self
+    }
+
+    /// Sends a `Server: helix-db/<version>` header with every response
+    pub fn server_header(mut self, enabled: bool) -> Self {
+        self.opts.server_header = enabled;
+        self
+    }
+
+    /// Answers CORS preflights and adds CORS headers to responses for allowed origins
+    pub fn cors(mut self, cors: CorsOpts) -> Self {
+        self.opts.cors = Some(cors);
+        self
+    }
+
+    /// Compresses response bodies of at least `min_size` bytes with brotli or gzip, whichever
+    /// the client accepts, preferring brotli, see
+    /// [`compress_response`](crate::protocol::encoding::compress_response).
+    /// [`DEFAULT_MIN_COMPRESS_SIZE`](crate::protocol::encoding::DEFAULT_MIN_COMPRESS_SIZE)
+    /// is a reasonable threshold. Brotli needs the `brotli` feature, which is on by default.
+    pub fn compression(mut self, min_size: usize) -> Self {
+        self.opts.compression = Some(min_size);
+        self
+    }
+
+    /// How long shutting down waits for open connections before closing them
+    pub fn drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.opts.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Accepts websocket handshakes and hands each upgraded connection to `on_websocket`,
+    /// which runs on its own task so it doesn't hold up a worker.
+    ///
+    /// Without it handshakes are routed like any other request.
+    pub fn on_websocket(mut self, on_websocket: WebSocketHandlerFn) -> Self {
+        self.opts.on_websocket = Some(on_websocket);
+        self
+    }
+
+    /// How the listener's connections are framed, HTTP unless set.
+    ///
+    /// A [`WireProtocol::Binary`] listener shares the router and engine with HTTP ones, but
+    /// skips what only applies to HTTP: CORS, compression, the server header and websockets.
+    /// Its connections are kept open between requests only with [`Self::keep_alive`].
+    pub fn protocol(mut self, protocol: WireProtocol) -> Self {
+        self.opts.protocol = protocol;
+        self
+    }
+
+    /// Serves each connection on a task of its own instead of queueing it for one of the
+    /// `pool_size` workers, so the pool doesn't cap how many requests are answered at once.
+    ///
+    /// Handlers always run on tokio's blocking pool then, so that a slow one doesn't hold up
+    /// the runtime, which caps them at its `max_blocking_threads` instead. `pool_size` is ignored.
+    /// Only available with the `task-per-connection` feature.
+    #[cfg(feature = "task-per-connection")]
+    pub fn task_per_connection(mut self, enabled: bool) -> Self {
+        self.opts.task_per_connection = enabled;
+        self
+    }
+
+    /// Accepts on the listener passed in through systemd socket activation instead of binding
+    /// to `address`, if the `LISTEN_PID` and `LISTEN_FDS` variables hand this process one, see
+    /// [`ConnectionHandler::take_listen_fd_from_env`]. Unix only.
+    ///
+    /// The variables are removed once read, so the gateway should be created at startup,
+    /// before any other thread that reads or writes the environment is spawned.
+    pub fn socket_activation(mut self, enabled: bool) -> Self {
+        self.opts.socket_activation = enabled;
+        self
+    }
+
+    pub fn build(self) -> GatewayOpts {
+        self.opts
+    }
+}
+
+/// Certificate and private key to terminate TLS with, both PEM encoded.
+///
+/// `cert_path` holds the certificate chain starting with the server's own certificate.
+#[derive(Debug, Clone)]
+pub struct TlsOpts {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+impl TlsOpts {
+    /// Loads the certificate chain and key into an acceptor for TLS 1.2 and 1.3 connections
+    pub fn acceptor(&self) -> Result<TlsAcceptor, GraphError> {
+        let tls_error = |message: String, e| GraphError::GraphConnectionError(message, e);
+
+        let cert_file = File::open(&self.cert_path)
+            .map_err(|e| tls_error(format!("Failed to open TLS cert {}", self.cert_path), e))?;
+        let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| tls_error(format!("Failed to read TLS cert {}", self.cert_path), e))?;
+
+        let key_file = File::open(&self.key_path)
+            .map_err(|e| tls_error(format!("Failed to open TLS key {}", self.key_path), e))?;
+        let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+            .map_err(|e| tls_error(format!("Failed to read TLS key {}", self.key_path), e))?
+            .ok_or_else(|| {
+                GraphError::New(format!("No private key found in {}", self.key_path))
+            })?;
+
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|e| GraphError::New(format!("Invalid TLS config: {}", e)))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Limits how often each client IP can make requests.
+///
+/// Clients can make up to `burst` requests at once and `requests_per_second` after that,
+/// with requests over the limit answered with `429 Too Many Requests`.
+#[derive(Debug, Clone)]
+pub struct RateLimitOpts {
+    pub requests_per_second: f64,
+    pub burst: u32,
 }
 
 pub struct HelixGateway {
@@ -23,10 +461,43 @@ impl HelixGateway {
         size: usize,
         routes: Option<HashMap<(String, String), HandlerFn>>,
         mcp_routes: Option<HashMap<(String, String), MCPHandlerFn>>,
-    ) -> HelixGateway {
+    ) -> Result<HelixGateway, GraphError> {
+        let opts = GatewayOpts::builder()
+            .address(address)
+            .pool_size(size)
+            .build();
+        Self::with_opts(graph, opts, routes, mcp_routes).await
+    }
+
+    /// Creates a gateway serving `routes` with `opts`.
+    ///
+    /// Fails if the TLS certificate or key can't be loaded, the rate limit is invalid, an
+    /// inherited listener can't be used, or an endpoint clashes with a route.
+    pub async fn with_opts(
+        graph: Arc<HelixGraphEngine>,
+        opts: GatewayOpts,
+        routes: Option<HashMap<(String, String), HandlerFn>>,
+        mcp_routes: Option<HashMap<(String, String), MCPHandlerFn>>,
+    ) -> Result<HelixGateway, GraphError> {
         let router = HelixRouter::new(routes, mcp_routes);
-        let connection_handler = ConnectionHandler::new(address, graph, size, router).unwrap();
+        // under socket activation the listener is passed in already bound
+        #[cfg(unix)]
+        let inherited = match opts.socket_activation {
+            // SAFETY: opting in means the gateway is created at startup, see `socket_activation`
+            true => unsafe { ConnectionHandler::take_listen_fd_from_env() },
+            false => None,
+        };
+        #[cfg(unix)]
+        let connection_handler = match inherited {
+            // SAFETY: the fd was handed to this process by the service manager for it to own
+            Some(fd) => unsafe {
+                ConnectionHandler::from_raw_fd_with_opts(fd, graph, router, &opts)
+            },
+            None => ConnectionHandler::with_opts(graph, router, &opts),
+        }?;
+        #[cfg(not(unix))]
+        let connection_handler = ConnectionHandler::with_opts(graph, router, &opts)?;
         println!("Gateway created");
-        HelixGateway { connection_handler }
+        Ok(HelixGateway { connection_handler })
     }
 }
@@ -0,0 +1,40 @@
+use crate::{
+    helix_engine::types::GraphError, helix_gateway::router::router::HandlerInput,
+    protocol::response::Response,
+};
+use serde::Serialize;
+
+/// The body the health endpoints answer with when they pass, `{"status": "ok"}`
+#[derive(Debug, Serialize)]
+struct Status {
+    status: &'static str,
+}
+
+const OK: Status = Status { status: "ok" };
+
+/// Answers `200` with `{"status": "ok"}` as long as the gateway can answer at all.
+///
+/// Registered at `GET /livez` when the gateway's health endpoints are enabled. It doesn't touch
+/// the engine, so a liveness probe only restarts a gateway that has stopped serving entirely,
+/// not one whose storage is briefly unavailable, which [`readyz_handler`] reports instead.
+pub fn livez_handler(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    response.json(&OK, input.request.wants_pretty_json())
+}
+
+/// Answers `200` with `{"status": "ok"}` if the engine can serve reads, checked by opening a read
+/// transaction and reading the edge count, or `503` with a `not_ready` error if it can't,
+/// logging why.
+///
+/// Registered at `GET /readyz` when the gateway's health endpoints are enabled.
+pub fn readyz_handler(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    match input.graph.edge_count() {
+        Ok(_) => response.json(&OK, input.request.wants_pretty_json()),
+        Err(e) => {
+            eprintln!("Readiness check failed: {:?}", e);
+            let head = response.head;
+            *response = Response::error(503, "not_ready", "Storage unavailable");
+            response.head = head;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use sonic_rs::{JsonValueTrait, Value as JsonValue};
+use tempfile::TempDir;
+
+use super::{
+    health::{livez_handler, readyz_handler},
+    router::router::HelixRouter,
+};
+use crate::{
+    helix_engine::graph_core::{
+        config::Config,
+        graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+    },
+    protocol::{headers::Headers, request::Request, response::Response},
+};
+
+fn get(path: &str) -> (u16, JsonValue) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    let graph = Arc::new(HelixGraphEngine::new(opts).unwrap());
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("GET", "/livez", livez_handler);
+    router.add_route("GET", "/readyz", readyz_handler);
+    let request = Request {
+        method: "GET".to_string(),
+        headers: Headers::new(),
+        path: path.to_string(),
+        raw_path: path.to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: Vec::new(),
+        request_id: String::new(),
+    };
+    let mut response = Response::new();
+    router.handle(graph, request, &mut response).unwrap();
+    assert_eq!(
+        response.headers.get("Content-Type").map(String::as_str),
+        Some("application/json")
+    );
+    (
+        response.status,
+        sonic_rs::from_slice(&response.body).unwrap(),
+    )
+}
+
+#[test]
+fn test_livez_answers_ok() {
+    let (status, body) = get("/livez");
+    assert_eq!(status, 200);
+    assert_eq!(body["status"].as_str(), Some("ok"));
+}
+
+#[test]
+fn test_readyz_answers_ok_once_engine_is_open() {
+    let (status, body) = get("/readyz");
+    assert_eq!(status, 200);
+    assert_eq!(body["status"].as_str(), Some("ok"));
+}
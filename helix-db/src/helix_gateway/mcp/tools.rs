@@ -11,7 +11,7 @@ use crate::{
             tr_val::{Traversable, TraversalVal},
             g::G,
         },
-        storage_core::storage_core::HelixGraphStorage,
+        storage_core::{storage_core::HelixGraphStorage, timestamps::now_millis},
         types::GraphError,
     },
     utils::label_hash::hash_label,
@@ -323,6 +323,7 @@ impl<'a> McpTools<'a> for McpBackend {
         let iter = NFromType {
             iter: db.nodes_db.lazily_decode_data().iter(txn).unwrap(),
             label: node_type,
+            expired: Some(Ok(db.expired_ids(txn, now_millis())?)),
         };
 
         let result = iter.take(100).collect::<Result<Vec<_>, _>>();
@@ -340,6 +341,7 @@ impl<'a> McpTools<'a> for McpBackend {
         let iter = EFromType {
             iter: db.edges_db.lazily_decode_data().iter(txn).unwrap(),
             label: edge_type,
+            expired: Some(Ok(db.expired_ids(txn, now_millis())?)),
         };
 
         let result = iter.take(100).collect::<Result<Vec<_>, _>>();
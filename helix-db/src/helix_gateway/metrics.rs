@@ -0,0 +1,190 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Upper bounds in seconds of the request duration histogram's buckets
+const DURATION_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Upper bounds of the requests per connection histogram's buckets
+const REQUESTS_PER_CONNECTION_BUCKETS: [u64; 6] = [1, 2, 5, 10, 50, 100];
+
+/// Counters for the gateway, shared by the connection handler and thread pool
+/// and rendered in the Prometheus text format by `GET /metrics` when it is enabled
+#[derive(Debug, Default)]
+pub struct GatewayMetrics {
+    requests: AtomicU64,
+    /// Responses by status class, `1xx` to `5xx`
+    status_classes: [AtomicU64; 5],
+    /// Requests per duration bucket, with the last for those slower than every bound
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    duration_sum_micros: AtomicU64,
+    /// Closed connections per number of requests they served, with the last for those that
+    /// served more than every bound
+    connection_buckets: [AtomicU64; REQUESTS_PER_CONNECTION_BUCKETS.len() + 1],
+    connection_requests_sum: AtomicU64,
+    pub(crate) active_connections: AtomicUsize,
+    pub(crate) queued: AtomicUsize,
+    pub(crate) idle_connections: AtomicUsize,
+    pub(crate) keep_alive_timeouts: AtomicU64,
+}
+
+impl GatewayMetrics {
+    /// Records a request that was answered with `status` after `duration`
+    pub fn record_request(&self, status: u16, duration: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(class) = self.status_classes.get((status / 100).wrapping_sub(1) as usize) {
+            class.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.duration_sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Records a connection closing after serving `requests` requests
+    pub fn record_connection(&self, requests: u64) {
+        let bucket = REQUESTS_PER_CONNECTION_BUCKETS
+            .iter()
+            .position(|&bound| requests <= bound)
+            .unwrap_or(REQUESTS_PER_CONNECTION_BUCKETS.len());
+        self.connection_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.connection_requests_sum.fetch_add(requests, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Acquire)
+    }
+
+    /// Number of connections waiting for a worker
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Number of kept-alive connections waiting for their next request
+    pub fn idle_connections(&self) -> usize {
+        self.idle_connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of kept-alive connections closed for sending nothing within the keep-alive timeout
+    pub fn keep_alive_timeouts(&self) -> u64 {
+        self.keep_alive_timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Number of connections that have closed after serving at least one request
+    pub fn closed_connections(&self) -> u64 {
+        self.connection_buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Number of requests served by the connections that have closed
+    pub fn closed_connection_requests(&self) -> u64 {
+        self.connection_requests_sum.load(Ordering::Relaxed)
+    }
+
+    /// Renders every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP helix_requests_total Requests handled.");
+        let _ = writeln!(out, "# TYPE helix_requests_total counter");
+        let _ = writeln!(out, "helix_requests_total {}", self.requests());
+
+        let _ = writeln!(out, "# HELP helix_responses_total Responses sent by status class.");
+        let _ = writeln!(out, "# TYPE helix_responses_total counter");
+        for (i, class) in self.status_classes.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "helix_responses_total{{status_class=\"{}xx\"}} {}",
+                i + 1,
+                class.load(Ordering::Relaxed)
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP helix_request_duration_seconds Time from a request being parsed to its response being sent."
+        );
+        let _ = writeln!(out, "# TYPE helix_request_duration_seconds histogram");
+        let mut cumulative = 0;
+        for (i, bucket) in self.duration_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let bound = match DURATION_BUCKETS.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "helix_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            );
+        }
+        let sum = self.duration_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "helix_request_duration_seconds_sum {}", sum);
+        let _ = writeln!(out, "helix_request_duration_seconds_count {}", cumulative);
+
+        let _ = writeln!(out, "# HELP helix_active_connections Connections currently open.");
+        let _ = writeln!(out, "# TYPE helix_active_connections gauge");
+        let _ = writeln!(out, "helix_active_connections {}", self.active_connections());
+
+        let _ = writeln!(
+            out,
+            "# HELP helix_thread_pool_queue_depth Connections waiting for a worker."
+        );
+        let _ = writeln!(out, "# TYPE helix_thread_pool_queue_depth gauge");
+        let _ = writeln!(out, "helix_thread_pool_queue_depth {}", self.queue_depth());
+
+        let _ = writeln!(
+            out,
+            "# HELP helix_idle_connections Kept-alive connections waiting for their next request."
+        );
+        let _ = writeln!(out, "# TYPE helix_idle_connections gauge");
+        let _ = writeln!(out, "helix_idle_connections {}", self.idle_connections());
+
+        let _ = writeln!(
+            out,
+            "# HELP helix_keep_alive_timeouts_total Kept-alive connections closed for being idle too long."
+        );
+        let _ = writeln!(out, "# TYPE helix_keep_alive_timeouts_total counter");
+        let _ = writeln!(out, "helix_keep_alive_timeouts_total {}", self.keep_alive_timeouts());
+
+        let _ = writeln!(
+            out,
+            "# HELP helix_requests_per_connection Requests served by each connection, recorded when it closes."
+        );
+        let _ = writeln!(out, "# TYPE helix_requests_per_connection histogram");
+        let mut cumulative = 0;
+        for (i, bucket) in self.connection_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let bound = match REQUESTS_PER_CONNECTION_BUCKETS.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "helix_requests_per_connection_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "helix_requests_per_connection_sum {}",
+            self.closed_connection_requests()
+        );
+        let _ = writeln!(out, "helix_requests_per_connection_count {}", cumulative);
+
+        out
+    }
+}
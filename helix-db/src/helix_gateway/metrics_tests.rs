@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use super::metrics::GatewayMetrics;
+
+#[test]
+fn test_requests_counted_by_status_class() {
+    let metrics = GatewayMetrics::default();
+    metrics.record_request(200, Duration::from_millis(2));
+    metrics.record_request(204, Duration::from_millis(2));
+    metrics.record_request(404, Duration::from_millis(2));
+    metrics.record_request(503, Duration::from_millis(2));
+
+    let rendered = metrics.render();
+    assert_eq!(metrics.requests(), 4);
+    assert!(rendered.contains("helix_requests_total 4\n"));
+    assert!(rendered.contains("helix_responses_total{status_class=\"2xx\"} 2\n"));
+    assert!(rendered.contains("helix_responses_total{status_class=\"3xx\"} 0\n"));
+    assert!(rendered.contains("helix_responses_total{status_class=\"4xx\"} 1\n"));
+    assert!(rendered.contains("helix_responses_total{status_class=\"5xx\"} 1\n"));
+}
+
+#[test]
+fn test_duration_histogram_is_cumulative() {
+    let metrics = GatewayMetrics::default();
+    metrics.record_request(200, Duration::from_micros(500));
+    metrics.record_request(200, Duration::from_millis(20));
+    metrics.record_request(200, Duration::from_secs(10));
+
+    let rendered = metrics.render();
+    assert!(rendered.contains("# TYPE helix_request_duration_seconds histogram\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_bucket{le=\"0.001\"} 1\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_bucket{le=\"0.01\"} 1\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_bucket{le=\"5\"} 2\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_sum 10.0205\n"));
+    assert!(rendered.contains("helix_request_duration_seconds_count 3\n"));
+}
+
+#[test]
+fn test_requests_per_connection_histogram() {
+    let metrics = GatewayMetrics::default();
+    metrics.record_connection(1);
+    metrics.record_connection(3);
+    metrics.record_connection(500);
+
+    let rendered = metrics.render();
+    assert_eq!(metrics.closed_connections(), 3);
+    assert_eq!(metrics.closed_connection_requests(), 504);
+    assert!(rendered.contains("# TYPE helix_requests_per_connection histogram\n"));
+    assert!(rendered.contains("helix_requests_per_connection_bucket{le=\"1\"} 1\n"));
+    assert!(rendered.contains("helix_requests_per_connection_bucket{le=\"2\"} 1\n"));
+    assert!(rendered.contains("helix_requests_per_connection_bucket{le=\"5\"} 2\n"));
+    assert!(rendered.contains("helix_requests_per_connection_bucket{le=\"+Inf\"} 3\n"));
+    assert!(rendered.contains("helix_requests_per_connection_sum 504\n"));
+    assert!(rendered.contains("helix_idle_connections 0\n"));
+    assert!(rendered.contains("helix_keep_alive_timeouts_total 0\n"));
+}
@@ -1,7 +1,30 @@
+pub mod access_log;
 pub mod connection;
+pub mod cors;
 pub mod gateway;
+pub mod health;
 pub mod router;
 pub mod thread_pool;
 pub mod mcp;
+pub mod metrics;
+pub mod query_endpoint;
+pub mod version;
 pub mod embedding_providers;
 
+#[cfg(test)]
+mod access_log_tests;
+
+#[cfg(test)]
+mod cors_tests;
+
+#[cfg(test)]
+mod health_tests;
+
+#[cfg(test)]
+mod metrics_tests;
+
+#[cfg(test)]
+mod query_endpoint_tests;
+
+#[cfg(test)]
+mod version_tests;
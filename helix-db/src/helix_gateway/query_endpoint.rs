@@ -0,0 +1,78 @@
+use crate::{
+    helix_engine::{
+        graph_core::ops::util::paginate::Page,
+        query::ast::{Cell, QueryResult},
+        types::GraphError,
+    },
+    helix_gateway::router::router::HandlerInput,
+    protocol::{response::Response, return_values::ReturnValue},
+};
+use serde::{Deserialize, Serialize};
+
+/// The body of a `POST /query`.
+///
+/// Without a `limit` every row is returned at once.
+#[derive(Debug, Deserialize)]
+pub struct QueryRequest {
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// The body a `POST /query` is answered with, with a cell in each row for each column
+#[derive(Debug, Serialize)]
+pub struct QueryResponse {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<ReturnValue>>,
+    pub next_cursor: Option<String>,
+}
+
+impl From<Cell> for ReturnValue {
+    fn from(cell: Cell) -> Self {
+        match cell {
+            Cell::Node(node) => ReturnValue::from(node),
+            Cell::Edge(edge) => ReturnValue::from(edge),
+            Cell::Value(value) => ReturnValue::from(value),
+        }
+    }
+}
+
+impl QueryResponse {
+    fn new(result: QueryResult, next_cursor: Option<String>) -> Self {
+        Self {
+            columns: result.columns,
+            rows: result
+                .rows
+                .into_iter()
+                .map(|row| row.into_iter().map(ReturnValue::from).collect())
+                .collect(),
+            next_cursor,
+        }
+    }
+}
+
+/// Runs the query in a JSON body such as `{"query": "MATCH (a) RETURN a", "limit": 100}`
+/// and answers with its rows as JSON.
+///
+/// Registered at `POST /query` when the gateway's query endpoint is enabled. A body that isn't
+/// valid JSON or a query that doesn't parse is answered with `400`, and a cursor from the
+/// previous response's `next_cursor` continues from where it stopped. The rows are compact JSON
+/// unless the request
+/// [asks for it pretty](crate::protocol::request::Request::wants_pretty_json).
+pub fn query_handler(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    let request: QueryRequest = sonic_rs::from_slice(&input.request.body)?;
+    let body = match request.limit {
+        Some(limit) => {
+            let page = Page {
+                limit,
+                cursor: request.cursor,
+            };
+            let paged = input.graph.query_page(&request.query, &page)?;
+            QueryResponse::new(paged.items, paged.next_cursor)
+        }
+        None => QueryResponse::new(input.graph.query(&request.query)?, None),
+    };
+    response.json(&body, input.request.wants_pretty_json())
+}
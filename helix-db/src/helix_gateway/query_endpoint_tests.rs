@@ -0,0 +1,159 @@
+use std::{collections::HashMap, sync::Arc};
+
+use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value as JsonValue};
+use tempfile::TempDir;
+
+use super::{query_endpoint::query_handler, router::router::HelixRouter};
+use crate::{
+    helix_engine::{
+        graph_core::{
+            config::Config,
+            graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+        },
+        storage_core::direction::EdgeDirection,
+    },
+    protocol::{headers::Headers, request::Request, response::Response, value::Value},
+};
+
+fn setup_test_engine() -> (Arc<HelixGraphEngine>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    (Arc::new(HelixGraphEngine::new(opts).unwrap()), temp_dir)
+}
+
+fn post_query(graph: &Arc<HelixGraphEngine>, body: &str) -> (u16, JsonValue) {
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/query", query_handler);
+    let request = Request {
+        method: "POST".to_string(),
+        headers: Headers::new(),
+        path: "/query".to_string(),
+        raw_path: "/query".to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: body.as_bytes().to_vec(),
+        request_id: String::new(),
+    };
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(graph), request, &mut response)
+        .unwrap();
+    (
+        response.status,
+        sonic_rs::from_slice(&response.body).unwrap(),
+    )
+}
+
+fn add_person(graph: &HelixGraphEngine, name: &str) -> u128 {
+    let properties = HashMap::from([("name".to_string(), Value::String(name.to_string()))]);
+    graph.insert_node(None, "person", Some(properties)).unwrap()
+}
+
+#[test]
+fn test_query_returns_rows_as_json() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let alice = add_person(&graph, "alice");
+    let bob = add_person(&graph, "bob");
+    graph
+        .insert_edge("FOLLOWS", None, alice, bob, EdgeDirection::Directed)
+        .unwrap();
+
+    let (status, body) = post_query(
+        &graph,
+        r#"{"query": "MATCH (a)-[FOLLOWS]->(b) WHERE a.name = \"alice\" RETURN b, b.name"}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body["columns"].as_array().unwrap().len(), 2);
+    assert_eq!(body["columns"][0].as_str(), Some("b"));
+    assert_eq!(body["columns"][1].as_str(), Some("b.name"));
+    let rows = body["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    let id = uuid::Uuid::from_u128(bob).to_string();
+    assert_eq!(rows[0][0]["id"].as_str(), Some(id.as_str()));
+    assert_eq!(rows[0][1].as_str(), Some("bob"));
+    assert!(body["next_cursor"].is_null());
+}
+
+#[test]
+fn test_query_pages_through_rows() {
+    let (graph, _temp_dir) = setup_test_engine();
+    for name in ["alice", "bob", "carol"] {
+        add_person(&graph, name);
+    }
+
+    let mut names = Vec::new();
+    let mut body = r#"{"query": "MATCH (a:person) RETURN a.name", "limit": 2}"#.to_string();
+    loop {
+        let (status, page) = post_query(&graph, &body);
+        assert_eq!(status, 200);
+        let rows = page["rows"].as_array().unwrap();
+        assert!(rows.len() <= 2);
+        names.extend(rows.iter().map(|row| row[0].as_str().unwrap().to_string()));
+        let Some(cursor) = page["next_cursor"].as_str() else {
+            break;
+        };
+        body = format!(
+            r#"{{"query": "MATCH (a:person) RETURN a.name", "limit": 2, "cursor": "{}"}}"#,
+            cursor
+        );
+    }
+    names.sort();
+    assert_eq!(names, ["alice", "bob", "carol"]);
+}
+
+#[test]
+fn test_invalid_query_is_bad_request() {
+    let (graph, _temp_dir) = setup_test_engine();
+
+    let (status, body) = post_query(&graph, r#"{"query": "MATCH (a RETURN a"}"#);
+    assert_eq!(status, 400);
+    assert_eq!(
+        body["error"]["message"].as_str(),
+        Some("Invalid query: Expected ) at offset 9")
+    );
+    assert_eq!(body["error"]["code"].as_str(), Some("invalid_query"));
+
+    let (status, _) = post_query(&graph, r#"{"statement": "MATCH (a) RETURN a"}"#);
+    assert_eq!(status, 400);
+    let (status, _) = post_query(
+        &graph,
+        r#"{"query": "MATCH (a) RETURN a", "limit": 1, "cursor": "zz"}"#,
+    );
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn test_query_pretty_printed_when_asked() {
+    let (graph, _temp_dir) = setup_test_engine();
+    add_person(&graph, "alice");
+
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/query", query_handler);
+    let send = |raw_path: &str| {
+        let request = Request {
+            method: "POST".to_string(),
+            headers: Headers::new(),
+            path: "/query".to_string(),
+            raw_path: raw_path.to_string(),
+            version: "HTTP/1.1".to_string(),
+            body: br#"{"query": "MATCH (a) RETURN a.name"}"#.to_vec(),
+            request_id: String::new(),
+        };
+        let mut response = Response::new();
+        router
+            .handle(Arc::clone(&graph), request, &mut response)
+            .unwrap();
+        String::from_utf8(response.body).unwrap()
+    };
+
+    let compact = send("/query");
+    assert!(!compact.contains('\n'));
+    let pretty = send("/query?pretty=1");
+    assert!(pretty.contains("\n  \""));
+    assert_eq!(
+        sonic_rs::from_str::<JsonValue>(&compact).unwrap(),
+        sonic_rs::from_str::<JsonValue>(&pretty).unwrap()
+    );
+}
@@ -45,6 +45,29 @@ impl Handler {
 
 inventory::collect!(HandlerSubmission);
 
+/// How much of a handler error is written to the response body.
+///
+/// The full error is always logged to stderr regardless of the verbosity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorVerbosity {
+    /// The error's message for client errors, and a generic message for server errors
+    /// so storage internals and paths don't leak to clients
+    #[default]
+    Minimal,
+    /// Full `GraphError` display, for development
+    Detailed,
+}
+
+impl ErrorVerbosity {
+    /// Reads the verbosity from `HELIX_ERROR_VERBOSITY` (`minimal` or `detailed`), defaulting to minimal
+    pub fn from_env() -> Self {
+        match std::env::var("HELIX_ERROR_VERBOSITY") {
+            Ok(val) if val.eq_ignore_ascii_case("detailed") => ErrorVerbosity::Detailed,
+            _ => ErrorVerbosity::Minimal,
+        }
+    }
+}
+
 /// Router for handling requests and MCP requests
 ///
 /// Standard Routes and MCP Routes are stored in a HashMap with the method and path as the key
@@ -52,6 +75,7 @@ pub struct HelixRouter {
     /// Method+Path => Function
     pub routes: HashMap<(String, String), HandlerFn>,
     pub mcp_routes: HashMap<(String, String), MCPHandlerFn>,
+    pub error_verbosity: ErrorVerbosity,
 }
 
 impl HelixRouter {
@@ -71,15 +95,64 @@ impl HelixRouter {
         Self {
             routes: rts,
             mcp_routes: mcp_rts,
+            error_verbosity: ErrorVerbosity::from_env(),
         }
     }
 
-    /// Add a route to the router
+    /// Sets how much of a handler error is exposed in the response body
+    pub fn with_error_verbosity(mut self, verbosity: ErrorVerbosity) -> Self {
+        self.error_verbosity = verbosity;
+        self
+    }
+
+    /// Add a route to the router, replacing any handler already added for the method and path
     pub fn add_route(&mut self, method: &str, path: &str, handler: BasicHandlerFn) {
         self.routes
             .insert((method.to_uppercase(), path.to_string()), Arc::new(handler));
     }
 
+    /// Add a route to the router like [`HelixRouter::add_route`], failing with
+    /// `RouterError::DuplicateRoute` instead if a standard or MCP route is already registered
+    /// for the method and path
+    pub fn try_add_route(
+        &mut self,
+        method: &str,
+        path: &str,
+        handler: BasicHandlerFn,
+    ) -> Result<(), RouterError> {
+        self.try_insert_route(method, path, Arc::new(handler))
+    }
+
+    /// Inserts a route like [`HelixRouter::try_add_route`] for a handler that isn't a plain function
+    pub(crate) fn try_insert_route(
+        &mut self,
+        method: &str,
+        path: &str,
+        handler: HandlerFn,
+    ) -> Result<(), RouterError> {
+        let route_key = (method.to_uppercase(), path.to_string());
+        if self.routes.contains_key(&route_key) || self.mcp_routes.contains_key(&route_key) {
+            return Err(RouterError::DuplicateRoute(route_key.0, route_key.1));
+        }
+        self.routes.insert(route_key, handler);
+        Ok(())
+    }
+
+    /// The method and path of every standard and MCP route, sorted by path then method
+    pub fn routes(&self) -> Vec<(String, String)> {
+        let mut routes: Vec<(String, String)> = self
+            .routes
+            .keys()
+            .chain(self.mcp_routes.keys())
+            .cloned()
+            .collect();
+        routes.sort_by(|(a_method, a_path), (b_method, b_path)| {
+            a_path.cmp(b_path).then_with(|| a_method.cmp(b_method))
+        });
+        routes.dedup();
+        routes
+    }
+
     /// Handle a request by finding the appropriate handler and executing it
     ///
     /// ## Arguments
@@ -90,22 +163,42 @@ impl HelixRouter {
     ///
     /// ## Returns
     ///
-    /// * `Ok(())` if the request was handled successfully
-    /// * `Err(RouterError)` if there was an error handling the request
+    /// * `Ok(())` once the response has been written, including when the handler failed
+    ///
+    /// A `GET` or `HEAD` whose `If-None-Match` matches the `ETag` the handler set is answered
+    /// with a bodyless `304`, see [`Response::with_etag`].
+    /// A `HEAD` request is handled by the `GET` route for its path unless a `HEAD` route is
+    /// added for it, and either way its response is sent without a body.
+    /// A handler that returns `Err` has its error written to the response by [`HelixRouter::write_error`].
+    /// A handler that returns `Ok(())` without touching the response sends a `200` with an empty body,
+    /// which is a valid response. A status outside of 100-599 is logged and sent as a `500`.
     pub fn handle(
         &self,
         graph_access: Arc<HelixGraphEngine>,
         request: Request,
         response: &mut Response,
     ) -> Result<(), GraphError> {
-        let route_key = (request.method.clone(), request.path.clone());
+        response.head = request.method == "HEAD";
+        let route_key = self.route_key(&request);
+        // only reads are answered with a 304, as a write has to happen whatever the client has cached
+        let if_none_match = match request.method.as_str() {
+            "GET" | "HEAD" => request.headers.get("if-none-match").cloned(),
+            _ => None,
+        };
 
         if let Some(handler) = self.routes.get(&route_key) {
             let input = HandlerInput {
                 request,
                 graph: Arc::clone(&graph_access),
             };
-            return handler(&input, response);
+            if let Err(e) = handler(&input, response) {
+                self.write_error(&e, response);
+            }
+            Self::check_status(response);
+            if let Some(if_none_match) = if_none_match {
+                response.check_not_modified(&if_none_match);
+            }
+            return Ok(());
         }
 
         if let Some(mcp_handler) = self.mcp_routes.get(&route_key) {
@@ -115,19 +208,67 @@ impl HelixRouter {
                 mcp_connections: Arc::clone(&graph_access.mcp_connections.as_ref().unwrap()),
                 schema: Some(graph_access.storage.schema.clone()),
             };
-            return mcp_handler(&mut mcp_input, response);
+            if let Err(e) = mcp_handler(&mut mcp_input, response) {
+                self.write_error(&e, response);
+            }
+            Self::check_status(response);
+            return Ok(());
         };
 
-        response.status = 404;
-        response.body = b"404 - Not Found".to_vec();
+        let head = response.head;
+        *response = Response::error(404, "route_not_found", "Route not found");
+        response.head = head;
         return Ok(());
     }
+
+    /// The method and path a request is routed by, which for a `HEAD` with no route of its own
+    /// is the `GET` for the same path
+    fn route_key(&self, request: &Request) -> (String, String) {
+        let route_key = (request.method.clone(), request.path.clone());
+        if request.method == "HEAD"
+            && !self.routes.contains_key(&route_key)
+            && !self.mcp_routes.contains_key(&route_key)
+        {
+            return ("GET".to_string(), request.path.clone());
+        }
+        route_key
+    }
+
+    /// Coerces a status code a handler set outside of 100-599 to a 500 so clients never receive an invalid status line
+    fn check_status(response: &mut Response) {
+        if !(100..=599).contains(&response.status) {
+            eprintln!(
+                "Handler set invalid status code {}, responding with 500",
+                response.status
+            );
+            response.status = 500;
+        }
+    }
+
+    /// Replaces the response with a failed handler's error, see [`Response::error`],
+    /// with the status from [`GraphError::status_code`] and the code from [`GraphError::code`].
+    ///
+    /// The full error is always logged, and server errors only include it in the body
+    /// with [`ErrorVerbosity::Detailed`].
+    pub fn write_error(&self, error: &GraphError, response: &mut Response) {
+        eprintln!("Error handling request: {:?}", error);
+        let status = error.status_code();
+        let message = match self.error_verbosity {
+            ErrorVerbosity::Minimal if status >= 500 => "Internal Server Error".to_string(),
+            _ => error.to_string(),
+        };
+        let head = response.head;
+        *response = Response::error(status, error.code(), &message);
+        response.head = head;
+    }
 }
 
 #[derive(Debug)]
 pub enum RouterError {
     Io(std::io::Error),
     New(String),
+    /// A route was already registered for the method and path
+    DuplicateRoute(String, String),
 }
 
 impl fmt::Display for RouterError {
@@ -135,6 +276,9 @@ impl fmt::Display for RouterError {
         match self {
             RouterError::Io(e) => write!(f, "IO error: {}", e),
             RouterError::New(msg) => write!(f, "Graph error: {}", msg),
+            RouterError::DuplicateRoute(method, path) => {
+                write!(f, "Route {} {} is already registered", method, path)
+            }
         }
     }
 }
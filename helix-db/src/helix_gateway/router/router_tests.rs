@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use tempfile::TempDir;
+
+use super::router::{ErrorVerbosity, HandlerInput, HelixRouter, RouterError};
+use crate::{
+    helix_engine::{
+        graph_core::{
+            config::Config,
+            graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+            ops::{g::G, source::add_n::AddNAdapter, tr_val::Traversable},
+        },
+        types::GraphError,
+    },
+    protocol::{etag::weak_etag, headers::Headers, request::Request, response::Response},
+};
+
+fn setup_test_engine() -> (Arc<HelixGraphEngine>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    (Arc::new(HelixGraphEngine::new(opts).unwrap()), temp_dir)
+}
+
+fn request(path: &str) -> Request {
+    Request {
+        method: "POST".to_string(),
+        headers: Headers::new(),
+        path: path.to_string(),
+        raw_path: path.to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: Vec::new(),
+        request_id: String::new(),
+    }
+}
+
+fn storage_error() -> GraphError {
+    GraphError::StorageError("MDB_CORRUPTED at /var/lib/helix/data.mdb".to_string())
+}
+
+#[test]
+fn test_minimal_error_hides_internals() {
+    let router = HelixRouter::new(None, None).with_error_verbosity(ErrorVerbosity::Minimal);
+    let mut response = Response::new();
+    router.write_error(&storage_error(), &mut response);
+
+    let body = String::from_utf8(response.body).unwrap();
+    assert_eq!(response.status, 500);
+    assert_eq!(
+        body,
+        r#"{"error":{"code":"storage_error","message":"Internal Server Error"}}"#
+    );
+    assert!(!body.contains("MDB_CORRUPTED"));
+    assert!(!body.contains("/var/lib/helix"));
+}
+
+#[test]
+fn test_detailed_error_includes_internals() {
+    let router = HelixRouter::new(None, None).with_error_verbosity(ErrorVerbosity::Detailed);
+    let mut response = Response::new();
+    router.write_error(&storage_error(), &mut response);
+
+    let body = String::from_utf8(response.body).unwrap();
+    assert_eq!(response.status, 500);
+    assert!(body.contains("Storage error: MDB_CORRUPTED at /var/lib/helix/data.mdb"));
+}
+
+#[test]
+fn test_handler_setting_nothing_sends_empty_200() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/noop", |_: &HandlerInput, _: &mut Response| Ok(()));
+
+    let mut response = Response::new();
+    router.handle(graph, request("/noop"), &mut response).unwrap();
+
+    assert_eq!(response.status, 200);
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_invalid_status_coerced_to_500() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("POST", "/zero", |_: &HandlerInput, response: &mut Response| {
+        response.status = 0;
+        Ok(())
+    });
+    router.add_route("POST", "/too-big", |_: &HandlerInput, response: &mut Response| {
+        response.status = 600;
+        Ok(())
+    });
+    router.add_route("POST", "/teapot", |_: &HandlerInput, response: &mut Response| {
+        response.status = 418;
+        Ok(())
+    });
+
+    for (path, expected) in [("/zero", 500), ("/too-big", 500), ("/teapot", 418)] {
+        let mut response = Response::new();
+        router
+            .handle(Arc::clone(&graph), request(path), &mut response)
+            .unwrap();
+        assert_eq!(response.status, expected, "status for {}", path);
+    }
+}
+
+#[test]
+fn test_graph_error_status_codes() {
+    assert_eq!(GraphError::NodeNotFound.status_code(), 404);
+    assert_eq!(GraphError::EdgeNotFound.status_code(), 404);
+    assert_eq!(
+        GraphError::TraversalError("bad step".to_string()).status_code(),
+        400
+    );
+    assert_eq!(GraphError::MultipleNodesWithSameId.status_code(), 409);
+    assert_eq!(GraphError::MultipleNodesWithSameId.code(), "duplicate_node_id");
+    let violation = GraphError::UniqueViolation {
+        label: "User".to_string(),
+        property: "email".to_string(),
+    };
+    assert_eq!(violation.status_code(), 409);
+    assert_eq!(violation.code(), "unique_violation");
+    assert_eq!(GraphError::ReadOnly.status_code(), 403);
+    assert_eq!(storage_error().status_code(), 500);
+    assert_eq!(storage_error().code(), "storage_error");
+    assert_eq!(
+        GraphError::Io(std::io::Error::other("disk")).status_code(),
+        500
+    );
+}
+
+#[test]
+fn test_handler_error_written_as_json() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None).with_error_verbosity(ErrorVerbosity::Minimal);
+    router.add_route("POST", "/missing", |_: &HandlerInput, _: &mut Response| {
+        Err(GraphError::NodeNotFound)
+    });
+    router.add_route("POST", "/broken", |_: &HandlerInput, _: &mut Response| {
+        Err(storage_error())
+    });
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), request("/missing"), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 404);
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"node_not_found","message":"Node not found"}}"#
+    );
+    assert_eq!(response.headers["Content-Type"], "application/json");
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), request("/broken"), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 500);
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"storage_error","message":"Internal Server Error"}}"#
+    );
+
+    // requests with no route get the same shape
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), request("/nowhere"), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 404);
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"route_not_found","message":"Route not found"}}"#
+    );
+}
+
+fn delete_node(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    let id = uuid::Uuid::parse_str(std::str::from_utf8(&input.request.body)?)?;
+    input.graph.drop_node(id.as_u128())?;
+    response.no_content();
+    Ok(())
+}
+
+#[test]
+fn test_delete_node_sends_204_then_404() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut txn = graph.storage.graph_env.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&graph.storage), &mut txn)
+        .add_n("person", None, None)
+        .collect_to_val();
+    txn.commit().unwrap();
+
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("DELETE", "/nodes", delete_node);
+    let delete = || Request {
+        method: "DELETE".to_string(),
+        headers: Headers::new(),
+        path: "/nodes".to_string(),
+        raw_path: "/nodes".to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: uuid::Uuid::from_u128(node.id()).to_string().into_bytes(),
+        request_id: String::new(),
+    };
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), delete(), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 204);
+    assert!(response.body.is_empty());
+    assert_eq!(graph.node_count().unwrap(), 0);
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), delete(), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 404);
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"node_not_found","message":"Node not found"}}"#
+    );
+}
+
+#[test]
+fn test_write_to_read_only_engine_gets_403() {
+    let (graph, temp_dir) = setup_test_engine();
+    let mut txn = graph.storage.graph_env.write_txn().unwrap();
+    let node = G::new_mut(Arc::clone(&graph.storage), &mut txn)
+        .add_n("person", None, None)
+        .collect_to_val();
+    txn.commit().unwrap();
+    drop(graph);
+    let path = temp_dir.path().to_str().unwrap();
+    let graph = Arc::new(HelixGraphEngine::open_read_only(path).unwrap());
+
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("DELETE", "/nodes", delete_node);
+    let mut delete = request("/nodes");
+    delete.method = "DELETE".to_string();
+    delete.body = uuid::Uuid::from_u128(node.id()).to_string().into_bytes();
+
+    let mut response = Response::new();
+    router.handle(Arc::clone(&graph), delete, &mut response).unwrap();
+    assert_eq!(response.status, 403);
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"read_only","message":"Storage is read-only"}}"#
+    );
+    assert_eq!(graph.node_count().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_no_content_sent_without_body() {
+    let mut response = Response::new();
+    response.body = b"ignored".to_vec();
+    response.no_content();
+
+    let mut stream = std::io::Cursor::new(Vec::new());
+    response.send(&mut stream).await.unwrap();
+    let data = String::from_utf8(stream.into_inner()).unwrap();
+
+    assert!(data.starts_with("HTTP/1.1 204 No Content\r\n"));
+    assert!(data.ends_with("\r\n\r\n"));
+    assert!(!data.contains("Content-Length"));
+    assert!(!data.contains("ignored"));
+}
+
+#[tokio::test]
+async fn test_head_falls_back_to_get_without_body() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("GET", "/hello", |_: &HandlerInput, response: &mut Response| {
+        response.body = b"Hello World".to_vec();
+        Ok(())
+    });
+    let head = Request {
+        method: "HEAD".to_string(),
+        ..request("/hello")
+    };
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), head, &mut response)
+        .unwrap();
+    assert_eq!(response.status, 200);
+
+    let mut stream = std::io::Cursor::new(Vec::new());
+    response.send(&mut stream).await.unwrap();
+    let data = String::from_utf8(stream.into_inner()).unwrap();
+
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(data.contains("Content-Length: 11\r\n"));
+    assert!(data.ends_with("\r\n\r\n"));
+    assert!(!data.contains("Hello World"));
+}
+
+#[test]
+fn test_head_route_takes_precedence_over_get() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("GET", "/hello", |_: &HandlerInput, _: &mut Response| {
+        Err(GraphError::New("GET handler called".to_string()))
+    });
+    router.add_route("HEAD", "/hello", |_: &HandlerInput, response: &mut Response| {
+        response.status = 204;
+        Ok(())
+    });
+    let head = Request {
+        method: "HEAD".to_string(),
+        ..request("/hello")
+    };
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), head, &mut response)
+        .unwrap();
+    assert_eq!(response.status, 204);
+}
+
+#[test]
+fn test_if_none_match_answered_with_304_for_reads() {
+    let (graph, _temp_dir) = setup_test_engine();
+    let mut router = HelixRouter::new(None, None);
+    let handler = |_: &HandlerInput, response: &mut Response| {
+        response.body = b"{\"name\":\"alice\"}".to_vec();
+        let etag = weak_etag(&response.body);
+        response.with_etag(&etag);
+        Ok(())
+    };
+    router.add_route("GET", "/node", handler);
+    router.add_route("POST", "/node", handler);
+    let etag = weak_etag(b"{\"name\":\"alice\"}");
+    let conditional = |method: &str, if_none_match: &str| {
+        let mut headers = Headers::new();
+        headers.insert("if-none-match", if_none_match);
+        Request {
+            method: method.to_string(),
+            headers,
+            ..request("/node")
+        }
+    };
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), conditional("GET", &etag), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 304);
+    assert!(response.body.is_empty());
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), conditional("GET", "W/\"stale\""), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"{\"name\":\"alice\"}");
+
+    let mut response = Response::new();
+    router
+        .handle(Arc::clone(&graph), conditional("POST", &etag), &mut response)
+        .unwrap();
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn test_try_add_route_rejects_duplicates() {
+    let mut router = HelixRouter::new(None, None);
+    let noop = |_: &HandlerInput, _: &mut Response| Ok(());
+    router.try_add_route("get", "/nodes", noop).unwrap();
+    router.try_add_route("POST", "/nodes", noop).unwrap();
+
+    // methods are matched case insensitively, so this is the same route
+    let result = router.try_add_route("GET", "/nodes", noop);
+    assert!(matches!(
+        result,
+        Err(RouterError::DuplicateRoute(ref method, ref path)) if method == "GET" && path == "/nodes"
+    ));
+    assert_eq!(result.unwrap_err().to_string(), "Route GET /nodes is already registered");
+
+    // add_route still replaces the handler
+    router.add_route("GET", "/nodes", noop);
+    assert_eq!(router.routes.len(), 2);
+}
+
+#[test]
+fn test_routes_lists_every_route_sorted() {
+    let mut router = HelixRouter::new(None, None);
+    let noop = |_: &HandlerInput, _: &mut Response| Ok(());
+    router.add_route("POST", "/query", noop);
+    router.add_route("GET", "/nodes", noop);
+    router.add_route("DELETE", "/nodes", noop);
+
+    assert_eq!(
+        router.routes(),
+        vec![
+            ("DELETE".to_string(), "/nodes".to_string()),
+            ("GET".to_string(), "/nodes".to_string()),
+            ("POST".to_string(), "/query".to_string()),
+        ]
+    );
+}
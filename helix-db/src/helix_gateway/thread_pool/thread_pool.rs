@@ -1,17 +1,28 @@
 use crate::helix_engine::graph_core::graph_core::HelixGraphEngine;
-use flume::{Receiver, Sender};
-use std::sync::{Arc, Mutex};
-use tokio::task::JoinHandle;
+use flume::{Receiver, Sender, WeakSender};
+use std::{
+    sync::{Arc, Mutex, RwLock, atomic::Ordering},
+    time::{Duration, Instant},
+};
+use tokio::{sync::watch, task::JoinHandle, time::Instant as Deadline};
 
+use crate::helix_gateway::access_log::RequestLog;
+use crate::helix_gateway::connection::connection::ClientStream;
+use crate::helix_gateway::connection::rate_limiter::RateLimiter;
+use crate::helix_gateway::cors::CorsOpts;
+use crate::helix_gateway::gateway::GatewayOpts;
+use crate::helix_gateway::metrics::GatewayMetrics;
 use crate::helix_gateway::router::router::{HelixRouter, RouterError};
-use crate::protocol::request::Request;
+use crate::helix_gateway::version::set_server_header;
+use crate::protocol::binary::WireProtocol;
+use crate::protocol::encoding::compress_response;
+use crate::protocol::request::{REQUEST_ID_HEADER, RejectedRequest, Request};
 use crate::protocol::response::Response;
+use crate::protocol::websocket::{self, WebSocket};
 
 
 extern crate tokio;
 
-use tokio::net::TcpStream;
-
 /// Worker for handling requests
 /// 
 /// A worker is a thread that handles requests
@@ -31,48 +42,27 @@ impl Worker {
     /// It sends the response back to the client
     fn new(
         id: usize,
-        graph_access: Arc<HelixGraphEngine>,
-        router: Arc<HelixRouter>,
-        rx: Receiver<TcpStream>,
+        serving: Arc<Serving>,
+        rx: Receiver<ClientStream>,
+        requeue: Requeue,
     ) -> Worker {
         let handle = tokio::spawn(async move {
             loop {
-                let mut conn = match rx.recv_async().await {
-                    Ok(stream) => stream,
-                    Err(e) => {
-                        eprintln!("Error receiving connection: {:?}", e);
-                        continue;
+                let conn = match rx.recv_async().await {
+                    Ok(stream) => {
+                        serving.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                        stream
                     }
-                };
-
-                let request = match Request::from_stream(&mut conn).await {
-                    Ok(request) => request,
+                    // every sender is gone, so no more connections will come
                     Err(e) => {
-                        eprintln!("Error parsing request: {:?}", e);
-                        continue;
+                        eprintln!("Error receiving connection: {:?}", e);
+                        break;
                     }
                 };
 
-                let mut response = Response::new();
-                if let Err(e) = router.handle(Arc::clone(&graph_access), request, &mut response) {
-                    eprintln!("Error handling request: {:?}", e);
-                    response.status = 500;
-                    response.body = format!("\n{:?}", e).into_bytes();
-                }
-
-                if let Err(e) = response.send(&mut conn).await {
-                    eprintln!("Error sending response: {:?}", e);
-                    match e.kind() {
-                        std::io::ErrorKind::BrokenPipe => {
-                            eprintln!("Client disconnected before response could be sent");
-                        }
-                        std::io::ErrorKind::ConnectionReset => {
-                            eprintln!("Connection was reset by peer");
-                        }
-                        _ => {
-                            eprintln!("Unexpected error type: {:?}", e);
-                        }
-                    }
+                if let Some((conn, idle_timeout)) = serve_next(conn, &serving).await {
+                    let metrics = Arc::clone(&serving.metrics);
+                    tokio::spawn(wait_for_next_request(conn, idle_timeout, requeue.clone(), metrics));
                 }
             }
         });
@@ -81,12 +71,439 @@ impl Worker {
     }
 }
 
+/// What serving a connection's requests takes, shared by the workers or the connection tasks
+pub(crate) struct Serving {
+    router: Arc<HelixRouter>,
+    graph: Arc<HelixGraphEngine>,
+    opts: GatewayOpts,
+    metrics: Arc<GatewayMetrics>,
+    rate_limiter: RwLock<Option<Arc<RateLimiter>>>,
+    // set once the pool stops keeping connections alive
+    closing: watch::Receiver<bool>,
+    // set once the pool is stopped, which ends the connection tasks
+    stopped: watch::Receiver<bool>,
+}
+
+impl Serving {
+    /// Takes a token for a request from the client on `conn`, returning how long until the
+    /// next one is available if it's over its rate limit
+    fn over_rate_limit(&self, conn: &ClientStream) -> Option<Duration> {
+        let rate_limiter = self.rate_limiter.read().unwrap();
+        rate_limiter.as_ref()?.check(conn.peer_addr().ip()).err()
+    }
+}
+
+/// Answers the next request on `conn`, returning the connection along with how long to wait
+/// for another request on it if it's kept alive
+async fn serve_next(conn: ClientStream, serving: &Serving) -> Option<(ClientStream, Duration)> {
+    match serving.opts.protocol {
+        WireProtocol::Http => serve_http(conn, serving).await,
+        WireProtocol::Binary => serve_binary(conn, serving).await,
+    }
+}
+
+/// Serves every request on `conn` on the task it's run on, as connections are with
+/// `task_per_connection` and the `task-per-connection` feature, waiting for the next one
+/// in between while the connection is kept alive.
+pub(crate) async fn serve_connection(mut conn: ClientStream, serving: Arc<Serving>) {
+    let mut closing = serving.closing.clone();
+    let mut stopped = serving.stopped.clone();
+    let serve = async {
+        while let Some((kept_alive, idle_timeout)) = serve_next(conn, &serving).await {
+            conn = kept_alive;
+            if !wait_idle(&mut conn, idle_timeout, &mut closing, &serving.metrics).await {
+                return;
+            }
+        }
+    };
+    tokio::select! {
+        _ = serve => {}
+        // errors if the pool is dropped, which ends the task too
+        _ = stopped.wait_for(|stopped| *stopped) => {}
+    }
+}
+
+/// Answers the next HTTP request on `conn`, see [`serve_next`]
+async fn serve_http(
+    mut conn: ClientStream,
+    serving: &Serving,
+) -> Option<(ClientStream, Duration)> {
+    let opts = &serving.opts;
+    let limits = opts.request_limits();
+    let access_log = opts.access_log;
+    let server_header = opts.server_header;
+    let keep_alive = opts.keep_alive;
+
+    let deadline = opts.request_timeout.map(|timeout| Deadline::now() + timeout);
+    let parsed = match deadline {
+        Some(deadline) => tokio::time::timeout_at(
+            deadline,
+            Request::from_stream_pipelined(&mut conn, &limits),
+        )
+        .await
+        .ok(),
+        None => Some(Request::from_stream_pipelined(&mut conn, &limits).await),
+    };
+    let request = match parsed {
+        // a pipelined request read along with this one is read again after it
+        Some(Ok((request, leftover))) => {
+            conn.unread(leftover);
+            request
+        }
+        None => {
+            let mut response = Response::error(408, "request_timeout", "Timeout reading request");
+            if server_header {
+                set_server_header(&mut response);
+            }
+            set_connection_header(&mut response, keep_alive, None);
+            if let Err(e) = response.send(&mut conn).await {
+                eprintln!("Error sending response: {:?}", e);
+            }
+            return None;
+        }
+        Some(Err(ref e)) if let Some(rejected) = RejectedRequest::from_error(e) => {
+            let mut response = Response::error(rejected.status, rejected.code, &rejected.reason);
+            if server_header {
+                set_server_header(&mut response);
+            }
+            // where a rejected request ends can't be trusted, so nothing more is read after it
+            response.headers.insert("Connection", "close");
+            if let Err(e) = response.send(&mut conn).await {
+                eprintln!("Error sending response: {:?}", e);
+            }
+            return None;
+        }
+        Some(Err(e)) => {
+            eprintln!("Error parsing request: {:?}", e);
+            return None;
+        }
+    };
+
+    conn.count_request();
+    let wants_keep_alive = request.wants_keep_alive();
+
+    let started = Instant::now();
+    let mut log = access_log.map(|_| RequestLog::start(conn.peer_addr(), &request));
+
+    let request_id = request.request_id.clone();
+    let over_rate_limit = serving.over_rate_limit(&conn);
+    let origin = request.headers.get("Origin").cloned();
+    let accept_encoding = request.headers.get("Accept-Encoding").cloned();
+    let on_websocket = opts.on_websocket.filter(|_| request.is_websocket_upgrade());
+    let mut upgraded = None;
+    let mut response = Response::new();
+    match &opts.cors {
+        // the request is still read in full, so the connection can be kept alive
+        _ if let Some(retry_after) = over_rate_limit => {
+            response = rate_limited(retry_after);
+        }
+        Some(cors) if CorsOpts::is_preflight(&request) => {
+            cors.preflight(origin.as_deref(), &mut response);
+        }
+        _ if on_websocket.is_some() => match websocket::handshake_response(&request) {
+            Some(accepted) => {
+                response = accepted;
+                upgraded = Some(request);
+            }
+            None => {
+                response = Response::error(
+                    400,
+                    "invalid_websocket_handshake",
+                    "Invalid websocket handshake",
+                );
+            }
+        },
+        cors => {
+            response = handle_request(serving, request, deadline).await;
+            if let Some(cors) = cors {
+                cors.apply(origin.as_deref(), &mut response);
+            }
+        }
+    }
+
+    if let Some(min_size) = opts.compression {
+        compress_response(&mut response, accept_encoding.as_deref(), min_size);
+    }
+    if server_header {
+        set_server_header(&mut response);
+    }
+    response.headers.insert(REQUEST_ID_HEADER, request_id);
+
+    let is_event_stream = response.event_stream.is_some();
+    // the connection is handed over after a websocket handshake or an event stream
+    let kept_alive = keep_alive
+        .filter(|_| wants_keep_alive && upgraded.is_none() && !is_event_stream)
+        .filter(|_| !*serving.closing.borrow());
+    if upgraded.is_none() {
+        set_connection_header(&mut response, keep_alive, kept_alive);
+    }
+
+    let metrics = Arc::clone(&serving.metrics);
+    let finish = async move {
+        let sent = response.send(&mut conn).await;
+        metrics.record_request(response.status, started.elapsed());
+        if let (Ok(()), Some(access_log), Some(log)) = (&sent, access_log, log.as_mut()) {
+            log.status = response.status;
+            log.bytes = response.body_len();
+            log.duration = started.elapsed();
+            access_log(log);
+        }
+
+        // the connection is the websocket handler's from here on
+        if let (Ok(()), Some(on_websocket), Some(request)) = (&sent, on_websocket, upgraded) {
+            tokio::spawn(on_websocket(request, WebSocket::new(conn)));
+            return None;
+        }
+
+        if let (Ok(()), Some(idle_timeout)) = (&sent, kept_alive) {
+            return Some((conn, idle_timeout));
+        }
+
+        if let Err(e) = sent {
+            eprintln!("Error sending response: {:?}", e);
+            match e.kind() {
+                std::io::ErrorKind::BrokenPipe => {
+                    eprintln!("Client disconnected before response could be sent");
+                }
+                std::io::ErrorKind::ConnectionReset => {
+                    eprintln!("Connection was reset by peer");
+                }
+                std::io::ErrorKind::TimedOut => {
+                    eprintln!("Client stopped reading the response, closing the connection");
+                }
+                _ => {
+                    eprintln!("Unexpected error type: {:?}", e);
+                }
+            }
+        }
+        None
+    };
+    // an event stream stays open until its handler is done with it,
+    // so it is sent off the worker like a websocket
+    if is_event_stream {
+        tokio::spawn(finish);
+        return None;
+    }
+    finish.await
+}
+
+/// `429 Too Many Requests`
+fn rate_limited(retry_after: Duration) -> Response {
+    let mut response = Response::error(429, "rate_limited", "Too many requests");
+    // Retry-After is in whole seconds, so round up to not invite a retry that fails again
+    let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    response
+        .headers
+        .insert("Retry-After".to_string(), retry_after.to_string());
+    response
+}
+
+/// Tells the client whether the connection stays open after `response` when keep-alive is
+/// enabled, and for how long it's kept waiting for the next request if it does.
+///
+/// Leaves the response as it is when keep-alive is disabled, as every connection is closed then.
+fn set_connection_header(
+    response: &mut Response,
+    keep_alive: Option<Duration>,
+    kept_alive: Option<Duration>,
+) {
+    match (keep_alive, kept_alive) {
+        (_, Some(idle_timeout)) => {
+            let timeout = format!("timeout={}", idle_timeout.as_secs().max(1));
+            response.headers.insert("Keep-Alive", timeout);
+        }
+        (Some(_), None) => {
+            response.headers.insert("Connection", "close");
+        }
+        (None, None) => {}
+    }
+}
+
+/// How a worker hands a kept-alive connection back to the pool
+#[derive(Clone)]
+struct Requeue {
+    // doesn't keep the pool's channel open
+    sender: WeakSender<ClientStream>,
+    closing: watch::Receiver<bool>,
+}
+
+/// Waits for the next request on a kept-alive connection without holding a worker, then queues
+/// the connection for one again, see [`wait_idle`]
+async fn wait_for_next_request(
+    mut conn: ClientStream,
+    idle_timeout: Duration,
+    mut requeue: Requeue,
+    metrics: Arc<GatewayMetrics>,
+) {
+    if !wait_idle(&mut conn, idle_timeout, &mut requeue.closing, &metrics).await {
+        return;
+    }
+
+    let Some(sender) = requeue.sender.upgrade() else {
+        return;
+    };
+    // counted before sending so a worker picking it up straight away can't go below zero
+    metrics.queued.fetch_add(1, Ordering::Relaxed);
+    if let Err(e) = sender.send_async(conn).await {
+        metrics.queued.fetch_sub(1, Ordering::Relaxed);
+        eprintln!("Error requeueing kept-alive connection: {}", e);
+    }
+}
+
+/// Waits for the client to send its next request on a kept-alive connection, returning whether
+/// it did.
+///
+/// Gives up if the client sends nothing within `idle_timeout` or closes the connection itself,
+/// or once the pool is closing.
+async fn wait_idle(
+    conn: &mut ClientStream,
+    idle_timeout: Duration,
+    closing: &mut watch::Receiver<bool>,
+    metrics: &GatewayMetrics,
+) -> bool {
+    metrics.idle_connections.fetch_add(1, Ordering::Relaxed);
+    let waited = tokio::select! {
+        waited = tokio::time::timeout(idle_timeout, conn.wait_for_data()) => waited,
+        // errors if the pool is dropped, which closes the connection too
+        _ = closing.wait_for(|closing| *closing) => Ok(Ok(false)),
+    };
+    metrics.idle_connections.fetch_sub(1, Ordering::Relaxed);
+    match waited {
+        Ok(Ok(true)) => true,
+        Ok(Ok(false) | Err(_)) => false,
+        Err(_) => {
+            metrics.keep_alive_timeouts.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+/// Answers the next request on a [`WireProtocol::Binary`] connection, see [`serve_next`].
+/// Its connection is kept alive like an HTTP one, if keep-alive is enabled.
+///
+/// A frame that isn't a valid request is answered with its error before the connection is
+/// closed, while one that can't be read at all just closes it.
+async fn serve_binary(
+    mut conn: ClientStream,
+    serving: &Serving,
+) -> Option<(ClientStream, Duration)> {
+    let opts = &serving.opts;
+    let request = match Request::from_binary_stream(&mut conn, &opts.request_limits()).await {
+        Ok(Some(request)) => request,
+        // the client closed the connection between requests
+        Ok(None) => return None,
+        Err(ref e) if let Some(rejected) = RejectedRequest::from_error(e) => {
+            let mut response = Response::error(rejected.status, rejected.code, &rejected.reason);
+            if let Err(e) = response.send_binary(&mut conn).await {
+                eprintln!("Error sending response: {:?}", e);
+            }
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Error reading binary request: {:?}", e);
+            return None;
+        }
+    };
+
+    conn.count_request();
+    let started = Instant::now();
+    let mut log = opts.access_log.map(|_| RequestLog::start(conn.peer_addr(), &request));
+    let request_id = request.request_id.clone();
+    let deadline = opts.request_timeout.map(|timeout| Deadline::now() + timeout);
+    let mut response = match serving.over_rate_limit(&conn) {
+        Some(retry_after) => rate_limited(retry_after),
+        None => handle_request(serving, request, deadline).await,
+    };
+    response.headers.insert(REQUEST_ID_HEADER, request_id);
+
+    let sent = response.send_binary(&mut conn).await;
+    serving.metrics.record_request(response.status, started.elapsed());
+    if let (Ok(()), Some(access_log), Some(log)) = (&sent, opts.access_log, log.as_mut()) {
+        log.status = response.status;
+        log.bytes = response.body_len();
+        log.duration = started.elapsed();
+        access_log(log);
+    }
+    match sent {
+        Ok(()) => {
+            let kept_alive = opts.keep_alive.filter(|_| !*serving.closing.borrow())?;
+            Some((conn, kept_alive))
+        }
+        Err(e) => {
+            eprintln!("Error sending response: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Handles `request` on the worker, or with [`handle_by`] when it has a deadline or the
+/// connection has its own task, as a handler run on a task would hold up the runtime
+async fn handle_request(
+    serving: &Serving,
+    request: Request,
+    deadline: Option<Deadline>,
+) -> Response {
+    if deadline.is_none() && !serving.opts.serves_task_per_connection() {
+        return handle(&serving.router, &serving.graph, request);
+    }
+    handle_by(deadline, &serving.router, &serving.graph, request).await
+}
+
+/// Handles `request` with the router, writing any error the handler returns to the response
+fn handle(router: &HelixRouter, graph: &Arc<HelixGraphEngine>, request: Request) -> Response {
+    let mut response = Response::new();
+    if let Err(e) = router.handle(Arc::clone(graph), request, &mut response) {
+        router.write_error(&e, &mut response);
+    }
+    response
+}
+
+/// Handles `request` like [`handle`] on the blocking pool, answering with `503` instead
+/// if it isn't done by `deadline`, or with `408` if the deadline passed before it started.
+///
+/// A handler that runs past the deadline is left to finish in the background.
+async fn handle_by(
+    deadline: Option<Deadline>,
+    router: &Arc<HelixRouter>,
+    graph: &Arc<HelixGraphEngine>,
+    request: Request,
+) -> Response {
+    if deadline.is_some_and(|deadline| Deadline::now() >= deadline) {
+        return Response::error(408, "request_timeout", "Timeout reading request");
+    }
+    let head = request.method == "HEAD";
+    let path = request.path.clone();
+    let (router, graph) = (Arc::clone(router), Arc::clone(graph));
+    let handled = tokio::task::spawn_blocking(move || handle(&router, &graph, request));
+    let handled = match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, handled).await.ok(),
+        None => Some(handled.await),
+    };
+    let mut response = match handled {
+        Some(Ok(response)) => return response,
+        Some(Err(e)) => {
+            eprintln!("Handler for {} panicked: {:?}", path, e);
+            Response::error(500, "internal_error", "Internal Server Error")
+        }
+        None => {
+            eprintln!("Handler for {} ran past the request timeout", path);
+            Response::error(503, "handler_timeout", "Timeout handling request")
+        }
+    };
+    response.head = head;
+    response
+}
+
 /// Thread pool for handling requests
 pub struct ThreadPool {
-    pub sender: Sender<TcpStream>,
+    pub sender: Sender<ClientStream>,
     pub num_unused_workers: Mutex<usize>,
     pub num_used_workers: Mutex<usize>,
     pub workers: Vec<Worker>,
+    // set once the pool stops keeping connections alive
+    closing: watch::Sender<bool>,
+    stopped: watch::Sender<bool>,
+    serving: Arc<Serving>,
 }
 
 impl ThreadPool {
@@ -95,16 +512,64 @@ impl ThreadPool {
         graph: Arc<HelixGraphEngine>,
         router: Arc<HelixRouter>,
     ) -> Result<ThreadPool, RouterError> {
+        let opts = GatewayOpts::builder().pool_size(size).build();
+        Self::with_opts(graph, router, &opts, Arc::new(GatewayMetrics::default()))
+    }
+
+    /// Creates a thread pool of `opts.pool_size` workers, which reject requests
+    /// that break the request limits in `opts`.
+    ///
+    /// No workers are started with `opts.task_per_connection`, as the connections are served
+    /// on tasks of their own, see [`Self::connection_tasks`].
+    pub fn with_opts(
+        graph: Arc<HelixGraphEngine>,
+        router: Arc<HelixRouter>,
+        opts: &GatewayOpts,
+        metrics: Arc<GatewayMetrics>,
+    ) -> Result<ThreadPool, RouterError> {
+        let size = opts.pool_size;
+        let (tx, rx) = flume::bounded::<ClientStream>(1000); // TODO: make this configurable
+        let (closing, _) = watch::channel(false);
+        let (stopped, _) = watch::channel(false);
+        let serving = Arc::new(Serving {
+            router,
+            graph,
+            opts: opts.clone(),
+            metrics,
+            rate_limiter: RwLock::new(None),
+            closing: closing.subscribe(),
+            stopped: stopped.subscribe(),
+        });
+
+        if opts.serves_task_per_connection() {
+            println!("Serving each connection on its own task");
+            return Ok(ThreadPool {
+                sender: tx,
+                num_unused_workers: Mutex::new(0),
+                num_used_workers: Mutex::new(0),
+                workers: Vec::new(),
+                closing,
+                stopped,
+                serving,
+            });
+        }
+
         assert!(
             size > 0,
             "Expected number of threads in thread pool to be more than 0, got {}",
             size
         );
-
-        let (tx, rx) = flume::bounded::<TcpStream>(1000); // TODO: make this configurable
         let mut workers = Vec::with_capacity(size);
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&graph), Arc::clone(&router), rx.clone()));
+            workers.push(Worker::new(
+                id,
+                Arc::clone(&serving),
+                rx.clone(),
+                Requeue {
+                    sender: tx.downgrade(),
+                    closing: closing.subscribe(),
+                },
+            ));
         }
         println!("Thread pool initialized with {} workers", workers.len());
 
@@ -114,6 +579,36 @@ impl ThreadPool {
             num_unused_workers: Mutex::new(size),
             num_used_workers: Mutex::new(0),
             workers,
+            closing,
+            stopped,
+            serving,
         })
     }
-}
\ No newline at end of file
+
+    /// What to serve connections with on tasks of their own, which is only set with
+    /// `task_per_connection`, see [`serve_connection`]
+    pub(crate) fn connection_tasks(&self) -> Option<Arc<Serving>> {
+        let task_per_connection = self.serving.opts.serves_task_per_connection();
+        task_per_connection.then(|| Arc::clone(&self.serving))
+    }
+
+    /// Answers each request from a client over `rate_limiter`'s limit with
+    /// `429 Too Many Requests` instead of passing it to the router
+    pub fn rate_limit(&self, rate_limiter: RateLimiter) {
+        *self.serving.rate_limiter.write().unwrap() = Some(Arc::new(rate_limiter));
+    }
+
+    /// Closes the kept-alive connections waiting for their next request, and stops keeping
+    /// connections alive after answering them
+    pub fn close_idle(&self) {
+        self.closing.send_replace(true);
+    }
+
+    /// Stops the workers, or the connection tasks, closing any connections they're serving
+    pub fn stop(&self) {
+        self.stopped.send_replace(true);
+        for worker in &self.workers {
+            worker.handle.abort();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    helix_engine::types::GraphError, helix_gateway::router::router::HandlerInput,
+    protocol::response::Response,
+};
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+
+/// The version of the crate the gateway was built from
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit the gateway was built from, or `unknown` if it wasn't built from a checkout.
+///
+/// Set by the build script, which takes it from `HELIX_GIT_COMMIT` instead if that's set.
+pub const GIT_COMMIT: &str = env!("HELIX_GIT_COMMIT");
+
+/// When the gateway was built, in seconds since the Unix epoch, or `SOURCE_DATE_EPOCH` if set
+pub const BUILD_TIMESTAMP: &str = env!("HELIX_BUILD_TIMESTAMP");
+
+/// Which build of Helix is running, as `GET /version` answers with it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    /// RFC 3339 in UTC, e.g. `2025-06-01T12:00:00Z`
+    pub built_at: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let built_at = BUILD_TIMESTAMP
+            .parse()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map_or_else(
+                || "unknown".to_string(),
+                |built_at| built_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            );
+        Self {
+            version: VERSION,
+            commit: GIT_COMMIT,
+            built_at,
+        }
+    }
+}
+
+/// The `Server` header sent with every response when the gateway's server header is enabled
+pub fn server_header() -> String {
+    format!("helix-db/{}", VERSION)
+}
+
+/// Sets the `Server` header to [`server_header`], unless a handler set one already
+pub fn set_server_header(response: &mut Response) {
+    if response.headers.get("Server").is_none() {
+        response.headers.insert("Server", server_header());
+    }
+}
+
+/// Answers with the [`BuildInfo`] of the running gateway as JSON, e.g.
+/// `{"version": "1.0.113", "commit": "4f51279…", "built_at": "2025-06-01T12:00:00Z"}`.
+///
+/// Registered at `GET /version` when the gateway's version endpoint is enabled.
+pub fn version_handler(input: &HandlerInput, response: &mut Response) -> Result<(), GraphError> {
+    response.json(&BuildInfo::current(), input.request.wants_pretty_json())
+}
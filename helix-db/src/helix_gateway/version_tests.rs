@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use sonic_rs::{JsonValueTrait, Value as JsonValue};
+use tempfile::TempDir;
+
+use super::{
+    router::router::HelixRouter,
+    version::{BuildInfo, VERSION, server_header, set_server_header, version_handler},
+};
+use crate::{
+    helix_engine::graph_core::{
+        config::Config,
+        graph_core::{HelixGraphEngine, HelixGraphEngineOpts},
+    },
+    protocol::{headers::Headers, request::Request, response::Response},
+};
+
+#[test]
+fn test_build_info() {
+    let info = BuildInfo::current();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert!(!info.commit.is_empty());
+    assert!(chrono::DateTime::parse_from_rfc3339(&info.built_at).is_ok());
+}
+
+#[test]
+fn test_version_handler_answers_with_build_info() {
+    let temp_dir = TempDir::new().unwrap();
+    let opts = HelixGraphEngineOpts {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        config: Config::default(),
+    };
+    let graph = Arc::new(HelixGraphEngine::new(opts).unwrap());
+    let mut router = HelixRouter::new(None, None);
+    router.add_route("GET", "/version", version_handler);
+    let request = Request {
+        method: "GET".to_string(),
+        headers: Headers::new(),
+        path: "/version".to_string(),
+        raw_path: "/version".to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: Vec::new(),
+        request_id: String::new(),
+    };
+    let mut response = Response::new();
+    router.handle(graph, request, &mut response).unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.headers.get("Content-Type").map(String::as_str),
+        Some("application/json")
+    );
+    let body: JsonValue = sonic_rs::from_slice(&response.body).unwrap();
+    let info = BuildInfo::current();
+    assert_eq!(body["version"].as_str(), Some(VERSION));
+    assert_eq!(body["commit"].as_str(), Some(info.commit));
+    assert_eq!(body["built_at"].as_str(), Some(info.built_at.as_str()));
+}
+
+#[test]
+fn test_server_header_keeps_one_set_by_handler() {
+    let mut response = Response::new();
+    set_server_header(&mut response);
+    assert_eq!(response.headers.get("Server"), Some(&server_header()));
+    assert_eq!(server_header(), format!("helix-db/{}", VERSION));
+
+    let mut response = Response::new();
+    response.headers.insert("Server", "custom");
+    set_server_header(&mut response);
+    assert_eq!(
+        response.headers.get("Server").map(String::as_str),
+        Some("custom")
+    );
+}
@@ -251,7 +251,7 @@ impl Display for Query {
         // if mut then get write txn
         // if not then get read txn
         if self.is_mut {
-            writeln!(f, "let mut txn = db.graph_env.write_txn().unwrap();")?;
+            writeln!(f, "let mut txn = db.write_txn().unwrap();")?;
         } else {
             writeln!(f, "let txn = db.graph_env.read_txn().unwrap();")?;
         }
@@ -271,10 +271,12 @@ impl Display for Query {
             }
         }
 
-        // commit the transaction
-        // if self.is_mut {
-        writeln!(f, "    txn.commit().unwrap();")?;
-        // }/
+        // commit the transaction, publishing its changes to subscribers if it wrote any
+        if self.is_mut {
+            writeln!(f, "    db.commit(txn).unwrap();")?;
+        } else {
+            writeln!(f, "    txn.commit().unwrap();")?;
+        }
         // closes the handler function
         write!(
             f,
@@ -0,0 +1,229 @@
+use crate::protocol::{
+    headers::Headers,
+    request::{IdleTimeout, RejectedRequest, Request, RequestLimits, request_id},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{io::Read, time::Duration};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Result};
+
+/// Frames larger than this are refused when no max body size is set, so a corrupt length
+/// prefix can't have up to 4 GiB allocated for it
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// The `version` of a request read from a binary frame, as it has no request line
+pub const BINARY_VERSION: &str = "HELIX-BINARY/1";
+
+/// How a listener's connections are framed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireProtocol {
+    /// HTTP/1.1 requests and responses
+    #[default]
+    Http,
+    /// Frames of a 4 byte big-endian length followed by that many bytes of a MessagePack
+    /// encoded [`BinaryRequest`], answered with frames of a [`BinaryResponse`]
+    Binary,
+}
+
+/// A request sent over the binary protocol, as a MessagePack map
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryRequest {
+    pub method: String,
+    /// Matched against routes as it is, so it isn't percent-encoded, and can have a query string
+    pub path: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+/// A response sent over the binary protocol, as a MessagePack map
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+/// [`BinaryResponse`] borrowing a response's headers and body so they aren't copied to be encoded
+#[derive(Serialize)]
+struct BinaryResponseRef<'a> {
+    status: u16,
+    headers: Vec<(&'a str, &'a str)>,
+    #[serde(with = "serde_bytes")]
+    body: &'a [u8],
+}
+
+/// Reads the next frame from `reader`, or `None` if the connection was closed before one began.
+///
+/// Fails with `InvalidData` if the frame is larger than `max_size`.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_size: usize,
+) -> Result<Option<Vec<u8>>> {
+    match read_frame_length(reader, max_size).await? {
+        Some(length) => read_frame_payload(reader, length).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads the length prefix of the next frame, or `None` if the connection was closed before
+/// one began, failing with `InvalidData` if it is larger than `max_size`
+async fn read_frame_length<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_size: usize,
+) -> Result<Option<usize>> {
+    let mut prefix = [0u8; 4];
+    match reader.read_exact(&mut prefix).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_be_bytes(prefix) as usize;
+    if length > max_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds max size of {} bytes", length, max_size),
+        ));
+    }
+    Ok(Some(length))
+}
+
+async fn read_frame_payload<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    length: usize,
+) -> Result<Vec<u8>> {
+    let mut frame = vec![0; length];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+/// Waits for `read` for up to `timeout`, rejecting the request with a `408` if it doesn't
+/// finish in time
+async fn within<T>(
+    timeout: Option<Duration>,
+    read: impl Future<Output = Result<T>>,
+    reason: &str,
+) -> Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read).await.map_err(|_| {
+            RejectedRequest::error(408, "request_timeout", reason.to_string())
+        })?,
+        None => read.await,
+    }
+}
+
+/// Writes `payload` to `writer` as a frame, prefixed with its length
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> Result<()> {
+    let length = u32::try_from(payload.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Frame of {} bytes is too large to send", payload.len()),
+        )
+    })?;
+    writer.write_all(&length.to_be_bytes()).await?;
+    writer.write_all(payload).await?;
+    writer.flush().await
+}
+
+impl Request {
+    /// Reads a request sent over the binary protocol, or `None` if the connection was closed
+    /// before the next one.
+    ///
+    /// The whole frame is held to `limits.max_body_size`, or [`DEFAULT_MAX_FRAME_SIZE`] without
+    /// one. Its length prefix has `limits.header_timeout` to arrive in, and the rest of it can't
+    /// stop arriving for longer than `limits.body_timeout`, so a client that stops sending it
+    /// can't hold the connection open, while `limits.read_timeout` covers the whole frame.
+    /// A frame that isn't a valid [`BinaryRequest`] is rejected with a `400`, and one that
+    /// doesn't arrive in time with a `408`, see [`RejectedRequest`].
+    pub async fn from_binary_stream<S: AsyncRead + Unpin>(
+        stream: &mut S,
+        limits: &RequestLimits,
+    ) -> Result<Option<Request>> {
+        let read = Self::read_binary_frame(stream, limits);
+        let frame = match limits.read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, read).await.map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout reading frame")
+            })??,
+            None => read.await?,
+        };
+        let Some(frame) = frame else {
+            return Ok(None);
+        };
+        let binary: BinaryRequest = rmp_serde::from_slice(&frame).map_err(|e| {
+            RejectedRequest::error(
+                400,
+                "invalid_binary_request",
+                format!("Invalid binary request: {}", e),
+            )
+        })?;
+
+        let headers: Headers = binary.headers.into_iter().collect();
+        let path = match binary.path.split_once('?') {
+            Some((path, _)) => path.to_string(),
+            None => binary.path.clone(),
+        };
+        Ok(Some(Request {
+            method: binary.method.to_uppercase(),
+            request_id: request_id(&headers),
+            headers,
+            path,
+            raw_path: binary.path,
+            version: BINARY_VERSION.to_string(),
+            body: binary.body,
+        }))
+    }
+
+    async fn read_binary_frame<S: AsyncRead + Unpin>(
+        stream: &mut S,
+        limits: &RequestLimits,
+    ) -> Result<Option<Vec<u8>>> {
+        let max_size = limits.max_body_size.unwrap_or(DEFAULT_MAX_FRAME_SIZE);
+        let length = within(
+            limits.header_timeout,
+            read_frame_length(stream, max_size),
+            "Timeout reading frame length",
+        );
+        let Some(length) = length.await? else {
+            return Ok(None);
+        };
+        let mut stream = IdleTimeout::new(stream, limits.body_timeout, "Timeout reading frame");
+        read_frame_payload(&mut stream, length).await.map(Some)
+    }
+}
+
+impl Response {
+    /// Sends the response over the binary protocol as a [`BinaryResponse`] frame.
+    ///
+    /// A streamed body is read in full to be sent, while an event stream can't be framed,
+    /// so the response is replaced with a `501` for it.
+    pub async fn send_binary<W: AsyncWrite + Unpin>(&mut self, stream: &mut W) -> Result<()> {
+        if self.event_stream.take().is_some() {
+            *self = Response::error(
+                501,
+                "unsupported_over_binary",
+                "Event streams can't be sent over the binary protocol",
+            );
+        }
+        if let Some(mut stream_body) = self.stream_body.take() {
+            self.body = tokio::task::spawn_blocking(move || {
+                let mut body = Vec::new();
+                stream_body.reader.read_to_end(&mut body).map(|_| body)
+            })
+            .await
+            .map_err(std::io::Error::other)??;
+        }
+        let response = BinaryResponseRef {
+            status: self.status,
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect(),
+            body: &self.body,
+        };
+        let payload = rmp_serde::to_vec_named(&response).map_err(std::io::Error::other)?;
+        write_frame(stream, &payload).await
+    }
+}
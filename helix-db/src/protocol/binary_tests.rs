@@ -0,0 +1,130 @@
+use std::{io::Cursor, time::Duration};
+use tokio::io::{AsyncWriteExt, duplex};
+
+use super::{
+    binary::{BINARY_VERSION, BinaryRequest, BinaryResponse, read_frame, write_frame},
+    request::{RejectedRequest, Request, RequestLimits},
+    response::Response,
+};
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(payload);
+    frame
+}
+
+async fn parse(frames: Vec<u8>) -> std::io::Result<Option<Request>> {
+    Request::from_binary_stream(&mut Cursor::new(frames), &RequestLimits::default()).await
+}
+
+#[tokio::test]
+async fn test_frames_round_trip() {
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, b"first").await.unwrap();
+    write_frame(&mut buffer, b"").await.unwrap();
+    assert_eq!(&buffer[..4], &[0, 0, 0, 5]);
+
+    let mut reader = Cursor::new(buffer);
+    assert_eq!(read_frame(&mut reader, 1024).await.unwrap(), Some(b"first".to_vec()));
+    assert_eq!(read_frame(&mut reader, 1024).await.unwrap(), Some(Vec::new()));
+    assert_eq!(read_frame(&mut reader, 1024).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_frame_over_max_size_is_refused() {
+    let mut reader = Cursor::new(frame(&[0; 64]));
+    let err = read_frame(&mut reader, 63).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn test_request_decoded_from_frame() {
+    let binary = BinaryRequest {
+        method: "post".to_string(),
+        path: "/query?pretty".to_string(),
+        headers: vec![("X-Request-Id".to_string(), "trace-7".to_string())],
+        body: b"{\"a\":1}".to_vec(),
+    };
+    let request = parse(frame(&rmp_serde::to_vec_named(&binary).unwrap()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/query");
+    assert_eq!(request.raw_path, "/query?pretty");
+    assert_eq!(request.version, BINARY_VERSION);
+    assert_eq!(request.request_id, "trace-7");
+    assert_eq!(request.body, b"{\"a\":1}");
+    assert!(request.wants_pretty_json());
+
+    // headers and body can be left out
+    #[derive(serde::Serialize)]
+    struct Minimal<'a> {
+        method: &'a str,
+        path: &'a str,
+    }
+    let minimal = Minimal { method: "GET", path: "/version" };
+    let request = parse(frame(&rmp_serde::to_vec_named(&minimal).unwrap()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.path, "/version");
+    assert!(request.body.is_empty());
+
+    assert!(parse(Vec::new()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_invalid_request_frame_rejected_as_bad_request() {
+    let err = parse(frame(b"not messagepack")).await.unwrap_err();
+    assert_eq!(RejectedRequest::from_error(&err).unwrap().status, 400);
+}
+
+#[tokio::test]
+async fn test_slow_frame_times_out_with_408() {
+    let limits = RequestLimits {
+        header_timeout: Some(Duration::from_millis(50)),
+        body_timeout: Some(Duration::from_millis(50)),
+        ..RequestLimits::default()
+    };
+    let rejected = |sent: &'static [u8]| async move {
+        let (mut client, mut server) = duplex(1024);
+        client.write_all(sent).await.unwrap();
+        let err = Request::from_binary_stream(&mut server, &limits).await.unwrap_err();
+        let rejected = RejectedRequest::from_error(&err).unwrap();
+        (rejected.status, rejected.reason.clone())
+    };
+    // the length prefix never finishes
+    assert_eq!(rejected(&[0, 0]).await, (408, "Timeout reading frame length".to_string()));
+    // the length arrives but the rest of the frame never does
+    assert_eq!(rejected(&[0, 0, 0, 5, 1]).await, (408, "Timeout reading frame".to_string()));
+}
+
+#[tokio::test]
+async fn test_response_sent_as_frame() {
+    let mut response = Response::new();
+    response.status = 201;
+    response.headers.insert("Content-Type", "application/json");
+    response.body = b"{\"id\":1}".to_vec();
+
+    let mut buffer = Vec::new();
+    response.send_binary(&mut buffer).await.unwrap();
+    let payload = read_frame(&mut Cursor::new(buffer), 1024).await.unwrap().unwrap();
+    let sent: BinaryResponse = rmp_serde::from_slice(&payload).unwrap();
+    assert_eq!(sent.status, 201);
+    assert_eq!(
+        sent.headers,
+        vec![("Content-Type".to_string(), "application/json".to_string())]
+    );
+    assert_eq!(sent.body, b"{\"id\":1}");
+}
+
+#[tokio::test]
+async fn test_event_stream_not_sent_over_binary() {
+    let (mut response, _sender) = Response::sse();
+    let mut buffer = Vec::new();
+    response.send_binary(&mut buffer).await.unwrap();
+    let payload = read_frame(&mut Cursor::new(buffer), 1024).await.unwrap().unwrap();
+    let sent: BinaryResponse = rmp_serde::from_slice(&payload).unwrap();
+    assert_eq!(sent.status, 501);
+}
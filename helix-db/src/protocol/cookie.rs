@@ -0,0 +1,117 @@
+use std::{collections::HashMap, fmt};
+
+use crate::helix_engine::types::GraphError;
+
+/// Attributes sent with a cookie in its `Set-Cookie` header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieAttrs {
+    pub path: Option<String>,
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: Option<SameSite>,
+    /// Seconds until the cookie expires, with zero or less expiring it straight away
+    pub max_age: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Browsers only accept this on cookies that are also `Secure`
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// Parses the value of a `Cookie` header, e.g. `session=abc; theme=dark`.
+///
+/// Pairs without a `=` are skipped, and a value wrapped in double quotes has them removed.
+/// If a name appears more than once the first value is kept, as that is the one
+/// browsers send for the most specific path.
+pub fn parse_cookies(header: &str) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    for pair in header.split(';') {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        cookies
+            .entry(name.to_string())
+            .or_insert_with(|| value.to_string());
+    }
+    cookies
+}
+
+/// Builds the value of a `Set-Cookie` header.
+///
+/// Fails with a `GraphError::ConversionError` if the name or value has characters a cookie
+/// can't hold, which also keeps them from breaking out of the header.
+pub fn set_cookie_header(
+    name: &str,
+    value: &str,
+    attrs: &CookieAttrs,
+) -> Result<String, GraphError> {
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        return Err(GraphError::ConversionError(format!(
+            "Invalid cookie name: {:?}",
+            name
+        )));
+    }
+    if !value.bytes().all(is_cookie_value_byte) {
+        return Err(GraphError::ConversionError(format!(
+            "Invalid value for cookie {}",
+            name
+        )));
+    }
+
+    let mut header = format!("{}={}", name, value);
+    if let Some(path) = &attrs.path {
+        if !path.bytes().all(|byte| (0x20..0x7f).contains(&byte) && byte != b';') {
+            return Err(GraphError::ConversionError(format!(
+                "Invalid path for cookie {}",
+                name
+            )));
+        }
+        header.push_str("; Path=");
+        header.push_str(path);
+    }
+    if let Some(max_age) = attrs.max_age {
+        header.push_str(&format!("; Max-Age={}", max_age));
+    }
+    if let Some(same_site) = attrs.same_site {
+        header.push_str(&format!("; SameSite={}", same_site));
+    }
+    if attrs.secure {
+        header.push_str("; Secure");
+    }
+    if attrs.http_only {
+        header.push_str("; HttpOnly");
+    }
+    Ok(header)
+}
+
+/// Characters allowed in a cookie name, the HTTP token characters
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Characters allowed in a cookie value, which exclude whitespace, `"`, `,`, `;` and `\`
+fn is_cookie_value_byte(byte: u8) -> bool {
+    matches!(byte, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e)
+}
@@ -0,0 +1,80 @@
+use super::{
+    cookie::{CookieAttrs, SameSite, parse_cookies},
+    headers::Headers,
+    request::Request,
+    response::Response,
+};
+
+#[test]
+fn test_parse_cookies() {
+    let cookies = parse_cookies("session=abc123; theme=\"dark\";bare; =skipped; session=shadowed");
+    assert_eq!(cookies.len(), 2);
+    assert_eq!(cookies["session"], "abc123");
+    assert_eq!(cookies["theme"], "dark");
+}
+
+#[test]
+fn test_request_cookies() {
+    let mut headers = Headers::new();
+    headers.append("cookie", "session=abc123");
+    headers.append("cookie", "theme=dark; session=shadowed");
+    let request = Request {
+        method: "GET".to_string(),
+        headers,
+        path: "/".to_string(),
+        raw_path: "/".to_string(),
+        version: "HTTP/1.1".to_string(),
+        body: Vec::new(),
+        request_id: String::new(),
+    };
+    let cookies = request.cookies();
+    assert_eq!(cookies["session"], "abc123");
+    assert_eq!(cookies["theme"], "dark");
+
+    let request = Request {
+        headers: Headers::new(),
+        ..request
+    };
+    assert!(request.cookies().is_empty());
+}
+
+#[tokio::test]
+async fn test_set_cookie_sends_a_header_per_cookie() {
+    let mut response = Response::new();
+    let attrs = CookieAttrs {
+        path: Some("/".to_string()),
+        http_only: true,
+        secure: true,
+        same_site: Some(SameSite::Lax),
+        max_age: Some(3600),
+    };
+    response.set_cookie("session", "abc123", &attrs).unwrap();
+    response
+        .set_cookie("theme", "dark", &CookieAttrs::default())
+        .unwrap();
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.contains(
+        "Set-Cookie: session=abc123; Path=/; Max-Age=3600; SameSite=Lax; Secure; HttpOnly\r\n"
+    ));
+    assert!(sent.contains("Set-Cookie: theme=dark\r\n"));
+}
+
+#[test]
+fn test_set_cookie_rejects_invalid_characters() {
+    let mut response = Response::new();
+    let attrs = CookieAttrs::default();
+    assert!(response.set_cookie("", "value", &attrs).is_err());
+    assert!(response.set_cookie("bad name", "value", &attrs).is_err());
+    assert!(response.set_cookie("name", "a;b", &attrs).is_err());
+    assert!(response.set_cookie("name", "a\r\nX-Injected: 1", &attrs).is_err());
+
+    let attrs = CookieAttrs {
+        path: Some("/; Domain=evil".to_string()),
+        ..CookieAttrs::default()
+    };
+    assert!(response.set_cookie("name", "value", &attrs).is_err());
+    assert!(!response.headers.contains_key("Set-Cookie"));
+}
@@ -0,0 +1,136 @@
+use crate::protocol::response::Response;
+use flate2::{Compression, write::GzEncoder};
+use std::io::{self, Write};
+
+/// Bodies smaller than this aren't worth compressing, as encoding them costs more than it saves
+pub const DEFAULT_MIN_COMPRESS_SIZE: usize = 1024;
+
+/// Quality brotli compresses with, out of 11, which is close to gzip's speed on JSON
+/// with a noticeably better ratio
+#[cfg(feature = "brotli")]
+const BROTLI_QUALITY: u32 = 5;
+
+/// Base 2 log of brotli's window size, its default
+#[cfg(feature = "brotli")]
+const BROTLI_WINDOW: u32 = 22;
+
+/// An encoding a response body can be sent in, from most to least preferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// `br`, only available with the `brotli` feature
+    #[cfg(feature = "brotli")]
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl ContentEncoding {
+    /// Every encoding the gateway can send, in order of preference
+    pub const PREFERRED: &[ContentEncoding] = &[
+        #[cfg(feature = "brotli")]
+        ContentEncoding::Brotli,
+        ContentEncoding::Gzip,
+        ContentEncoding::Identity,
+    ];
+
+    /// The name of the encoding in `Accept-Encoding` and `Content-Encoding` headers
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Identity => "identity",
+        }
+    }
+
+    /// Picks the most preferred encoding the client accepts, given its `Accept-Encoding` header.
+    ///
+    /// The client's own weights only count to rule an encoding out with `q=0`, and `*` stands
+    /// for every encoding it doesn't name. `identity` is acceptable unless it's ruled out, and
+    /// is what a client that accepts nothing the gateway can send gets.
+    pub fn negotiate(accept_encoding: Option<&str>) -> ContentEncoding {
+        let Some(accept_encoding) = accept_encoding else {
+            return ContentEncoding::Identity;
+        };
+        let weights: Vec<(&str, bool)> = accept_encoding
+            .split(',')
+            .filter_map(|part| {
+                let mut params = part.split(';');
+                let name = params.next()?.trim();
+                let refused = params.any(|param| {
+                    param
+                        .trim()
+                        .strip_prefix("q=")
+                        .and_then(|q| q.trim().parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                });
+                (!name.is_empty()).then_some((name, !refused))
+            })
+            .collect();
+        let accepts = |encoding: &ContentEncoding| {
+            let named = weights
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(encoding.name()));
+            let wildcard = weights.iter().find(|(name, _)| *name == "*");
+            named
+                .or(wildcard)
+                .map_or(*encoding == ContentEncoding::Identity, |(_, accepted)| {
+                    *accepted
+                })
+        };
+        ContentEncoding::PREFERRED
+            .iter()
+            .copied()
+            .find(accepts)
+            .unwrap_or(ContentEncoding::Identity)
+    }
+
+    /// Encodes `body`, which for `identity` is a copy of it
+    pub fn encode(&self, body: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => {
+                let mut encoder =
+                    brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                encoder.write_all(body)?;
+                Ok(encoder.into_inner())
+            }
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            ContentEncoding::Identity => Ok(body.to_vec()),
+        }
+    }
+}
+
+/// Compresses the body of `response` with the encoding [negotiated](ContentEncoding::negotiate)
+/// from the request's `Accept-Encoding`, if it's at least `min_size` bytes, setting
+/// `Content-Encoding` and `Vary: Accept-Encoding`.
+///
+/// Streamed bodies, event streams, bodies a handler already encoded and responses that have
+/// no body, such as `204` and `304`, are sent as they are.
+pub fn compress_response(response: &mut Response, accept_encoding: Option<&str>, min_size: usize) {
+    if response.body.len() < min_size
+        || response.stream_body.is_some()
+        || response.event_stream.is_some()
+        || matches!(response.status, 100..=199 | 204 | 304)
+        || response.headers.contains_key("Content-Encoding")
+    {
+        return;
+    }
+    // the body sent depends on what the client accepts, even when it's sent as it is
+    response.headers.append("Vary", "Accept-Encoding");
+    let encoding = ContentEncoding::negotiate(accept_encoding);
+    if encoding == ContentEncoding::Identity {
+        return;
+    }
+    match encoding.encode(&response.body) {
+        Ok(encoded) => {
+            response.body = encoded;
+            response.headers.insert("Content-Encoding", encoding.name());
+        }
+        Err(e) => eprintln!("Failed to {} encode response: {}", encoding.name(), e),
+    }
+}
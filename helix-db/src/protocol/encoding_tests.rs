@@ -0,0 +1,124 @@
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+use super::{
+    encoding::{ContentEncoding, compress_response},
+    response::Response,
+};
+
+fn json_response(len: usize) -> Response {
+    let mut response = Response::new();
+    response.headers.insert("Content-Type", "application/json");
+    response.body = format!("[{}]", "1,".repeat(len / 2)).into_bytes();
+    response
+}
+
+#[test]
+fn test_negotiate_prefers_best_accepted_encoding() {
+    let negotiate = |accept| ContentEncoding::negotiate(accept);
+
+    assert_eq!(negotiate(None), ContentEncoding::Identity);
+    assert_eq!(negotiate(Some("gzip")), ContentEncoding::Gzip);
+    assert_eq!(
+        negotiate(Some("GZIP;q=0.5, deflate")),
+        ContentEncoding::Gzip
+    );
+    assert_eq!(
+        negotiate(Some("gzip;q=0, deflate")),
+        ContentEncoding::Identity
+    );
+    assert_eq!(negotiate(Some("deflate")), ContentEncoding::Identity);
+    assert_eq!(negotiate(Some("")), ContentEncoding::Identity);
+    // nothing else is acceptable, but a body still has to be sent
+    assert_eq!(negotiate(Some("identity;q=0")), ContentEncoding::Identity);
+
+    #[cfg(feature = "brotli")]
+    {
+        assert_eq!(
+            negotiate(Some("gzip, deflate, br")),
+            ContentEncoding::Brotli
+        );
+        // the gateway's preference wins over the client's weights
+        assert_eq!(
+            negotiate(Some("gzip;q=1.0, br;q=0.1")),
+            ContentEncoding::Brotli
+        );
+        assert_eq!(negotiate(Some("br;q=0, gzip")), ContentEncoding::Gzip);
+        assert_eq!(negotiate(Some("*")), ContentEncoding::Brotli);
+        assert_eq!(negotiate(Some("br;q=0, *")), ContentEncoding::Gzip);
+    }
+    #[cfg(not(feature = "brotli"))]
+    assert_eq!(negotiate(Some("br, gzip")), ContentEncoding::Gzip);
+}
+
+#[test]
+fn test_compress_response_gzip() {
+    let mut response = json_response(4096);
+    let original = response.body.clone();
+    compress_response(&mut response, Some("gzip"), 1024);
+
+    assert_eq!(
+        response.headers.get("Content-Encoding").map(String::as_str),
+        Some("gzip")
+    );
+    assert_eq!(
+        response.headers.get("Vary").map(String::as_str),
+        Some("Accept-Encoding")
+    );
+    assert!(response.body.len() < original.len());
+    let mut decoded = Vec::new();
+    GzDecoder::new(response.body.as_slice())
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, original);
+}
+
+#[cfg(feature = "brotli")]
+#[test]
+fn test_compress_response_brotli() {
+    let mut response = json_response(4096);
+    let original = response.body.clone();
+    compress_response(&mut response, Some("gzip, br"), 1024);
+
+    assert_eq!(
+        response.headers.get("Content-Encoding").map(String::as_str),
+        Some("br")
+    );
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(response.body.as_slice(), 4096)
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, original);
+}
+
+#[test]
+fn test_compress_response_leaves_small_and_unaccepted_bodies() {
+    let mut small = json_response(100);
+    let original = small.body.clone();
+    compress_response(&mut small, Some("gzip"), 1024);
+    assert_eq!(small.body, original);
+    assert!(!small.headers.contains_key("Content-Encoding"));
+    assert!(!small.headers.contains_key("Vary"));
+
+    // a cache still has to know the body depends on Accept-Encoding
+    let mut unaccepted = json_response(4096);
+    let original = unaccepted.body.clone();
+    compress_response(&mut unaccepted, None, 1024);
+    assert_eq!(unaccepted.body, original);
+    assert!(!unaccepted.headers.contains_key("Content-Encoding"));
+    assert_eq!(
+        unaccepted.headers.get("Vary").map(String::as_str),
+        Some("Accept-Encoding")
+    );
+
+    let mut encoded = json_response(4096);
+    encoded.headers.insert("Content-Encoding", "zstd");
+    let original = encoded.body.clone();
+    compress_response(&mut encoded, Some("gzip"), 1024);
+    assert_eq!(encoded.body, original);
+    assert_eq!(
+        encoded.headers.get("Content-Encoding").map(String::as_str),
+        Some("zstd")
+    );
+}
@@ -0,0 +1,32 @@
+use twox_hash::XxHash64;
+
+/// Computes a weak ETag from a hash of `body`, e.g. `W/"3f2a9c1d04b7e611"`,
+/// so the same body always gets the same tag
+pub fn weak_etag(body: &[u8]) -> String {
+    format!("W/\"{:016x}\"", XxHash64::oneshot(0, body))
+}
+
+/// Wraps `etag` in double quotes unless it already is, as a weak `W/"..."` tag or a strong one
+pub fn quote_etag(etag: &str) -> String {
+    let opaque = etag.strip_prefix("W/").unwrap_or(etag);
+    if opaque.len() >= 2 && opaque.starts_with('"') && opaque.ends_with('"') {
+        etag.to_string()
+    } else {
+        format!("\"{}\"", etag)
+    }
+}
+
+/// Whether the value of an `If-None-Match` header, e.g. `"a", W/"b"`, matches `etag`.
+///
+/// Tags are compared weakly as `If-None-Match` requires, so `W/"a"` matches `"a"`,
+/// and `*` matches any tag.
+pub fn if_none_match(header: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| {
+        let tag = tag.trim();
+        tag.strip_prefix("W/").unwrap_or(tag).to_string()
+    };
+    let etag = opaque(etag);
+    header
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
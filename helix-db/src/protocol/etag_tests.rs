@@ -0,0 +1,30 @@
+use super::etag::{if_none_match, quote_etag, weak_etag};
+
+#[test]
+fn test_weak_etag_follows_the_body() {
+    let etag = weak_etag(b"{\"name\":\"alice\"}");
+    assert!(etag.starts_with("W/\"") && etag.ends_with('"'));
+    assert_eq!(etag.len(), 20);
+    assert_eq!(etag, weak_etag(b"{\"name\":\"alice\"}"));
+    assert_ne!(etag, weak_etag(b"{\"name\":\"bob\"}"));
+}
+
+#[test]
+fn test_quote_etag() {
+    assert_eq!(quote_etag("v1"), "\"v1\"");
+    assert_eq!(quote_etag("\"v1\""), "\"v1\"");
+    assert_eq!(quote_etag("W/\"v1\""), "W/\"v1\"");
+    assert_eq!(quote_etag("\""), "\"\"\"");
+}
+
+#[test]
+fn test_if_none_match_compares_weakly() {
+    assert!(if_none_match("\"v1\"", "\"v1\""));
+    assert!(if_none_match("W/\"v1\"", "\"v1\""));
+    assert!(if_none_match("\"v1\"", "W/\"v1\""));
+    assert!(if_none_match("\"v0\", W/\"v1\" ", "\"v1\""));
+    assert!(if_none_match("*", "\"v1\""));
+
+    assert!(!if_none_match("\"v2\"", "\"v1\""));
+    assert!(!if_none_match("v1", "\"v1\""));
+}
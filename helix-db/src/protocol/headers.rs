@@ -0,0 +1,110 @@
+use std::{ops::Index, slice};
+
+/// HTTP headers in the order they were added, allowing more than one value per name.
+///
+/// Names are matched case-insensitively but kept as they were given.
+/// [`Headers::insert`] replaces every value for a name like a map does,
+/// while [`Headers::append`] adds another, e.g. for a second `Set-Cookie`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first value for `name`
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Every value for `name`, in the order they were added
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to just `value`, returning the first value it replaced
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let name = name.into();
+        let previous = self.remove(&name);
+        self.entries.push((name, value.into()));
+        previous
+    }
+
+    /// Adds a value for `name`, keeping any it already has
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Removes every value for `name`, returning the first
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.entries.retain_mut(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(std::mem::take(value));
+            }
+            false
+        });
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.into_iter()
+    }
+}
+
+/// Iterator over every name and value, including repeated names
+pub type Iter<'a> = std::iter::Map<
+    slice::Iter<'a, (String, String)>,
+    fn(&'a (String, String)) -> (&'a String, &'a String),
+>;
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a String, &'a String);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+}
+
+impl FromIterator<(String, String)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Index<&str> for Headers {
+    type Output = String;
+
+    /// Panics if there is no value for `name`
+    fn index(&self, name: &str) -> &String {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no header named {}", name))
+    }
+}
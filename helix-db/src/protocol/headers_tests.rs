@@ -0,0 +1,64 @@
+use super::headers::Headers;
+
+#[test]
+fn test_lookup_is_case_insensitive() {
+    let mut headers = Headers::new();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+
+    assert_eq!(headers.get("content-type").unwrap(), "application/json");
+    assert_eq!(headers["CONTENT-TYPE"], "application/json");
+    assert!(headers.contains_key("Content-type"));
+    assert!(headers.get("content-length").is_none());
+
+    // names keep the case they were added with
+    let (name, _) = headers.iter().next().unwrap();
+    assert_eq!(name, "Content-Type");
+}
+
+#[test]
+fn test_append_keeps_repeated_names() {
+    let mut headers = Headers::new();
+    headers.append("Set-Cookie", "a=1");
+    headers.append("Vary", "Origin");
+    headers.append("set-cookie", "b=2");
+
+    assert_eq!(headers.len(), 3);
+    assert_eq!(headers.get("Set-Cookie").unwrap(), "a=1");
+    assert_eq!(
+        headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+        vec!["a=1", "b=2"]
+    );
+}
+
+#[test]
+fn test_insert_replaces_every_value() {
+    let mut headers = Headers::new();
+    headers.append("Vary", "Origin");
+    headers.append("Accept", "*/*");
+    headers.append("vary", "Accept-Encoding");
+
+    assert_eq!(headers.insert("VARY", "Cookie"), Some("Origin".to_string()));
+    assert_eq!(headers.get_all("vary").collect::<Vec<_>>(), vec!["Cookie"]);
+    assert_eq!(headers.len(), 2);
+
+    assert_eq!(headers.remove("vary"), Some("Cookie".to_string()));
+    assert_eq!(headers.remove("vary"), None);
+    assert_eq!(headers.len(), 1);
+}
+
+#[tokio::test]
+async fn test_repeated_request_headers_kept() {
+    let raw = "GET / HTTP/1.1\r\nAccept: text/html\r\nX-Forwarded-For: 10.0.0.1\r\nX-Forwarded-For: 10.0.0.2\r\n\r\n";
+    let request =
+        super::request::Request::from_stream(&mut std::io::Cursor::new(raw.as_bytes().to_vec()))
+            .await
+            .unwrap();
+    assert_eq!(
+        request
+            .headers
+            .get_all("x-forwarded-for")
+            .collect::<Vec<_>>(),
+        vec!["10.0.0.1", "10.0.0.2"]
+    );
+    assert_eq!(request.headers["accept"], "text/html");
+}
@@ -1,6 +1,40 @@
+pub mod binary;
+pub mod cookie;
 pub mod date;
+pub mod encoding;
+pub mod etag;
+pub mod headers;
 pub mod remapping;
 pub mod request;
 pub mod response;
 pub mod return_values;
+pub mod sse;
 pub mod value;
+pub mod websocket;
+
+#[cfg(test)]
+mod binary_tests;
+
+#[cfg(test)]
+mod cookie_tests;
+
+#[cfg(test)]
+mod encoding_tests;
+
+#[cfg(test)]
+mod etag_tests;
+
+#[cfg(test)]
+mod headers_tests;
+
+#[cfg(test)]
+mod request_tests;
+
+#[cfg(test)]
+mod response_tests;
+
+#[cfg(test)]
+mod sse_tests;
+
+#[cfg(test)]
+mod websocket_tests;
@@ -1,15 +1,278 @@
-use std::collections::HashMap;
-use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader, Result};
+use std::{
+    collections::HashMap,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use crate::protocol::{cookie::parse_cookies, headers::Headers};
+use tokio::{
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf,
+        Result,
+    },
+    time::Sleep,
+};
 
 #[derive(Debug)]
 pub struct Request {
     pub method: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
+    /// The path without its query string and with its percent-encoding decoded, which routes
+    /// are matched against
     pub path: String,
+    /// The path exactly as the client sent it, including any query string
+    pub raw_path: String,
+    /// The HTTP version from the request line, e.g. `HTTP/1.1`
+    pub version: String,
     pub body: Vec<u8>,
+    /// The ID the request is traced by, which the gateway echoes back in its response's
+    /// `X-Request-Id`, see [`request_id`]
+    pub request_id: String,
+}
+
+/// The header a request's ID is read from and echoed back in
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The longest `X-Request-Id` a client can send and have kept
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The ID to trace a request by: the `X-Request-Id` it was sent with, or a new UUID if it
+/// was sent without one.
+///
+/// An ID over [`MAX_REQUEST_ID_LEN`] bytes or with anything but visible ASCII in it is replaced
+/// too, so a client can't break log lines or response headers with it.
+pub fn request_id(headers: &Headers) -> String {
+    match headers.get(REQUEST_ID_HEADER) {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            id.clone()
+        }
+        _ => uuid::Uuid::new_v4().to_string(),
+    }
+}
+
+/// The error a request fails to parse with when the client should be answered with `status`
+/// rather than having the connection dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedRequest {
+    pub status: u16,
+    /// Sent as the `code` of the error response, see [`crate::protocol::response::Response::error`]
+    pub code: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for RejectedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for RejectedRequest {}
+
+impl RejectedRequest {
+    pub(crate) fn error(status: u16, code: &'static str, reason: String) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            RejectedRequest { status, code, reason },
+        )
+    }
+
+    /// The rejection `error` carries, if a request failed to parse with one
+    pub fn from_error(error: &std::io::Error) -> Option<&RejectedRequest> {
+        error.get_ref()?.downcast_ref::<RejectedRequest>()
+    }
+}
+
+/// Reader that rejects the request with a `408` once a read has been pending for longer than
+/// `timeout`, which starts again each time one finishes
+pub(crate) struct IdleTimeout<'a, R> {
+    reader: R,
+    timeout: Option<Duration>,
+    reason: &'a str,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<'a, R> IdleTimeout<'a, R> {
+    pub(crate) fn new(reader: R, timeout: Option<Duration>, reason: &'a str) -> Self {
+        Self {
+            reader,
+            timeout,
+            reason,
+            deadline: None,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for IdleTimeout<'_, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.reader).poll_read(cx, buf);
+        let (Poll::Pending, Some(timeout)) = (&poll, this.timeout) else {
+            this.deadline = None;
+            return poll;
+        };
+        let deadline = this
+            .deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                this.deadline = None;
+                let reason = this.reason.to_string();
+                Poll::Ready(Err(RejectedRequest::error(408, "request_timeout", reason)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Decodes every `%XX` in `path`, or `None` if one isn't two hex digits or the result isn't UTF-8.
+///
+/// `+` is left as it is, as it only means a space in form encoded query strings.
+pub fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3)?;
+                let hex = std::str::from_utf8(hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Limits on reading a request, none of which are set by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLimits {
+    /// Requests with a larger `Content-Length` are rejected before their body is read
+    pub max_body_size: Option<usize>,
+    /// How long the whole request has to arrive in
+    pub read_timeout: Option<Duration>,
+    /// How long the request line and headers have to arrive in, so a client sending them
+    /// a byte at a time can't hold the connection open.
+    ///
+    /// A client that doesn't send them in time is answered with `408 Request Timeout`.
+    pub header_timeout: Option<Duration>,
+    /// How long the body can go without any of it arriving once the headers have, so a slow
+    /// client can take as long as it needs to send a large body as long as it keeps sending.
+    ///
+    /// A client that stops sending it for longer is answered with `408 Request Timeout`.
+    pub body_timeout: Option<Duration>,
+}
+
+/// Bytes of a body allocated up front, with the rest allocated as it arrives, so a large
+/// `Content-Length` alone can't make the server allocate it
+const BODY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The body length a request's `Content-Length` header gives, if it has one.
+///
+/// A length that isn't only digits, including a negative one, or more than one
+/// `Content-Length` header, is rejected with `400 Bad Request`.
+fn content_length(headers: &Headers) -> Result<Option<usize>> {
+    let invalid = |reason: String| RejectedRequest::error(400, "invalid_content_length", reason);
+    let mut lengths = headers.get_all("content-length");
+    match (lengths.next(), lengths.next()) {
+        (None, _) => Ok(None),
+        (Some(length), None) => match length.bytes().all(|b| b.is_ascii_digit()) {
+            true => length
+                .parse()
+                .map(Some)
+                .map_err(|_| invalid(format!("Invalid Content-Length: {}", length))),
+            false => Err(invalid(format!("Invalid Content-Length: {}", length))),
+        },
+        (Some(_), Some(_)) => Err(invalid("More than one Content-Length".to_string())),
+    }
 }
 
 impl Request {
+    /// Cookies sent in the request's `Cookie` headers, by name
+    pub fn cookies(&self) -> HashMap<String, String> {
+        let mut cookies = HashMap::new();
+        for header in self.headers.get_all("cookie") {
+            for (name, value) in parse_cookies(header) {
+                cookies.entry(name).or_insert(value);
+            }
+        }
+        cookies
+    }
+
+    /// Parameters of the query string after the path, by name, with `+` read as a space and
+    /// percent-encoding decoded. The first of repeated parameters is kept, a parameter without
+    /// `=` has an empty value, and one that doesn't decode is left out.
+    pub fn query_params(&self) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        let Some((_, query)) = self.raw_path.split_once('?') else {
+            return params;
+        };
+        let decode = |part: &str| percent_decode(&part.replace('+', " "));
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if let (Some(name), Some(value)) = (decode(name), decode(value)) {
+                params.entry(name).or_insert(value);
+            }
+        }
+        params
+    }
+
+    /// Whether the client asked for pretty-printed JSON with `?pretty=1` or an `X-Pretty: 1`
+    /// header, either of which may also be `true`. A bare `?pretty` counts too.
+    pub fn wants_pretty_json(&self) -> bool {
+        let is_set = |value: &str| {
+            let value = value.trim();
+            value == "1" || value.eq_ignore_ascii_case("true")
+        };
+        if let Some(pretty) = self.query_params().get("pretty") {
+            return pretty.is_empty() || is_set(pretty);
+        }
+        self.headers.get("x-pretty").is_some_and(|pretty| is_set(pretty))
+    }
+
+    /// Whether this is the opening handshake of a websocket, asking to upgrade the connection
+    pub fn is_websocket_upgrade(&self) -> bool {
+        let has_token = |name: &str, token: &str| {
+            self.headers.get_all(name).any(|value| {
+                value
+                    .split(',')
+                    .any(|part| part.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        self.method == "GET" && has_token("upgrade", "websocket") && has_token("connection", "upgrade")
+    }
+
+    /// Whether the client is willing to send another request on the connection once this one
+    /// is answered, which HTTP/1.1 clients are unless they send `Connection: close`, and
+    /// HTTP/1.0 clients only if they send `Connection: keep-alive`
+    pub fn wants_keep_alive(&self) -> bool {
+        let has_token = |token: &str| {
+            self.headers.get_all("connection").any(|value| {
+                value
+                    .split(',')
+                    .any(|part| part.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        match self.version.as_str() {
+            _ if has_token("close") => false,
+            "HTTP/1.1" => true,
+            _ => has_token("keep-alive"),
+        }
+    }
+
     /// Parse a request from a stream
     ///
     /// # Example
@@ -22,26 +285,144 @@ impl Request {
     /// assert_eq!(request.method, "GET");
     /// assert_eq!(request.path, "/test");
     /// ```
-    pub async fn from_stream<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Request> {
+    pub async fn from_stream<R: AsyncRead + AsyncWrite + Unpin>(stream: &mut R) -> Result<Request> {
+        Self::from_stream_with_limits(stream, &RequestLimits::default()).await
+    }
+
+    /// Parse a request from a stream, failing if it breaks any of `limits`.
+    ///
+    /// Bytes read past the end of the request, such as the start of a pipelined one after it,
+    /// are lost, see [`Request::from_stream_pipelined`] for a stream more requests are read from.
+    pub async fn from_stream_with_limits<R: AsyncRead + AsyncWrite + Unpin>(
+        stream: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<Request> {
+        Ok(Self::from_stream_pipelined(stream, limits).await?.0)
+    }
+
+    /// Parse a request from a stream like [`Request::from_stream_with_limits`], also returning
+    /// the bytes read past the end of its body.
+    ///
+    /// Exactly one request, headers and body, is taken from those bytes, so they have to be read
+    /// again first as the start of the next request on the connection.
+    pub async fn from_stream_pipelined<R: AsyncRead + AsyncWrite + Unpin>(
+        stream: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<(Request, Vec<u8>)> {
+        match limits.read_timeout {
+            Some(read_timeout) => {
+                tokio::time::timeout(read_timeout, Self::read(stream, limits))
+                    .await
+                    .map_err(|_| std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Timeout reading request"
+                    ))?
+            }
+            None => Self::read(stream, limits).await,
+        }
+    }
+
+    async fn read<R: AsyncRead + AsyncWrite + Unpin>(
+        stream: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<(Request, Vec<u8>)> {
         let mut reader = BufReader::new(stream);
+        let (method, raw_path, version, headers) = match limits.header_timeout {
+            Some(header_timeout) => {
+                tokio::time::timeout(header_timeout, Self::read_head(&mut reader))
+                    .await
+                    .map_err(|_| RejectedRequest::error(
+                        408,
+                        "request_timeout",
+                        "Timeout reading request headers".to_string()
+                    ))??
+            }
+            None => Self::read_head(&mut reader).await?,
+        };
+        let encoded_path = raw_path.split_once('?').map_or(raw_path.as_str(), |(path, _)| path);
+        let path = percent_decode(encoded_path).ok_or_else(|| RejectedRequest::error(
+            400,
+            "invalid_path",
+            format!("Invalid percent-encoding in path: {}", raw_path)
+        ))?;
+
+        // a chunked body can't be told apart from the next request without decoding it
+        if headers.get("transfer-encoding").is_some() {
+            return Err(RejectedRequest::error(
+                501,
+                "not_implemented",
+                "Transfer-Encoding is not supported, send a Content-Length".to_string()
+            ));
+        }
+        let length = content_length(&headers)?.unwrap_or(0);
+        if let Some(max_body_size) = limits.max_body_size && length > max_body_size {
+            let reason = format!("Body of {} bytes exceeds max size of {} bytes", length, max_body_size);
+            // answered without reading the body, which a client expecting 100 Continue
+            // won't have sent yet
+            return Err(RejectedRequest::error(413, "body_too_large", reason));
+        }
+
+        // a client expecting 100 Continue waits to be told to send the body
+        let expects_continue = headers
+            .get("expect")
+            .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+        if expects_continue && length > 0 {
+            let stream = reader.get_mut();
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+            stream.flush().await?;
+        }
+
+        // Read body
+        let mut body = Vec::with_capacity(length.min(BODY_CHUNK_SIZE));
+        let limited = (&mut reader).take(length as u64);
+        IdleTimeout::new(limited, limits.body_timeout, "Timeout reading request body")
+            .read_to_end(&mut body)
+            .await?;
+        if body.len() < length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Connection closed before the body was read"
+            ));
+        }
+
+        let request_id = request_id(&headers);
+        let request = Request {
+            method,
+            headers,
+            path,
+            raw_path,
+            version,
+            body,
+            request_id,
+        };
+        Ok((request, reader.buffer().to_vec()))
+    }
+
+    /// Reads the request line and headers, returning the method, the raw path, the HTTP version
+    /// and the headers
+    async fn read_head<R: AsyncRead + Unpin>(
+        reader: &mut BufReader<R>,
+    ) -> Result<(String, String, String, Headers)> {
         let mut first_line = String::new();
         reader.read_line(&mut first_line).await?;
 
         // Get method and path
-        let mut parts = first_line.trim().split_whitespace();
+        let mut parts = first_line.split_whitespace();
         let method = parts.next()
             .ok_or_else(|| std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("Missing HTTP method: {}", first_line)
             ))?.to_string();
-        let path = parts.next()
+        let raw_path = parts.next()
             .ok_or_else(|| std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("Missing path: {}", first_line)
             ))?.to_string();
+        // a request line without a version is from before HTTP/1.1
+        let version = parts.next().unwrap_or("HTTP/1.0").to_string();
 
         // Parse headers
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         let mut line = String::new();
         loop {
             line.clear();
@@ -50,46 +431,13 @@ impl Request {
                 break;
             }
             if let Some((key, value)) = line.trim().split_once(':') {
-                headers.insert(
+                // repeated headers are all kept
+                headers.append(
                     key.trim().to_lowercase(),
                     value.trim().to_string()
                 );
             }
         }
-
-        // Read body
-        let mut body = Vec::new();
-        if let Some(length) = headers.get("content-length") {
-            if let Ok(length) = length.parse::<usize>() {
-                let mut buffer = vec![0; length];
-                match tokio::time::timeout(
-                    std::time::Duration::from_secs(5),
-                    reader.read_exact(&mut buffer)
-                ).await {
-                    Ok(Ok(_)) => body = buffer,
-                    Ok(Err(e)) => {
-                        eprintln!("Error reading body: {}", e);
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "Error reading body"
-                        ));
-                    },
-                    Err(_) => {
-                        eprintln!("Timeout reading body");
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::TimedOut,
-                            "Timeout reading body"
-                        ));
-                    }
-                }
-            }
-        }
-
-        Ok(Request {
-            method,
-            headers,
-            path,
-            body,
-        })
+        Ok((method, raw_path, version, headers))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,265 @@
+use std::io::Cursor;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+use super::request::{
+    MAX_REQUEST_ID_LEN, RejectedRequest, Request, RequestLimits, percent_decode,
+};
+
+async fn parse(raw: &str) -> std::io::Result<Request> {
+    Request::from_stream(&mut Cursor::new(raw.as_bytes().to_vec())).await
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("/nodes/foo%2Fbar").as_deref(), Some("/nodes/foo/bar"));
+    assert_eq!(percent_decode("/caf%C3%a9").as_deref(), Some("/café"));
+    assert_eq!(percent_decode("/a+b%20c").as_deref(), Some("/a+b c"));
+    assert_eq!(percent_decode("/plain").as_deref(), Some("/plain"));
+
+    assert_eq!(percent_decode("/bad%2"), None);
+    assert_eq!(percent_decode("/bad%zz"), None);
+    assert_eq!(percent_decode("/bad%"), None);
+    // decodes to bytes that aren't UTF-8
+    assert_eq!(percent_decode("/bad%FF"), None);
+}
+
+#[tokio::test]
+async fn test_path_decoded_with_raw_path_kept() {
+    let request = parse("GET /nodes/foo%2Fbar HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    assert_eq!(request.path, "/nodes/foo/bar");
+    assert_eq!(request.raw_path, "/nodes/foo%2Fbar");
+}
+
+#[tokio::test]
+async fn test_invalid_percent_encoding_is_rejected_as_bad_request() {
+    let err = parse("GET /nodes/%E2%82 HTTP/1.1\r\n\r\n").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(RejectedRequest::from_error(&err).unwrap().status, 400);
+
+    let err = parse("GET\r\n\r\n").await.unwrap_err();
+    assert!(RejectedRequest::from_error(&err).is_none());
+}
+
+#[tokio::test]
+async fn test_expect_continue_answered_before_body_is_read() {
+    let (mut server, mut client) = duplex(1024);
+    let reading = tokio::spawn(async move { Request::from_stream(&mut server).await.unwrap() });
+
+    client
+        .write_all(b"POST /upload HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n")
+        .await
+        .unwrap();
+    let mut interim = [0u8; 25];
+    client.read_exact(&mut interim).await.unwrap();
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+    client.write_all(b"hello").await.unwrap();
+    let request = reading.await.unwrap();
+    assert_eq!(request.body, b"hello");
+}
+
+#[tokio::test]
+async fn test_body_over_max_size_is_rejected() {
+    let limits = RequestLimits {
+        max_body_size: Some(4),
+        ..RequestLimits::default()
+    };
+    let raw = "POST /upload HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n";
+    let mut stream = Cursor::new(raw.as_bytes().to_vec());
+    let err = Request::from_stream_with_limits(&mut stream, &limits)
+        .await
+        .unwrap_err();
+    assert_eq!(RejectedRequest::from_error(&err).unwrap().status, 413);
+    // nothing was written asking for the body
+    assert_eq!(stream.get_ref().len(), raw.len());
+
+    let raw = "POST /upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+    let err = Request::from_stream_with_limits(&mut Cursor::new(raw.as_bytes().to_vec()), &limits)
+        .await
+        .unwrap_err();
+    assert_eq!(RejectedRequest::from_error(&err).unwrap().status, 413);
+}
+
+#[tokio::test]
+async fn test_slow_headers_time_out_with_408() {
+    let limits = RequestLimits {
+        header_timeout: Some(std::time::Duration::from_millis(50)),
+        ..RequestLimits::default()
+    };
+    let (mut client, mut server) = duplex(1024);
+    // the request line arrives but the headers never finish
+    client.write_all(b"GET /nodes HTTP/1.1\r\nHost: loc").await.unwrap();
+
+    let err = Request::from_stream_with_limits(&mut server, &limits)
+        .await
+        .unwrap_err();
+    let rejected = RejectedRequest::from_error(&err).unwrap();
+    assert_eq!(rejected.status, 408);
+    assert_eq!(rejected.reason, "Timeout reading request headers");
+
+    // the body isn't held to it
+    let (mut client, mut server) = duplex(1024);
+    let reading = tokio::spawn(async move {
+        Request::from_stream_with_limits(&mut server, &limits).await.unwrap()
+    });
+    client
+        .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    client.write_all(b"hello").await.unwrap();
+    assert_eq!(reading.await.unwrap().body, b"hello");
+}
+
+#[tokio::test]
+async fn test_slow_body_times_out_with_408() {
+    let limits = RequestLimits {
+        body_timeout: Some(std::time::Duration::from_millis(50)),
+        ..RequestLimits::default()
+    };
+    let (mut client, mut server) = duplex(1024);
+    client
+        .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nhel")
+        .await
+        .unwrap();
+
+    let err = Request::from_stream_with_limits(&mut server, &limits)
+        .await
+        .unwrap_err();
+    let rejected = RejectedRequest::from_error(&err).unwrap();
+    assert_eq!(rejected.status, 408);
+    assert_eq!(rejected.reason, "Timeout reading request body");
+
+    // a body that keeps arriving can take longer than the timeout in all
+    let (mut client, mut server) = duplex(1024);
+    let reading = tokio::spawn(async move {
+        Request::from_stream_with_limits(&mut server, &limits).await.unwrap()
+    });
+    client
+        .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+        .await
+        .unwrap();
+    for byte in b"hello" {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        client.write_all(&[*byte]).await.unwrap();
+    }
+    assert_eq!(reading.await.unwrap().body, b"hello");
+}
+
+#[tokio::test]
+async fn test_ambiguous_body_length_is_rejected() {
+    let rejected = |raw: String| async move {
+        let err = parse(&raw).await.unwrap_err();
+        RejectedRequest::from_error(&err).map(|rejected| (rejected.status, rejected.code))
+    };
+    for length in ["-1", "+3", "0x3", "", "3, 3", "99999999999999999999999"] {
+        assert_eq!(
+            rejected(format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\nabc", length)).await,
+            Some((400, "invalid_content_length")),
+            "{:?}",
+            length
+        );
+    }
+    let raw = "POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 3\r\n\r\nabc";
+    assert_eq!(rejected(raw.to_string()).await, Some((400, "invalid_content_length")));
+    let raw = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+    assert_eq!(rejected(raw.to_string()).await, Some((501, "not_implemented")));
+}
+
+#[tokio::test]
+async fn test_body_cut_short_fails_without_reserving_its_length() {
+    // a huge length with no max body size set fails once the client stops sending
+    let err = parse("POST / HTTP/1.1\r\nContent-Length: 1099511627776\r\n\r\nabc")
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(RejectedRequest::from_error(&err).is_none());
+
+    let body = "x".repeat(200 * 1024);
+    let raw = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    let request = parse(&raw).await.unwrap();
+    assert_eq!(request.body, body.as_bytes());
+}
+
+#[tokio::test]
+async fn test_wants_keep_alive_by_version_and_connection_header() {
+    let wants = |raw: &'static str| async move { parse(raw).await.unwrap().wants_keep_alive() };
+
+    assert!(wants("GET / HTTP/1.1\r\n\r\n").await);
+    assert!(!wants("GET / HTTP/1.1\r\nConnection: close\r\n\r\n").await);
+    assert!(!wants("GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n\r\n").await);
+    assert!(!wants("GET / HTTP/1.0\r\n\r\n").await);
+    assert!(wants("GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").await);
+    assert!(!wants("GET /\r\n\r\n").await);
+
+    assert_eq!(parse("GET / HTTP/1.0\r\n\r\n").await.unwrap().version, "HTTP/1.0");
+}
+
+#[tokio::test]
+async fn test_query_string_parsed_and_left_out_of_path() {
+    let request = parse("GET /nodes/a%3Fb?name=J%C3%B6rg+M&empty&tag=1&tag=2 HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    assert_eq!(request.path, "/nodes/a?b");
+    assert_eq!(request.raw_path, "/nodes/a%3Fb?name=J%C3%B6rg+M&empty&tag=1&tag=2");
+
+    let params = request.query_params();
+    assert_eq!(params["name"], "Jörg M");
+    assert_eq!(params["empty"], "");
+    assert_eq!(params["tag"], "1");
+    assert_eq!(params.len(), 3);
+
+    assert!(parse("GET /nodes HTTP/1.1\r\n\r\n").await.unwrap().query_params().is_empty());
+}
+
+#[tokio::test]
+async fn test_wants_pretty_json_from_query_or_header() {
+    let wants = |raw: &'static str| async move { parse(raw).await.unwrap().wants_pretty_json() };
+
+    assert!(!wants("GET /query HTTP/1.1\r\n\r\n").await);
+    assert!(wants("GET /query?pretty=1 HTTP/1.1\r\n\r\n").await);
+    assert!(wants("GET /query?pretty=true HTTP/1.1\r\n\r\n").await);
+    assert!(wants("GET /query?pretty HTTP/1.1\r\n\r\n").await);
+    assert!(!wants("GET /query?pretty=0 HTTP/1.1\r\n\r\n").await);
+    assert!(wants("GET /query HTTP/1.1\r\nX-Pretty: 1\r\n\r\n").await);
+    assert!(!wants("GET /query HTTP/1.1\r\nX-Pretty: no\r\n\r\n").await);
+    // the query string wins over the header
+    assert!(!wants("GET /query?pretty=0 HTTP/1.1\r\nX-Pretty: 1\r\n\r\n").await);
+}
+
+#[tokio::test]
+async fn test_pipelined_request_left_over_for_next_read() {
+    let raw = b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /b HTTP/1.1\r\n\r\n";
+    let mut stream = Cursor::new(raw.to_vec());
+    let (request, leftover) = Request::from_stream_pipelined(&mut stream, &RequestLimits::default())
+        .await
+        .unwrap();
+    assert_eq!(request.path, "/a");
+    assert_eq!(request.body, b"abc");
+    assert_eq!(leftover, b"GET /b HTTP/1.1\r\n\r\n");
+
+    let next = parse(std::str::from_utf8(&leftover).unwrap()).await.unwrap();
+    assert_eq!(next.path, "/b");
+}
+
+#[tokio::test]
+async fn test_request_id_kept_or_generated() {
+    let request = parse("GET / HTTP/1.1\r\nX-Request-Id: trace-42\r\n\r\n")
+        .await
+        .unwrap();
+    assert_eq!(request.request_id, "trace-42");
+
+    let request = parse("GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    assert!(uuid::Uuid::parse_str(&request.request_id).is_ok());
+
+    // IDs that could break a log line or header, or are too long, are replaced
+    let too_long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+    for id in ["two words", "caf\u{e9}", too_long.as_str()] {
+        let raw = format!("GET / HTTP/1.1\r\nX-Request-Id: {}\r\n\r\n", id);
+        let request = parse(&raw).await.unwrap();
+        assert!(uuid::Uuid::parse_str(&request.request_id).is_ok());
+    }
+}
@@ -1,16 +1,66 @@
-use std::collections::HashMap;
+use std::{fmt, io::Read};
 use tokio::io::{AsyncWrite, AsyncWriteExt, Result};
+use crate::{
+    helix_engine::types::GraphError,
+    protocol::{
+        cookie::{CookieAttrs, set_cookie_header},
+        etag::{if_none_match, quote_etag},
+        headers::Headers,
+        sse::{SseEvent, SseSender, write_events},
+    },
+};
+use flume::Receiver;
+use serde::Serialize;
+/// Size of the chunks a streamed body is read and written in
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
 #[derive(Debug)]
 pub struct Response {
     pub status: u16,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: Vec<u8>,
+    /// Sent in place of `body` when set, see [`Response::stream`]
+    pub stream_body: Option<StreamBody>,
+    /// Events sent in place of `body` when set, see [`Response::sse`]
+    pub event_stream: Option<Receiver<SseEvent>>,
+    /// Answers a `HEAD` request, so [`Response::send`] sends the headers, including
+    /// the `Content-Length` the body would have had, but not the body
+    pub head: bool,
+}
+
+/// The body of [`Response::error`], as structs so its keys are always written in the same order
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+/// A body read from `reader` as it is sent rather than held in memory
+pub struct StreamBody {
+    pub reader: Box<dyn Read + Send>,
+    /// Sent as the `Content-Length` if known, otherwise the body is sent chunked
+    pub content_length: Option<u64>,
+}
+
+impl fmt::Debug for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamBody")
+            .field("content_length", &self.content_length)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Response {
     /// Create a new response
+    ///
+    /// Defaults to a `200` status with an empty body, so a handler that sets nothing sends `200 OK`.
     pub fn new() -> Response {
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         // TODO: Change to use router config for headers and default routes
         headers.insert("Content-Type".to_string(), "text/plain".to_string());
 
@@ -18,9 +68,166 @@ impl Response {
             status: 200,
             headers,
             body: Vec::new(),
+            stream_body: None,
+            event_stream: None,
+            head: false,
         }
     }
 
+    /// Creates a response whose body is copied from `reader` to the client in chunks
+    /// as it is sent, so it never has to be held in memory all at once.
+    ///
+    /// With a `content_length` the body is sent as exactly that many bytes, and sending fails
+    /// if the reader ends before then. Without one it is sent with chunked transfer encoding.
+    pub fn stream(
+        status: u16,
+        headers: Headers,
+        reader: Box<dyn Read + Send>,
+        content_length: Option<u64>,
+    ) -> Response {
+        Response {
+            status,
+            headers,
+            body: Vec::new(),
+            stream_body: Some(StreamBody {
+                reader,
+                content_length,
+            }),
+            event_stream: None,
+            head: false,
+        }
+    }
+
+    /// Creates a `text/event-stream` response, and the sender its events are pushed into.
+    ///
+    /// Each event is written and flushed as it is sent, with no `Content-Length`,
+    /// and the connection stays open until every sender is dropped or the client disconnects.
+    pub fn sse() -> (Response, SseSender) {
+        let (sender, events) = SseSender::channel();
+        let mut response = Response::new();
+        response.headers.insert("Content-Type", "text/event-stream");
+        response.headers.insert("Cache-Control", "no-cache");
+        response.event_stream = Some(events);
+        (response, sender)
+    }
+
+    /// Length of the body, or the declared length of a streamed one, which is zero if unknown
+    pub fn body_len(&self) -> usize {
+        match &self.stream_body {
+            Some(stream_body) => stream_body.content_length.unwrap_or(0) as usize,
+            None => self.body.len(),
+        }
+    }
+
+    /// Creates a JSON error response with the body `{"error": {"code": "...", "message": "..."}}`,
+    /// the shape of every error the gateway sends.
+    ///
+    /// `code` is a stable, machine-readable name such as [`GraphError::code`], and `message`
+    /// is meant for people.
+    pub fn error(status: u16, code: &str, message: &str) -> Response {
+        let mut response = Response::new();
+        response.status = status;
+        response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        let body = ErrorBody {
+            error: ErrorDetail { code, message },
+        };
+        response.body = sonic_rs::to_vec(&body).unwrap_or_default();
+        response
+    }
+
+    /// Sets `body` as the JSON body, with a `Content-Type` of `application/json`.
+    ///
+    /// The JSON is compact unless `pretty`, which handlers pass as
+    /// [`Request::wants_pretty_json`](crate::protocol::request::Request::wants_pretty_json).
+    pub fn json<T: Serialize>(&mut self, body: &T, pretty: bool) -> std::result::Result<(), GraphError> {
+        self.body = match pretty {
+            true => sonic_rs::to_vec_pretty(body)?,
+            false => sonic_rs::to_vec(body)?,
+        };
+        self.headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        Ok(())
+    }
+
+    /// Sets a `204 No Content` status with no body, e.g. for a successful delete
+    pub fn no_content(&mut self) {
+        self.status = 204;
+        self.body.clear();
+    }
+
+    /// Redirects to `location` with a `3xx` status, clearing the body.
+    ///
+    /// Fails for any other status, or a location that can't be sent as a header.
+    pub fn redirect(&mut self, status: u16, location: &str) -> std::result::Result<(), GraphError> {
+        if !(300..400).contains(&status) {
+            return Err(GraphError::New(format!(
+                "Redirect status must be 3xx, got {}",
+                status
+            )));
+        }
+        if location.is_empty() || location.bytes().any(|byte| byte.is_ascii_control()) {
+            return Err(GraphError::New(format!(
+                "Invalid redirect location: {:?}",
+                location
+            )));
+        }
+        self.status = status;
+        self.headers.insert("Location", location);
+        self.body.clear();
+        Ok(())
+    }
+
+    /// Redirects with `302 Found`
+    pub fn redirect_temporary(&mut self, location: &str) -> std::result::Result<(), GraphError> {
+        self.redirect(302, location)
+    }
+
+    /// Redirects with `301 Moved Permanently`
+    pub fn redirect_permanent(&mut self, location: &str) -> std::result::Result<(), GraphError> {
+        self.redirect(301, location)
+    }
+
+    /// Adds a `Set-Cookie` header, so calling this again for another cookie sends both
+    pub fn set_cookie(
+        &mut self,
+        name: &str,
+        value: &str,
+        attrs: &CookieAttrs,
+    ) -> std::result::Result<(), GraphError> {
+        self.headers
+            .append("Set-Cookie", set_cookie_header(name, value, attrs)?);
+        Ok(())
+    }
+
+    /// Sets the `ETag` the body is sent with, quoting it if it isn't already,
+    /// e.g. one from [`weak_etag`](crate::protocol::etag::weak_etag).
+    ///
+    /// The router answers a `GET` or `HEAD` whose `If-None-Match` matches it with a `304`.
+    pub fn with_etag(&mut self, etag: &str) {
+        self.headers.insert("ETag", quote_etag(etag));
+    }
+
+    /// Turns a `200` into a bodyless `304 Not Modified` if `header`, the value of the
+    /// request's `If-None-Match`, matches the response's `ETag`.
+    ///
+    /// Returns whether it did. The `ETag` is kept so the client can go on sending it.
+    pub fn check_not_modified(&mut self, header: &str) -> bool {
+        let matches = self.status == 200
+            && self
+                .headers
+                .get("ETag")
+                .is_some_and(|etag| if_none_match(header, etag));
+        if matches {
+            self.status = 304;
+            self.body.clear();
+            self.stream_body = None;
+            self.event_stream = None;
+        }
+        matches
+    }
+
     /// Send response back via stream
     ///
     /// # Example
@@ -46,15 +253,37 @@ impl Response {
 
     pub async fn send<W: AsyncWrite + Unpin>(&mut self, stream: &mut W) -> Result<()> {
         let status_message = match self.status {
+            101 => "Switching Protocols",
             200 => "OK",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            403 => "Forbidden",
             404 => {
-                self.body = b"404 - Route Not Found\n".to_vec();
+                // keep the body of a handler's not found error
+                if self.body.is_empty() && self.stream_body.is_none() {
+                    self.body = b"404 - Route Not Found\n".to_vec();
+                }
                 "Not Found"
             }
+            408 => "Request Timeout",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            417 => "Expectation Failed",
+            429 => "Too Many Requests",
             500 => {
                 // self.body = b"500 - Internal Server Error\n".to_vec();
                 "Internal Server Error"
             }
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            507 => "Insufficient Storage",
             _ => "Unknown",
         };
         let mut writer = tokio::io::BufWriter::new(stream);
@@ -77,13 +306,97 @@ impl Response {
                 })?;
         }
 
-        writer
-            .write_all(format!("Content-Length: {}\r\n\r\n", self.body.len()).as_bytes())
-            .await?;
+        // a 101, 204 or 304 has neither a body nor a Content-Length
+        if matches!(self.status, 101 | 204 | 304) {
+            writer.write_all(b"\r\n").await?;
+        } else if self.head {
+            // the length is only known up front for a buffered body or a streamed one that declares it
+            let content_length = match &self.stream_body {
+                Some(stream_body) => stream_body.content_length,
+                None if self.event_stream.is_some() => None,
+                None => Some(self.body.len() as u64),
+            };
+            if let Some(length) = content_length {
+                writer
+                    .write_all(format!("Content-Length: {}\r\n", length).as_bytes())
+                    .await?;
+            }
+            writer.write_all(b"\r\n").await?;
+            self.event_stream = None;
+        } else if let Some(events) = self.event_stream.take() {
+            writer.write_all(b"\r\n").await?;
+            writer.flush().await?;
+            write_events(&mut writer, events).await?;
+        } else if let Some(stream_body) = &mut self.stream_body {
+            // the reader can only be sent once, the declared length is kept for logging
+            let reader = std::mem::replace(&mut stream_body.reader, Box::new(std::io::empty()));
+            write_stream_body(&mut writer, reader, stream_body.content_length).await?;
+        } else {
+            writer
+                .write_all(format!("Content-Length: {}\r\n\r\n", self.body.len()).as_bytes())
+                .await?;
 
-        // Write body
-        writer.write_all(&self.body).await?;
+            // Write body
+            writer.write_all(&self.body).await?;
+        }
         writer.flush().await?;
         Ok(())
     }
 }
+
+/// Writes the framing headers and then the body, reading from the blocking reader
+/// off the async runtime
+async fn write_stream_body<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut reader: Box<dyn Read + Send>,
+    content_length: Option<u64>,
+) -> Result<()> {
+    match content_length {
+        Some(length) => {
+            writer
+                .write_all(format!("Content-Length: {}\r\n\r\n", length).as_bytes())
+                .await?
+        }
+        None => writer.write_all(b"Transfer-Encoding: chunked\r\n\r\n").await?,
+    }
+
+    let mut buf = vec![0; STREAM_CHUNK_SIZE];
+    let mut sent = 0u64;
+    loop {
+        let want = match content_length {
+            Some(length) if sent == length => break,
+            Some(length) => (length - sent).min(STREAM_CHUNK_SIZE as u64) as usize,
+            None => STREAM_CHUNK_SIZE,
+        };
+        let (returned_reader, returned_buf, read) = tokio::task::spawn_blocking(move || {
+            let read = reader.read(&mut buf[..want]);
+            (reader, buf, read)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        reader = returned_reader;
+        buf = returned_buf;
+
+        let read = read?;
+        if read == 0 {
+            if let Some(length) = content_length {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("Streamed body ended after {} of {} bytes", sent, length),
+                ));
+            }
+            writer.write_all(b"0\r\n\r\n").await?;
+            break;
+        }
+        match content_length {
+            Some(_) => writer.write_all(&buf[..read]).await?,
+            None => {
+                writer.write_all(format!("{:x}\r\n", read).as_bytes()).await?;
+                writer.write_all(&buf[..read]).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+        }
+        sent += read as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use super::{headers::Headers, response::Response};
+
+#[tokio::test]
+async fn test_redirect_sets_location_and_clears_body() {
+    let mut response = Response::new();
+    response.body = b"stale".to_vec();
+    response.redirect(303, "/users/1").unwrap();
+
+    assert_eq!(response.status, 303);
+    assert_eq!(response.headers["location"], "/users/1");
+    assert!(response.body.is_empty());
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 303 See Other\r\n"));
+    assert!(sent.contains("Location: /users/1\r\n"));
+    assert!(sent.ends_with("Content-Length: 0\r\n\r\n"));
+}
+
+#[test]
+fn test_redirect_temporary_and_permanent() {
+    let mut response = Response::new();
+    response.redirect_temporary("/login").unwrap();
+    assert_eq!(response.status, 302);
+
+    response.redirect_permanent("https://example.com/").unwrap();
+    assert_eq!(response.status, 301);
+    // the earlier location is replaced rather than sent twice
+    assert_eq!(response.headers.get_all("Location").count(), 1);
+    assert_eq!(response.headers["Location"], "https://example.com/");
+}
+
+#[test]
+fn test_redirect_rejects_non_3xx_status() {
+    let mut response = Response::new();
+    assert!(response.redirect(200, "/").is_err());
+    assert!(response.redirect(404, "/").is_err());
+    assert!(response.redirect(302, "").is_err());
+    assert!(response.redirect(302, "/\r\nX-Injected: 1").is_err());
+
+    assert_eq!(response.status, 200);
+    assert!(!response.headers.contains_key("Location"));
+}
+
+/// Reader that hands out at most `chunk` bytes per read, like a file or socket might
+struct SlowReader {
+    data: Vec<u8>,
+    pos: usize,
+    chunk: usize,
+}
+
+impl std::io::Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.chunk).min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn slow_reader(data: &[u8], chunk: usize) -> Box<SlowReader> {
+    Box::new(SlowReader {
+        data: data.to_vec(),
+        pos: 0,
+        chunk,
+    })
+}
+
+#[tokio::test]
+async fn test_stream_with_content_length() {
+    let data = vec![b'x'; 200 * 1024];
+    let mut response = Response::stream(
+        200,
+        Headers::new(),
+        slow_reader(&data, 10_000),
+        Some(data.len() as u64),
+    );
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let header_end = sent.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8_lossy(&sent[..header_end]);
+    assert!(head.contains(&format!("Content-Length: {}\r\n", data.len())));
+    assert!(!head.contains("Transfer-Encoding"));
+    assert_eq!(&sent[header_end..], &data[..]);
+    assert_eq!(response.body_len(), data.len());
+}
+
+#[tokio::test]
+async fn test_stream_without_length_is_chunked() {
+    let mut response = Response::stream(200, Headers::new(), slow_reader(b"hello world", 5), None);
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.contains("Transfer-Encoding: chunked\r\n\r\n"));
+    assert!(!sent.contains("Content-Length"));
+    assert!(sent.ends_with("\r\n\r\n5\r\nhello\r\n5\r\n worl\r\n1\r\nd\r\n0\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn test_stream_shorter_than_content_length_fails() {
+    let mut response = Response::stream(200, Headers::new(), slow_reader(b"short", 5), Some(100));
+    let mut sent = Vec::new();
+    let err = response.send(&mut sent).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[tokio::test]
+async fn test_in_memory_body_unchanged() {
+    let mut response = Response::new();
+    response.body = b"Hello World".to_vec();
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.contains("Content-Length: 11\r\n\r\nHello World"));
+}
+
+#[tokio::test]
+async fn test_matching_etag_sends_304_without_body() {
+    let mut response = Response::new();
+    response.body = b"Hello World".to_vec();
+    response.with_etag("v1");
+    assert_eq!(response.headers["etag"], "\"v1\"");
+
+    assert!(!response.check_not_modified("\"v0\""));
+    assert_eq!(response.status, 200);
+    assert!(response.check_not_modified("W/\"v1\""));
+    assert_eq!(response.status, 304);
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    assert!(sent.contains("ETag: \"v1\"\r\n"));
+    assert!(!sent.contains("Content-Length"));
+    assert!(sent.ends_with("\r\n\r\n"));
+
+    // only a successful response is cached
+    let mut response = Response::new();
+    response.status = 404;
+    response.with_etag("v1");
+    assert!(!response.check_not_modified("\"v1\""));
+}
+
+#[tokio::test]
+async fn test_error_response_body() {
+    let mut response = Response::error(429, "rate_limited", "Too many \"requests\"");
+    assert_eq!(response.status, 429);
+    assert_eq!(response.headers["Content-Type"], "application/json");
+    assert_eq!(
+        response.body,
+        br#"{"error":{"code":"rate_limited","message":"Too many \"requests\""}}"#
+    );
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    assert!(sent.ends_with(r#"{"error":{"code":"rate_limited","message":"Too many \"requests\""}}"#));
+}
+
+#[test]
+fn test_json_compact_or_pretty() {
+    let body = HashMap::from([("name", "alice")]);
+
+    let mut response = Response::new();
+    response.json(&body, false).unwrap();
+    assert_eq!(response.body, br#"{"name":"alice"}"#);
+    assert_eq!(response.headers["Content-Type"], "application/json");
+
+    response.json(&body, true).unwrap();
+    assert_eq!(String::from_utf8(response.body).unwrap(), "{\n  \"name\": \"alice\"\n}");
+}
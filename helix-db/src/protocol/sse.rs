@@ -0,0 +1,110 @@
+use flume::{Receiver, Sender};
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt, Result};
+
+use crate::helix_engine::types::GraphError;
+
+/// How long an event stream can go without an event before a comment is sent,
+/// which keeps proxies from timing it out and finds clients that have gone away
+pub const SSE_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A single server-sent event.
+///
+/// Line breaks in `event` and `id` are removed when it is sent,
+/// while `data` is sent as one `data:` line for each of its lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+impl SseEvent {
+    /// An unnamed event, which browsers deliver to `onmessage`
+    pub fn data(data: impl Into<String>) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Names the event, so browsers deliver it to listeners for that name
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Sets the id browsers send back in `Last-Event-ID` when they reconnect
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The event as it is written to the stream, ending with the blank line that dispatches it
+    pub fn encode(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+        let mut encoded = String::with_capacity(self.data.len() + 16);
+        if let Some(event) = &self.event {
+            encoded.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            encoded.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        for line in self.data.lines() {
+            encoded.push_str(&format!("data: {}\n", line));
+        }
+        if self.data.is_empty() {
+            encoded.push_str("data: \n");
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+/// Pushes events into the stream of a response created with
+/// [`Response::sse`](crate::protocol::response::Response::sse).
+///
+/// The stream ends once every sender is dropped. It can be cloned and moved to
+/// other threads to keep sending after the handler has returned.
+#[derive(Debug, Clone)]
+pub struct SseSender {
+    tx: Sender<SseEvent>,
+}
+
+impl SseSender {
+    pub(crate) fn channel() -> (Self, Receiver<SseEvent>) {
+        let (tx, rx) = flume::unbounded();
+        (Self { tx }, rx)
+    }
+
+    /// Queues an event to be written and flushed to the client.
+    ///
+    /// Fails once the client has disconnected, so a producer knows to stop.
+    pub fn send(&self, event: SseEvent) -> std::result::Result<(), GraphError> {
+        self.tx
+            .send(event)
+            .map_err(|_| GraphError::New("Event stream client disconnected".to_string()))
+    }
+
+    /// Whether the client has disconnected
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_disconnected()
+    }
+}
+
+/// Writes each event as it arrives, flushing after every one, until the senders are dropped
+pub(crate) async fn write_events<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    events: Receiver<SseEvent>,
+) -> Result<()> {
+    loop {
+        match tokio::time::timeout(SSE_KEEP_ALIVE, events.recv_async()).await {
+            Ok(Ok(event)) => writer.write_all(event.encode().as_bytes()).await?,
+            // every sender is gone, so the stream is over
+            Ok(Err(_)) => break,
+            Err(_) => writer.write_all(b": keep-alive\n\n").await?,
+        }
+        writer.flush().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use super::{
+    response::Response,
+    sse::{SseEvent, SseSender},
+};
+
+#[test]
+fn test_event_encoding() {
+    assert_eq!(SseEvent::data("hello").encode(), "data: hello\n\n");
+    assert_eq!(
+        SseEvent::data("line one\nline two")
+            .event("node\nupdated")
+            .id("7")
+            .encode(),
+        "event: nodeupdated\nid: 7\ndata: line one\ndata: line two\n\n"
+    );
+    // an empty event still has the data field browsers need to dispatch it
+    assert_eq!(SseEvent::data("").encode(), "data: \n\n");
+}
+
+#[tokio::test]
+async fn test_sse_response_streams_events_until_senders_dropped() {
+    let (mut response, events) = Response::sse();
+    events.send(SseEvent::data("first")).unwrap();
+    let producer = events.clone();
+    std::thread::spawn(move || {
+        producer
+            .send(SseEvent::data("second").event("update"))
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+    drop(events);
+
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(sent.contains("Content-Type: text/event-stream\r\n"));
+    assert!(!sent.contains("Content-Length"));
+    assert!(sent.ends_with("\r\n\r\ndata: first\n\nevent: update\ndata: second\n\n"));
+}
+
+#[tokio::test]
+async fn test_send_fails_once_client_disconnects() {
+    let (response, events): (Response, SseSender) = Response::sse();
+    assert!(!events.is_closed());
+    drop(response);
+    assert!(events.is_closed());
+    assert!(events.send(SseEvent::data("lost")).is_err());
+}
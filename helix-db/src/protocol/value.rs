@@ -73,6 +73,40 @@ impl Value {
         }
     }
 
+    /// Returns the value as an `f64` if it is numeric.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::F32(f) => Some(*f as f64),
+            Value::F64(f) => Some(*f),
+            Value::I8(i) => Some(*i as f64),
+            Value::I16(i) => Some(*i as f64),
+            Value::I32(i) => Some(*i as f64),
+            Value::I64(i) => Some(*i as f64),
+            Value::U8(u) => Some(*u as f64),
+            Value::U16(u) => Some(*u as f64),
+            Value::U32(u) => Some(*u as f64),
+            Value::U64(u) => Some(*u as f64),
+            Value::U128(u) => Some(*u as f64),
+            _ => None,
+        }
+    }
+
+    /// How the value orders against `other` when filtering by it, or `None` if they can't be
+    /// compared, e.g. a string with a number or a missing property with anything.
+    ///
+    /// Unlike [`Ord`], numbers of different types compare by their values.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::U128(a), Value::I64(b)) => Some(match u128::try_from(*b) {
+                Ok(b) => a.cmp(&b),
+                Err(_) => Ordering::Greater,
+            }),
+            _ => self.as_f64()?.partial_cmp(&other.as_f64()?),
+        }
+    }
+
     #[inline]
     #[allow(unused_variables)] // default is not used but needed for function signature
     pub fn map_value_or(
@@ -0,0 +1,266 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Error, ErrorKind, Result};
+
+use crate::protocol::{request::Request, response::Response};
+
+/// Appended to the client's key before hashing it, as fixed by RFC 6455
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message that will be read, including every fragment of it
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Close codes sent when the client breaks the protocol
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// The `Sec-WebSocket-Accept` value answering a client's `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+/// The `101 Switching Protocols` response accepting a websocket handshake,
+/// or `None` if the request has no key or asks for a version other than 13
+pub fn handshake_response(request: &Request) -> Option<Response> {
+    let key = request.headers.get("sec-websocket-key")?;
+    if request
+        .headers
+        .get("sec-websocket-version")
+        .map(|v| v.trim())
+        != Some("13")
+    {
+        return None;
+    }
+
+    let mut response = Response::new();
+    response.status = 101;
+    response.headers.remove("Content-Type");
+    response.headers.insert("Upgrade", "websocket");
+    response.headers.insert("Connection", "Upgrade");
+    response
+        .headers
+        .insert("Sec-WebSocket-Accept", accept_key(key));
+    Some(response)
+}
+
+/// A whole websocket message or control frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The close code and reason, if one was given
+    Close(Option<(u16, String)>),
+}
+
+/// The server's side of an upgraded connection.
+///
+/// Reads the masked frames a client sends and writes unmasked ones.
+pub struct WebSocket<S> {
+    stream: S,
+    close_sent: bool,
+    // opcode and data so far of a fragmented message, kept while control frames are returned
+    partial: Option<(u8, Vec<u8>)>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    /// Wraps a stream the handshake has already been answered on
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            close_sent: false,
+            partial: None,
+        }
+    }
+
+    /// Reads the next message or control frame, joining fragmented messages back together.
+    ///
+    /// Pings are answered with a pong and a close with a close before they are returned.
+    /// Returns `None` once the client has closed the connection.
+    /// Frames that break the protocol fail with `InvalidData`, after closing the websocket.
+    pub async fn read_frame(&mut self) -> Result<Option<Frame>> {
+        loop {
+            let Some((fin, opcode, payload)) = self.read_raw().await? else {
+                return Ok(None);
+            };
+
+            match opcode {
+                OPCODE_PING => {
+                    self.write_raw(OPCODE_PONG, &payload).await?;
+                    return Ok(Some(Frame::Ping(payload)));
+                }
+                OPCODE_PONG => return Ok(Some(Frame::Pong(payload))),
+                OPCODE_CLOSE => {
+                    let close = match payload.len() {
+                        0 => None,
+                        1 => {
+                            return Err(self
+                                .fail(CLOSE_PROTOCOL_ERROR, "Truncated close code")
+                                .await);
+                        }
+                        _ => {
+                            let code = u16::from_be_bytes([payload[0], payload[1]]);
+                            let reason = String::from_utf8_lossy(&payload[2..]).into_owned();
+                            Some((code, reason))
+                        }
+                    };
+                    if !self.close_sent {
+                        self.write_raw(OPCODE_CLOSE, &payload[..payload.len().min(2)])
+                            .await?;
+                        self.close_sent = true;
+                    }
+                    return Ok(Some(Frame::Close(close)));
+                }
+                OPCODE_TEXT | OPCODE_BINARY if self.partial.is_none() => {
+                    self.partial = Some((opcode, payload));
+                }
+                OPCODE_CONTINUATION if self.partial.is_some() => {
+                    let (_, data) = self.partial.as_mut().unwrap();
+                    if data.len() + payload.len() > MAX_MESSAGE_SIZE {
+                        return Err(self.fail(CLOSE_TOO_BIG, "Message too big").await);
+                    }
+                    data.extend_from_slice(&payload);
+                }
+                _ => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Unexpected opcode").await),
+            }
+
+            if fin && let Some((opcode, data)) = self.partial.take() {
+                return match opcode {
+                    OPCODE_TEXT => match String::from_utf8(data) {
+                        Ok(text) => Ok(Some(Frame::Text(text))),
+                        Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "Text is not UTF-8").await),
+                    },
+                    _ => Ok(Some(Frame::Binary(data))),
+                };
+            }
+        }
+    }
+
+    /// Writes a frame as a single unfragmented one
+    pub async fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        match frame {
+            Frame::Text(text) => self.write_raw(OPCODE_TEXT, text.as_bytes()).await,
+            Frame::Binary(data) => self.write_raw(OPCODE_BINARY, data).await,
+            Frame::Ping(data) => self.write_raw(OPCODE_PING, data).await,
+            Frame::Pong(data) => self.write_raw(OPCODE_PONG, data).await,
+            Frame::Close(close) => {
+                let mut payload = Vec::new();
+                if let Some((code, reason)) = close {
+                    payload.extend_from_slice(&code.to_be_bytes());
+                    payload.extend_from_slice(reason.as_bytes());
+                }
+                self.close_sent = true;
+                self.write_raw(OPCODE_CLOSE, &payload).await
+            }
+        }
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> Result<()> {
+        self.write_raw(OPCODE_TEXT, text.as_bytes()).await
+    }
+
+    pub async fn send_binary(&mut self, data: &[u8]) -> Result<()> {
+        self.write_raw(OPCODE_BINARY, data).await
+    }
+
+    pub async fn ping(&mut self, data: &[u8]) -> Result<()> {
+        self.write_raw(OPCODE_PING, data).await
+    }
+
+    /// Starts closing the websocket, after which the client answers with a close of its own
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<()> {
+        self.write_frame(&Frame::Close(Some((code, reason.to_string()))))
+            .await
+    }
+
+    /// Gives back the stream, e.g. to shut it down
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Reads one frame, unmasking its payload, or `None` on a clean end of stream
+    async fn read_raw(&mut self) -> Result<Option<(bool, u8, Vec<u8>)>> {
+        let mut head = [0u8; 2];
+        match self.stream.read_exact(&mut head).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+        if head[0] & 0x70 != 0 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Reserved bits set").await);
+        }
+        if !masked {
+            return Err(self
+                .fail(CLOSE_PROTOCOL_ERROR, "Client frames must be masked")
+                .await);
+        }
+
+        let len = match head[1] & 0x7F {
+            126 => self.stream.read_u16().await? as u64,
+            127 => self.stream.read_u64().await?,
+            len => len as u64,
+        };
+        // control frames can't be fragmented, so they are kept small
+        if opcode >= OPCODE_CLOSE && (len > 125 || !fin) {
+            return Err(self
+                .fail(CLOSE_PROTOCOL_ERROR, "Invalid control frame")
+                .await);
+        }
+        if len > MAX_MESSAGE_SIZE as u64 {
+            return Err(self.fail(CLOSE_TOO_BIG, "Message too big").await);
+        }
+
+        let mut mask = [0u8; 4];
+        self.stream.read_exact(&mut mask).await?;
+        let mut payload = vec![0u8; len as usize];
+        self.stream.read_exact(&mut payload).await?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok(Some((fin, opcode, payload)))
+    }
+
+    async fn write_raw(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 10);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => frame.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        self.stream.write_all(&frame).await?;
+        self.stream.flush().await
+    }
+
+    /// Closes the websocket with `code`, returning the error to fail the read with
+    async fn fail(&mut self, code: u16, message: &str) -> Error {
+        if !self.close_sent {
+            self.close_sent = true;
+            // the client may already be gone, in which case there is nothing to tell it
+            let _ = self.write_raw(OPCODE_CLOSE, &code.to_be_bytes()).await;
+        }
+        Error::new(ErrorKind::InvalidData, message)
+    }
+}
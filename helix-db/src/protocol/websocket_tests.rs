@@ -0,0 +1,182 @@
+use std::io::Cursor;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
+
+use super::{
+    request::Request,
+    websocket::{Frame, WebSocket, accept_key, handshake_response},
+};
+
+/// A frame as a client sends it, masked with a fixed key
+fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![(fin as u8) << 7 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    frame
+}
+
+fn websocket_pair() -> (WebSocket<DuplexStream>, DuplexStream) {
+    let (server, client) = duplex(1 << 20);
+    (WebSocket::new(server), client)
+}
+
+async fn parse(raw: &str) -> Request {
+    Request::from_stream(&mut Cursor::new(raw.as_bytes().to_vec()))
+        .await
+        .unwrap()
+}
+
+#[test]
+fn test_accept_key_matches_rfc_example() {
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[tokio::test]
+async fn test_handshake_detected_and_accepted() {
+    let request = parse(
+        "GET /live HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+    )
+    .await;
+    assert!(request.is_websocket_upgrade());
+
+    let mut response = handshake_response(&request).unwrap();
+    let mut sent = Vec::new();
+    response.send(&mut sent).await.unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(sent.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(!sent.contains("Content-Length"));
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_without_key_or_version() {
+    let plain = parse("GET /live HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert!(!plain.is_websocket_upgrade());
+
+    let no_key = parse(
+        "GET /live HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\r\n",
+    )
+    .await;
+    assert!(no_key.is_websocket_upgrade());
+    assert!(handshake_response(&no_key).is_none());
+
+    let old_version = parse(
+        "GET /live HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: a2V5\r\nSec-WebSocket-Version: 8\r\n\r\n",
+    )
+    .await;
+    assert!(handshake_response(&old_version).is_none());
+}
+
+#[tokio::test]
+async fn test_read_text_binary_and_fragmented_frames() {
+    let (mut websocket, mut client) = websocket_pair();
+    client
+        .write_all(&client_frame(true, 0x1, b"hello"))
+        .await
+        .unwrap();
+    client
+        .write_all(&client_frame(true, 0x2, &[0, 1, 2]))
+        .await
+        .unwrap();
+    client
+        .write_all(&client_frame(false, 0x1, b"split "))
+        .await
+        .unwrap();
+    // a ping can arrive between the fragments of a message
+    client
+        .write_all(&client_frame(true, 0x9, b"p"))
+        .await
+        .unwrap();
+    client
+        .write_all(&client_frame(true, 0x0, &[b'x'; 300]))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        websocket.read_frame().await.unwrap(),
+        Some(Frame::Text("hello".to_string()))
+    );
+    assert_eq!(
+        websocket.read_frame().await.unwrap(),
+        Some(Frame::Binary(vec![0, 1, 2]))
+    );
+    assert_eq!(
+        websocket.read_frame().await.unwrap(),
+        Some(Frame::Ping(b"p".to_vec()))
+    );
+    let expected = format!("split {}", "x".repeat(300));
+    assert_eq!(
+        websocket.read_frame().await.unwrap(),
+        Some(Frame::Text(expected))
+    );
+
+    // the ping was answered with an unmasked pong
+    let mut pong = [0u8; 3];
+    client.read_exact(&mut pong).await.unwrap();
+    assert_eq!(pong, [0x8A, 1, b'p']);
+
+    drop(client);
+    assert_eq!(websocket.read_frame().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_close_is_answered() {
+    let (mut websocket, mut client) = websocket_pair();
+    let mut payload = 1000u16.to_be_bytes().to_vec();
+    payload.extend_from_slice(b"bye");
+    client
+        .write_all(&client_frame(true, 0x8, &payload))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        websocket.read_frame().await.unwrap(),
+        Some(Frame::Close(Some((1000, "bye".to_string()))))
+    );
+    let mut close = [0u8; 4];
+    client.read_exact(&mut close).await.unwrap();
+    assert_eq!(close, [0x88, 2, 0x03, 0xE8]);
+}
+
+#[tokio::test]
+async fn test_unmasked_frame_fails_with_protocol_error() {
+    let (mut websocket, mut client) = websocket_pair();
+    client.write_all(&[0x81, 2, b'h', b'i']).await.unwrap();
+
+    let err = websocket.read_frame().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let mut close = [0u8; 4];
+    client.read_exact(&mut close).await.unwrap();
+    assert_eq!(close, [0x88, 2, 0x03, 0xEA]);
+}
+
+#[tokio::test]
+async fn test_write_frames_use_extended_lengths() {
+    let (mut websocket, mut client) = websocket_pair();
+    websocket.send_text("hi").await.unwrap();
+    websocket.send_binary(&[7; 200]).await.unwrap();
+    websocket.close(1001, "").await.unwrap();
+    drop(websocket);
+
+    let mut sent = Vec::new();
+    client.read_to_end(&mut sent).await.unwrap();
+    assert_eq!(&sent[..4], &[0x81, 2, b'h', b'i']);
+    assert_eq!(&sent[4..8], &[0x82, 126, 0, 200]);
+    assert_eq!(&sent[208..], &[0x88, 2, 0x03, 0xE9]);
+}
@@ -5,7 +5,7 @@
 //! Nodes and edges are serialised without enum variant names in JSON format.
 
 use crate::protocol::value::Value;
-use crate::helix_engine::types::GraphError;
+use crate::helix_engine::{storage_core::compression::Compression, types::GraphError};
 use sonic_rs::{Deserialize, Serialize};
 use std::{cmp::Ordering, collections::HashMap};
 
@@ -39,7 +39,7 @@ impl Node {
     /// Takes ID as the ID is not serialized when stored as it is the key.
     /// Uses the known ID (either from the query or the key in an LMDB iterator) to construct a new node.
     pub fn decode_node(bytes: &[u8], id: u128) -> Result<Node, GraphError> {
-        match bincode::deserialize::<Node>(bytes) {
+        match bincode::deserialize::<Node>(&Compression::decompress(bytes)?) {
             Ok(node) => Ok(Node {
                 id,
                 label: node.label,
@@ -130,7 +130,7 @@ impl Edge {
     /// Takes ID as the ID is not serialized when stored as it is the key.
     /// Uses the known ID (either from the query or the key in an LMDB iterator) to construct a new edge.
     pub fn decode_edge(bytes: &[u8], id: u128) -> Result<Edge, GraphError> {
-        match bincode::deserialize::<Edge>(bytes) {
+        match bincode::deserialize::<Edge>(&Compression::decompress(bytes)?) {
             Ok(edge) => Ok(Edge {
                 id,
                 label: edge.label,
@@ -0,0 +1,107 @@
+use crate::helix_engine::types::GraphError;
+use serde::de::{DeserializeOwned, Deserializer as _, Error as _, SeqAccess, Visitor};
+use std::{
+    fmt,
+    io::{self, Read},
+    marker::PhantomData,
+};
+
+/// Deserializes the elements of the JSON array read from `reader` one at a time, passing each
+/// to `f` before the next is read.
+///
+/// Only the current element is held in memory, so memory use is bounded by the largest element
+/// rather than the size of the array. Fails with a `GraphError::ConversionError` once more than
+/// `max_size` bytes have been read, and stops at the first error, including one from `f`.
+///
+/// Returns the number of elements.
+pub fn for_each_array_element<R, T>(
+    reader: R,
+    max_size: Option<usize>,
+    f: impl FnMut(T) -> Result<(), GraphError>,
+) -> Result<usize, GraphError>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut reader = Limited {
+        reader,
+        read: 0,
+        max_size,
+    };
+    let mut failed = None;
+    let mut de = serde_json::Deserializer::from_reader(&mut reader);
+    let elements = de
+        .deserialize_seq(EachElement {
+            f,
+            failed: &mut failed,
+            _marker: PhantomData,
+        })
+        .and_then(|elements| de.end().map(|_| elements));
+
+    match (elements, failed) {
+        (_, Some(e)) => Err(e),
+        (Ok(elements), None) => Ok(elements),
+        (Err(_), None) if reader.exceeded() => Err(GraphError::ConversionError(format!(
+            "JSON body exceeds max size of {} bytes",
+            reader.max_size.unwrap_or_default()
+        ))),
+        (Err(e), None) => Err(GraphError::ConversionError(format!("JSON error: {}", e))),
+    }
+}
+
+struct EachElement<'a, F, T> {
+    f: F,
+    failed: &'a mut Option<GraphError>,
+    _marker: PhantomData<T>,
+}
+
+impl<'de, F, T> Visitor<'de> for EachElement<'_, F, T>
+where
+    F: FnMut(T) -> Result<(), GraphError>,
+    T: DeserializeOwned,
+{
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<usize, A::Error> {
+        let mut elements = 0;
+        while let Some(element) = seq.next_element::<T>()? {
+            if let Err(e) = (self.f)(element) {
+                *self.failed = Some(e);
+                return Err(A::Error::custom("stopped reading JSON array"));
+            }
+            elements += 1;
+        }
+        Ok(elements)
+    }
+}
+
+/// Reader that fails once more than `max_size` bytes have been read from it
+struct Limited<R> {
+    reader: R,
+    read: usize,
+    max_size: Option<usize>,
+}
+
+impl<R> Limited<R> {
+    fn exceeded(&self) -> bool {
+        self.max_size.is_some_and(|max_size| self.read > max_size)
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.exceeded() {
+            return Err(io::Error::other("JSON body exceeds max size"));
+        }
+        let n = self.reader.read(buf)?;
+        self.read += n;
+        match self.exceeded() {
+            true => Err(io::Error::other("JSON body exceeds max size")),
+            false => Ok(n),
+        }
+    }
+}
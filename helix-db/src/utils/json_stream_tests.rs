@@ -0,0 +1,81 @@
+use std::io::Cursor;
+
+use super::json_stream::for_each_array_element;
+use crate::helix_engine::types::GraphError;
+use serde_json::Value;
+
+fn parse(input: &str) -> Result<Vec<Value>, GraphError> {
+    let mut values = Vec::new();
+    for_each_array_element(Cursor::new(input), None, |value| {
+        values.push(value);
+        Ok(())
+    })?;
+    Ok(values)
+}
+
+#[test]
+fn test_stream_yields_each_element() {
+    let values = parse(
+        r#" [ {"name": "a ] , } [ \" quoted"}, [1, [2, 3]], "plain", 42 , -1.5e3, true, null ] "#,
+    )
+    .unwrap();
+
+    assert_eq!(values.len(), 7);
+    assert_eq!(values[0]["name"].as_str(), Some(r#"a ] , } [ " quoted"#));
+    assert_eq!(values[1][1][1].as_i64(), Some(3));
+    assert_eq!(values[2].as_str(), Some("plain"));
+    assert_eq!(values[3].as_i64(), Some(42));
+    assert_eq!(values[4].as_f64(), Some(-1500.0));
+    assert_eq!(values[5].as_bool(), Some(true));
+    assert!(values[6].is_null());
+}
+
+#[test]
+fn test_stream_empty_array() {
+    assert!(parse("[]").unwrap().is_empty());
+    assert!(parse(" [ \n ] ").unwrap().is_empty());
+}
+
+#[test]
+fn test_stream_rejects_malformed_input() {
+    for input in [
+        "",
+        "{\"a\": 1}",
+        "[1, 2",
+        "[1,, 2]",
+        "[1, 2,]",
+        "[1 2]",
+        "[{\"a\": 1]",
+        "[\"unterminated]",
+    ] {
+        assert!(
+            matches!(parse(input), Err(GraphError::ConversionError(_))),
+            "accepted {:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_stream_enforces_max_size() {
+    let input = "[1, 2, 3, 4]";
+    let count = |max_size| {
+        for_each_array_element(Cursor::new(input), Some(max_size), |_: Value| Ok(()))
+    };
+    assert_eq!(count(input.len()).unwrap(), 4);
+    assert!(matches!(count(5), Err(GraphError::ConversionError(_))));
+}
+
+#[test]
+fn test_stream_stops_at_callback_error() {
+    let mut seen = 0;
+    let result = for_each_array_element(Cursor::new("[1, 2, 3]"), None, |_: Value| {
+        seen += 1;
+        match seen {
+            2 => Err(GraphError::NodeNotFound),
+            _ => Ok(()),
+        }
+    });
+    assert!(matches!(result, Err(GraphError::NodeNotFound)));
+    assert_eq!(seen, 2);
+}
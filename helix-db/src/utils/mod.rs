@@ -3,4 +3,7 @@ pub mod count;
 pub mod filterable;
 pub mod id;
 pub mod items;
+pub mod json_stream;
+#[cfg(test)]
+mod json_stream_tests;
 pub mod label_hash;
\ No newline at end of file